@@ -0,0 +1,438 @@
+//! Vector ASCII log (.asc) reader, as produced by CANoe/CANalyzer.
+//!
+//! Supports the `base hex|dec` and `timestamps absolute|relative` header modes,
+//! classic, remote, CAN FD and error frame lines.
+//! Timestamps are relative to the start of the measurement unless absolute timestamps
+//! are requested, in which case the header `date` (assumed to be UTC) is added.
+
+use crate::import::{
+    add_timestamps, month_from_name, parse_decimal_seconds, unix_seconds, Direction, LogFrame,
+    MAX_CLASSIC_DATA_LEN,
+};
+use socketcan::{
+    frame::FdFlags, CanAnyFrame, CanDataFrame, CanErrorFrame, CanFdFrame, CanRemoteFrame,
+    EmbeddedFrame, ExtendedId, Id as CanId, StandardId, Timestamp,
+};
+use std::io::BufRead;
+use tracing::{debug, warn};
+
+pub struct Reader<R> {
+    reader: R,
+    parser: Parser,
+    line_buf: String,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(reader: R, absolute_timestamps: bool) -> Self {
+        Self {
+            reader,
+            parser: Parser::new(absolute_timestamps),
+            line_buf: String::with_capacity(8 * 1024),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = Result<LogFrame, anyhow::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line_buf.clear();
+            match self.reader.read_line(&mut self.line_buf) {
+                Ok(0) => return None,
+                Ok(_) => {
+                    if let Some(f) = self.parser.parse_line(&self.line_buf) {
+                        return Some(Ok(f));
+                    }
+                }
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Parser {
+    absolute_timestamps: bool,
+    /// Numeric base of the IDs and data bytes
+    base: u32,
+    /// Timestamps are deltas from the previous event rather than the measurement start
+    relative: bool,
+    start_time: Option<Timestamp>,
+    last_timestamp: Timestamp,
+}
+
+impl Parser {
+    fn new(absolute_timestamps: bool) -> Self {
+        Self {
+            absolute_timestamps,
+            base: 16,
+            relative: false,
+            start_time: None,
+            last_timestamp: Timestamp {
+                seconds: 0,
+                nanoseconds: 0,
+            },
+        }
+    }
+
+    fn parse_line(&mut self, line: &str) -> Option<LogFrame> {
+        let line = line.trim();
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let first = *tokens.first()?;
+
+        if first.starts_with("//") {
+            return None;
+        }
+
+        match first.to_ascii_lowercase().as_str() {
+            "date" => {
+                self.start_time = parse_date(&tokens[1..]);
+                if self.start_time.is_none() {
+                    warn!(line, "Failed to parse ASC date header");
+                }
+                return None;
+            }
+            "base" => {
+                self.parse_base_header(&tokens);
+                return None;
+            }
+            "begin" => {
+                // 'Begin Triggerblock <date>', use it if the header didn't have one
+                if self.start_time.is_none() && tokens.len() > 2 {
+                    self.start_time = parse_date(&tokens[2..]);
+                }
+                return None;
+            }
+            _ => (),
+        }
+
+        // Everything else of interest starts with a timestamp
+        let timestamp = parse_decimal_seconds(first)?;
+        let timestamp = if self.relative {
+            add_timestamps(&self.last_timestamp, &timestamp)
+        } else {
+            timestamp
+        };
+        self.last_timestamp = timestamp;
+
+        let res = if tokens.get(1).map(|t| t.eq_ignore_ascii_case("CANFD")) == Some(true) {
+            self.parse_fd_frame(&tokens[2..])
+        } else {
+            self.parse_classic_frame(&tokens[1..])
+        };
+
+        let (interface, direction, frame) = match res {
+            Some(f) => f,
+            None => {
+                debug!(line, "Skipping ASC line");
+                return None;
+            }
+        };
+
+        let timestamp = match self.start_time.as_ref() {
+            Some(start) if self.absolute_timestamps => add_timestamps(start, &timestamp),
+            _ => timestamp,
+        };
+
+        Some(LogFrame {
+            timestamp: Some(timestamp),
+            interface: Some(interface.to_owned()),
+            direction,
+            frame,
+        })
+    }
+
+    /// base <hex|dec> timestamps <absolute|relative>
+    fn parse_base_header(&mut self, tokens: &[&str]) {
+        let mut iter = tokens.iter().skip(1);
+        while let Some(t) = iter.next() {
+            match t.to_ascii_lowercase().as_str() {
+                "hex" => self.base = 16,
+                "dec" => self.base = 10,
+                "timestamps" => {
+                    if let Some(mode) = iter.next() {
+                        self.relative = mode.eq_ignore_ascii_case("relative");
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// <channel> <id> <Rx|Tx> d <dlc> <data...>
+    /// <channel> <id> <Rx|Tx> r [<dlc>]
+    /// <channel> ErrorFrame ...
+    fn parse_classic_frame<'a>(
+        &self,
+        tokens: &[&'a str],
+    ) -> Option<(&'a str, Option<Direction>, CanAnyFrame)> {
+        let channel = *tokens.first()?;
+        if !channel.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        if tokens.get(1)?.eq_ignore_ascii_case("ErrorFrame") {
+            return Some((channel, None, CanAnyFrame::Error(error_frame()?)));
+        }
+
+        let id = self.parse_id(tokens.get(1)?)?;
+        let direction = parse_direction(tokens.get(2)?)?;
+        let kind = tokens.get(3)?;
+
+        let frame = if kind.eq_ignore_ascii_case("r") {
+            let dlc = match tokens.get(4) {
+                Some(t) => usize::from_str_radix(t, self.base).ok()?,
+                None => 0,
+            };
+            CanAnyFrame::Remote(CanRemoteFrame::new_remote(id, dlc)?)
+        } else if kind.eq_ignore_ascii_case("d") {
+            let dlc = usize::from_str_radix(tokens.get(4)?, self.base).ok()?;
+            let data = self.parse_data(&tokens[5..], dlc.min(MAX_CLASSIC_DATA_LEN))?;
+            CanAnyFrame::Normal(CanDataFrame::new(id, &data)?)
+        } else {
+            return None;
+        };
+
+        Some((channel, Some(direction), frame))
+    }
+
+    /// CANFD <channel> <Rx|Tx> <id> [<symbolic name>] <brs> <esi> <dlc> <data length> <data...> ...
+    /// CANFD <channel> <Rx|Tx> ErrorFrame ...
+    fn parse_fd_frame<'a>(
+        &self,
+        tokens: &[&'a str],
+    ) -> Option<(&'a str, Option<Direction>, CanAnyFrame)> {
+        let channel = *tokens.first()?;
+        let direction = parse_direction(tokens.get(1)?)?;
+
+        if tokens.get(2)?.eq_ignore_ascii_case("ErrorFrame") {
+            return Some((channel, Some(direction), CanAnyFrame::Error(error_frame()?)));
+        }
+
+        let id = self.parse_id(tokens.get(2)?)?;
+
+        // The symbolic message name is optional
+        let rest = if matches!(tokens.get(3), Some(&"0") | Some(&"1")) {
+            &tokens[3..]
+        } else {
+            tokens.get(4..)?
+        };
+
+        let brs = *rest.first()? == "1";
+        let esi = *rest.get(1)? == "1";
+        let dlc = usize::from_str_radix(rest.get(2)?, 16).ok()?;
+        let data_len: usize = rest.get(3)?.parse().ok()?;
+
+        let frame = if data_len == 0 && dlc != 0 {
+            CanAnyFrame::Remote(CanRemoteFrame::new_remote(id, dlc)?)
+        } else {
+            let data = self.parse_data(&rest[4..], data_len)?;
+            let mut flags = FdFlags::empty();
+            if brs {
+                flags |= FdFlags::BRS;
+            }
+            if esi {
+                flags |= FdFlags::ESI;
+            }
+            CanAnyFrame::Fd(CanFdFrame::with_flags(id, &data, flags)?)
+        };
+
+        Some((channel, Some(direction), frame))
+    }
+
+    /// Extended IDs have an 'x' suffix
+    fn parse_id(&self, s: &str) -> Option<CanId> {
+        if let Some(ext) = s.strip_suffix(['x', 'X']) {
+            u32::from_str_radix(ext, self.base)
+                .ok()
+                .and_then(ExtendedId::new)
+                .map(CanId::from)
+        } else {
+            u16::from_str_radix(s, self.base)
+                .ok()
+                .and_then(StandardId::new)
+                .map(CanId::from)
+        }
+    }
+
+    fn parse_data(&self, tokens: &[&str], len: usize) -> Option<Vec<u8>> {
+        if tokens.len() < len {
+            return None;
+        }
+        tokens[..len]
+            .iter()
+            .map(|t| u8::from_str_radix(t, self.base).ok())
+            .collect()
+    }
+}
+
+fn parse_direction(s: &str) -> Option<Direction> {
+    match s {
+        "Rx" => Some(Direction::Rx),
+        "Tx" | "TxRq" => Some(Direction::Tx),
+        _ => None,
+    }
+}
+
+/// ASC error frames don't carry the SocketCAN error class details
+fn error_frame() -> Option<CanErrorFrame> {
+    CanErrorFrame::new_error(0, &[]).ok()
+}
+
+/// <weekday> <month> <day> <hh:mm:ss[.fff]> [am|pm] <year>
+fn parse_date(tokens: &[&str]) -> Option<Timestamp> {
+    let month = month_from_name(tokens.get(1)?)?;
+    let day: u32 = tokens.get(2)?.parse().ok()?;
+
+    let mut time_parts = tokens.get(3)?.splitn(3, ':');
+    let mut hour: u32 = time_parts.next()?.parse().ok()?;
+    let min: u32 = time_parts.next()?.parse().ok()?;
+    let sec = parse_decimal_seconds(time_parts.next()?)?;
+
+    let mut year_idx = 4;
+    match tokens.get(4).map(|t| t.to_ascii_lowercase()).as_deref() {
+        Some("am") => {
+            if hour == 12 {
+                hour = 0;
+            }
+            year_idx = 5;
+        }
+        Some("pm") => {
+            if hour != 12 {
+                hour += 12;
+            }
+            year_idx = 5;
+        }
+        _ => (),
+    }
+    let year: i64 = tokens.get(year_idx)?.parse().ok()?;
+
+    Some(Timestamp {
+        seconds: unix_seconds(year, month, day, hour, min, 0) + sec.seconds,
+        nanoseconds: sec.nanoseconds,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(seconds: i64, nanoseconds: i64) -> Timestamp {
+        Timestamp {
+            seconds,
+            nanoseconds,
+        }
+    }
+
+    #[test]
+    fn date_header() {
+        let tokens: Vec<&str> = "Thu Jun 6 11:29:54.500 am 2024".split(' ').collect();
+        assert_eq!(parse_date(&tokens), Some(ts(1717673394, 500_000_000)));
+        let tokens: Vec<&str> = "Thu Jun 6 11:29:54.500 pm 2024".split(' ').collect();
+        assert_eq!(parse_date(&tokens), Some(ts(1717716594, 500_000_000)));
+        let tokens: Vec<&str> = "Thu Jun 06 11:29:54 2024".split(' ').collect();
+        assert_eq!(parse_date(&tokens), Some(ts(1717673394, 0)));
+    }
+
+    #[test]
+    fn classic_frames() {
+        let mut p = Parser::new(false);
+        let f = p
+            .parse_line("   0.015991 1  18A             Rx   d 2 F4 7E  Length = 0 BitCount = 0")
+            .unwrap();
+        assert_eq!(f.timestamp, Some(ts(0, 15_991_000)));
+        assert_eq!(f.interface.as_deref(), Some("1"));
+        assert_eq!(f.direction, Some(Direction::Rx));
+        let CanAnyFrame::Normal(frame) = f.frame else {
+            panic!();
+        };
+        assert_eq!(frame.id(), StandardId::new(0x18A).unwrap().into());
+        assert_eq!(frame.data(), &[0xF4, 0x7E]);
+
+        let f = p.parse_line("1.5 2 1BED56DAx Tx d 1 01").unwrap();
+        assert_eq!(f.direction, Some(Direction::Tx));
+        assert_eq!(f.frame_id(), ExtendedId::new(0x1BED56DA).unwrap().into());
+
+        let f = p.parse_line("1.6 1 1C9 Rx r 8").unwrap();
+        let CanAnyFrame::Remote(frame) = f.frame else {
+            panic!();
+        };
+        assert_eq!(frame.dlc(), 8);
+
+        let f = p.parse_line("1.7 1 ErrorFrame").unwrap();
+        assert!(matches!(f.frame, CanAnyFrame::Error(_)));
+
+        assert!(p.parse_line("1.8 1 Statistic: D 0 R 0").is_none());
+        assert!(p.parse_line("// version 13.0.0").is_none());
+    }
+
+    #[test]
+    fn decimal_base_and_relative_timestamps() {
+        let mut p = Parser::new(false);
+        assert!(p.parse_line("base dec  timestamps relative").is_none());
+        let f = p.parse_line("1.0 1 394 Rx d 2 244 126").unwrap();
+        assert_eq!(f.timestamp, Some(ts(1, 0)));
+        assert_eq!(f.frame_id(), StandardId::new(394).unwrap().into());
+        let CanAnyFrame::Normal(frame) = f.frame else {
+            panic!();
+        };
+        assert_eq!(frame.data(), &[0xF4, 0x7E]);
+
+        let f = p.parse_line("0.25 1 394 Rx d 0").unwrap();
+        assert_eq!(f.timestamp, Some(ts(1, 250_000_000)));
+    }
+
+    #[test]
+    fn fd_frames() {
+        let mut p = Parser::new(false);
+        let f = p
+            .parse_line(
+                "2.0 CANFD 1 Rx 2CD 1 0 9 12 01 02 03 04 05 06 07 08 09 0A 0B 0C 0 0 3000 0",
+            )
+            .unwrap();
+        assert_eq!(f.interface.as_deref(), Some("1"));
+        let CanAnyFrame::Fd(frame) = f.frame else {
+            panic!();
+        };
+        assert_eq!(frame.id(), StandardId::new(0x2CD).unwrap().into());
+        assert!(frame.is_brs());
+        assert!(!frame.is_esi());
+        assert_eq!(frame.data().len(), 12);
+
+        let f = p
+            .parse_line("2.1 CANFD 2 Tx 17F4200Ax  SomeMessage 0 1 1 1 10 0 0 1000 0")
+            .unwrap();
+        assert_eq!(f.interface.as_deref(), Some("2"));
+        let CanAnyFrame::Fd(frame) = f.frame else {
+            panic!();
+        };
+        assert_eq!(frame.id(), ExtendedId::new(0x17F4200A).unwrap().into());
+        assert!(frame.is_esi());
+        assert_eq!(frame.data(), &[0x10]);
+
+        let f = p.parse_line("2.2 CANFD 1 Rx ErrorFrame").unwrap();
+        assert!(matches!(f.frame, CanAnyFrame::Error(_)));
+    }
+
+    #[test]
+    fn absolute_timestamps() {
+        let mut p = Parser::new(true);
+        assert!(p
+            .parse_line("date Thu Jun 6 11:29:54.000 am 2024")
+            .is_none());
+        let f = p.parse_line("0.5 1 18A Rx d 0").unwrap();
+        assert_eq!(f.timestamp, Some(ts(1717673394, 500_000_000)));
+    }
+
+    impl LogFrame {
+        fn frame_id(&self) -> CanId {
+            match &self.frame {
+                CanAnyFrame::Normal(f) => f.id(),
+                CanAnyFrame::Remote(f) => f.id(),
+                CanAnyFrame::Error(f) => f.id(),
+                CanAnyFrame::Fd(f) => f.id(),
+            }
+        }
+    }
+}
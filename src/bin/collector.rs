@@ -2,24 +2,57 @@ use anyhow::anyhow;
 use auxon_sdk::plugin_utils::serde::from_str;
 use auxon_sdk::{init_tracing, plugin_utils::ingest::Config};
 use futures_util::StreamExt;
-use modality_can::{CanParser, Dbc, HasCommonConfig, Sender, PLUGIN_VERSION};
+use modality_can::{CanParser, Dbc, HasCommonConfig, InterfaceTimelines, Sender, PLUGIN_VERSION};
 use serde::{Deserialize, Serialize};
 use socketcan::{
     nl::{CanBitTiming, CanCtrlMode, CanCtrlModes},
     tokio::CanFdSocket,
-    CanInterface, SetCanParams, SocketOptions,
+    CanInterface, SetCanParams, SocketOptions, Timestamp,
 };
-use std::str::FromStr;
+use std::{
+    collections::HashSet,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+use tokio::time::MissedTickBehavior;
 use tokio_util::{sync::CancellationToken, task::TaskTracker};
 use tracing::{debug, error, info};
 
-/// Collect CAN data from a SocketCAN interface.
+/// Collect CAN data from one or more SocketCAN interfaces.
 #[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(default, rename_all = "kebab-case")]
 struct CollectorConfig {
     /// The SocketCAN interface to use. Defaults to 'can0'.
+    /// Ignored when `interfaces` is provided.
     interface: Option<String>,
 
+    /// The SocketCAN interfaces to collect from concurrently.
+    /// Settings not given for an interface are taken from the top-level settings.
+    interfaces: Option<Vec<InterfaceConfig>>,
+
+    #[serde(flatten)]
+    settings: InterfaceSettings,
+}
+
+impl HasCommonConfig for CollectorConfig {
+    fn common_config(&self) -> &modality_can::CommonConfig {
+        &self.settings.common
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct InterfaceConfig {
+    /// The SocketCAN interface name.
+    name: String,
+
+    #[serde(flatten)]
+    settings: InterfaceSettings,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct InterfaceSettings {
     /// List of CAN filters to apply.
     filters: Option<Vec<CanFilter>>,
 
@@ -28,6 +61,11 @@ struct CollectorConfig {
     #[serde(deserialize_with = "from_str", alias = "hw_timestamps")]
     hw_timestamps: Option<bool>,
 
+    /// Receive error frames.
+    /// Defaults to true.
+    #[serde(deserialize_with = "from_str", alias = "error_frames")]
+    error_frames: Option<bool>,
+
     /// Brings the interface up by settings its “up” flag enabled via netlink.
     /// Defaults to false.
     /// This is a privileged operation that requires the `CAP_NET_ADMIN` capability.
@@ -75,12 +113,97 @@ struct CollectorConfig {
     common: modality_can::CommonConfig,
 }
 
-impl HasCommonConfig for CollectorConfig {
-    fn common_config(&self) -> &modality_can::CommonConfig {
-        &self.common
+impl InterfaceSettings {
+    /// Fill in any settings that weren't given from `defaults`
+    fn or(self, defaults: &InterfaceSettings) -> Self {
+        // Destructured exhaustively, so that a new setting can't be left out of the merge
+        let InterfaceSettings {
+            filters,
+            hw_timestamps,
+            error_frames,
+            bring_up,
+            bitrate,
+            data_bitrate,
+            restart_ms,
+            termination,
+            listen_only,
+            fd,
+            common,
+        } = self;
+        InterfaceSettings {
+            filters: filters.or_else(|| defaults.filters.clone()),
+            hw_timestamps: hw_timestamps.or(defaults.hw_timestamps),
+            error_frames: error_frames.or(defaults.error_frames),
+            bring_up: bring_up.or(defaults.bring_up),
+            bitrate: bitrate.or(defaults.bitrate),
+            data_bitrate: data_bitrate.or(defaults.data_bitrate),
+            restart_ms: restart_ms.or(defaults.restart_ms),
+            termination: termination.or(defaults.termination),
+            listen_only: listen_only.or(defaults.listen_only),
+            fd: fd.or(defaults.fd),
+            common: common.or(&defaults.common),
+        }
     }
 }
 
+impl CollectorConfig {
+    /// The interfaces to collect from, with their effective settings
+    fn interfaces(&self) -> Result<Vec<InterfaceConfig>, anyhow::Error> {
+        let interfaces = match self.interfaces.as_ref() {
+            Some(interfaces) if !interfaces.is_empty() => interfaces
+                .iter()
+                .map(|iface| InterfaceConfig {
+                    name: iface.name.clone(),
+                    settings: iface.settings.clone().or(&self.settings),
+                })
+                .collect::<Vec<_>>(),
+            _ => vec![InterfaceConfig {
+                name: self.interface.clone().unwrap_or_else(|| "can0".to_owned()),
+                settings: self.settings.clone(),
+            }],
+        };
+
+        let mut names = HashSet::new();
+        for iface in interfaces.iter() {
+            if iface.name.is_empty() {
+                return Err(anyhow!("Interface names must not be empty"));
+            }
+            if !names.insert(iface.name.as_str()) {
+                return Err(anyhow!(
+                    "The CAN interface '{}' is configured more than once",
+                    iface.name
+                ));
+            }
+        }
+
+        Ok(interfaces)
+    }
+}
+
+/// An interface being collected from
+struct Collection {
+    name: String,
+    parser: CanParser,
+    /// The timestamp of the most recent frame, and when it was received
+    last_timestamp: Option<(Timestamp, Instant)>,
+}
+
+impl Collection {
+    /// The current time in the interface's clock, extrapolated from the most recent frame
+    fn now(&self) -> Option<Timestamp> {
+        let (ts, received) = self.last_timestamp.as_ref()?;
+        let elapsed = received.elapsed();
+        let nanos = ts.nanoseconds + i64::from(elapsed.subsec_nanos());
+        Some(Timestamp {
+            seconds: ts.seconds + elapsed.as_secs() as i64 + nanos / 1_000_000_000,
+            nanoseconds: nanos % 1_000_000_000,
+        })
+    }
+}
+
+/// How often periodic messages are checked for timeouts while the bus is quiet
+const CYCLE_TIME_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     init_tracing!(tracing_subscriber::EnvFilter::new(format!(
@@ -113,67 +236,238 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                 filters.push(f_toml);
             }
             Ok(Some(("filters".to_owned(), toml::Value::Array(filters))))
+        } else if env_key == "INTERFACES" {
+            let interfaces = env_val
+                .split(',')
+                .map(|name| {
+                    let mut iface = toml::value::Table::new();
+                    iface.insert("name".to_owned(), name.trim().into());
+                    toml::Value::Table(iface)
+                })
+                .collect();
+            Ok(Some((
+                "interfaces".to_owned(),
+                toml::Value::Array(interfaces),
+            )))
         } else {
             Ok(None)
         }
     })?;
 
-    let dbc_path = match config.plugin.common.envsub_dbc_path() {
-        Ok(maybe_cfg) => maybe_cfg,
-        Err(e) => {
-            error!(%e, "Failed to run envsub on DBC  path from reflector configuration file");
-            config.plugin.common.dbc.clone()
+    let mut interfaces = config.plugin.interfaces()?;
+    if let [iface] = interfaces.as_mut_slice() {
+        let common = &mut iface.settings.common;
+        common.timeline_from_interface = common.timeline_from_interface.or(Some(false));
+    }
+
+    let mut collections = Vec::with_capacity(interfaces.len());
+    let mut socks = Vec::with_capacity(interfaces.len());
+    let mut interface_timelines = Vec::with_capacity(interfaces.len());
+    for iface in interfaces.into_iter() {
+        let settings = &iface.settings;
+
+        let dbc_path = match settings.common.envsub_dbc_path() {
+            Ok(maybe_cfg) => maybe_cfg,
+            Err(e) => {
+                error!(%e, "Failed to run envsub on DBC  path from reflector configuration file");
+                settings.common.dbc.clone()
+            }
+        };
+
+        let dbc = dbc_path.as_ref().map(Dbc::from_file).transpose()?;
+
+        let parser = CanParser::new(&settings.common, dbc.as_ref().map(|dbc| &dbc.inner))?;
+
+        socks.push(open_interface(&iface.name, settings)?);
+
+        let uses_hw_timestamps = settings.hw_timestamps.unwrap_or(true);
+        let timeline_attrs = vec![
+            (
+                "timeline.modality_can.socketcan.interface".into(),
+                iface.name.as_str().into(),
+            ),
+            (
+                "timeline.modality_can.socketcan.hw_timestamp".into(),
+                uses_hw_timestamps.into(),
+            ),
+            (
+                "timeline.clock_style".into(),
+                if uses_hw_timestamps {
+                    "relative".into()
+                } else {
+                    "absolute".into()
+                },
+            ),
+        ];
+
+        interface_timelines.push((
+            iface.name.clone(),
+            InterfaceTimelines {
+                config: iface.settings.common,
+                dbc,
+                timeline_attrs: timeline_attrs.into_iter().collect(),
+            },
+        ));
+        collections.push(Collection {
+            name: iface.name,
+            parser,
+            last_timestamp: None,
+        });
+    }
+
+    let client = config.connect_and_authenticate_ingest().await?;
+    info!("Connected to Modality backend");
+
+    let common_timeline_attrs = vec![(
+        "timeline.modality_can.plugin.version".into(),
+        PLUGIN_VERSION.into(),
+    )];
+    let mut sender = Sender::new(
+        client,
+        common_timeline_attrs.into_iter().collect(),
+        None,
+        config,
+    );
+    for (name, timelines) in interface_timelines.into_iter() {
+        sender.add_interface(name, timelines);
+    }
+
+    // Tag each socket's frames with the index of its collection
+    let mut frames = futures_util::stream::select_all(
+        socks
+            .into_iter()
+            .enumerate()
+            .map(|(idx, sock)| sock.map(move |res| (idx, res))),
+    );
+
+    let cancel_token = CancellationToken::new();
+
+    let task_tracker = TaskTracker::new();
+    let task_cancel_token = cancel_token.clone();
+    let mut join_handle: tokio::task::JoinHandle<Result<(), anyhow::Error>> =
+        task_tracker.spawn(async move {
+            let mut poll_interval = tokio::time::interval(CYCLE_TIME_POLL_INTERVAL);
+            poll_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = task_cancel_token.cancelled() => {
+                        // Task was cancelled
+                        for collection in collections.iter_mut() {
+                            for parsed_frame in collection.parser.finish().into_iter() {
+                                sender.handle_frame(parsed_frame).await?;
+                            }
+                        }
+                        sender.close().await?;
+                        break;
+                    }
+                    _ = poll_interval.tick() => {
+                        for collection in collections.iter_mut() {
+                            let Some(now) = collection.now() else {
+                                continue;
+                            };
+                            for parsed_frame in collection
+                                .parser
+                                .poll(Some(&collection.name), now)
+                                .into_iter()
+                            {
+                                sender.handle_frame(parsed_frame).await?;
+                            }
+                        }
+                    }
+                    maybe_res = frames.next() => {
+                        if let Some((idx, res)) = maybe_res {
+                            let collection = &mut collections[idx];
+                            let (frame, hw_timestamp) = res.map_err(|e| {
+                                anyhow!(
+                                    "Failed to read from CAN interface '{}'. {}",
+                                    collection.name,
+                                    e
+                                )
+                            })?;
+                            if let Some(ts) = hw_timestamp {
+                                collection.last_timestamp = Some((ts, Instant::now()));
+                            }
+                            let parsed_frames = collection.parser.parse_all(
+                                &frame,
+                                hw_timestamp,
+                                Some(&collection.name),
+                            )?;
+                            for parsed_frame in parsed_frames.into_iter() {
+                                sender.handle_frame(parsed_frame).await?;
+                            }
+                        } else {
+                            break;
+                        }
+                    }
+                }
+            }
+            Ok(())
+        });
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {
+            debug!("User signaled shutdown");
+        }
+        res = &mut join_handle => {
+            match res? {
+                Ok(_) => {},
+                Err(e) => return Err(e.into()),
+            }
         }
     };
 
-    let dbc = dbc_path.as_ref().map(Dbc::from_file).transpose()?;
+    cancel_token.cancel();
+    task_tracker.close();
+    task_tracker.wait().await;
 
-    let mut parser = CanParser::new(&config.plugin.common, dbc.as_ref().map(|dbc| &dbc.inner))?;
+    Ok(())
+}
 
-    let iface = config.plugin.interface.as_deref().unwrap_or("can0");
+/// Configure the interface as requested, and open a socket on it
+fn open_interface(iface: &str, settings: &InterfaceSettings) -> Result<CanFdSocket, anyhow::Error> {
     info!(interface = iface, "Opening CAN interface");
 
     let can_iface = CanInterface::open(iface)
         .map_err(|e| anyhow!("Failed to open CAN interface '{}'. {}", iface, e))?;
 
     // Configure the interface if asked to do so
-    let uses_params = config.plugin.bitrate.is_some()
-        || config.plugin.data_bitrate.is_some()
-        || config.plugin.restart_ms.is_some()
-        || config.plugin.termination.is_some()
-        || config.plugin.listen_only.is_some()
-        || config.plugin.fd.is_some();
+    let uses_params = settings.bitrate.is_some()
+        || settings.data_bitrate.is_some()
+        || settings.restart_ms.is_some()
+        || settings.termination.is_some()
+        || settings.listen_only.is_some()
+        || settings.fd.is_some();
     if uses_params {
         let mut params = SetCanParams::default();
 
         // Set/clear control mode bits
-        if config.plugin.listen_only.is_some() || config.plugin.fd.is_some() {
+        if settings.listen_only.is_some() || settings.fd.is_some() {
             let mut ctrl_modes = CanCtrlModes::default();
-            if let Some(on) = config.plugin.listen_only {
+            if let Some(on) = settings.listen_only {
                 ctrl_modes.add(CanCtrlMode::ListenOnly, on);
             }
-            if let Some(on) = config.plugin.fd {
+            if let Some(on) = settings.fd {
                 ctrl_modes.add(CanCtrlMode::Fd, on);
             }
             params.ctrl_mode = Some(ctrl_modes);
         }
 
-        if let Some(bitrate) = config.plugin.bitrate {
+        if let Some(bitrate) = settings.bitrate {
             params.bit_timing = Some(CanBitTiming {
                 bitrate,
                 ..Default::default()
             });
         }
-        if let Some(bitrate) = config.plugin.data_bitrate {
+        if let Some(bitrate) = settings.data_bitrate {
             params.data_bit_timing = Some(CanBitTiming {
                 bitrate,
                 ..Default::default()
             });
         }
-        if let Some(restart_ms) = config.plugin.restart_ms {
+        if let Some(restart_ms) = settings.restart_ms {
             params.restart_ms = Some(restart_ms);
         }
-        if let Some(termination) = config.plugin.termination {
+        if let Some(termination) = settings.termination {
             params.termination = Some(termination);
         }
 
@@ -186,7 +480,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         })?;
     }
 
-    if config.plugin.bring_up.unwrap_or(false) {
+    if settings.bring_up.unwrap_or(false) {
         can_iface
             .bring_up()
             .map_err(|e| anyhow!("Failed to bring up CAN interface '{}'. {}", iface, e))?;
@@ -198,96 +492,25 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let mut sock = CanFdSocket::open(iface)
         .map_err(|e| anyhow!("Failed to open CAN interface '{}'. {}", iface, e))?;
 
-    let uses_hw_timestamps = config.plugin.hw_timestamps.unwrap_or(true);
-    if uses_hw_timestamps {
+    if settings.hw_timestamps.unwrap_or(true) {
         sock.set_timestamps(true)
-            .map_err(|e| anyhow!("Failed to enable timestamps. {}", e))?;
+            .map_err(|e| anyhow!("Failed to enable timestamps on '{}'. {}", iface, e))?;
     }
 
     sock.set_filter_accept_all()?;
 
-    let can_filters = config.plugin.filters.as_deref().unwrap_or(&[]);
+    if settings.error_frames.unwrap_or(true) {
+        sock.set_error_filter_accept_all()
+            .map_err(|e| anyhow!("Failed to enable error frames on '{}'. {}", iface, e))?;
+    }
+
+    let can_filters = settings.filters.as_deref().unwrap_or(&[]);
     if !can_filters.is_empty() {
         sock.set_filters(can_filters)
-            .map_err(|e| anyhow!("Failed to set CAN filters. {}", e))?;
+            .map_err(|e| anyhow!("Failed to set CAN filters on '{}'. {}", iface, e))?;
     }
 
-    let client = config.connect_and_authenticate_ingest().await?;
-    info!("Connected to Modality backend");
-
-    let common_timeline_attrs = vec![
-        (
-            "timeline.modality_can.plugin.version".into(),
-            PLUGIN_VERSION.into(),
-        ),
-        (
-            "timeline.modality_can.socketcan.interface".into(),
-            iface.into(),
-        ),
-        (
-            "timeline.modality_can.socketcan.hw_timestamp".into(),
-            uses_hw_timestamps.into(),
-        ),
-        (
-            "timeline.clock_style".into(),
-            if uses_hw_timestamps {
-                "relative".into()
-            } else {
-                "absolute".into()
-            },
-        ),
-    ];
-    let mut sender = Sender::new(
-        client,
-        common_timeline_attrs.into_iter().collect(),
-        dbc,
-        config,
-    );
-
-    let cancel_token = CancellationToken::new();
-
-    let task_tracker = TaskTracker::new();
-    let task_cancel_token = cancel_token.clone();
-    let mut join_handle: tokio::task::JoinHandle<Result<(), anyhow::Error>> =
-        task_tracker.spawn(async move {
-            loop {
-                tokio::select! {
-                    _ = task_cancel_token.cancelled() => {
-                        // Task was cancelled
-                        sender.close().await?;
-                        break;
-                    }
-                    maybe_res = sock.next() => {
-                        if let Some(res) = maybe_res {
-                            let (frame, hw_timestamp) = res?;
-                            let parsed_frame = parser.parse(&frame, hw_timestamp)?;
-                            sender.handle_frame(parsed_frame).await?;
-                        } else {
-                            break;
-                        }
-                    }
-                }
-            }
-            Ok(())
-        });
-
-    tokio::select! {
-        _ = tokio::signal::ctrl_c() => {
-            debug!("User signaled shutdown");
-        }
-        res = &mut join_handle => {
-            match res? {
-                Ok(_) => {},
-                Err(e) => return Err(e.into()),
-            }
-        }
-    };
-
-    cancel_token.cancel();
-    task_tracker.close();
-    task_tracker.wait().await;
-
-    Ok(())
+    Ok(sock)
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -387,4 +610,64 @@ mod test {
             })
         );
     }
+
+    #[test]
+    fn interface_settings() {
+        let cfg: CollectorConfig = toml::from_str(
+            r#"
+            interface = "vcan9"
+            bitrate = 500000
+            dbc = "body.dbc"
+            default-timeline = "bus"
+
+            [[interfaces]]
+            name = "can0"
+
+            [[interfaces]]
+            name = "can1"
+            bitrate = 250000
+            dbc = "powertrain.dbc"
+            hw-timestamps = false
+            filters = [{ inverted = false, id = 1, mask = 2 }]
+            "#,
+        )
+        .unwrap();
+        let ifaces = cfg.interfaces().unwrap();
+        assert_eq!(ifaces.len(), 2);
+
+        assert_eq!(ifaces[0].name, "can0");
+        assert_eq!(ifaces[0].settings.bitrate, Some(500000));
+        assert_eq!(ifaces[0].settings.common.dbc, Some("body.dbc".into()));
+        assert_eq!(ifaces[0].settings.hw_timestamps, None);
+        assert_eq!(ifaces[0].settings.filters, None);
+
+        assert_eq!(ifaces[1].name, "can1");
+        assert_eq!(ifaces[1].settings.bitrate, Some(250000));
+        assert_eq!(ifaces[1].settings.common.dbc, Some("powertrain.dbc".into()));
+        assert_eq!(ifaces[1].settings.hw_timestamps, Some(false));
+        assert_eq!(ifaces[1].settings.filters.as_ref().unwrap().len(), 1);
+        for iface in ifaces.iter() {
+            assert_eq!(
+                iface.settings.common.default_timeline.as_deref(),
+                Some("bus")
+            );
+        }
+
+        let cfg: CollectorConfig = toml::from_str(r#"bitrate = 500000"#).unwrap();
+        let ifaces = cfg.interfaces().unwrap();
+        assert_eq!(ifaces.len(), 1);
+        assert_eq!(ifaces[0].name, "can0");
+        assert_eq!(ifaces[0].settings.bitrate, Some(500000));
+
+        let cfg: CollectorConfig = toml::from_str(
+            r#"
+            [[interfaces]]
+            name = "can0"
+            [[interfaces]]
+            name = "can0"
+            "#,
+        )
+        .unwrap();
+        assert!(cfg.interfaces().is_err());
+    }
 }
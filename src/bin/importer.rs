@@ -2,11 +2,13 @@ use anyhow::anyhow;
 use auxon_sdk::plugin_utils::serde::from_str;
 use auxon_sdk::{init_tracing, plugin_utils::ingest::Config};
 use clap::Parser;
-use modality_can::{candump, CanParser, Dbc, HasCommonConfig, Sender, PLUGIN_VERSION};
+use modality_can::{
+    asc, blf, candump, mdf, pcap, trc, CanParser, Dbc, HasCommonConfig, LogFormat, LogFrame,
+    Sender, PLUGIN_VERSION,
+};
 use serde::{Deserialize, Serialize};
-use std::io::BufRead;
 use std::{fs::File, io::BufReader, path::PathBuf};
-use tracing::{error, info, warn};
+use tracing::{error, info};
 
 /// Import CAN log files
 #[derive(clap::Parser)]
@@ -24,10 +26,19 @@ struct ImporterConfig {
     #[serde(deserialize_with = "from_str", alias = "absolute_timestamps")]
     absolute_timestamps: Option<bool>,
 
+    /// The candump log timestamps are deltas from the previous frame (`candump -td`).
+    /// Defaults to false.
+    #[serde(deserialize_with = "from_str", alias = "delta_timestamps")]
+    delta_timestamps: Option<bool>,
+
     /// File to import (e.g. candump.log)
     #[serde(deserialize_with = "from_str")]
     file: Option<PathBuf>,
 
+    /// The log file format, one of 'candump', 'asc', 'blf', 'pcap', 'mdf' or 'trc'.
+    /// Defaults to being picked from the file extension, falling back to 'candump'.
+    format: Option<LogFormat>,
+
     #[serde(flatten)]
     common: modality_can::CommonConfig,
 }
@@ -74,9 +85,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         )
     })?;
 
-    let mut reader = BufReader::new(file);
+    let format = config
+        .plugin
+        .format
+        .or_else(|| LogFormat::from_path(log_file_path))
+        .unwrap_or(LogFormat::Candump);
+    info!(format = format.as_str(), "Using log file format");
+
+    let reader = BufReader::new(file);
 
     let absolute_timestamps = config.plugin.absolute_timestamps.unwrap_or(false);
+    let frames: Box<dyn Iterator<Item = Result<LogFrame, anyhow::Error>>> = match format {
+        LogFormat::Candump => Box::new(candump::Reader::new(
+            reader,
+            config.plugin.delta_timestamps.unwrap_or(false),
+        )),
+        LogFormat::Asc => Box::new(asc::Reader::new(reader, absolute_timestamps)),
+        LogFormat::Blf => Box::new(blf::Reader::new(reader, absolute_timestamps)?),
+        LogFormat::Pcap => Box::new(pcap::Reader::new(reader)?),
+        LogFormat::Mdf => Box::new(mdf::Reader::new(reader, absolute_timestamps)?),
+        LogFormat::Trc => Box::new(trc::Reader::new(reader, absolute_timestamps)),
+    };
+
     let common_timeline_attrs = vec![
         (
             "timeline.modality_can.plugin.version".into(),
@@ -98,6 +128,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                 .unwrap_or_else(|| "NA".to_owned())
                 .into(),
         ),
+        (
+            "timeline.modality_can.importer.format".into(),
+            format.as_str().into(),
+        ),
     ];
 
     let client = config.connect_and_authenticate_ingest().await?;
@@ -111,29 +145,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     );
 
     let mut frame_count = 0_u64;
-    let mut line_buf = String::with_capacity(8 * 1024);
-    loop {
-        line_buf.clear();
-        let line_size = reader.read_line(&mut line_buf)?;
-        if line_size == 0 {
-            break;
-        }
-
-        // Skip if not an entry (comment/etc)
-        if !line_buf.starts_with(candump::SOF) {
-            continue;
-        }
-
-        match candump::parse(&line_buf) {
-            Ok((_, (timestamp, _iface, frame))) => {
-                let parsed_frame = parser.parse(&frame, Some(timestamp))?;
-                sender.handle_frame(parsed_frame).await?;
-                frame_count += 1;
-            }
-            Err(e) => {
-                warn!(%e, line = line_buf, "Failed to parse log file line");
+    for log_frame in frames {
+        let log_frame = log_frame?;
+        let parsed_frames = parser.parse_all(
+            &log_frame.frame,
+            log_frame.timestamp,
+            log_frame.interface.as_deref(),
+        )?;
+        for mut parsed_frame in parsed_frames.into_iter() {
+            if let Some(direction) = log_frame.direction {
+                parsed_frame.set_direction(direction);
             }
+            sender.handle_frame(parsed_frame).await?;
         }
+        frame_count += 1;
+    }
+
+    for parsed_frame in parser.finish().into_iter() {
+        sender.handle_frame(parsed_frame).await?;
     }
 
     sender.close().await?;
@@ -0,0 +1,729 @@
+//! Vector Binary Logging Format (.blf) reader.
+//!
+//! The file is a sequence of `LOBJ` objects, most of which are log containers
+//! holding (usually zlib compressed) runs of the actual log objects.
+//! Objects may span container boundaries.
+//! Object timestamps are relative to the start of the measurement unless absolute
+//! timestamps are requested, in which case the file header start time (assumed to be UTC)
+//! is added.
+
+use crate::error_frame::{
+    CAN_ERR_PROT, CAN_ERR_PROT_BIT, CAN_ERR_PROT_FORM, CAN_ERR_PROT_LOC_ACK_DEL,
+    CAN_ERR_PROT_LOC_CRC_SEQ, CAN_ERR_PROT_STUFF, CAN_ERR_PROT_TX, CAN_ERR_PROT_UNSPEC,
+};
+use crate::import::{
+    add_timestamps, timestamp_from_nanos, unix_seconds, Direction, LogFrame, MAX_CLASSIC_DATA_LEN,
+};
+use anyhow::{anyhow, bail};
+use flate2::read::ZlibDecoder;
+use socketcan::{
+    frame::FdFlags, CanAnyFrame, CanDataFrame, CanErrorFrame, CanFdFrame, CanRemoteFrame,
+    EmbeddedFrame, ExtendedId, Id as CanId, StandardId, Timestamp,
+};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Read};
+use tracing::{debug, warn};
+
+const FILE_SIGNATURE: &[u8] = b"LOGG";
+const OBJ_SIGNATURE: &[u8] = b"LOBJ";
+
+const FILE_HEADER_MIN_SIZE: usize = 72;
+const FILE_HEADER_START_TIME_OFFSET: usize = 40;
+const OBJ_HEADER_BASE_SIZE: usize = 16;
+const OBJ_HEADER_TIMESTAMP_OFFSET: usize = 24;
+const LOG_CONTAINER_HEADER_SIZE: usize = 16;
+
+const CAN_MESSAGE: u32 = 1;
+const LOG_CONTAINER: u32 = 10;
+const CAN_ERROR_EXT: u32 = 73;
+const CAN_MESSAGE2: u32 = 86;
+const CAN_FD_MESSAGE: u32 = 100;
+const CAN_FD_MESSAGE_64: u32 = 101;
+
+const NO_COMPRESSION: u16 = 0;
+const ZLIB_DEFLATE: u16 = 2;
+
+const TIME_TEN_MICS: u32 = 0x0000_0001;
+
+const CAN_MSG_EXT: u32 = 0x8000_0000;
+const CAN_MSG_DIR_TX: u8 = 0x01;
+const CAN_MSG_REMOTE: u8 = 0x80;
+
+const CAN_FD_EDL: u8 = 0x01;
+const CAN_FD_BRS: u8 = 0x02;
+const CAN_FD_ESI: u8 = 0x04;
+
+// Which of the CAN_ERROR_EXT error details are valid
+const CAN_ERROR_EXT_SJA1000_ECC: u32 = 0x0001;
+const CAN_ERROR_EXT_CAN_CORE_CODE: u32 = 0x0002;
+
+// SJA1000 error code capture register layout, also used by the low byte of the extended flags
+const ECC_SEGMENT: u8 = 0x1F;
+const ECC_DIR_RX: u8 = 0x20;
+
+const CAN_FD_64_REMOTE: u32 = 0x0010;
+const CAN_FD_64_EDL: u32 = 0x1000;
+const CAN_FD_64_BRS: u32 = 0x2000;
+const CAN_FD_64_ESI: u32 = 0x4000;
+const CAN_FD_64_HEADER_SIZE: usize = 40;
+
+pub struct Reader<R> {
+    reader: R,
+    start_time: Option<Timestamp>,
+    /// Unparsed object data, from containers and top-level objects
+    buf: Vec<u8>,
+    frames: VecDeque<LogFrame>,
+    /// Object type to count of objects that aren't supported
+    unsupported_objects: BTreeMap<u32, u64>,
+    done: bool,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(mut reader: R, absolute_timestamps: bool) -> Result<Self, anyhow::Error> {
+        let mut prefix = [0_u8; 8];
+        reader
+            .read_exact(&mut prefix)
+            .map_err(|e| anyhow!("Failed to read the BLF file header. {e}"))?;
+        if &prefix[..4] != FILE_SIGNATURE {
+            bail!("Invalid BLF file signature");
+        }
+        let header_size = u32_le(&prefix, 4) as usize;
+        if header_size < FILE_HEADER_MIN_SIZE {
+            bail!("Invalid BLF file header size {header_size}");
+        }
+        let mut header = vec![0_u8; header_size];
+        header[..8].copy_from_slice(&prefix);
+        reader
+            .read_exact(&mut header[8..])
+            .map_err(|e| anyhow!("Failed to read the BLF file header. {e}"))?;
+
+        let start_time = parse_system_time(&header[FILE_HEADER_START_TIME_OFFSET..]);
+        debug!(?start_time, "Read BLF file header");
+
+        Ok(Self {
+            reader,
+            start_time: start_time.filter(|_| absolute_timestamps),
+            buf: Vec::new(),
+            frames: VecDeque::new(),
+            unsupported_objects: BTreeMap::new(),
+            done: false,
+        })
+    }
+
+    /// Read the next top-level object into the object buffer.
+    /// Returns false at the end of the file.
+    fn read_object(&mut self) -> Result<bool, anyhow::Error> {
+        let mut base = [0_u8; OBJ_HEADER_BASE_SIZE];
+        match self.reader.read_exact(&mut base) {
+            Ok(()) => (),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+            Err(e) => return Err(e.into()),
+        }
+        if &base[..4] != OBJ_SIGNATURE {
+            bail!("Invalid BLF object signature");
+        }
+        let header_size = u16_le(&base, 4) as usize;
+        let obj_size = u32_le(&base, 8) as usize;
+        let obj_type = u32_le(&base, 12);
+        if obj_size < OBJ_HEADER_BASE_SIZE || header_size > obj_size {
+            bail!("Invalid BLF object size {obj_size}");
+        }
+
+        let mut obj = vec![0_u8; obj_size];
+        obj[..OBJ_HEADER_BASE_SIZE].copy_from_slice(&base);
+        self.reader.read_exact(&mut obj[OBJ_HEADER_BASE_SIZE..])?;
+
+        // Top-level objects are padded to 4 bytes
+        let mut padding = [0_u8; 4];
+        let _ = self.reader.read_exact(&mut padding[..obj_size % 4]);
+
+        if obj_type == LOG_CONTAINER {
+            let container = obj
+                .get(header_size..)
+                .filter(|c| c.len() >= LOG_CONTAINER_HEADER_SIZE)
+                .ok_or_else(|| anyhow!("Truncated BLF log container"))?;
+            let compression = u16_le(container, 0);
+            let payload = &container[LOG_CONTAINER_HEADER_SIZE..];
+            match compression {
+                NO_COMPRESSION => self.buf.extend_from_slice(payload),
+                ZLIB_DEFLATE => {
+                    ZlibDecoder::new(payload)
+                        .read_to_end(&mut self.buf)
+                        .map_err(|e| anyhow!("Failed to decompress BLF log container. {e}"))?;
+                }
+                _ => bail!("Unsupported BLF log container compression method {compression}"),
+            }
+        } else {
+            self.buf.extend_from_slice(&obj);
+        }
+
+        Ok(true)
+    }
+
+    /// Parse all of the complete objects in the object buffer
+    fn parse_buffered_objects(&mut self) -> Result<(), anyhow::Error> {
+        let mut pos = 0;
+        loop {
+            // Objects within containers may be padded, find the start of the next one
+            let search_end = (pos + OBJ_SIGNATURE.len() + 4).min(self.buf.len());
+            match self.buf[pos..search_end]
+                .windows(OBJ_SIGNATURE.len())
+                .position(|w| w == OBJ_SIGNATURE)
+            {
+                Some(offset) => pos += offset,
+                None if search_end == self.buf.len() => break,
+                None => bail!("Could not find the next BLF object"),
+            }
+
+            if pos + OBJ_HEADER_BASE_SIZE > self.buf.len() {
+                break;
+            }
+            let obj_size = u32_le(&self.buf, pos + 8) as usize;
+            if obj_size < OBJ_HEADER_BASE_SIZE {
+                bail!("Invalid BLF object size {obj_size}");
+            }
+            if pos + obj_size > self.buf.len() {
+                // The rest is in the next container
+                break;
+            }
+
+            let obj = &self.buf[pos..pos + obj_size];
+            let obj_type = u32_le(obj, 12);
+            match parse_object(obj, obj_type) {
+                Some(mut f) => {
+                    if let Some(start) = self.start_time.as_ref() {
+                        f.timestamp = f.timestamp.map(|t| add_timestamps(start, &t));
+                    }
+                    self.frames.push_back(f);
+                }
+                None => *self.unsupported_objects.entry(obj_type).or_default() += 1,
+            }
+            pos += obj_size;
+        }
+        self.buf.drain(..pos);
+        Ok(())
+    }
+
+    fn report_unsupported_objects(&self) {
+        for (object_type, count) in self.unsupported_objects.iter() {
+            warn!(object_type, count, "Skipped unsupported BLF objects");
+        }
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<LogFrame, anyhow::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(f) = self.frames.pop_front() {
+                return Some(Ok(f));
+            }
+            if self.done {
+                return None;
+            }
+
+            let res = self
+                .read_object()
+                .and_then(|more| self.parse_buffered_objects().map(|_| more));
+            match res {
+                Ok(true) => (),
+                Ok(false) => {
+                    self.done = true;
+                    if !self.buf.is_empty() {
+                        warn!(
+                            bytes = self.buf.len(),
+                            "BLF file ended with a truncated object"
+                        );
+                    }
+                    self.report_unsupported_objects();
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// Returns None for object types that aren't supported, or are malformed
+fn parse_object(obj: &[u8], obj_type: u32) -> Option<LogFrame> {
+    let header_size = u16_le(obj, 4) as usize;
+    let header_flags = u32_le(obj.get(..OBJ_HEADER_TIMESTAMP_OFFSET)?, 16);
+    let ticks = u64_le(obj.get(..OBJ_HEADER_TIMESTAMP_OFFSET + 8)?, 24);
+    let ns = match header_flags {
+        TIME_TEN_MICS => ticks.checked_mul(10_000)?,
+        // TIME_ONE_NANS (0x0000_0002)
+        _ => ticks,
+    };
+    let timestamp = timestamp_from_nanos(ns as i64);
+    let data = obj.get(header_size..)?;
+
+    let (channel, direction, frame) = match obj_type {
+        CAN_MESSAGE | CAN_MESSAGE2 => parse_can_message(data)?,
+        CAN_FD_MESSAGE => parse_can_fd_message(data)?,
+        CAN_FD_MESSAGE_64 => parse_can_fd_message_64(data)?,
+        CAN_ERROR_EXT => parse_can_error_ext(data)?,
+        _ => return None,
+    };
+
+    Some(LogFrame {
+        timestamp: Some(timestamp),
+        interface: Some(channel.to_string()),
+        direction,
+        frame,
+    })
+}
+
+/// channel u16, flags u8, dlc u8, id u32, data [u8; 8]
+fn parse_can_message(data: &[u8]) -> Option<(u16, Option<Direction>, CanAnyFrame)> {
+    let data = data.get(..16)?;
+    let channel = u16_le(data, 0);
+    let flags = data[2];
+    let dlc = data[3] as usize;
+    let id = can_id(u32_le(data, 4))?;
+
+    let frame = if flags & CAN_MSG_REMOTE != 0 {
+        CanAnyFrame::Remote(CanRemoteFrame::new_remote(
+            id,
+            dlc.min(MAX_CLASSIC_DATA_LEN),
+        )?)
+    } else {
+        let len = dlc.min(MAX_CLASSIC_DATA_LEN);
+        CanAnyFrame::Normal(CanDataFrame::new(id, &data[8..8 + len])?)
+    };
+
+    Some((channel, Some(direction(flags & CAN_MSG_DIR_TX != 0)), frame))
+}
+
+/// channel u16, flags u8, dlc u8, id u32, frame length u32, bit count u8,
+/// FD flags u8, valid data bytes u8, reserved [u8; 5], data [u8; 64]
+fn parse_can_fd_message(data: &[u8]) -> Option<(u16, Option<Direction>, CanAnyFrame)> {
+    let data = data.get(..84)?;
+    let channel = u16_le(data, 0);
+    let flags = data[2];
+    let dlc = data[3] as usize;
+    let id = can_id(u32_le(data, 4))?;
+    let fd_flags = data[13];
+    let valid_bytes = (data[14] as usize).min(64);
+    let payload = &data[20..20 + valid_bytes];
+
+    let frame = if fd_flags & CAN_FD_EDL != 0 {
+        CanAnyFrame::Fd(CanFdFrame::with_flags(
+            id,
+            payload,
+            fd_flags_from(fd_flags & CAN_FD_BRS != 0, fd_flags & CAN_FD_ESI != 0),
+        )?)
+    } else if flags & CAN_MSG_REMOTE != 0 {
+        CanAnyFrame::Remote(CanRemoteFrame::new_remote(
+            id,
+            dlc.min(MAX_CLASSIC_DATA_LEN),
+        )?)
+    } else {
+        let len = valid_bytes.min(dlc).min(MAX_CLASSIC_DATA_LEN);
+        CanAnyFrame::Normal(CanDataFrame::new(id, &payload[..len])?)
+    };
+
+    Some((channel, Some(direction(flags & CAN_MSG_DIR_TX != 0)), frame))
+}
+
+/// channel u8, dlc u8, valid data bytes u8, tx count u8, id u32, frame length u32,
+/// flags u32, btr cfg arb u32, btr cfg data u32, brs time offset u32, crc time offset u32,
+/// bit count u16, direction u8, ext data offset u8, crc u32, data [u8; valid data bytes]
+fn parse_can_fd_message_64(data: &[u8]) -> Option<(u16, Option<Direction>, CanAnyFrame)> {
+    let header = data.get(..CAN_FD_64_HEADER_SIZE)?;
+    let channel = header[0] as u16;
+    let dlc = header[1] as usize;
+    let valid_bytes = (header[2] as usize).min(64);
+    let id = can_id(u32_le(header, 4))?;
+    let flags = u32_le(header, 12);
+    let dir = header[34];
+    let payload = data.get(CAN_FD_64_HEADER_SIZE..CAN_FD_64_HEADER_SIZE + valid_bytes)?;
+
+    let frame = if flags & CAN_FD_64_EDL != 0 {
+        CanAnyFrame::Fd(CanFdFrame::with_flags(
+            id,
+            payload,
+            fd_flags_from(flags & CAN_FD_64_BRS != 0, flags & CAN_FD_64_ESI != 0),
+        )?)
+    } else if flags & CAN_FD_64_REMOTE != 0 {
+        CanAnyFrame::Remote(CanRemoteFrame::new_remote(
+            id,
+            dlc.min(MAX_CLASSIC_DATA_LEN),
+        )?)
+    } else {
+        let len = valid_bytes.min(dlc).min(MAX_CLASSIC_DATA_LEN);
+        CanAnyFrame::Normal(CanDataFrame::new(id, &payload[..len])?)
+    };
+
+    // 0 is Rx, 1 is Tx and 2 is a Tx request
+    Some((channel, Some(direction(dir != 0)), frame))
+}
+
+/// channel u16, length u16, flags u32, ecc u8, position u8, dlc u8, reserved u8,
+/// frame length u32, id u32, ext flags u16, reserved u16, data [u8; 8]
+fn parse_can_error_ext(data: &[u8]) -> Option<(u16, Option<Direction>, CanAnyFrame)> {
+    let data = data.get(..32)?;
+    let channel = u16_le(data, 0);
+    let flags = u32_le(data, 4);
+    let ecc = data[8];
+    let flags_ext = u16_le(data, 20);
+
+    // Mapped to the SocketCAN error class and data, as the SJA1000 driver does
+    let mut class = 0;
+    let mut err = [0_u8; 8];
+    let mut is_rx = None;
+    if flags & CAN_ERROR_EXT_SJA1000_ECC != 0 {
+        class |= CAN_ERR_PROT;
+        err[2] = match ecc >> 6 {
+            0 => CAN_ERR_PROT_BIT,
+            1 => CAN_ERR_PROT_FORM,
+            2 => CAN_ERR_PROT_STUFF,
+            _ => 0,
+        };
+        err[3] = ecc & ECC_SEGMENT;
+        is_rx = Some(ecc & ECC_DIR_RX != 0);
+    } else if flags & CAN_ERROR_EXT_CAN_CORE_CODE != 0 {
+        // The CAN core error code is in the high byte of the extended flags
+        class |= CAN_ERR_PROT;
+        match flags_ext >> 8 {
+            0 => err[2] = CAN_ERR_PROT_BIT,
+            1 => err[2] = CAN_ERR_PROT_FORM,
+            2 => err[2] = CAN_ERR_PROT_STUFF,
+            // Any other error
+            3 => err[2] = CAN_ERR_PROT_UNSPEC,
+            4 => err[3] = CAN_ERR_PROT_LOC_CRC_SEQ,
+            5 => {
+                err[2] = CAN_ERR_PROT_FORM;
+                err[3] = CAN_ERR_PROT_LOC_ACK_DEL;
+            }
+            _ => (),
+        }
+        is_rx = Some(flags_ext as u8 & ECC_DIR_RX != 0);
+    }
+    if is_rx == Some(false) {
+        err[2] |= CAN_ERR_PROT_TX;
+    }
+
+    let frame = CanErrorFrame::new_error(class, &err).ok()?;
+    Some((
+        channel,
+        is_rx.map(|rx| direction(!rx)),
+        CanAnyFrame::Error(frame),
+    ))
+}
+
+fn can_id(raw: u32) -> Option<CanId> {
+    if raw & CAN_MSG_EXT != 0 {
+        ExtendedId::new(raw & !CAN_MSG_EXT).map(CanId::from)
+    } else {
+        u16::try_from(raw)
+            .ok()
+            .and_then(StandardId::new)
+            .map(CanId::from)
+    }
+}
+
+fn direction(is_tx: bool) -> Direction {
+    if is_tx {
+        Direction::Tx
+    } else {
+        Direction::Rx
+    }
+}
+
+fn fd_flags_from(brs: bool, esi: bool) -> FdFlags {
+    let mut flags = FdFlags::empty();
+    if brs {
+        flags |= FdFlags::BRS;
+    }
+    if esi {
+        flags |= FdFlags::ESI;
+    }
+    flags
+}
+
+/// Windows SYSTEMTIME: year, month, day of week, day, hour, minute, second, milliseconds
+fn parse_system_time(b: &[u8]) -> Option<Timestamp> {
+    let b = b.get(..16)?;
+    let year = u16_le(b, 0) as i64;
+    let month = u16_le(b, 2) as u32;
+    let day = u16_le(b, 6) as u32;
+    if year == 0 || !(1..=12).contains(&month) || day == 0 {
+        return None;
+    }
+    let seconds = unix_seconds(
+        year,
+        month,
+        day,
+        u16_le(b, 8) as u32,
+        u16_le(b, 10) as u32,
+        u16_le(b, 12) as u32,
+    );
+    Some(Timestamp {
+        seconds,
+        nanoseconds: u16_le(b, 14) as i64 * 1_000_000,
+    })
+}
+
+fn u16_le(b: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([b[offset], b[offset + 1]])
+}
+
+fn u32_le(b: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0_u8; 4];
+    bytes.copy_from_slice(&b[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn u64_le(b: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0_u8; 8];
+    bytes.copy_from_slice(&b[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_frame;
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::Write;
+
+    const TIME_ONE_NANS: u32 = 0x0000_0002;
+
+    fn file_header() -> Vec<u8> {
+        let mut h = vec![0_u8; 144];
+        h[..4].copy_from_slice(FILE_SIGNATURE);
+        h[4..8].copy_from_slice(&144_u32.to_le_bytes());
+        // 2024-06-06 11:29:54.250
+        for (i, v) in [2024_u16, 6, 4, 6, 11, 29, 54, 250].iter().enumerate() {
+            let o = FILE_HEADER_START_TIME_OFFSET + i * 2;
+            h[o..o + 2].copy_from_slice(&v.to_le_bytes());
+        }
+        h
+    }
+
+    fn object(obj_type: u32, timestamp_flags: u32, timestamp: u64, body: &[u8]) -> Vec<u8> {
+        let header_size = 32_u16;
+        let obj_size = header_size as u32 + body.len() as u32;
+        let mut o = Vec::new();
+        o.extend_from_slice(OBJ_SIGNATURE);
+        o.extend_from_slice(&header_size.to_le_bytes());
+        o.extend_from_slice(&1_u16.to_le_bytes());
+        o.extend_from_slice(&obj_size.to_le_bytes());
+        o.extend_from_slice(&obj_type.to_le_bytes());
+        o.extend_from_slice(&timestamp_flags.to_le_bytes());
+        o.extend_from_slice(&[0_u8; 4]);
+        o.extend_from_slice(&timestamp.to_le_bytes());
+        o.extend_from_slice(body);
+        o
+    }
+
+    fn container(objects: &[u8], compression: u16) -> Vec<u8> {
+        let payload = if compression == ZLIB_DEFLATE {
+            let mut enc = ZlibEncoder::new(Vec::new(), Compression::default());
+            enc.write_all(objects).unwrap();
+            enc.finish().unwrap()
+        } else {
+            objects.to_vec()
+        };
+        let mut body = Vec::new();
+        body.extend_from_slice(&compression.to_le_bytes());
+        body.extend_from_slice(&[0_u8; 6]);
+        body.extend_from_slice(&(objects.len() as u32).to_le_bytes());
+        body.extend_from_slice(&[0_u8; 4]);
+        body.extend_from_slice(&payload);
+        let mut c = object(LOG_CONTAINER, 0, 0, &body);
+        c.resize(c.len() + c.len() % 4, 0);
+        c
+    }
+
+    fn can_message(channel: u16, flags: u8, id: u32, data: &[u8]) -> Vec<u8> {
+        let mut b = Vec::new();
+        b.extend_from_slice(&channel.to_le_bytes());
+        b.push(flags);
+        b.push(data.len() as u8);
+        b.extend_from_slice(&id.to_le_bytes());
+        let mut d = [0_u8; 8];
+        d[..data.len()].copy_from_slice(data);
+        b.extend_from_slice(&d);
+        b
+    }
+
+    fn can_fd_message_64(channel: u8, flags: u32, id: u32, data: &[u8]) -> Vec<u8> {
+        let mut b = vec![0_u8; CAN_FD_64_HEADER_SIZE];
+        b[0] = channel;
+        b[1] = 15;
+        b[2] = data.len() as u8;
+        b[4..8].copy_from_slice(&id.to_le_bytes());
+        b[12..16].copy_from_slice(&flags.to_le_bytes());
+        b[34] = 1;
+        b.extend_from_slice(data);
+        b
+    }
+
+    #[test]
+    fn reads_containers() {
+        let mut objects = Vec::new();
+        objects.extend(object(
+            CAN_MESSAGE,
+            TIME_ONE_NANS,
+            1_500,
+            &can_message(1, 0, 0x18A, &[0xF4, 0x7E]),
+        ));
+        objects.extend(object(
+            CAN_MESSAGE2,
+            TIME_TEN_MICS,
+            2,
+            &can_message(2, CAN_MSG_DIR_TX, 0x1BED56DA | CAN_MSG_EXT, &[1]),
+        ));
+        objects.extend(object(96, TIME_ONE_NANS, 3, &[0_u8; 8]));
+        objects.extend(object(
+            CAN_FD_MESSAGE_64,
+            TIME_ONE_NANS,
+            4,
+            &can_fd_message_64(1, CAN_FD_64_EDL | CAN_FD_64_BRS, 0x2CD, &[0xAA; 12]),
+        ));
+
+        // Split the objects across two containers, with different compression
+        let (a, b) = objects.split_at(40);
+        let mut file = file_header();
+        file.extend(container(a, ZLIB_DEFLATE));
+        file.extend(container(b, NO_COMPRESSION));
+
+        let mut reader = Reader::new(file.as_slice(), false).unwrap();
+        let frames: Vec<LogFrame> = reader.by_ref().map(|f| f.unwrap()).collect();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(reader.unsupported_objects.get(&96), Some(&1));
+
+        assert_eq!(frames[0].timestamp, Some(timestamp_from_nanos(1_500)));
+        assert_eq!(frames[0].interface.as_deref(), Some("1"));
+        assert_eq!(frames[0].direction, Some(Direction::Rx));
+        let CanAnyFrame::Normal(f) = frames[0].frame else {
+            panic!();
+        };
+        assert_eq!(f.id(), StandardId::new(0x18A).unwrap().into());
+        assert_eq!(f.data(), &[0xF4, 0x7E]);
+
+        assert_eq!(frames[1].timestamp, Some(timestamp_from_nanos(20_000)));
+        assert_eq!(frames[1].direction, Some(Direction::Tx));
+        let CanAnyFrame::Normal(f) = frames[1].frame else {
+            panic!();
+        };
+        assert_eq!(f.id(), ExtendedId::new(0x1BED56DA).unwrap().into());
+
+        let CanAnyFrame::Fd(f) = frames[2].frame else {
+            panic!();
+        };
+        assert!(f.is_brs());
+        assert_eq!(f.data(), &[0xAA; 12]);
+    }
+
+    fn can_error_ext(flags: u32, ecc: u8, position: u8, flags_ext: u16) -> Vec<u8> {
+        let mut b = vec![0_u8; 32];
+        b[..2].copy_from_slice(&1_u16.to_le_bytes());
+        b[4..8].copy_from_slice(&flags.to_le_bytes());
+        b[8] = ecc;
+        b[9] = position;
+        b[20..22].copy_from_slice(&flags_ext.to_le_bytes());
+        b
+    }
+
+    #[test]
+    fn error_frames() {
+        let error = |body: &[u8]| {
+            let (_, dir, frame) = parse_can_error_ext(body).unwrap();
+            let CanAnyFrame::Error(f) = frame else {
+                panic!();
+            };
+            let info = error_frame::decode(f.error_bits(), f.data());
+            let attr = |k: &str| {
+                info.attrs
+                    .iter()
+                    .find(|(key, _)| key == k)
+                    .map(|(_, v)| v.clone())
+            };
+            (
+                dir,
+                info.name,
+                attr("protocol.type"),
+                attr("protocol.location"),
+                attr("controller_specific"),
+            )
+        };
+
+        // SJA1000 stuff error in the data field, while receiving
+        assert_eq!(
+            error(&can_error_ext(CAN_ERROR_EXT_SJA1000_ECC, 0xAA, 0, 0)),
+            (
+                Some(Direction::Rx),
+                "protocol_violation",
+                Some("stuff".into()),
+                Some("data".into()),
+                None
+            )
+        );
+        // CAN core CRC error at bit 40, while transmitting. The bit position isn't logged.
+        assert_eq!(
+            error(&can_error_ext(
+                CAN_ERROR_EXT_CAN_CORE_CODE | 0x0004,
+                0,
+                40,
+                0x0400
+            )),
+            (
+                Some(Direction::Tx),
+                "protocol_violation",
+                Some("tx".into()),
+                Some("crc_sequence".into()),
+                None
+            )
+        );
+        // CAN core other error, while receiving and transmitting
+        assert_eq!(
+            error(&can_error_ext(CAN_ERROR_EXT_CAN_CORE_CODE, 0, 0, 0x0320)),
+            (Some(Direction::Rx), "protocol_violation", None, None, None)
+        );
+        assert_eq!(
+            error(&can_error_ext(CAN_ERROR_EXT_CAN_CORE_CODE, 0, 0, 0x0300)),
+            (
+                Some(Direction::Tx),
+                "protocol_violation",
+                Some("tx".into()),
+                None,
+                None
+            )
+        );
+        // No details
+        assert_eq!(
+            error(&can_error_ext(0, 0xAA, 3, 0x0400)),
+            (None, "error_frame", None, None, None)
+        );
+    }
+
+    #[test]
+    fn absolute_timestamps() {
+        let mut file = file_header();
+        file.extend(container(
+            &object(CAN_MESSAGE, TIME_ONE_NANS, 10, &can_message(1, 0, 0x1, &[])),
+            ZLIB_DEFLATE,
+        ));
+        let frames: Vec<LogFrame> = Reader::new(file.as_slice(), true)
+            .unwrap()
+            .map(|f| f.unwrap())
+            .collect();
+        assert_eq!(
+            frames[0].timestamp.unwrap(),
+            Timestamp {
+                seconds: 1717673394,
+                nanoseconds: 250_000_010,
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_signature() {
+        assert!(Reader::new(&b"NOPE0000"[..], false).is_err());
+    }
+}
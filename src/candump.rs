@@ -1,24 +1,105 @@
+use crate::error_frame::{CAN_ERR_FLAG, CAN_ERR_MASK};
+use crate::import::{add_timestamps, parse_decimal_seconds, unix_seconds, Direction, LogFrame};
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take},
-    character::complete::{alphanumeric1, digit1, space0},
-    combinator::{map, map_opt, map_res, opt},
-    multi::fold_many0,
-    sequence::{delimited, preceded, separated_pair, terminated, tuple},
+    bytes::complete::{tag, take, take_while1, take_while_m_n},
+    character::complete::{digit1, space0, space1},
+    combinator::{map, map_opt, map_res, opt, recognize, value},
+    multi::{fold_many0, many0},
+    sequence::{delimited, preceded, terminated, tuple},
     IResult,
 };
 use socketcan::{
-    frame::FdFlags, CanAnyFrame, CanDataFrame, CanFdFrame, CanRemoteFrame, EmbeddedFrame,
-    ExtendedId, Id as CanId, StandardId, Timestamp,
+    frame::FdFlags, CanAnyFrame, CanDataFrame, CanErrorFrame, CanFdFrame, CanRemoteFrame,
+    EmbeddedFrame, ExtendedId, Id as CanId, StandardId, Timestamp,
 };
+use std::io::BufRead;
+use tracing::{debug, warn};
 
 pub const SOF: char = '(';
 
 const CANID_DELIM: &str = "#";
 const DATA_SEPERATOR: &str = ".";
 
+const CANFD_MAX_DLEN: usize = 64;
+
 pub type CanInterface<'a> = &'a str;
 
+pub struct Reader<R> {
+    reader: R,
+    line_buf: String,
+    /// Timestamps are deltas from the previous frame (`candump -td`)
+    delta_timestamps: bool,
+    last_timestamp: Timestamp,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(reader: R, delta_timestamps: bool) -> Self {
+        Self {
+            reader,
+            line_buf: String::with_capacity(8 * 1024),
+            delta_timestamps,
+            last_timestamp: Timestamp {
+                seconds: 0,
+                nanoseconds: 0,
+            },
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = Result<LogFrame, anyhow::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line_buf.clear();
+            match self.reader.read_line(&mut self.line_buf) {
+                Ok(0) => return None,
+                Ok(_) => (),
+                Err(e) => return Some(Err(e.into())),
+            }
+
+            let line = self.line_buf.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            // Logfile (-l/-L) lines, falling back to console output
+            let res = parse(line)
+                .map(|(_, (timestamp, iface, frame))| (Some(timestamp), iface, None, frame))
+                .or_else(|_| parse_console(line).map(|(_, f)| f));
+
+            match res {
+                Ok((timestamp, iface, direction, frame)) => {
+                    let timestamp = match timestamp {
+                        Some(t) if self.delta_timestamps => {
+                            Some(add_timestamps(&self.last_timestamp, &t))
+                        }
+                        t => t,
+                    };
+                    if let Some(t) = timestamp {
+                        self.last_timestamp = t;
+                    }
+                    return Some(Ok(LogFrame {
+                        timestamp,
+                        interface: Some(iface.to_owned()),
+                        direction,
+                        frame,
+                    }));
+                }
+                Err(e) => {
+                    if line.starts_with(SOF) {
+                        warn!(%e, line, "Failed to parse log file line");
+                    } else {
+                        // Comments, error frame descriptions (candump -e), etc
+                        debug!(line, "Skipping log file line");
+                    }
+                }
+            }
+        }
+    }
+}
+
 pub fn parse(s: &str) -> IResult<&str, (Timestamp, CanInterface<'_>, CanAnyFrame)> {
     tuple((
         timestamp,
@@ -27,30 +108,49 @@ pub fn parse(s: &str) -> IResult<&str, (Timestamp, CanInterface<'_>, CanAnyFrame
     ))(s)
 }
 
+/// Seconds (`-ta`, `-td`, `-tz` and logfiles) or a date (`-tA`), e.g.
+/// `(1717673394.196203)`, `(000.000120)` or `(2024-06-06 11:29:54.196203)`
 fn timestamp(s: &str) -> IResult<&str, Timestamp> {
-    map(
-        delimited(
-            tag("("),
-            separated_pair(
-                map_res(digit1, |out: &str| out.parse::<i64>()),
-                tag("."),
-                map_res(digit1, |out: &str| out.parse::<i64>()),
-            ),
-            tag(")"),
-        ),
-        |(seconds, nanoseconds)| Timestamp {
-            seconds,
-            nanoseconds,
+    delimited(tag("("), alt((date_time, decimal_seconds)), tag(")"))(s)
+}
+
+fn decimal_seconds(s: &str) -> IResult<&str, Timestamp> {
+    map_opt(
+        recognize(tuple((digit1, tag("."), digit1))),
+        parse_decimal_seconds,
+    )(s)
+}
+
+/// `-tA` dates are in the local time of the capturing machine, they're treated as UTC
+fn date_time(s: &str) -> IResult<&str, Timestamp> {
+    let num = |s| map_res(digit1, |out: &str| out.parse::<u32>())(s);
+    map_opt(
+        tuple((
+            map_res(digit1, |out: &str| out.parse::<i64>()),
+            preceded(tag("-"), num),
+            preceded(tag("-"), num),
+            preceded(space1, num),
+            preceded(tag(":"), num),
+            preceded(tag(":"), decimal_seconds),
+        )),
+        |(year, month, day, hour, min, sec)| {
+            Some(Timestamp {
+                seconds: unix_seconds(year, month, day, hour, min, 0) + sec.seconds,
+                nanoseconds: sec.nanoseconds,
+            })
         },
     )(s)
 }
 
+/// Any non-whitespace characters, e.g. `vcan0`, `can_docker0` or `vcan-1`
 fn interface(s: &str) -> IResult<&str, &str> {
-    alphanumeric1(s)
+    take_while1(|c: char| !c.is_whitespace())(s)
 }
 
 fn can_any_frame(s: &str) -> IResult<&str, CanAnyFrame> {
     alt((
+        map(can_error_frame, CanAnyFrame::Error),
+        map(can_xl_frame, CanAnyFrame::Fd),
         map(can_remote_frame, CanAnyFrame::Remote),
         map(can_fd_frame, CanAnyFrame::Fd),
         map(can_data_frame, CanAnyFrame::Normal),
@@ -76,6 +176,44 @@ fn can_data_frame(s: &str) -> IResult<&str, CanDataFrame> {
     })(s)
 }
 
+/// Error frames have the `CAN_ERR_FLAG` set in their 8 character ID
+fn can_error_frame(s: &str) -> IResult<&str, CanErrorFrame> {
+    map_opt(
+        tuple((
+            map_res(terminated(take(8_usize), tag(CANID_DELIM)), |out: &str| {
+                u32::from_str_radix(out, 16)
+            }),
+            hex_data,
+        )),
+        |(id, data)| error_frame(id, &data),
+    )(s)
+}
+
+/// `<prio>###<flags>:<sdt>:<af>#<data>`, the colons are optional.
+/// There's no CAN XL frame type, so these are converted to CAN FD frames like the ID is
+/// converted to an ExtendedId, dropping the SDU type and acceptance field.
+/// Frames with more than 64 bytes of data are rejected.
+fn can_xl_frame(s: &str) -> IResult<&str, CanFdFrame> {
+    let hex_field = |n: usize| take_while_m_n(n, n, |c: char| c.is_ascii_hexdigit());
+    map_opt(
+        tuple((
+            can_id,
+            tag("##"),
+            hex_field(2),
+            preceded(opt(tag(":")), hex_field(2)),
+            preceded(opt(tag(":")), hex_field(8)),
+            preceded(tag(CANID_DELIM), hex_data),
+        )),
+        |(id, _, _flags, _sdt, _af, data)| {
+            if data.len() > CANFD_MAX_DLEN {
+                None
+            } else {
+                CanFdFrame::with_flags(id, &data, FdFlags::empty())
+            }
+        },
+    )(s)
+}
+
 fn can_fd_frame(s: &str) -> IResult<&str, CanFdFrame> {
     map_opt(
         tuple((can_id, preceded(tag("#"), fd_flags), hex_data)),
@@ -105,6 +243,155 @@ fn hex_data(s: &str) -> IResult<&str, Vec<u8>> {
     )(s)
 }
 
+/// A frame from `candump` console output (i.e. without -l/-L), e.g.
+/// `  can0  123   [8]  11 22 33 44 55 66 77 88`, optionally with a timestamp (-t) and
+/// extra message infos (-x)
+#[allow(clippy::type_complexity)]
+pub fn parse_console(
+    s: &str,
+) -> IResult<
+    &str,
+    (
+        Option<Timestamp>,
+        CanInterface<'_>,
+        Option<Direction>,
+        CanAnyFrame,
+    ),
+> {
+    map_opt(
+        tuple((
+            preceded(space0, opt(terminated(timestamp, space1))),
+            interface,
+            preceded(space1, opt(terminated(console_extra_info, space1))),
+            console_id,
+            preceded(space1, opt(terminated(console_fd_marker, space1))),
+            console_len,
+            console_body,
+        )),
+        |(timestamp, iface, info, id, fd_marker, (len, len_digits), body)| {
+            let (direction, info_flags) = match info {
+                Some((direction, flags)) => (Some(direction), Some(flags)),
+                None => (None, None),
+            };
+            let frame = console_frame(id, fd_marker.or(info_flags), len, len_digits, body)?;
+            Some((timestamp, iface, direction, frame))
+        },
+    )(s)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum ConsoleBody {
+    Remote,
+    Data { data: Vec<u8>, is_error: bool },
+}
+
+fn console_frame(
+    id: &str,
+    fd_flags: Option<FdFlags>,
+    len: usize,
+    len_digits: usize,
+    body: ConsoleBody,
+) -> Option<CanAnyFrame> {
+    let raw_id = u32::from_str_radix(id, 16).ok()?;
+    let data = match body {
+        ConsoleBody::Data { data, is_error } if is_error => {
+            return error_frame(raw_id, &data).map(CanAnyFrame::Error);
+        }
+        ConsoleBody::Data { data, .. } => data,
+        ConsoleBody::Remote => {
+            let id = console_can_id(id, raw_id)?;
+            return CanRemoteFrame::new_remote(id, len).map(CanAnyFrame::Remote);
+        }
+    };
+    let id = console_can_id(id, raw_id)?;
+    let data = data.get(..len)?;
+    // FD lengths are always printed with two digits, e.g. [08]
+    if fd_flags.is_some() || len_digits > 1 {
+        CanFdFrame::with_flags(id, data, fd_flags.unwrap_or(FdFlags::empty())).map(CanAnyFrame::Fd)
+    } else {
+        CanDataFrame::new(id, data).map(CanAnyFrame::Normal)
+    }
+}
+
+/// Standard IDs are printed with 3 characters, extended IDs with 8
+fn console_can_id(id: &str, raw_id: u32) -> Option<CanId> {
+    if id.len() > 3 {
+        ExtendedId::new(raw_id).map(CanId::from)
+    } else {
+        StandardId::new(u16::try_from(raw_id).ok()?).map(CanId::from)
+    }
+}
+
+fn error_frame(id: u32, data: &[u8]) -> Option<CanErrorFrame> {
+    if id & CAN_ERR_FLAG == 0 {
+        return None;
+    }
+    CanErrorFrame::new_error(id & CAN_ERR_MASK, data).ok()
+}
+
+/// `RX - -` or `TX B E` (bit rate switch, error state indicator)
+fn console_extra_info(s: &str) -> IResult<&str, (Direction, FdFlags)> {
+    map(
+        tuple((
+            alt((
+                value(Direction::Rx, tag("RX")),
+                value(Direction::Tx, tag("TX")),
+            )),
+            preceded(space1, alt((tag("B"), tag("-")))),
+            preceded(space1, alt((tag("E"), tag("-")))),
+        )),
+        |(direction, brs, esi)| {
+            let mut flags = FdFlags::empty();
+            if brs == "B" {
+                flags |= FdFlags::BRS;
+            }
+            if esi == "E" {
+                flags |= FdFlags::ESI;
+            }
+            (direction, flags)
+        },
+    )(s)
+}
+
+fn console_id(s: &str) -> IResult<&str, &str> {
+    take_while_m_n(1, 8, |c: char| c.is_ascii_hexdigit())(s)
+}
+
+/// `##<flags>`, marks an FD frame
+fn console_fd_marker(s: &str) -> IResult<&str, FdFlags> {
+    preceded(tag("##"), fd_flags)(s)
+}
+
+/// `[<len>]`, returns the length and the number of digits it was printed with
+fn console_len(s: &str) -> IResult<&str, (usize, usize)> {
+    map_res(delimited(tag("["), digit1, tag("]")), |out: &str| {
+        out.parse::<usize>().map(|len| (len, out.len()))
+    })(s)
+}
+
+fn console_body(s: &str) -> IResult<&str, ConsoleBody> {
+    alt((
+        value(ConsoleBody::Remote, preceded(space1, tag("remote request"))),
+        map(
+            tuple((
+                many0(preceded(space1, console_hex_byte)),
+                opt(preceded(space1, tag("ERRORFRAME"))),
+            )),
+            |(data, err)| ConsoleBody::Data {
+                data,
+                is_error: err.is_some(),
+            },
+        ),
+    ))(s)
+}
+
+fn console_hex_byte(s: &str) -> IResult<&str, u8> {
+    map_res(
+        take_while_m_n(2, 2, |c: char| c.is_ascii_hexdigit()),
+        |out: &str| u8::from_str_radix(out, 16),
+    )(s)
+}
+
 fn can_id(s: &str) -> IResult<&str, CanId> {
     alt((
         map_opt(terminated(take(8_usize), tag(CANID_DELIM)), |out: &str| {
@@ -142,7 +429,7 @@ mod tests {
                 "",
                 Timestamp {
                     seconds: 0,
-                    nanoseconds: 1,
+                    nanoseconds: 1000,
                 }
             ))
         );
@@ -152,15 +439,215 @@ mod tests {
                 "",
                 Timestamp {
                     seconds: 1717673394,
-                    nanoseconds: 196203,
+                    nanoseconds: 196203000,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn timestamp_modes() {
+        // -ta
+        assert_eq!(
+            timestamp("(1717673394.196203) "),
+            Ok((
+                " ",
+                Timestamp {
+                    seconds: 1717673394,
+                    nanoseconds: 196203000,
+                }
+            ))
+        );
+        // -td and -tz
+        assert_eq!(
+            timestamp("(000.000120)"),
+            Ok((
+                "",
+                Timestamp {
+                    seconds: 0,
+                    nanoseconds: 120000,
+                }
+            ))
+        );
+        assert_eq!(
+            timestamp("(012.500000)"),
+            Ok((
+                "",
+                Timestamp {
+                    seconds: 12,
+                    nanoseconds: 500000000,
+                }
+            ))
+        );
+        // -tA
+        assert_eq!(
+            timestamp("(2024-06-06 11:29:54.196203)"),
+            Ok((
+                "",
+                Timestamp {
+                    seconds: 1717673394,
+                    nanoseconds: 196203000,
                 }
             ))
         );
+        assert!(timestamp("(1717673394)").is_err());
+        assert!(timestamp("(2024-06-06)").is_err());
+    }
+
+    #[test]
+    fn delta_timestamps() {
+        let log =
+            "(000.000000) vcan0 18A#F47E\n (000.250000) vcan0 18A#F47E\n(001.000001) vcan0 18A#\n";
+        let ts: Vec<Timestamp> = Reader::new(log.as_bytes(), true)
+            .map(|f| f.unwrap().timestamp.unwrap())
+            .collect();
+        assert_eq!(
+            ts,
+            vec![
+                Timestamp {
+                    seconds: 0,
+                    nanoseconds: 0,
+                },
+                Timestamp {
+                    seconds: 0,
+                    nanoseconds: 250000000,
+                },
+                Timestamp {
+                    seconds: 1,
+                    nanoseconds: 250001000,
+                },
+            ]
+        );
+
+        let ts: Vec<Timestamp> = Reader::new(log.as_bytes(), false)
+            .map(|f| f.unwrap().timestamp.unwrap())
+            .collect();
+        assert_eq!(
+            ts[2],
+            Timestamp {
+                seconds: 1,
+                nanoseconds: 1000,
+            }
+        );
     }
 
     #[test]
     fn interface_parser() {
         assert_eq!(interface("vcan0"), Ok(("", "vcan0")));
+        assert_eq!(interface("can_docker0 123"), Ok((" 123", "can_docker0")));
+        assert_eq!(interface("vcan-1"), Ok(("", "vcan-1")));
+        let (_, (_, iface, _)) = parse("(1717689368.527737) vcan-1 18A#F47E").unwrap();
+        assert_eq!(iface, "vcan-1");
+    }
+
+    #[test]
+    fn can_error_frame_parser() {
+        let (rem, f) = can_error_frame("20000004#0008000000000000").unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(f.error_bits(), 0x4);
+        assert_eq!(f.data()[1], 0x08);
+        assert!(can_error_frame("1BED56DA#00").is_err());
+    }
+
+    #[test]
+    fn can_xl_frame_parser() {
+        let (rem, f) = can_xl_frame("45123###80:00:11223344#11223344").unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(f.id(), ExtendedId::new(0x45123).unwrap().into());
+        assert_eq!(f.data(), &[0x11, 0x22, 0x33, 0x44]);
+
+        let (rem, f) = can_xl_frame("242###800011223344#0102").unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(f.id(), StandardId::new(0x242).unwrap().into());
+        assert_eq!(f.data(), &[0x01, 0x02]);
+
+        let big = format!("242###80:00:11223344#{}", "AA".repeat(65));
+        assert!(can_xl_frame(&big).is_err());
+
+        let (_, (_, _, frame)) =
+            parse("(1717689368.527737) xlcan0 242###80:00:11223344#01").unwrap();
+        assert!(matches!(frame, CanAnyFrame::Fd(_)));
+    }
+
+    #[test]
+    fn console_parser() {
+        let (_, (ts, iface, dir, frame)) =
+            parse_console("  can0  123   [8]  11 22 33 44 55 66 77 88").unwrap();
+        assert_eq!(ts, None);
+        assert_eq!(iface, "can0");
+        assert_eq!(dir, None);
+        let CanAnyFrame::Normal(f) = frame else {
+            panic!();
+        };
+        assert_eq!(f.id(), StandardId::new(0x123).unwrap().into());
+        assert_eq!(f.data(), &[0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88]);
+
+        let (_, (ts, iface, _, frame)) =
+            parse_console(" (1717673394.196203)  can_docker0  1BED56DA   [2]  F4 7E").unwrap();
+        assert_eq!(
+            ts,
+            Some(Timestamp {
+                seconds: 1717673394,
+                nanoseconds: 196203000,
+            })
+        );
+        assert_eq!(iface, "can_docker0");
+        let CanAnyFrame::Normal(f) = frame else {
+            panic!();
+        };
+        assert_eq!(f.id(), ExtendedId::new(0x1BED56DA).unwrap().into());
+
+        let (_, (_, _, _, frame)) = parse_console("  vcan0  1C9   [8]  remote request").unwrap();
+        let CanAnyFrame::Remote(f) = frame else {
+            panic!();
+        };
+        assert_eq!(f.dlc(), 8);
+
+        let (_, (_, _, _, frame)) = parse_console(&format!(
+            "  vcan-1  2CD  [64]  {}",
+            vec!["AB"; 64].join(" ")
+        ))
+        .unwrap();
+        let CanAnyFrame::Fd(f) = frame else {
+            panic!();
+        };
+        assert_eq!(f.data().len(), 64);
+        assert_eq!(f.flags(), FdFlags::empty());
+
+        let (_, (_, _, _, frame)) = parse_console("  vcan0  2CD  ##1  [02]  01 02").unwrap();
+        let CanAnyFrame::Fd(f) = frame else {
+            panic!();
+        };
+        assert_eq!(f.flags(), FdFlags::BRS);
+
+        let (_, (_, _, dir, frame)) =
+            parse_console("  vcan0  TX B E  2CD  [12]  01 02 03 04 05 06 07 08 09 0A 0B 0C")
+                .unwrap();
+        assert_eq!(dir, Some(Direction::Tx));
+        let CanAnyFrame::Fd(f) = frame else {
+            panic!();
+        };
+        assert_eq!(f.flags(), FdFlags::BRS | FdFlags::ESI);
+
+        let (_, (_, _, _, frame)) =
+            parse_console("  can0  20000004   [8]  00 08 00 00 00 00 00 00   ERRORFRAME").unwrap();
+        let CanAnyFrame::Error(f) = frame else {
+            panic!();
+        };
+        assert_eq!(f.error_bits(), 0x4);
+
+        assert!(parse_console("\tcontroller-problem{rx-error-warning}").is_err());
+    }
+
+    #[test]
+    fn console_reader() {
+        let log = "  can0  123   [2]  11 22\n\tcontroller-problem{rx-error-warning}\n (0.5) can0 18A#F47E\n";
+        let frames: Vec<LogFrame> = Reader::new(log.as_bytes(), false)
+            .map(|f| f.unwrap())
+            .collect();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].timestamp, None);
+        assert!(frames[1].timestamp.is_some());
     }
 
     #[test]
@@ -206,7 +693,7 @@ mod tests {
         assert!(f.is_data_frame());
         assert_eq!(f.flags(), FdFlags::empty());
         assert_eq!(f.dlc(), 0);
-        assert_eq!(f.data(), &[]);
+        assert_eq!(f.data(), &[0_u8; 0]);
 
         let (rem, f) = can_fd_frame("17F4200A##410").unwrap();
         assert!(rem.is_empty());
@@ -240,7 +727,7 @@ mod tests {
         assert_eq!(f.id(), StandardId::new(0x6BD).unwrap().into());
         assert!(f.is_data_frame());
         assert_eq!(f.dlc(), 0);
-        assert_eq!(f.data(), &[]);
+        assert_eq!(f.data(), &[0_u8; 0]);
 
         let (rem, f) = can_data_frame("27E#39.DB").unwrap();
         assert!(rem.is_empty());
@@ -258,7 +745,7 @@ mod tests {
             ts,
             Timestamp {
                 seconds: 1717689368,
-                nanoseconds: 527737,
+                nanoseconds: 527737000,
             }
         );
         assert_eq!(iface, "vcan0");
@@ -0,0 +1,994 @@
+//! CANopen (CiA 301) protocol decoding.
+
+use crate::{eds::ObjectDictionary, parser::hex};
+use auxon_sdk::{
+    api::AttrVal,
+    reflector_config::{envsub, EnvSubError},
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::PathBuf};
+use tracing::warn;
+
+// SDO command specifiers, client (request) and server (response)
+const CCS_DOWNLOAD_SEGMENT: u8 = 0;
+const CCS_INITIATE_DOWNLOAD: u8 = 1;
+const CCS_INITIATE_UPLOAD: u8 = 2;
+const CCS_UPLOAD_SEGMENT: u8 = 3;
+const SCS_UPLOAD_SEGMENT: u8 = 0;
+const SCS_DOWNLOAD_SEGMENT: u8 = 1;
+const SCS_INITIATE_UPLOAD: u8 = 2;
+const SCS_INITIATE_DOWNLOAD: u8 = 3;
+const CS_ABORT: u8 = 4;
+const CS_BLOCK_UPLOAD: u8 = 5;
+const CS_BLOCK_DOWNLOAD: u8 = 6;
+
+// PDO communication and mapping parameter objects
+const RPDO_COMMUNICATION: u16 = 0x1400;
+const RPDO_MAPPING: u16 = 0x1600;
+const TPDO_COMMUNICATION: u16 = 0x1800;
+const TPDO_MAPPING: u16 = 0x1A00;
+const MAX_PDOS: u16 = 512;
+const COB_ID_INVALID: u32 = 0x8000_0000;
+const COB_ID_MASK: u32 = 0x1FFF_FFFF;
+
+/// A CANopen node and its object dictionary
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct CanOpenNodeConfig {
+    /// The node-id. Defaults to the `NodeID` of a DCF file.
+    #[serde(alias = "node_id")]
+    pub node_id: Option<u8>,
+
+    /// A name for the node's timeline.
+    pub name: Option<String>,
+
+    /// The EDS or DCF file describing the node's object dictionary.
+    pub eds: Option<PathBuf>,
+}
+
+impl CanOpenNodeConfig {
+    pub fn envsub_eds_path(&self) -> Result<Option<PathBuf>, EnvSubError> {
+        let maybe_str = self.eds.as_ref().and_then(|p| p.as_os_str().to_str());
+
+        if let Some(s) = maybe_str {
+            envsub(s).map(|s| Some(PathBuf::from(s)))
+        } else {
+            Ok(self.eds.clone())
+        }
+    }
+}
+
+/// A node with its object dictionary loaded
+#[derive(Debug)]
+pub(crate) struct CanOpenNode {
+    pub node_id: u8,
+    pub name: Option<String>,
+    pub od: ObjectDictionary,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Pdo {
+    cob_id: u32,
+    /// The mapped objects, index << 16 | subindex << 8 | bit length
+    mapping: Vec<u32>,
+}
+
+/// Node-id, transmit, and zero-based PDO number
+type PdoKey = (u8, bool, u16);
+
+/// The communication object, from the function code of the COB-ID
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Function {
+    Nmt,
+    Sync,
+    Emcy,
+    Time,
+    /// Transmit PDO, numbered from 1
+    Tpdo(u16),
+    /// Receive PDO, numbered from 1
+    Rpdo(u16),
+    /// SDO server to client
+    SdoResponse,
+    /// SDO client to server
+    SdoRequest,
+    Heartbeat,
+}
+
+impl Function {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Function::Nmt => "nmt",
+            Function::Sync => "sync",
+            Function::Emcy => "emcy",
+            Function::Time => "time",
+            Function::Tpdo(n) => ["tpdo1", "tpdo2", "tpdo3", "tpdo4"]
+                .get(*n as usize - 1)
+                .unwrap_or(&"tpdo"),
+            Function::Rpdo(n) => ["rpdo1", "rpdo2", "rpdo3", "rpdo4"]
+                .get(*n as usize - 1)
+                .unwrap_or(&"rpdo"),
+            Function::SdoResponse => "sdo_response",
+            Function::SdoRequest => "sdo_request",
+            Function::Heartbeat => "heartbeat",
+        }
+    }
+}
+
+/// The function and node-id of an 11-bit COB-ID, using the predefined connection set
+pub(crate) fn classify(cob_id: u32) -> Option<(Function, u8)> {
+    let node_id = (cob_id & 0x7F) as u8;
+    let function = match (cob_id >> 7, node_id) {
+        (0x0, 0) => Function::Nmt,
+        (0x1, 0) => Function::Sync,
+        (0x1, _) => Function::Emcy,
+        (0x2, 0) => Function::Time,
+        (0x3..=0xA, 0) => return None,
+        (fc @ 0x3..=0xA, _) => {
+            let pdo = ((fc - 1) / 2) as u16;
+            if fc % 2 == 1 {
+                Function::Tpdo(pdo)
+            } else {
+                Function::Rpdo(pdo)
+            }
+        }
+        (0xB, n) if n != 0 => Function::SdoResponse,
+        (0xC, n) if n != 0 => Function::SdoRequest,
+        (0xE, n) if n != 0 => Function::Heartbeat,
+        _ => return None,
+    };
+    Some((function, node_id))
+}
+
+/// A decoded CANopen frame
+#[derive(Debug, PartialEq)]
+pub(crate) struct CanOpenInfo {
+    pub name: &'static str,
+    /// The node the frame belongs to, for its timeline. `None` for frames from the
+    /// NMT master or SDO client.
+    pub node_id: Option<u8>,
+    /// The configured name of the node the frame belongs to
+    pub node_name: Option<String>,
+    /// Attribute keys are relative to `event.canopen.`
+    pub attrs: Vec<(String, AttrVal)>,
+    /// PDO contents, named after the mapped object dictionary entries
+    pub signals: Vec<(String, AttrVal)>,
+}
+
+#[derive(Debug)]
+struct SdoTransfer {
+    index: u16,
+    subindex: u8,
+    is_upload: bool,
+    size: Option<u32>,
+    data: Vec<u8>,
+}
+
+/// The object, subindex and data of a completed SDO transfer
+type SdoData = (u16, u8, Vec<u8>);
+
+/// Decodes frames, tracking segmented SDO transfers for each node, and the PDO mappings of
+/// nodes with an object dictionary
+#[derive(Debug, Default)]
+pub(crate) struct CanOpenDecoder {
+    sdo: HashMap<(Option<String>, u8), SdoTransfer>,
+    nodes: HashMap<u8, CanOpenNode>,
+    pdos: HashMap<PdoKey, Pdo>,
+    cob_id_to_pdo: HashMap<u32, PdoKey>,
+    /// Update the PDO mappings from SDO writes to the mapping objects
+    sdo_mapping: bool,
+}
+
+impl CanOpenDecoder {
+    pub fn new(nodes: Vec<CanOpenNode>, sdo_mapping: bool) -> Self {
+        let mut decoder = Self {
+            sdo_mapping,
+            ..Default::default()
+        };
+        for node in nodes.into_iter() {
+            for is_transmit in [false, true] {
+                let (comm, map) = pdo_objects(is_transmit);
+                for n in 0..MAX_PDOS {
+                    let Some(count) = node.od.value(map + n, 0, node.node_id) else {
+                        continue;
+                    };
+                    let mapping = (1..=count.min(64) as u8)
+                        .map(|sub| node.od.value(map + n, sub, node.node_id).unwrap_or(0) as u32)
+                        .collect();
+                    let cob_id = node
+                        .od
+                        .value(comm + n, 1, node.node_id)
+                        .map(|id| id as u32)
+                        .or_else(|| default_pdo_cob_id(node.node_id, is_transmit, n))
+                        .unwrap_or(COB_ID_INVALID);
+                    decoder
+                        .pdos
+                        .insert((node.node_id, is_transmit, n), Pdo { cob_id, mapping });
+                }
+            }
+            if decoder.nodes.insert(node.node_id, node).is_some() {
+                warn!("Duplicate CANopen node-id");
+            }
+        }
+        decoder.index_pdos();
+        decoder
+    }
+
+    /// Receive PDOs first, so the transmitting node's mapping wins for a shared COB-ID
+    fn index_pdos(&mut self) {
+        let mut keys: Vec<_> = self.pdos.keys().copied().collect();
+        keys.sort();
+        keys.sort_by_key(|k| k.1);
+        self.cob_id_to_pdo = keys
+            .into_iter()
+            .filter_map(|k| {
+                let cob_id = self.pdos[&k].cob_id;
+                (cob_id & COB_ID_INVALID == 0).then_some((cob_id & COB_ID_MASK, k))
+            })
+            .collect();
+    }
+
+    pub fn decode(
+        &mut self,
+        interface: Option<&str>,
+        cob_id: u32,
+        data: &[u8],
+    ) -> Option<CanOpenInfo> {
+        let mut signals = Vec::new();
+        let (function, node_id) = match self.cob_id_to_pdo.get(&cob_id) {
+            Some(&(node_id, is_transmit, n)) => {
+                signals = self.pdo_signals((node_id, is_transmit, n), data);
+                let function = if is_transmit {
+                    Function::Tpdo(n + 1)
+                } else {
+                    Function::Rpdo(n + 1)
+                };
+                (function, node_id)
+            }
+            None => classify(cob_id)?,
+        };
+        let mut attrs: Vec<(String, AttrVal)> =
+            vec![("function".to_owned(), function.as_str().into())];
+        let mut name = function.as_str();
+        let byte = |i: usize| data.get(i).copied();
+
+        let timeline_node = match function {
+            Function::Nmt => {
+                if let (Some(cs), Some(target)) = (byte(0), byte(1)) {
+                    attrs.push(("nmt.command_specifier".to_owned(), (cs as u64).into()));
+                    if let Some(cmd) = nmt_command(cs) {
+                        attrs.push(("nmt.command".to_owned(), cmd.into()));
+                    }
+                    attrs.push(("nmt.node_id".to_owned(), (target as u64).into()));
+                }
+                None
+            }
+            Function::Sync => {
+                if let Some(counter) = byte(0) {
+                    attrs.push(("sync.counter".to_owned(), (counter as u64).into()));
+                }
+                None
+            }
+            Function::Time => None,
+            Function::Emcy => {
+                if data.len() >= 3 {
+                    let code = u16::from_le_bytes([data[0], data[1]]);
+                    attrs.push(("emcy.error_code".to_owned(), (code as u64).into()));
+                    attrs.push(("emcy.error_class".to_owned(), emcy_error_class(code).into()));
+                    attrs.push(("emcy.error_register".to_owned(), (data[2] as u64).into()));
+                    if data.len() > 3 {
+                        attrs.push(("emcy.manufacturer_data".to_owned(), hex(&data[3..]).into()));
+                    }
+                }
+                Some(node_id)
+            }
+            Function::Heartbeat => {
+                if let Some(state) = byte(0) {
+                    let state = state & 0x7F;
+                    if state == 0 {
+                        name = "boot_up";
+                    }
+                    attrs.push(("state".to_owned(), nmt_state(state).into()));
+                }
+                Some(node_id)
+            }
+            Function::Tpdo(n) => {
+                attrs.push(("pdo.number".to_owned(), (n as u64).into()));
+                Some(node_id)
+            }
+            // Received by the node, sent by another
+            Function::Rpdo(n) => {
+                attrs.push(("pdo.number".to_owned(), (n as u64).into()));
+                None
+            }
+            Function::SdoRequest => {
+                let written = self.sdo_request(interface, node_id, data, &mut attrs);
+                if let (true, Some(written)) = (self.sdo_mapping, written) {
+                    self.update_pdo(node_id, written);
+                }
+                None
+            }
+            Function::SdoResponse => {
+                self.sdo_response(interface, node_id, data, &mut attrs);
+                Some(node_id)
+            }
+        };
+        attrs.push(("node_id".to_owned(), (node_id as u64).into()));
+
+        Some(CanOpenInfo {
+            name,
+            node_id: timeline_node,
+            node_name: timeline_node
+                .and_then(|n| self.nodes.get(&n))
+                .and_then(|n| n.name.clone()),
+            attrs,
+            signals,
+        })
+    }
+
+    /// Decode the mapped objects, in order from the least significant bit of the first byte
+    fn pdo_signals(&self, key: PdoKey, data: &[u8]) -> Vec<(String, AttrVal)> {
+        let (Some(pdo), Some(node)) = (self.pdos.get(&key), self.nodes.get(&key.0)) else {
+            return Vec::new();
+        };
+        let mut signals = Vec::new();
+        let mut offset = 0;
+        for entry in pdo.mapping.iter() {
+            let index = (entry >> 16) as u16;
+            let subindex = (entry >> 8) as u8;
+            let bits = (entry & 0xFF) as usize;
+            // Dummy entries (data type indexes) only take up space
+            if index >= 0x1000 {
+                let od_entry = node.od.entries.get(&(index, subindex));
+                let data_type = od_entry.and_then(|e| e.data_type);
+                match decode_value(data, offset, bits, data_type) {
+                    Some(val) => {
+                        let name = match od_entry {
+                            Some(e) => e.name.replace(' ', "_"),
+                            None => format!("{index:04X}sub{subindex}"),
+                        };
+                        signals.push((name, val));
+                    }
+                    None => {
+                        warn!(index, subindex, "PDO is shorter than its mapping");
+                        break;
+                    }
+                }
+            }
+            offset += bits;
+        }
+        signals
+    }
+
+    /// Apply a write to a PDO communication (COB-ID) or mapping parameter
+    fn update_pdo(&mut self, node_id: u8, (index, subindex, data): SdoData) {
+        let value = data
+            .iter()
+            .take(4)
+            .enumerate()
+            .fold(0_u32, |v, (i, b)| v | (*b as u32) << (8 * i));
+        let (is_transmit, base) = match index {
+            RPDO_COMMUNICATION..=0x15FF => (false, RPDO_COMMUNICATION),
+            RPDO_MAPPING..=0x17FF => (false, RPDO_MAPPING),
+            TPDO_COMMUNICATION..=0x19FF => (true, TPDO_COMMUNICATION),
+            TPDO_MAPPING..=0x1BFF => (true, TPDO_MAPPING),
+            _ => return,
+        };
+        let n = index - base;
+        let pdo = self
+            .pdos
+            .entry((node_id, is_transmit, n))
+            .or_insert_with(|| Pdo {
+                cob_id: default_pdo_cob_id(node_id, is_transmit, n).unwrap_or(COB_ID_INVALID),
+                mapping: Vec::new(),
+            });
+        if base == RPDO_COMMUNICATION || base == TPDO_COMMUNICATION {
+            if subindex == 1 {
+                pdo.cob_id = value;
+            }
+        } else if subindex == 0 {
+            pdo.mapping.resize(value.min(64) as usize, 0);
+        } else if let Some(entry) = pdo.mapping.get_mut(subindex as usize - 1) {
+            *entry = value;
+        } else {
+            // Entries are written before the count
+            pdo.mapping.resize(subindex as usize, 0);
+            pdo.mapping[subindex as usize - 1] = value;
+        }
+        self.index_pdos();
+    }
+
+    fn sdo_request(
+        &mut self,
+        interface: Option<&str>,
+        node_id: u8,
+        data: &[u8],
+        attrs: &mut Vec<(String, AttrVal)>,
+    ) -> Option<SdoData> {
+        let &b0 = data.first()?;
+        let key = (interface.map(str::to_owned), node_id);
+        let mut written = None;
+        let command = match b0 >> 5 {
+            CCS_INITIATE_DOWNLOAD => {
+                written = self.initiate(key, false, data, attrs);
+                "initiate_download_request"
+            }
+            CCS_DOWNLOAD_SEGMENT => {
+                written = self.segment(key, false, data, attrs);
+                "download_segment_request"
+            }
+            CCS_INITIATE_UPLOAD => {
+                self.sdo.remove(&key);
+                push_index(data, attrs);
+                "initiate_upload_request"
+            }
+            CCS_UPLOAD_SEGMENT => {
+                push_toggle(b0, attrs);
+                self.push_session_index(&key, attrs);
+                "upload_segment_request"
+            }
+            CS_ABORT => {
+                self.abort(key, data, attrs);
+                "abort"
+            }
+            CS_BLOCK_UPLOAD => "block_upload",
+            CS_BLOCK_DOWNLOAD => "block_download",
+            _ => "unknown",
+        };
+        attrs.push(("sdo.command".to_owned(), command.into()));
+        written
+    }
+
+    fn sdo_response(
+        &mut self,
+        interface: Option<&str>,
+        node_id: u8,
+        data: &[u8],
+        attrs: &mut Vec<(String, AttrVal)>,
+    ) {
+        let Some(&b0) = data.first() else {
+            return;
+        };
+        let key = (interface.map(str::to_owned), node_id);
+        let command = match b0 >> 5 {
+            SCS_INITIATE_DOWNLOAD => {
+                push_index(data, attrs);
+                "initiate_download_response"
+            }
+            SCS_DOWNLOAD_SEGMENT => {
+                push_toggle(b0, attrs);
+                self.push_session_index(&key, attrs);
+                "download_segment_response"
+            }
+            SCS_INITIATE_UPLOAD => {
+                self.initiate(key, true, data, attrs);
+                "initiate_upload_response"
+            }
+            SCS_UPLOAD_SEGMENT => {
+                self.segment(key, true, data, attrs);
+                "upload_segment_response"
+            }
+            CS_ABORT => {
+                self.abort(key, data, attrs);
+                "abort"
+            }
+            CS_BLOCK_UPLOAD => "block_upload",
+            CS_BLOCK_DOWNLOAD => "block_download",
+            _ => "unknown",
+        };
+        attrs.push(("sdo.command".to_owned(), command.into()));
+    }
+
+    /// The initiate frame that carries the data or size, expedited transfers are complete
+    fn initiate(
+        &mut self,
+        key: (Option<String>, u8),
+        is_upload: bool,
+        data: &[u8],
+        attrs: &mut Vec<(String, AttrVal)>,
+    ) -> Option<SdoData> {
+        self.sdo.remove(&key);
+        let (index, subindex) = push_index(data, attrs)?;
+        let b0 = data[0];
+        let n = ((b0 >> 2) & 0x3) as usize;
+        let expedited = b0 & 0x2 != 0;
+        let size_indicated = b0 & 0x1 != 0;
+        let payload = data.get(4..).unwrap_or_default();
+
+        if expedited {
+            let len = if size_indicated { 4 - n } else { 4 };
+            let payload = &payload[..payload.len().min(len)];
+            attrs.push(("sdo.expedited".to_owned(), true.into()));
+            attrs.push(("sdo.size".to_owned(), (payload.len() as u64).into()));
+            attrs.push(("sdo.data".to_owned(), hex(payload).into()));
+            Some((index, subindex, payload.to_vec()))
+        } else {
+            let size = (size_indicated && payload.len() >= 4)
+                .then(|| u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]));
+            if let Some(size) = size {
+                attrs.push(("sdo.size".to_owned(), (size as u64).into()));
+            }
+            self.sdo.insert(
+                key,
+                SdoTransfer {
+                    index,
+                    subindex,
+                    is_upload,
+                    size,
+                    data: Vec::new(),
+                },
+            );
+            None
+        }
+    }
+
+    /// A segment carrying data, the last one completes the transfer
+    fn segment(
+        &mut self,
+        key: (Option<String>, u8),
+        is_upload: bool,
+        data: &[u8],
+        attrs: &mut Vec<(String, AttrVal)>,
+    ) -> Option<SdoData> {
+        let b0 = data[0];
+        push_toggle(b0, attrs);
+        let transfer = self
+            .sdo
+            .get_mut(&key)
+            .filter(|t| t.is_upload == is_upload)?;
+        attrs.push(("sdo.index".to_owned(), (transfer.index as u64).into()));
+        attrs.push(("sdo.subindex".to_owned(), (transfer.subindex as u64).into()));
+
+        let unused = ((b0 >> 1) & 0x7) as usize;
+        let segment = data.get(1..).unwrap_or_default();
+        let len = segment.len().min(7 - unused);
+        transfer.data.extend_from_slice(&segment[..len]);
+
+        if b0 & 0x1 == 0 {
+            return None;
+        }
+        let transfer = self.sdo.remove(&key)?;
+        attrs.push(("sdo.size".to_owned(), (transfer.data.len() as u64).into()));
+        attrs.push(("sdo.data".to_owned(), hex(&transfer.data).into()));
+        if transfer
+            .size
+            .is_some_and(|size| size as usize != transfer.data.len())
+        {
+            attrs.push(("sdo.size_mismatch".to_owned(), true.into()));
+        }
+        Some((transfer.index, transfer.subindex, transfer.data))
+    }
+
+    fn abort(
+        &mut self,
+        key: (Option<String>, u8),
+        data: &[u8],
+        attrs: &mut Vec<(String, AttrVal)>,
+    ) {
+        self.sdo.remove(&key);
+        push_index(data, attrs);
+        if let Some(code) = data.get(4..8) {
+            let code = u32::from_le_bytes([code[0], code[1], code[2], code[3]]);
+            attrs.push(("sdo.abort_code".to_owned(), code.into()));
+            if let Some(reason) = abort_reason(code) {
+                attrs.push(("sdo.abort_reason".to_owned(), reason.into()));
+            }
+        }
+    }
+
+    fn push_session_index(&self, key: &(Option<String>, u8), attrs: &mut Vec<(String, AttrVal)>) {
+        if let Some(transfer) = self.sdo.get(key) {
+            attrs.push(("sdo.index".to_owned(), (transfer.index as u64).into()));
+            attrs.push(("sdo.subindex".to_owned(), (transfer.subindex as u64).into()));
+        }
+    }
+}
+
+fn pdo_objects(is_transmit: bool) -> (u16, u16) {
+    if is_transmit {
+        (TPDO_COMMUNICATION, TPDO_MAPPING)
+    } else {
+        (RPDO_COMMUNICATION, RPDO_MAPPING)
+    }
+}
+
+/// The predefined connection set only covers the first four PDOs
+fn default_pdo_cob_id(node_id: u8, is_transmit: bool, n: u16) -> Option<u32> {
+    let base = if is_transmit { 0x180 } else { 0x200 };
+    (n < 4).then(|| base + 0x100 * n as u32 + node_id as u32)
+}
+
+/// Decode `bits` bits at `offset`, based on the CANopen data type
+fn decode_value(
+    data: &[u8],
+    offset: usize,
+    bits: usize,
+    data_type: Option<u16>,
+) -> Option<AttrVal> {
+    if bits == 0 || offset + bits > data.len() * 8 {
+        return None;
+    }
+    // Strings and domains are byte aligned
+    if let Some(0x09 | 0x0A | 0x0B | 0x0F) = data_type {
+        let bytes = data.get(offset / 8..(offset + bits) / 8)?;
+        return Some(match data_type {
+            Some(0x09) => String::from_utf8_lossy(bytes)
+                .trim_end_matches('\0')
+                .to_owned()
+                .into(),
+            _ => hex(bytes).into(),
+        });
+    }
+    if bits > 64 {
+        return None;
+    }
+
+    let raw = (0..bits)
+        .filter(|i| data[(offset + i) / 8] >> ((offset + i) % 8) & 1 != 0)
+        .fold(0_u64, |v, i| v | 1 << i);
+    Some(match data_type {
+        Some(0x01) => (raw != 0).into(),
+        // INTEGER8-64
+        Some(0x02..=0x04 | 0x10 | 0x12..=0x15) => {
+            let shift = 64 - bits;
+            (((raw << shift) as i64) >> shift).into()
+        }
+        Some(0x08) if bits == 32 => (f32::from_bits(raw as u32) as f64).into(),
+        Some(0x11) if bits == 64 => f64::from_bits(raw).into(),
+        _ => raw.into(),
+    })
+}
+
+/// The multiplexer (index and subindex) of an initiate or abort frame
+fn push_index(data: &[u8], attrs: &mut Vec<(String, AttrVal)>) -> Option<(u16, u8)> {
+    let index = u16::from_le_bytes([*data.get(1)?, *data.get(2)?]);
+    let subindex = *data.get(3)?;
+    attrs.push(("sdo.index".to_owned(), (index as u64).into()));
+    attrs.push(("sdo.subindex".to_owned(), (subindex as u64).into()));
+    Some((index, subindex))
+}
+
+fn push_toggle(b0: u8, attrs: &mut Vec<(String, AttrVal)>) {
+    attrs.push(("sdo.toggle".to_owned(), (b0 & 0x10 != 0).into()));
+}
+
+fn nmt_command(cs: u8) -> Option<&'static str> {
+    Some(match cs {
+        0x01 => "start_remote_node",
+        0x02 => "stop_remote_node",
+        0x80 => "enter_pre_operational",
+        0x81 => "reset_node",
+        0x82 => "reset_communication",
+        _ => return None,
+    })
+}
+
+fn nmt_state(state: u8) -> &'static str {
+    match state {
+        0x00 => "boot_up",
+        0x04 => "stopped",
+        0x05 => "operational",
+        0x7F => "pre_operational",
+        _ => "unknown",
+    }
+}
+
+fn emcy_error_class(code: u16) -> &'static str {
+    match code {
+        0x0000 => "no_error",
+        0x8110 => "can_overrun",
+        0x8120 => "can_error_passive",
+        0x8130 => "life_guard_or_heartbeat_error",
+        0x8140 => "recovered_from_bus_off",
+        0x8250 => "rpdo_timeout",
+        _ => match code >> 8 {
+            0x10 => "generic_error",
+            0x20..=0x23 => "current",
+            0x30..=0x33 => "voltage",
+            0x40..=0x42 => "temperature",
+            0x50 => "device_hardware",
+            0x60..=0x63 => "device_software",
+            0x70 => "additional_modules",
+            0x80..=0x82 => "monitoring",
+            0x90 => "external_error",
+            0xF0 => "additional_functions",
+            0xFF => "device_specific",
+            _ => "unknown",
+        },
+    }
+}
+
+fn abort_reason(code: u32) -> Option<&'static str> {
+    Some(match code {
+        0x0503_0000 => "toggle_bit_not_alternated",
+        0x0504_0000 => "sdo_protocol_timed_out",
+        0x0504_0001 => "invalid_command_specifier",
+        0x0504_0005 => "out_of_memory",
+        0x0601_0000 => "unsupported_access",
+        0x0601_0001 => "read_of_write_only_object",
+        0x0601_0002 => "write_of_read_only_object",
+        0x0602_0000 => "object_does_not_exist",
+        0x0604_0041 => "object_cannot_be_mapped_to_pdo",
+        0x0604_0042 => "pdo_length_exceeded",
+        0x0604_0043 => "general_parameter_incompatibility",
+        0x0604_0047 => "general_internal_incompatibility",
+        0x0606_0000 => "hardware_error",
+        0x0607_0010 => "data_type_mismatch",
+        0x0607_0012 => "data_type_length_too_high",
+        0x0607_0013 => "data_type_length_too_low",
+        0x0609_0011 => "subindex_does_not_exist",
+        0x0609_0030 => "invalid_value",
+        0x0609_0031 => "value_too_high",
+        0x0609_0032 => "value_too_low",
+        0x0800_0000 => "general_error",
+        0x0800_0020 => "data_cannot_be_transferred",
+        0x0800_0021 => "data_cannot_be_transferred_local_control",
+        0x0800_0022 => "data_cannot_be_transferred_device_state",
+        0x0800_0024 => "no_data_available",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attr<'a>(info: &'a CanOpenInfo, key: &str) -> Option<&'a AttrVal> {
+        info.attrs.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    #[test]
+    fn cob_ids() {
+        assert_eq!(classify(0x000), Some((Function::Nmt, 0)));
+        assert_eq!(classify(0x080), Some((Function::Sync, 0)));
+        assert_eq!(classify(0x085), Some((Function::Emcy, 5)));
+        assert_eq!(classify(0x100), Some((Function::Time, 0)));
+        assert_eq!(classify(0x185), Some((Function::Tpdo(1), 5)));
+        assert_eq!(classify(0x205), Some((Function::Rpdo(1), 5)));
+        assert_eq!(classify(0x485), Some((Function::Tpdo(4), 5)));
+        assert_eq!(classify(0x505), Some((Function::Rpdo(4), 5)));
+        assert_eq!(classify(0x585), Some((Function::SdoResponse, 5)));
+        assert_eq!(classify(0x605), Some((Function::SdoRequest, 5)));
+        assert_eq!(classify(0x705), Some((Function::Heartbeat, 5)));
+        assert_eq!(classify(0x180), None);
+        assert_eq!(classify(0x7E5), None);
+    }
+
+    #[test]
+    fn nmt_heartbeat_emcy() {
+        let mut d = CanOpenDecoder::default();
+        let info = d.decode(None, 0x000, &[0x01, 0x05]).unwrap();
+        assert_eq!(info.name, "nmt");
+        assert_eq!(info.node_id, None);
+        assert_eq!(
+            attr(&info, "nmt.command"),
+            Some(&"start_remote_node".into())
+        );
+        assert_eq!(attr(&info, "nmt.node_id"), Some(&5_u64.into()));
+
+        let info = d.decode(None, 0x705, &[0x00]).unwrap();
+        assert_eq!(info.name, "boot_up");
+        assert_eq!(info.node_id, Some(5));
+        let info = d.decode(None, 0x705, &[0x05]).unwrap();
+        assert_eq!(info.name, "heartbeat");
+        assert_eq!(attr(&info, "state"), Some(&"operational".into()));
+
+        let info = d
+            .decode(None, 0x085, &[0x30, 0x81, 0x11, 0, 0, 0, 0, 0])
+            .unwrap();
+        assert_eq!(info.name, "emcy");
+        assert_eq!(attr(&info, "emcy.error_code"), Some(&0x8130_u64.into()));
+        assert_eq!(
+            attr(&info, "emcy.error_class"),
+            Some(&"life_guard_or_heartbeat_error".into())
+        );
+        assert_eq!(attr(&info, "emcy.error_register"), Some(&0x11_u64.into()));
+    }
+
+    #[test]
+    fn expedited_sdo() {
+        let mut d = CanOpenDecoder::default();
+        let info = d
+            .decode(None, 0x605, &[0x40, 0x18, 0x10, 0x01, 0, 0, 0, 0])
+            .unwrap();
+        assert_eq!(info.name, "sdo_request");
+        assert_eq!(
+            attr(&info, "sdo.command"),
+            Some(&"initiate_upload_request".into())
+        );
+        assert_eq!(attr(&info, "sdo.index"), Some(&0x1018_u64.into()));
+        assert_eq!(attr(&info, "sdo.subindex"), Some(&1_u64.into()));
+
+        let info = d
+            .decode(
+                None,
+                0x585,
+                &[0x43, 0x18, 0x10, 0x01, 0x78, 0x56, 0x34, 0x12],
+            )
+            .unwrap();
+        assert_eq!(info.node_id, Some(5));
+        assert_eq!(attr(&info, "sdo.expedited"), Some(&true.into()));
+        assert_eq!(attr(&info, "sdo.size"), Some(&4_u64.into()));
+        assert_eq!(attr(&info, "sdo.data"), Some(&"78563412".into()));
+
+        let info = d
+            .decode(None, 0x605, &[0x2B, 0x17, 0x10, 0x00, 0xE8, 0x03, 0, 0])
+            .unwrap();
+        assert_eq!(attr(&info, "sdo.size"), Some(&2_u64.into()));
+        assert_eq!(attr(&info, "sdo.data"), Some(&"e803".into()));
+
+        let info = d
+            .decode(
+                None,
+                0x585,
+                &[0x80, 0x00, 0x20, 0x00, 0x00, 0x00, 0x02, 0x06],
+            )
+            .unwrap();
+        assert_eq!(attr(&info, "sdo.command"), Some(&"abort".into()));
+        assert_eq!(attr(&info, "sdo.abort_code"), Some(&0x0602_0000_u32.into()));
+        assert_eq!(
+            attr(&info, "sdo.abort_reason"),
+            Some(&"object_does_not_exist".into())
+        );
+    }
+
+    #[test]
+    fn segmented_sdo() {
+        let mut d = CanOpenDecoder::default();
+        let iface = Some("can0");
+        // Upload of 0x1008 (device name), 10 bytes
+        d.decode(iface, 0x605, &[0x40, 0x08, 0x10, 0x00, 0, 0, 0, 0]);
+        let info = d
+            .decode(iface, 0x585, &[0x41, 0x08, 0x10, 0x00, 10, 0, 0, 0])
+            .unwrap();
+        assert_eq!(attr(&info, "sdo.size"), Some(&10_u64.into()));
+        assert_eq!(attr(&info, "sdo.data"), None);
+
+        let info = d
+            .decode(iface, 0x605, &[0x60, 0, 0, 0, 0, 0, 0, 0])
+            .unwrap();
+        assert_eq!(attr(&info, "sdo.index"), Some(&0x1008_u64.into()));
+        let info = d.decode(iface, 0x585, b"\x00Robot A").unwrap();
+        assert_eq!(attr(&info, "sdo.toggle"), Some(&false.into()));
+        assert_eq!(attr(&info, "sdo.data"), None);
+        // Another interface doesn't interfere
+        d.decode(Some("can1"), 0x585, &[0x1B, b'x', b'y', 0, 0, 0, 0, 0]);
+        d.decode(iface, 0x605, &[0x70, 0, 0, 0, 0, 0, 0, 0]);
+        let info = d
+            .decode(iface, 0x585, &[0x1B, b'r', b'm', 0, 0, 0, 0, 0])
+            .unwrap();
+        assert_eq!(attr(&info, "sdo.toggle"), Some(&true.into()));
+        assert_eq!(attr(&info, "sdo.size"), Some(&9_u64.into()));
+        assert_eq!(attr(&info, "sdo.data"), Some(&hex(b"Robot Arm").into()));
+        assert_eq!(attr(&info, "sdo.size_mismatch"), Some(&true.into()));
+
+        // Download of 9 bytes
+        d.decode(iface, 0x605, &[0x21, 0x00, 0x20, 0x01, 9, 0, 0, 0]);
+        d.decode(iface, 0x585, &[0x60, 0x00, 0x20, 0x01, 0, 0, 0, 0]);
+        d.decode(iface, 0x605, &[0x00, 1, 2, 3, 4, 5, 6, 7]);
+        d.decode(iface, 0x585, &[0x20, 0, 0, 0, 0, 0, 0, 0]);
+        let info = d
+            .decode(iface, 0x605, &[0x1B, 8, 9, 0, 0, 0, 0, 0])
+            .unwrap();
+        assert_eq!(
+            attr(&info, "sdo.command"),
+            Some(&"download_segment_request".into())
+        );
+        assert_eq!(attr(&info, "sdo.index"), Some(&0x2000_u64.into()));
+        assert_eq!(attr(&info, "sdo.data"), Some(&"010203040506070809".into()));
+        assert_eq!(attr(&info, "sdo.size_mismatch"), None);
+    }
+
+    const EDS: &str = "[6000]\nParameterName=Digital inputs\nDataType=0x0005\n\
+                       [6401]\nParameterName=Analog input\nDataType=0x0003\n\
+                       [6402]\nParameterName=Temperature\nDataType=0x0008\n\
+                       [6403]\nParameterName=Enabled\nDataType=0x0001\n\
+                       [1800]\nSubNumber=2\n\
+                       [1800sub1]\nParameterName=COB-ID\nDataType=0x0007\nDefaultValue=$NODEID+0x180\n\
+                       [1800sub2]\nParameterName=Transmission type\nDataType=0x0005\nDefaultValue=0xFF\n\
+                       [1A00]\nSubNumber=4\n\
+                       [1A00sub0]\nParameterName=Number of entries\nDataType=0x0005\nDefaultValue=3\n\
+                       [1A00sub1]\nParameterName=Mapped object 1\nDataType=0x0007\nDefaultValue=0x60000008\n\
+                       [1A00sub2]\nParameterName=Mapped object 2\nDataType=0x0007\nDefaultValue=0x00050008\n\
+                       [1A00sub3]\nParameterName=Mapped object 3\nDataType=0x0007\nDefaultValue=0x64010010\n\
+                       [1A01]\nSubNumber=2\n\
+                       [1A01sub0]\nParameterName=Number of entries\nDataType=0x0005\nDefaultValue=0\n";
+
+    fn decoder(sdo_mapping: bool) -> CanOpenDecoder {
+        let node = CanOpenNode {
+            node_id: 5,
+            name: Some("io".to_owned()),
+            od: ObjectDictionary::parse(EDS),
+        };
+        CanOpenDecoder::new(vec![node], sdo_mapping)
+    }
+
+    fn signal<'a>(info: &'a CanOpenInfo, key: &str) -> Option<&'a AttrVal> {
+        info.signals.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    #[test]
+    fn pdo_mapping() {
+        let mut d = decoder(false);
+        let info = d.decode(None, 0x185, &[0x81, 0xAA, 0x30, 0xF8]).unwrap();
+        assert_eq!(info.name, "tpdo1");
+        assert_eq!(info.node_id, Some(5));
+        assert_eq!(info.node_name.as_deref(), Some("io"));
+        assert_eq!(info.signals.len(), 2);
+        assert_eq!(signal(&info, "Digital_inputs"), Some(&0x81_u64.into()));
+        assert_eq!(signal(&info, "Analog_input"), Some(&(-2000_i64).into()));
+
+        // Too short for the mapping
+        let info = d.decode(None, 0x185, &[0x81]).unwrap();
+        assert_eq!(signal(&info, "Digital_inputs"), Some(&0x81_u64.into()));
+        assert_eq!(signal(&info, "Analog_input"), None);
+
+        // No mapped objects
+        let info = d.decode(None, 0x285, &[0x01]).unwrap();
+        assert_eq!(info.name, "tpdo2");
+        assert!(info.signals.is_empty());
+    }
+
+    #[test]
+    fn pdo_values() {
+        assert_eq!(decode_value(&[0x01], 0, 1, Some(0x01)), Some(true.into()));
+        assert_eq!(
+            decode_value(&[0xF0], 4, 4, Some(0x02)),
+            Some((-1_i64).into())
+        );
+        assert_eq!(
+            decode_value(&[0xFF, 0xFF, 0x7F], 0, 24, Some(0x10)),
+            Some(0x7FFFFF_i64.into())
+        );
+        assert_eq!(
+            decode_value(&1.5_f32.to_le_bytes(), 0, 32, Some(0x08)),
+            Some(1.5_f64.into())
+        );
+        assert_eq!(
+            decode_value(&[0x2A, b'o', b'k', 0], 8, 24, Some(0x09)),
+            Some("ok".into())
+        );
+        assert_eq!(
+            decode_value(&[0x2A, 0x01, 0x02], 8, 16, Some(0x0A)),
+            Some("0102".into())
+        );
+        assert_eq!(decode_value(&[0x2A], 4, 8, None), None);
+    }
+
+    #[test]
+    fn sdo_remapping() {
+        let mut d = decoder(true);
+        let write = |d: &mut CanOpenDecoder, index: u16, subindex: u8, value: u32| {
+            let [i0, i1] = index.to_le_bytes();
+            let [v0, v1, v2, v3] = value.to_le_bytes();
+            d.decode(None, 0x605, &[0x23, i0, i1, subindex, v0, v1, v2, v3])
+                .unwrap();
+        };
+
+        // Disable, remap, move and enable TPDO1
+        write(&mut d, 0x1800, 1, 0x8000_0185);
+        assert!(d.decode(None, 0x185, &[0x81]).unwrap().signals.is_empty());
+        write(&mut d, 0x1A00, 0, 0);
+        write(&mut d, 0x1A00, 1, 0x6402_0020);
+        write(&mut d, 0x1A00, 2, 0x6403_0001);
+        write(&mut d, 0x1A00, 0, 2);
+        write(&mut d, 0x1800, 1, 0x0000_0195);
+
+        let mut data = 20.25_f32.to_le_bytes().to_vec();
+        data.push(0x01);
+        let info = d.decode(None, 0x195, &data).unwrap();
+        assert_eq!(info.name, "tpdo1");
+        assert_eq!(info.node_id, Some(5));
+        assert_eq!(signal(&info, "Temperature"), Some(&20.25_f64.into()));
+        assert_eq!(signal(&info, "Enabled"), Some(&true.into()));
+
+        // The predefined COB-ID no longer belongs to the PDO
+        let info = d.decode(None, 0x185, &[0x81]).unwrap();
+        assert!(info.signals.is_empty());
+
+        // Ignored unless enabled
+        let mut d = decoder(false);
+        write(&mut d, 0x1A00, 0, 0);
+        let info = d.decode(None, 0x185, &[0x81]).unwrap();
+        assert_eq!(signal(&info, "Digital_inputs"), Some(&0x81_u64.into()));
+    }
+}
@@ -1,14 +1,18 @@
 use crate::{
-    dbc::{Dbc, EmptyStringExt},
+    dbc::{AttributeDefinitions, AttributeObject, Dbc, EmptyStringExt},
     parser::ParsedCanFrame,
     CommonConfig,
 };
 use auxon_sdk::api::AttrVal;
+use can_dbc::{AttributeValuedForObjectType, Comment};
 
 #[derive(Eq, PartialEq, Hash, Default)]
 pub struct TimelineKey {
     node_name: Option<String>,
     default_name: Option<String>,
+    interface: Option<String>,
+    source_address: Option<u8>,
+    node_id: Option<u8>,
 }
 
 impl TimelineKey {
@@ -21,6 +25,30 @@ impl TimelineKey {
         if let Some(n) = config.default_timeline.as_ref() {
             key.default_name = Some(n.to_owned());
         }
+        if config.timeline_from_interface.unwrap_or(true) {
+            key.interface.clone_from(&pcf.interface);
+        }
+
+        let j1939 = config.j1939.unwrap_or(false) || config.nmea2000.unwrap_or(false);
+        if j1939 && config.timeline_from_source_address.unwrap_or(true) {
+            key.source_address = pcf.source_address;
+            if let Some(sa) = key.source_address {
+                key.default_name = Some(format!(
+                    "{}_sa_{sa:#04x}",
+                    key.default_name.as_deref().unwrap_or("canbus")
+                ));
+            }
+        }
+
+        if config.canopen.unwrap_or(false) {
+            key.node_id = pcf.node_id;
+            if let Some(node_id) = key.node_id {
+                key.default_name = Some(format!(
+                    "{}_node_{node_id}",
+                    key.default_name.as_deref().unwrap_or("canbus")
+                ));
+            }
+        }
 
         key
     }
@@ -32,27 +60,94 @@ impl TimelineKey {
             .unwrap_or("canbus")
     }
 
-    pub fn timeline_attrs(&self, dbc: &Option<Dbc>) -> Vec<(&'static str, AttrVal)> {
+    pub fn timeline_attrs(
+        &self,
+        dbc: &Option<Dbc>,
+        config: &CommonConfig,
+    ) -> Vec<(String, AttrVal)> {
         let mut attrs = vec![];
 
         if let Some(node) = self.node_name.as_ref() {
-            attrs.push(("timeline.transmitter", node.into()));
+            attrs.push(("timeline.transmitter".to_owned(), node.into()));
+        }
+
+        if let Some(iface) = self.interface.as_ref() {
+            attrs.push(("timeline.interface".to_owned(), iface.into()));
+        }
+
+        if let Some(sa) = self.source_address {
+            attrs.push((
+                "timeline.j1939.source_address".to_owned(),
+                (sa as u64).into(),
+            ));
+        }
+
+        if let Some(node_id) = self.node_id {
+            attrs.push((
+                "timeline.canopen.node_id".to_owned(),
+                (node_id as u64).into(),
+            ));
         }
 
         if let Some(dbc) = dbc {
             gather_dbc_attrs(dbc, &mut attrs);
+            if let Some(node) = self.node_name.as_ref() {
+                gather_dbc_node_attrs(dbc, node, config, &mut attrs);
+            }
         }
 
         attrs
     }
 }
 
-fn gather_dbc_attrs(dbc: &Dbc, attrs: &mut Vec<(&'static str, AttrVal)>) {
+fn gather_dbc_attrs(dbc: &Dbc, attrs: &mut Vec<(String, AttrVal)>) {
     if let Some(version) = (&dbc.inner.version().0).empty_opt() {
-        attrs.push(("timeline.dbc.version", version.into()));
+        attrs.push(("timeline.dbc.version".to_owned(), version.into()));
     }
     if let Some(file_name) = dbc.file_name.as_ref() {
-        attrs.push(("timeline.dbc.file_name", file_name.into()));
+        attrs.push(("timeline.dbc.file_name".to_owned(), file_name.into()));
+    }
+    attrs.push(("timeline.dbc.sha256".to_owned(), dbc.sha256.as_str().into()));
+}
+
+/// The configured comment and attributes of the transmitting node
+fn gather_dbc_node_attrs(
+    dbc: &Dbc,
+    node: &str,
+    config: &CommonConfig,
+    attrs: &mut Vec<(String, AttrVal)>,
+) {
+    if config.dbc_node_comments.unwrap_or(false) {
+        let comment = dbc.inner.comments().iter().find_map(|c| match c {
+            Comment::Node { node_name, comment } if node_name == node => Some(comment),
+            _ => None,
+        });
+        if let Some(comment) = comment.filter(|c| !c.is_empty()) {
+            attrs.push(("timeline.transmitter.comment".to_owned(), comment.into()));
+        }
+    }
+
+    let names = config.dbc_node_attributes.as_deref().unwrap_or_default();
+    if names.is_empty() {
+        return;
+    }
+    let attr_defs = AttributeDefinitions::new(&dbc.inner);
+    for name in names {
+        let val =
+            dbc.inner
+                .attribute_values()
+                .iter()
+                .find_map(|attr| match attr.attribute_value() {
+                    AttributeValuedForObjectType::NetworkNodeAttributeValue(node_name, val)
+                        if node_name == node && attr.attribute_name() == name =>
+                    {
+                        Some(attr_defs.value(name, val))
+                    }
+                    _ => None,
+                });
+        // Nodes without a value of their own take the attribute's default
+        if let Some(val) = val.or_else(|| attr_defs.default_value(name, AttributeObject::Node)) {
+            attrs.push((format!("timeline.transmitter.{name}"), val));
+        }
     }
-    attrs.push(("timeline.dbc.sha256", dbc.sha256.as_str().into()));
 }
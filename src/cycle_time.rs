@@ -0,0 +1,281 @@
+//! Message cycle-time monitoring.
+
+use crate::parser::{config_message_id, RawCanIdExt};
+use anyhow::anyhow;
+use can_dbc::{AttributeValue, AttributeValuedForObjectType, MessageId, DBC};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const NANOS_PER_MS: i64 = 1_000_000;
+
+/// Default fraction of the cycle time a message may be late by
+const DEFAULT_TOLERANCE: f64 = 0.5;
+
+/// The cycle time of a message, overriding its `GenMsgCycleTime` DBC attribute
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct CycleTimeConfig {
+    /// The DBC message name.
+    pub message: Option<String>,
+
+    /// The CAN ID, for messages not in the DBC.
+    pub id: Option<u32>,
+
+    /// Is `id` an extended (29-bit) ID? Defaults to false, IDs above 0x7FF are always extended.
+    pub extended: Option<bool>,
+
+    /// The cycle time in milliseconds, 0 to disable monitoring.
+    #[serde(alias = "cycle_time_ms")]
+    pub cycle_time_ms: u64,
+}
+
+/// The inter-arrival time of a frame
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Period {
+    pub delta_ns: i64,
+    pub cycle_time_ns: i64,
+}
+
+impl Period {
+    /// Relative to the cycle time, positive when late
+    pub fn deviation(&self) -> f64 {
+        (self.delta_ns - self.cycle_time_ns) as f64 / self.cycle_time_ns as f64
+    }
+}
+
+/// A periodic message that wasn't received within its cycle time and tolerance
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct MessageTimeout {
+    pub interface: Option<String>,
+    pub id: MessageId,
+    pub cycle_time_ns: i64,
+    /// When the message was due, including the tolerance
+    pub deadline_ns: i64,
+}
+
+#[derive(Debug)]
+struct MessageState {
+    last_ns: i64,
+    timed_out: bool,
+}
+
+/// Tracks the arrival of periodic messages on each interface
+#[derive(Debug, Default)]
+pub(crate) struct CycleMonitor {
+    cycle_times_ns: HashMap<MessageId, i64>,
+    tolerance: f64,
+    messages: HashMap<(Option<String>, MessageId), MessageState>,
+}
+
+impl CycleMonitor {
+    /// Configured cycle times take precedence over the `GenMsgCycleTime` DBC attributes
+    pub fn new(
+        configs: &[CycleTimeConfig],
+        tolerance: Option<f64>,
+        dbc: Option<&DBC>,
+    ) -> Result<Self, anyhow::Error> {
+        let mut cycle_times_ns = HashMap::new();
+        if let Some(dbc) = dbc {
+            for attr in dbc
+                .attribute_values()
+                .iter()
+                .filter(|a| a.attribute_name() == "GenMsgCycleTime")
+            {
+                if let AttributeValuedForObjectType::MessageDefinitionAttributeValue(
+                    id,
+                    Some(val),
+                ) = attr.attribute_value()
+                {
+                    if let Some(ms) = attr_value_ms(val).filter(|ms| *ms > 0) {
+                        cycle_times_ns.insert(*id, ms as i64 * NANOS_PER_MS);
+                    }
+                }
+            }
+        }
+
+        for cfg in configs.iter() {
+            let id = match (cfg.id, cfg.message.as_ref()) {
+                (Some(id), _) => config_message_id(id, cfg.extended),
+                (None, Some(name)) => dbc
+                    .and_then(|dbc| {
+                        dbc.messages()
+                            .iter()
+                            .find(|m| m.message_name() == name)
+                            .map(|m| *m.message_id())
+                    })
+                    .ok_or_else(|| anyhow!("Cycle time message '{}' not found in the DBC", name))?,
+                (None, None) => return Err(anyhow!("Cycle time has no message or id")),
+            };
+            if cfg.cycle_time_ms == 0 {
+                cycle_times_ns.remove(&id);
+            } else {
+                cycle_times_ns.insert(id, cfg.cycle_time_ms as i64 * NANOS_PER_MS);
+            }
+        }
+
+        Ok(Self {
+            cycle_times_ns,
+            tolerance: tolerance.unwrap_or(DEFAULT_TOLERANCE).max(0.0),
+            messages: Default::default(),
+        })
+    }
+
+    /// Record a frame, returning its inter-arrival time for periodic messages
+    pub fn frame(&mut self, interface: Option<&str>, id: MessageId, now_ns: i64) -> Option<Period> {
+        let cycle_time_ns = *self.cycle_times_ns.get(&id)?;
+        let state = MessageState {
+            last_ns: now_ns,
+            timed_out: false,
+        };
+        let last = self
+            .messages
+            .insert((interface.map(str::to_owned), id), state)?;
+        Some(Period {
+            delta_ns: now_ns - last.last_ns,
+            cycle_time_ns,
+        })
+    }
+
+    /// Messages on the interface that have gone missing by `now_ns`, each reported once
+    /// until it's received again
+    pub fn expire(&mut self, interface: Option<&str>, now_ns: i64) -> Vec<MessageTimeout> {
+        let mut timeouts = Vec::new();
+        for ((iface, id), state) in self.messages.iter_mut() {
+            if iface.as_deref() != interface || state.timed_out {
+                continue;
+            }
+            let cycle_time_ns = self.cycle_times_ns[id];
+            let allowed_ns = cycle_time_ns + (cycle_time_ns as f64 * self.tolerance) as i64;
+            let deadline_ns = state.last_ns + allowed_ns;
+            if now_ns > deadline_ns {
+                state.timed_out = true;
+                timeouts.push(MessageTimeout {
+                    interface: iface.clone(),
+                    id: *id,
+                    cycle_time_ns,
+                    deadline_ns,
+                });
+            }
+        }
+        timeouts.sort_by_key(|t| (t.deadline_ns, t.id.raw_can_id()));
+        timeouts
+    }
+}
+
+fn attr_value_ms(val: &AttributeValue) -> Option<u64> {
+    match val {
+        AttributeValue::AttributeValueU64(v) => Some(*v),
+        AttributeValue::AttributeValueI64(v) => u64::try_from(*v).ok(),
+        AttributeValue::AttributeValueF64(v) if *v >= 0.0 => Some(*v as u64),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: i64 = NANOS_PER_MS;
+
+    fn std_id(id: u16) -> MessageId {
+        MessageId::Standard(id)
+    }
+
+    #[test]
+    fn periods_and_timeouts() {
+        let dbc = DBC::try_from(
+            r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: Engine
+
+BO_ 256 Fast: 8 Engine
+ SG_ A : 0|8@1+ (1,0) [0|255] "" Vector__XXX
+
+BO_ 512 Slow: 8 Engine
+ SG_ B : 0|8@1+ (1,0) [0|255] "" Vector__XXX
+
+BO_ 768 Disabled: 8 Engine
+ SG_ C : 0|8@1+ (1,0) [0|255] "" Vector__XXX
+
+BA_DEF_ BO_ "GenMsgCycleTime" INT 0 65535;
+BA_DEF_DEF_ "GenMsgCycleTime" 0;
+BA_ "GenMsgCycleTime" BO_ 256 10;
+BA_ "GenMsgCycleTime" BO_ 512 1000;
+BA_ "GenMsgCycleTime" BO_ 768 50;
+"#,
+        )
+        .unwrap();
+        let configs = [
+            CycleTimeConfig {
+                message: Some("Slow".to_owned()),
+                cycle_time_ms: 100,
+                ..Default::default()
+            },
+            CycleTimeConfig {
+                id: Some(768),
+                cycle_time_ms: 0,
+                ..Default::default()
+            },
+            CycleTimeConfig {
+                id: Some(256),
+                extended: Some(true),
+                cycle_time_ms: 20,
+                ..Default::default()
+            },
+        ];
+        let mut m = CycleMonitor::new(&configs, Some(0.2), Some(&dbc)).unwrap();
+
+        assert_eq!(m.frame(None, std_id(256), 0), None);
+        assert_eq!(m.frame(None, std_id(512), 0), None);
+        assert_eq!(m.frame(None, std_id(768), 0), None);
+        let period = m.frame(None, std_id(256), 11 * MS).unwrap();
+        assert_eq!(period.delta_ns, 11 * MS);
+        assert_eq!(period.cycle_time_ns, 10 * MS);
+        assert!((period.deviation() - 0.1).abs() < 1e-9);
+
+        assert!(m.expire(None, 23 * MS).is_empty());
+        let timeouts = m.expire(None, 24 * MS);
+        assert_eq!(
+            timeouts,
+            vec![MessageTimeout {
+                interface: None,
+                id: std_id(256),
+                cycle_time_ns: 10 * MS,
+                deadline_ns: 23 * MS,
+            }]
+        );
+        // Reported once
+        assert!(m.expire(None, 50 * MS).is_empty());
+        let timeouts = m.expire(None, 121 * MS);
+        assert_eq!(timeouts.len(), 1);
+        assert_eq!(timeouts[0].id, std_id(512));
+
+        // Extended IDs are separate from standard IDs of the same value
+        let ext_id = MessageId::Extended(256);
+        assert_eq!(m.frame(None, ext_id, 100 * MS), None);
+        let period = m.frame(None, ext_id, 120 * MS).unwrap();
+        assert_eq!(period.cycle_time_ns, 20 * MS);
+        assert_eq!(m.expire(None, 151 * MS)[0].id, ext_id);
+
+        // Other interfaces are tracked separately
+        assert!(m.expire(Some("can1"), 1000 * MS).is_empty());
+
+        // Received again
+        assert!(m.frame(None, std_id(256), 200 * MS).is_some());
+        assert_eq!(m.expire(None, 300 * MS).len(), 1);
+    }
+
+    #[test]
+    fn unknown_message() {
+        let configs = [CycleTimeConfig {
+            message: Some("Missing".to_owned()),
+            cycle_time_ms: 100,
+            ..Default::default()
+        }];
+        assert!(CycleMonitor::new(&configs, None, None).is_err());
+    }
+}
@@ -1,6 +1,8 @@
 use anyhow::anyhow;
-use can_dbc::DBC;
+use auxon_sdk::api::AttrVal;
+use can_dbc::{AttributeDefinition, AttributeValue, DBC};
 use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 use tracing::{info, warn};
@@ -40,7 +42,9 @@ impl Dbc {
                 ));
             }
             Err(can_dbc::Error::MultipleMultiplexors) => {
-                return Err(anyhow!("Failed to read the DBC file '{path_display}' due to unsupported extended multimultiplexing"));
+                return Err(anyhow!(
+                    "Failed to read the DBC file '{path_display}' due to an invalid multiplexor definition"
+                ));
             }
         };
         Ok(Self {
@@ -65,3 +69,105 @@ impl EmptyStringExt for &String {
         }
     }
 }
+
+pub(crate) fn attribute_value_to_attr_val(val: &AttributeValue) -> AttrVal {
+    match val {
+        AttributeValue::AttributeValueU64(v) => (*v).into(),
+        AttributeValue::AttributeValueI64(v) => (*v).into(),
+        AttributeValue::AttributeValueF64(v) => (*v).into(),
+        AttributeValue::AttributeValueCharString(v) => v.into(),
+    }
+}
+
+/// The kind of object a DBC attribute is defined for
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum AttributeObject {
+    Node,
+    Message,
+    Signal,
+    Other,
+}
+
+#[derive(Debug)]
+struct AttributeDef {
+    object: AttributeObject,
+    /// The labels of an ENUM attribute, indexed by value
+    enum_values: Option<Vec<String>>,
+    default: Option<AttributeValue>,
+}
+
+/// The `BA_DEF_` definitions and `BA_DEF_DEF_` defaults of the attributes in a DBC file
+#[derive(Debug, Default)]
+pub(crate) struct AttributeDefinitions {
+    defs: HashMap<String, AttributeDef>,
+}
+
+impl AttributeDefinitions {
+    pub(crate) fn new(dbc: &DBC) -> Self {
+        let mut defs = HashMap::new();
+        for def in dbc.attribute_definitions().iter() {
+            let (object, text) = match def {
+                AttributeDefinition::Node(t) => (AttributeObject::Node, t),
+                AttributeDefinition::Message(t) => (AttributeObject::Message, t),
+                AttributeDefinition::Signal(t) => (AttributeObject::Signal, t),
+                AttributeDefinition::EnvironmentVariable(t) | AttributeDefinition::Plain(t) => {
+                    (AttributeObject::Other, t)
+                }
+            };
+            // The definition text is `"<name>" <type> <values>`, where the values of an ENUM
+            // are a comma separated list of quoted labels
+            let parts: Vec<&str> = text.split('"').collect();
+            let Some(name) = parts.get(1) else {
+                continue;
+            };
+            let is_enum = parts
+                .get(2)
+                .is_some_and(|t| t.split_whitespace().next() == Some("ENUM"));
+            let enum_values = is_enum.then(|| {
+                parts[3..]
+                    .iter()
+                    .step_by(2)
+                    .map(|l| (*l).to_owned())
+                    .collect()
+            });
+            defs.insert(
+                (*name).to_owned(),
+                AttributeDef {
+                    object,
+                    enum_values,
+                    default: None,
+                },
+            );
+        }
+        for default in dbc.attribute_defaults().iter() {
+            if let Some(def) = defs.get_mut(default.attribute_name()) {
+                def.default = Some(default.attribute_value().clone());
+            }
+        }
+        Self { defs }
+    }
+
+    /// Convert an attribute value, resolving ENUM indices to their labels
+    pub(crate) fn value(&self, name: &str, val: &AttributeValue) -> AttrVal {
+        let labels = self.defs.get(name).and_then(|d| d.enum_values.as_ref());
+        let index = match val {
+            AttributeValue::AttributeValueU64(v) => usize::try_from(*v).ok(),
+            AttributeValue::AttributeValueI64(v) => usize::try_from(*v).ok(),
+            _ => None,
+        };
+        match labels.zip(index).and_then(|(l, i)| l.get(i)) {
+            Some(label) => label.into(),
+            None => attribute_value_to_attr_val(val),
+        }
+    }
+
+    /// The default value of an attribute defined for `object`. Empty string defaults are
+    /// treated as no default.
+    pub(crate) fn default_value(&self, name: &str, object: AttributeObject) -> Option<AttrVal> {
+        let def = self.defs.get(name).filter(|d| d.object == object)?;
+        match def.default.as_ref()? {
+            AttributeValue::AttributeValueCharString(v) if v.is_empty() => None,
+            v => Some(self.value(name, v)),
+        }
+    }
+}
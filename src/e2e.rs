@@ -0,0 +1,845 @@
+//! AUTOSAR end-to-end (E2E) protection checking, for profiles 1, 2, 4, 5 and 11.
+
+use crate::parser::config_message_id;
+use anyhow::anyhow;
+use can_dbc::{AttributeValue, AttributeValuedForObjectType, ByteOrder, Message, MessageId, DBC};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::warn;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum E2EProfile {
+    /// 8-bit CRC and 4-bit counter
+    P01,
+    /// 8-bit CRC and 4-bit counter, with a data ID for each counter value
+    P02,
+    /// 12-byte header with length, 16-bit counter, data ID and 32-bit CRC
+    P04,
+    /// 16-bit CRC and 8-bit counter
+    P05,
+    /// 8-bit CRC and 4-bit counter
+    P11,
+}
+
+impl E2EProfile {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            E2EProfile::P01 => "p01",
+            E2EProfile::P02 => "p02",
+            E2EProfile::P04 => "p04",
+            E2EProfile::P05 => "p05",
+            E2EProfile::P11 => "p11",
+        }
+    }
+
+    /// From the profile number, e.g. `1`, `P01` or `PROFILE_01`
+    fn from_attr(s: &str) -> Option<Self> {
+        let digits: String = s.chars().filter(|c| c.is_ascii_digit()).collect();
+        Some(match digits.parse::<u8>().ok()? {
+            1 => E2EProfile::P01,
+            2 => E2EProfile::P02,
+            4 => E2EProfile::P04,
+            5 => E2EProfile::P05,
+            11 => E2EProfile::P11,
+            _ => return None,
+        })
+    }
+
+    /// The number of counter values
+    fn counter_modulus(&self) -> u32 {
+        match self {
+            // 0xF is not a valid counter value
+            E2EProfile::P01 | E2EProfile::P11 => 15,
+            E2EProfile::P02 => 16,
+            E2EProfile::P04 => 0x10000,
+            E2EProfile::P05 => 0x100,
+        }
+    }
+}
+
+/// How the data ID of profiles 1 and 11 is included in the CRC
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum E2EDataIdMode {
+    /// Both bytes
+    #[default]
+    Both,
+    /// The low byte for even counter values, the high byte for odd
+    Alt,
+    /// Only the low byte
+    Low,
+    /// The low byte, with the high nibble sent in the message
+    Nibble,
+}
+
+/// E2E protection of a message
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct E2EConfig {
+    /// The DBC message name.
+    pub message: Option<String>,
+
+    /// The CAN ID, for messages not in the DBC.
+    pub id: Option<u32>,
+
+    /// Is `id` an extended (29-bit) ID? Defaults to false, IDs above 0x7FF are always extended.
+    pub extended: Option<bool>,
+
+    pub profile: Option<E2EProfile>,
+
+    /// The data ID, 16 bits for profiles 1, 5 and 11, 32 bits for profile 4.
+    #[serde(alias = "data_id")]
+    pub data_id: Option<u32>,
+
+    /// The 16 data IDs of profile 2, indexed by the counter.
+    #[serde(alias = "data_id_list")]
+    pub data_id_list: Option<Vec<u8>>,
+
+    /// Profiles 1 and 11 only. Defaults to both.
+    #[serde(alias = "data_id_mode")]
+    pub data_id_mode: Option<E2EDataIdMode>,
+
+    /// The DBC signal holding the CRC. Defaults to the profile's standard layout.
+    #[serde(alias = "crc_signal")]
+    pub crc_signal: Option<String>,
+
+    /// The DBC signal holding the counter. Defaults to the profile's standard layout.
+    #[serde(alias = "counter_signal")]
+    pub counter_signal: Option<String>,
+
+    /// The largest counter increment that isn't a wrong sequence. Defaults to 1.
+    #[serde(alias = "max_delta_counter")]
+    pub max_delta_counter: Option<u32>,
+}
+
+impl E2EConfig {
+    /// Messages with an `E2EProfile` attribute, and their `E2EDataID`, `E2EDataIDList`,
+    /// `E2EDataIDMode` and `E2EMaxDeltaCounter` attributes. Signals are located by their
+    /// `E2ESignal` attribute, `crc` or `counter`.
+    pub(crate) fn from_dbc(dbc: &DBC) -> Vec<Self> {
+        let mut configs: Vec<Self> = Vec::new();
+        for msg in dbc.messages().iter() {
+            let msg_attr = |name: &str| {
+                dbc.attribute_values()
+                    .iter()
+                    .filter(|a| a.attribute_name() == name)
+                    .find_map(|a| match a.attribute_value() {
+                        AttributeValuedForObjectType::MessageDefinitionAttributeValue(
+                            id,
+                            Some(val),
+                        ) if id == msg.message_id() => Some(attr_string(val)),
+                        _ => None,
+                    })
+            };
+            let Some(profile) = msg_attr("E2EProfile") else {
+                continue;
+            };
+            let Some(profile) = E2EProfile::from_attr(&profile) else {
+                warn!(
+                    msg = msg.message_name(),
+                    profile, "Unsupported E2E profile, the message won't be checked"
+                );
+                continue;
+            };
+            let signal = |kind: &str| {
+                dbc.attribute_values()
+                    .iter()
+                    .filter(|a| a.attribute_name() == "E2ESignal")
+                    .find_map(|a| match a.attribute_value() {
+                        AttributeValuedForObjectType::SignalAttributeValue(id, name, val)
+                            if id == msg.message_id()
+                                && attr_string(val).eq_ignore_ascii_case(kind) =>
+                        {
+                            Some(name.clone())
+                        }
+                        _ => None,
+                    })
+            };
+            configs.push(E2EConfig {
+                message: Some(msg.message_name().clone()),
+                id: None,
+                extended: None,
+                profile: Some(profile),
+                data_id: msg_attr("E2EDataID").and_then(|v| v.parse().ok()),
+                data_id_list: msg_attr("E2EDataIDList")
+                    .map(|v| v.split(',').filter_map(|id| parse_u8(id.trim())).collect()),
+                data_id_mode: msg_attr("E2EDataIDMode").and_then(|v| {
+                    match v.to_ascii_lowercase().as_str() {
+                        "both" => Some(E2EDataIdMode::Both),
+                        "alt" => Some(E2EDataIdMode::Alt),
+                        "low" => Some(E2EDataIdMode::Low),
+                        "nibble" => Some(E2EDataIdMode::Nibble),
+                        _ => None,
+                    }
+                }),
+                crc_signal: signal("crc"),
+                counter_signal: signal("counter"),
+                max_delta_counter: msg_attr("E2EMaxDeltaCounter").and_then(|v| v.parse().ok()),
+            });
+        }
+        configs
+    }
+}
+
+fn attr_string(val: &AttributeValue) -> String {
+    match val {
+        AttributeValue::AttributeValueU64(v) => v.to_string(),
+        AttributeValue::AttributeValueI64(v) => v.to_string(),
+        AttributeValue::AttributeValueF64(v) => v.to_string(),
+        AttributeValue::AttributeValueCharString(s) => s.clone(),
+    }
+}
+
+fn parse_u8(s: &str) -> Option<u8> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum E2EStatus {
+    Ok,
+    WrongCrc,
+    Repeated,
+    WrongSequence,
+    /// The frame is too short to hold the protection
+    NoNewData,
+}
+
+impl E2EStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            E2EStatus::Ok => "OK",
+            E2EStatus::WrongCrc => "WRONG_CRC",
+            E2EStatus::Repeated => "REPEATED",
+            E2EStatus::WrongSequence => "WRONG_SEQUENCE",
+            E2EStatus::NoNewData => "NO_NEW_DATA",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct E2EResult {
+    pub profile: E2EProfile,
+    pub status: E2EStatus,
+    pub counter: Option<u32>,
+    /// The counter increment since the last frame with a valid CRC
+    pub counter_delta: Option<u32>,
+    pub crc: Option<u32>,
+    /// Only when the CRC is wrong
+    pub computed_crc: Option<u32>,
+    /// A profile 4 header field that doesn't match, `length` or `data_id`
+    pub mismatch: Option<&'static str>,
+}
+
+/// The protection of a message, with the byte and bit offsets of its fields
+#[derive(Clone, Debug, PartialEq)]
+struct Protection {
+    profile: E2EProfile,
+    data_id: u32,
+    data_id_list: Vec<u8>,
+    data_id_mode: E2EDataIdMode,
+    /// Byte offset of the CRC, or the header for profile 4
+    crc_offset: usize,
+    /// Bit offset of the counter's least significant bit
+    counter_offset: usize,
+    /// The counter is a Motorola signal, which may cross into the previous byte
+    counter_big_endian: bool,
+    max_delta_counter: u32,
+}
+
+impl Protection {
+    fn new(cfg: &E2EConfig, msg: Option<&Message>) -> Result<Self, anyhow::Error> {
+        let name = cfg
+            .message
+            .clone()
+            .or_else(|| cfg.id.map(|id| format!("{id:#x}")))
+            .unwrap_or_default();
+        let profile = cfg
+            .profile
+            .ok_or_else(|| anyhow!("E2E protection of '{}' has no profile", name))?;
+        let signal_offsets = |signal: Option<&String>| -> Result<Option<(usize, usize, bool)>, _> {
+            let Some(signal) = signal else {
+                return Ok(None);
+            };
+            let sig = msg
+                .and_then(|m| m.signals().iter().find(|s| s.name() == signal))
+                .ok_or_else(|| anyhow!("E2E signal '{}' not found in '{}'", signal, name))?;
+            let start = sig.start_bit as usize;
+            let size = sig.signal_size as usize;
+            // Bit offset of the least significant bit, the byte offset of the first byte
+            let bit = match sig.byte_order() {
+                ByteOrder::LittleEndian => Some(start),
+                // Motorola signals run from their most significant bit through each byte's
+                // bits in descending order, as in the parser's `be_start_end_bit`
+                ByteOrder::BigEndian => {
+                    let msb = (start & !0x7) + 7 - (start & 0x7);
+                    (msb + size)
+                        .checked_sub(1)
+                        .map(|lsb| (lsb & !0x7) + 7 - (lsb & 0x7))
+                }
+            }
+            .ok_or_else(|| anyhow!("E2E signal '{}' of '{}' is empty", signal, name))?;
+            let big_endian = sig.byte_order() == &ByteOrder::BigEndian;
+            Ok::<_, anyhow::Error>(Some((bit, start / 8, big_endian)))
+        };
+        let crc = signal_offsets(cfg.crc_signal.as_ref())?;
+        let counter = signal_offsets(cfg.counter_signal.as_ref())?;
+
+        // The standard layouts, with the CRC first
+        let (crc_offset, counter_offset) = match profile {
+            E2EProfile::P01 | E2EProfile::P02 | E2EProfile::P11 => (
+                crc.map(|c| c.1).unwrap_or(0),
+                counter.map(|c| c.0).unwrap_or(8),
+            ),
+            // The header starts with the length and counter, followed by the data ID and CRC
+            E2EProfile::P04 => {
+                let header = crc
+                    .map(|c| c.1.saturating_sub(8))
+                    .or_else(|| counter.map(|c| c.1.saturating_sub(2)))
+                    .unwrap_or(0);
+                (header, (header + 2) * 8)
+            }
+            E2EProfile::P05 => {
+                let crc_offset = crc.map(|c| c.1).unwrap_or(0);
+                (
+                    crc_offset,
+                    counter.map(|c| c.1 * 8).unwrap_or((crc_offset + 2) * 8),
+                )
+            }
+        };
+
+        let data_id_list = cfg.data_id_list.clone().unwrap_or_default();
+        if profile == E2EProfile::P02 && data_id_list.len() != 16 {
+            return Err(anyhow!(
+                "E2E profile 2 protection of '{}' needs a data-id-list of 16 data IDs",
+                name
+            ));
+        }
+        if profile != E2EProfile::P02 && cfg.data_id.is_none() {
+            return Err(anyhow!("E2E protection of '{}' has no data-id", name));
+        }
+
+        Ok(Protection {
+            profile,
+            counter_big_endian: counter.is_some_and(|c| c.2),
+            data_id: cfg.data_id.unwrap_or(0),
+            data_id_list,
+            data_id_mode: cfg.data_id_mode.unwrap_or_default(),
+            crc_offset,
+            counter_offset,
+            max_delta_counter: cfg.max_delta_counter.unwrap_or(1).max(1),
+        })
+    }
+
+    /// The counter, received CRC, computed CRC and any header mismatch
+    fn verify(&self, data: &[u8]) -> Option<(u32, u32, u32, Option<&'static str>)> {
+        let nibble = |bit: usize| read_bits(data, bit, 4, self.counter_big_endian);
+        let without = |offset: usize, len: usize| -> Vec<u8> {
+            let mut d = data[..offset].to_vec();
+            d.extend_from_slice(&data[offset + len..]);
+            d
+        };
+        let [id_lo, id_hi, ..] = self.data_id.to_le_bytes();
+
+        match self.profile {
+            E2EProfile::P01 | E2EProfile::P11 => {
+                let crc = *data.get(self.crc_offset)? as u32;
+                let counter = nibble(self.counter_offset)?;
+                let mut bytes = match self.data_id_mode {
+                    E2EDataIdMode::Both => vec![id_lo, id_hi],
+                    E2EDataIdMode::Alt if counter & 1 == 0 => vec![id_lo],
+                    E2EDataIdMode::Alt => vec![id_hi],
+                    E2EDataIdMode::Low => vec![id_lo],
+                    E2EDataIdMode::Nibble => vec![id_lo, 0],
+                };
+                bytes.extend(without(self.crc_offset, 1));
+                // Profile 1 effectively uses a start value and final XOR of 0x00
+                let computed = if self.profile == E2EProfile::P01 {
+                    crc8(0x1D, 0x00, &bytes)
+                } else {
+                    crc8(0x1D, 0xFF, &bytes) ^ 0xFF
+                };
+                Some((counter, crc, computed as u32, None))
+            }
+            E2EProfile::P02 => {
+                let crc = *data.get(self.crc_offset)? as u32;
+                let counter = nibble(self.counter_offset)?;
+                let mut bytes = without(self.crc_offset, 1);
+                bytes.push(self.data_id_list[counter as usize]);
+                Some((counter, crc, (crc8(0x2F, 0xFF, &bytes) ^ 0xFF) as u32, None))
+            }
+            E2EProfile::P04 => {
+                let h = self.crc_offset;
+                let header = data.get(h..h + 12)?;
+                let length = u16::from_be_bytes([header[0], header[1]]);
+                let counter = u16::from_be_bytes([header[2], header[3]]) as u32;
+                let data_id = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+                let crc = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+                let computed = crc32p4(&without(h + 8, 4));
+                let mismatch = if length as usize != data.len() {
+                    Some("length")
+                } else if data_id != self.data_id {
+                    Some("data_id")
+                } else {
+                    None
+                };
+                Some((counter, crc, computed, mismatch))
+            }
+            E2EProfile::P05 => {
+                let crc_bytes = data.get(self.crc_offset..self.crc_offset + 2)?;
+                let crc = u16::from_le_bytes([crc_bytes[0], crc_bytes[1]]) as u32;
+                let counter = *data.get(self.counter_offset / 8)? as u32;
+                let mut bytes = without(self.crc_offset, 2);
+                bytes.extend([id_lo, id_hi]);
+                Some((counter, crc, crc16_ccitt(&bytes) as u32, None))
+            }
+        }
+    }
+}
+
+fn crc8(poly: u8, init: u8, bytes: &[u8]) -> u8 {
+    let mut crc = init;
+    for b in bytes.iter() {
+        crc ^= b;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ poly
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// CRC-16/CCITT-FALSE
+fn crc16_ccitt(bytes: &[u8]) -> u16 {
+    let mut crc = 0xFFFF_u16;
+    for b in bytes.iter() {
+        crc ^= (*b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// CRC-32P4, polynomial 0xF4ACFB13 reflected
+fn crc32p4(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF_u32;
+    for b in bytes.iter() {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xC8DF_352F
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc ^ 0xFFFF_FFFF
+}
+
+/// Read `size` bits from their least significant bit. Intel values continue into the next
+/// byte, Motorola values into the previous one.
+fn read_bits(data: &[u8], lsb: usize, size: usize, big_endian: bool) -> Option<u32> {
+    let mut value = 0;
+    let mut bit = lsb;
+    for i in 0..size {
+        value |= ((*data.get(bit / 8)? as u32 >> (bit & 0x7)) & 1) << i;
+        bit = if big_endian && bit & 0x7 == 7 {
+            match (bit / 8).checked_sub(1) {
+                Some(byte) => byte * 8,
+                None if i + 1 == size => break,
+                None => return None,
+            }
+        } else {
+            bit + 1
+        };
+    }
+    Some(value)
+}
+
+/// Checks the protected messages, tracking the counter of each on each interface
+#[derive(Debug, Default)]
+pub(crate) struct E2EChecker {
+    protections: HashMap<MessageId, Protection>,
+    counters: HashMap<(Option<String>, MessageId), u32>,
+}
+
+impl E2EChecker {
+    /// Configured protections take precedence over those from DBC attributes
+    pub fn new(configs: &[E2EConfig], dbc: Option<&DBC>) -> Result<Self, anyhow::Error> {
+        let mut checker = Self::default();
+        // Incomplete DBC attributes only disable the check of their message
+        for cfg in dbc.map(E2EConfig::from_dbc).unwrap_or_default().iter() {
+            match Self::protection(cfg, dbc) {
+                Ok((id, protection)) => {
+                    checker.protections.insert(id, protection);
+                }
+                Err(e) => warn!(
+                    msg = cfg.message.as_deref().unwrap_or_default(),
+                    "Invalid E2E attributes, the message won't be checked. {e}"
+                ),
+            }
+        }
+        for cfg in configs.iter() {
+            let (id, protection) = Self::protection(cfg, dbc)?;
+            checker.protections.insert(id, protection);
+        }
+        Ok(checker)
+    }
+
+    fn protection(
+        cfg: &E2EConfig,
+        dbc: Option<&DBC>,
+    ) -> Result<(MessageId, Protection), anyhow::Error> {
+        let cfg_id = cfg.id.map(|id| config_message_id(id, cfg.extended));
+        let msg = dbc.and_then(|dbc| {
+            dbc.messages().iter().find(|m| {
+                Some(m.message_name()) == cfg.message.as_ref() || Some(*m.message_id()) == cfg_id
+            })
+        });
+        let id = match (cfg_id, msg) {
+            (Some(id), _) => id,
+            (None, Some(msg)) => *msg.message_id(),
+            (None, None) => {
+                return Err(anyhow!(
+                    "E2E protected message '{}' not found in the DBC",
+                    cfg.message.as_deref().unwrap_or_default()
+                ))
+            }
+        };
+        Ok((id, Protection::new(cfg, msg)?))
+    }
+
+    pub fn check(
+        &mut self,
+        interface: Option<&str>,
+        id: MessageId,
+        data: &[u8],
+    ) -> Option<E2EResult> {
+        let protection = self.protections.get(&id)?;
+        let mut result = E2EResult {
+            profile: protection.profile,
+            status: E2EStatus::NoNewData,
+            counter: None,
+            counter_delta: None,
+            crc: None,
+            computed_crc: None,
+            mismatch: None,
+        };
+        let Some((counter, crc, computed_crc, mismatch)) = protection.verify(data) else {
+            return Some(result);
+        };
+        result.counter = Some(counter);
+        result.crc = Some(crc);
+        result.mismatch = mismatch;
+        if crc != computed_crc || mismatch.is_some() {
+            result.status = E2EStatus::WrongCrc;
+            result.computed_crc = Some(computed_crc);
+            return Some(result);
+        }
+
+        let modulus = protection.profile.counter_modulus();
+        let key = (interface.map(str::to_owned), id);
+        result.status = if counter >= modulus {
+            E2EStatus::WrongSequence
+        } else {
+            match self.counters.insert(key, counter) {
+                None => E2EStatus::Ok,
+                Some(last) => {
+                    let delta = (counter + modulus - last) % modulus;
+                    result.counter_delta = Some(delta);
+                    if delta == 0 {
+                        E2EStatus::Repeated
+                    } else if delta <= protection.max_delta_counter {
+                        E2EStatus::Ok
+                    } else {
+                        E2EStatus::WrongSequence
+                    }
+                }
+            }
+        };
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: MessageId = MessageId::Standard(0x100);
+
+    fn checker(cfg: E2EConfig) -> E2EChecker {
+        E2EChecker::new(
+            &[E2EConfig {
+                id: Some(0x100),
+                ..cfg
+            }],
+            None,
+        )
+        .unwrap()
+    }
+
+    fn status(c: &mut E2EChecker, data: &[u8]) -> (&'static str, Option<u32>) {
+        let result = c.check(None, ID, data).unwrap();
+        (result.status.as_str(), result.counter_delta)
+    }
+
+    #[test]
+    fn crcs() {
+        // Check values of the standard parameterizations
+        let check = b"123456789";
+        assert_eq!(crc8(0x1D, 0xFF, check) ^ 0xFF, 0x4B);
+        assert_eq!(crc8(0x2F, 0xFF, check) ^ 0xFF, 0xDF);
+        assert_eq!(crc16_ccitt(check), 0x29B1);
+        assert_eq!(crc32p4(check), 0x1697_D06A);
+    }
+
+    /// Protect a profile 1 or 11 frame, with the CRC in byte 0 and counter in byte 1
+    fn protect_p01(profile: E2EProfile, data_id: u16, counter: u8, payload: &[u8]) -> Vec<u8> {
+        let mut data = vec![0, counter];
+        data.extend_from_slice(payload);
+        let mut bytes = data_id.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&data[1..]);
+        data[0] = match profile {
+            E2EProfile::P01 => crc8(0x1D, 0x00, &bytes),
+            _ => crc8(0x1D, 0xFF, &bytes) ^ 0xFF,
+        };
+        data
+    }
+
+    #[test]
+    fn counters() {
+        let mut c = checker(E2EConfig {
+            profile: Some(E2EProfile::P01),
+            data_id: Some(0x123),
+            ..Default::default()
+        });
+        let frame = |counter| protect_p01(E2EProfile::P01, 0x123, counter, &[1, 2, 3]);
+        assert_eq!(status(&mut c, &frame(13)), ("OK", None));
+        assert_eq!(status(&mut c, &frame(14)), ("OK", Some(1)));
+        assert_eq!(status(&mut c, &frame(0)), ("OK", Some(1)));
+        assert_eq!(status(&mut c, &frame(0)), ("REPEATED", Some(0)));
+        assert_eq!(status(&mut c, &frame(3)), ("WRONG_SEQUENCE", Some(3)));
+        assert_eq!(status(&mut c, &frame(4)), ("OK", Some(1)));
+        assert_eq!(status(&mut c, &frame(15)), ("WRONG_SEQUENCE", None));
+
+        let mut corrupt = frame(5);
+        corrupt[3] ^= 0x10;
+        let result = c.check(None, ID, &corrupt).unwrap();
+        assert_eq!(result.status, E2EStatus::WrongCrc);
+        assert_eq!(result.crc, Some(corrupt[0] as u32));
+        assert_ne!(result.computed_crc, result.crc);
+        // The counter isn't updated by a frame with a wrong CRC
+        assert_eq!(status(&mut c, &frame(5)), ("OK", Some(1)));
+
+        assert_eq!(status(&mut c, &[]), ("NO_NEW_DATA", None));
+        assert!(c
+            .check(None, MessageId::Standard(0x101), &frame(6))
+            .is_none());
+        assert!(c
+            .check(None, MessageId::Extended(0x100), &frame(6))
+            .is_none());
+        // Interfaces are tracked separately
+        assert_eq!(
+            c.check(Some("can1"), ID, &frame(9)).unwrap().counter_delta,
+            None
+        );
+
+        let mut c = checker(E2EConfig {
+            profile: Some(E2EProfile::P11),
+            data_id: Some(0x123),
+            max_delta_counter: Some(2),
+            ..Default::default()
+        });
+        let frame = |counter| protect_p01(E2EProfile::P11, 0x123, counter, &[1, 2, 3]);
+        assert_eq!(status(&mut c, &frame(1)), ("OK", None));
+        assert_eq!(status(&mut c, &frame(3)), ("OK", Some(2)));
+        assert_eq!(status(&mut c, &frame(6)), ("WRONG_SEQUENCE", Some(3)));
+        let p01 = protect_p01(E2EProfile::P01, 0x123, 7, &[1, 2, 3]);
+        assert_eq!(status(&mut c, &p01).0, "WRONG_CRC");
+    }
+
+    #[test]
+    fn profiles() {
+        let data_id_list: Vec<u8> = (0x10..0x20).collect();
+        let mut c = checker(E2EConfig {
+            profile: Some(E2EProfile::P02),
+            data_id_list: Some(data_id_list.clone()),
+            ..Default::default()
+        });
+        let mut data = vec![0, 0x05, 0xAA, 0xBB];
+        let mut bytes = data[1..].to_vec();
+        bytes.push(data_id_list[5]);
+        data[0] = crc8(0x2F, 0xFF, &bytes) ^ 0xFF;
+        assert_eq!(status(&mut c, &data), ("OK", None));
+        data[1] = 0x06;
+        assert_eq!(status(&mut c, &data).0, "WRONG_CRC");
+
+        let mut c = checker(E2EConfig {
+            profile: Some(E2EProfile::P04),
+            data_id: Some(0x0A0B0C0D),
+            ..Default::default()
+        });
+        let p04 = |length: u16, counter: u16, data_id: u32| {
+            let mut data = length.to_be_bytes().to_vec();
+            data.extend_from_slice(&counter.to_be_bytes());
+            data.extend_from_slice(&data_id.to_be_bytes());
+            data.extend_from_slice(&[0, 0, 0, 0, 1, 2, 3, 4]);
+            let mut bytes = data[..8].to_vec();
+            bytes.extend_from_slice(&data[12..]);
+            let crc = crc32p4(&bytes).to_be_bytes();
+            data[8..12].copy_from_slice(&crc);
+            data
+        };
+        assert_eq!(status(&mut c, &p04(16, 0xFFFF, 0x0A0B0C0D)), ("OK", None));
+        assert_eq!(status(&mut c, &p04(16, 0, 0x0A0B0C0D)), ("OK", Some(1)));
+        let result = c.check(None, ID, &p04(16, 1, 0x0A0B0C0E)).unwrap();
+        assert_eq!(result.status, E2EStatus::WrongCrc);
+        assert_eq!(result.mismatch, Some("data_id"));
+        let result = c.check(None, ID, &p04(20, 1, 0x0A0B0C0D)).unwrap();
+        assert_eq!(result.mismatch, Some("length"));
+        assert_eq!(status(&mut c, &[0; 8]).0, "NO_NEW_DATA");
+
+        let mut c = checker(E2EConfig {
+            profile: Some(E2EProfile::P05),
+            data_id: Some(0x1234),
+            ..Default::default()
+        });
+        let p05 = |counter: u8| {
+            let mut data = vec![0, 0, counter, 9, 8, 7];
+            let mut bytes = data[2..].to_vec();
+            bytes.extend_from_slice(&[0x34, 0x12]);
+            let crc = crc16_ccitt(&bytes).to_le_bytes();
+            data[..2].copy_from_slice(&crc);
+            data
+        };
+        assert_eq!(status(&mut c, &p05(0xFF)), ("OK", None));
+        assert_eq!(status(&mut c, &p05(0x00)), ("OK", Some(1)));
+        assert_eq!(status(&mut c, &p05(0x02)), ("WRONG_SEQUENCE", Some(2)));
+    }
+
+    #[test]
+    fn big_endian_counter() {
+        // The counter crosses from byte 0 into byte 1
+        let dbc = DBC::try_from(
+            r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: ECU
+
+BO_ 256 Status: 4 ECU
+ SG_ Status_Counter : 1|4@0+ (1,0) [0|15] "" Vector__XXX
+ SG_ Status_CRC : 23|8@0+ (1,0) [0|255] "" Vector__XXX
+ SG_ Value : 24|8@1+ (1,0) [0|255] "" Vector__XXX
+"#,
+        )
+        .unwrap();
+        let mut c = E2EChecker::new(
+            &[E2EConfig {
+                message: Some("Status".to_owned()),
+                profile: Some(E2EProfile::P01),
+                data_id: Some(0x123),
+                crc_signal: Some("Status_CRC".to_owned()),
+                counter_signal: Some("Status_Counter".to_owned()),
+                ..Default::default()
+            }],
+            Some(&dbc),
+        )
+        .unwrap();
+        let frame = |counter: u8| {
+            let mut data = vec![counter >> 2, (counter & 0x3) << 6, 0, 0x55];
+            let bytes = [0x23, 0x01, data[0], data[1], data[3]];
+            data[2] = crc8(0x1D, 0x00, &bytes);
+            data
+        };
+        assert_eq!(status(&mut c, &frame(13)), ("OK", None));
+        let result = c.check(None, ID, &frame(14)).unwrap();
+        assert_eq!(result.counter, Some(14));
+        assert_eq!(result.counter_delta, Some(1));
+
+        assert_eq!(read_bits(&[0x01, 0x80], 14, 4, true), Some(0b0110));
+        assert_eq!(read_bits(&[0x80], 7, 2, true), None);
+    }
+
+    #[test]
+    fn configuration() {
+        assert_eq!(E2EProfile::from_attr("PROFILE_05"), Some(E2EProfile::P05));
+        assert_eq!(E2EProfile::from_attr("11"), Some(E2EProfile::P11));
+        assert_eq!(E2EProfile::from_attr("3"), None);
+
+        let missing_profile = E2EChecker::new(
+            &[E2EConfig {
+                id: Some(0x100),
+                data_id: Some(1),
+                ..Default::default()
+            }],
+            None,
+        );
+        assert!(missing_profile.is_err());
+        let short_list = E2EChecker::new(
+            &[E2EConfig {
+                id: Some(0x100),
+                profile: Some(E2EProfile::P02),
+                data_id_list: Some(vec![1, 2, 3]),
+                ..Default::default()
+            }],
+            None,
+        );
+        assert!(short_list.is_err());
+        let not_in_dbc = E2EChecker::new(
+            &[E2EConfig {
+                message: Some("Missing".to_owned()),
+                profile: Some(E2EProfile::P01),
+                data_id: Some(1),
+                ..Default::default()
+            }],
+            None,
+        );
+        assert!(not_in_dbc.is_err());
+
+        // Unsupported or incomplete DBC attributes are skipped
+        let dbc = DBC::try_from(
+            r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: ECU
+
+BO_ 256 Unsupported: 8 ECU
+ SG_ A : 0|8@1+ (1,0) [0|255] "" Vector__XXX
+
+BO_ 512 NoDataId: 8 ECU
+ SG_ B : 0|8@1+ (1,0) [0|255] "" Vector__XXX
+
+BO_ 768 Protected: 8 ECU
+ SG_ C : 0|8@1+ (1,0) [0|255] "" Vector__XXX
+
+BA_DEF_ BO_ "E2EProfile" STRING ;
+BA_DEF_ BO_ "E2EDataID" INT 0 65535;
+BA_ "E2EProfile" BO_ 256 "PROFILE_07";
+BA_ "E2EProfile" BO_ 512 "PROFILE_01";
+BA_ "E2EProfile" BO_ 768 "PROFILE_01";
+BA_ "E2EDataID" BO_ 768 291;
+"#,
+        )
+        .unwrap();
+        let c = E2EChecker::new(&[], Some(&dbc)).unwrap();
+        assert_eq!(c.protections.len(), 1);
+        assert!(c.protections.contains_key(&MessageId::Standard(768)));
+    }
+}
@@ -0,0 +1,176 @@
+//! CANopen EDS and DCF (CiA 306) object dictionary files.
+
+use anyhow::anyhow;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tracing::{info, warn};
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct OdEntry {
+    pub name: String,
+    pub data_type: Option<u16>,
+    /// `ParameterValue` for DCF files, otherwise `DefaultValue`
+    pub value: Option<String>,
+}
+
+/// The entries of an object dictionary, keyed by index and subindex.
+/// Variables without subindexes use subindex 0.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct ObjectDictionary {
+    pub entries: HashMap<(u16, u8), OdEntry>,
+    /// From the `[DeviceComissioning]` section of DCF files
+    pub node_id: Option<u8>,
+}
+
+impl ObjectDictionary {
+    pub fn from_file<P: AsRef<Path>>(p: P) -> Result<Self, anyhow::Error> {
+        let path_display = p.as_ref().display();
+        info!(eds = %path_display, "Reading EDS file");
+        let content = fs::read(p.as_ref())
+            .map_err(|e| anyhow!("Failed to read EDS file '{}'. {}", path_display, e))?;
+        // EDS files are commonly Latin-1 encoded
+        let content: String = content.iter().map(|b| *b as char).collect();
+        Ok(Self::parse(&content))
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let mut sections: HashMap<String, HashMap<String, String>> = HashMap::new();
+        let mut current = None;
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let name = name.trim().to_ascii_lowercase();
+                sections.entry(name.clone()).or_default();
+                current = Some(name);
+            } else if let (Some(section), Some((k, v))) = (current.as_ref(), line.split_once('=')) {
+                if let Some(s) = sections.get_mut(section) {
+                    s.insert(k.trim().to_ascii_lowercase(), v.trim().to_owned());
+                }
+            }
+        }
+
+        let mut od = ObjectDictionary {
+            node_id: sections
+                .get("devicecomissioning")
+                .and_then(|s| s.get("nodeid"))
+                .and_then(|v| parse_int(v, None))
+                .and_then(|v| u8::try_from(v).ok()),
+            ..Default::default()
+        };
+        for (name, keys) in sections.iter() {
+            let Some((index, subindex)) = parse_section_name(name) else {
+                continue;
+            };
+            // Arrays and records are described by their subindex sections
+            let has_subs = keys
+                .get("subnumber")
+                .and_then(|v| parse_int(v, None))
+                .is_some_and(|n| n > 0);
+            if subindex.is_none() && has_subs {
+                continue;
+            }
+            let entry = OdEntry {
+                name: keys
+                    .get("parametername")
+                    .cloned()
+                    .unwrap_or_else(|| format!("{index:04X}sub{}", subindex.unwrap_or(0))),
+                data_type: keys
+                    .get("datatype")
+                    .and_then(|v| parse_int(v, None))
+                    .and_then(|v| u16::try_from(v).ok()),
+                value: keys
+                    .get("parametervalue")
+                    .or_else(|| keys.get("defaultvalue"))
+                    .filter(|v| !v.is_empty())
+                    .cloned(),
+            };
+            od.entries.insert((index, subindex.unwrap_or(0)), entry);
+        }
+        od
+    }
+
+    /// The integer value of an entry, with `$NODEID` substituted
+    pub fn value(&self, index: u16, subindex: u8, node_id: u8) -> Option<u64> {
+        let entry = self.entries.get(&(index, subindex))?;
+        let value = entry.value.as_deref()?;
+        let v = parse_int(value, Some(node_id));
+        if v.is_none() {
+            warn!(index, subindex, value, "Invalid object dictionary value");
+        }
+        v
+    }
+}
+
+/// `1A00` or `1A00sub1`
+fn parse_section_name(name: &str) -> Option<(u16, Option<u8>)> {
+    let (index, sub) = match name.split_once("sub") {
+        Some((index, sub)) => (index, Some(u8::from_str_radix(sub, 16).ok()?)),
+        None => (name, None),
+    };
+    if index.len() != 4 {
+        return None;
+    }
+    Some((u16::from_str_radix(index, 16).ok()?, sub))
+}
+
+/// Integers are decimal, hex (`0x`) or octal (leading `0`), and may be relative to the
+/// node-id, e.g. `$NODEID+0x180`
+fn parse_int(s: &str, node_id: Option<u8>) -> Option<u64> {
+    let s = s.trim();
+    let mut total = 0_u64;
+    for term in s.split('+') {
+        let term = term.trim();
+        let v = if term.eq_ignore_ascii_case("$nodeid") {
+            node_id? as u64
+        } else if let Some(hex) = term.strip_prefix("0x").or_else(|| term.strip_prefix("0X")) {
+            u64::from_str_radix(hex, 16).ok()?
+        } else if term.len() > 1 && term.starts_with('0') {
+            u64::from_str_radix(&term[1..], 8).ok()?
+        } else {
+            term.parse().ok()?
+        };
+        total = total.checked_add(v)?;
+    }
+    Some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integers() {
+        assert_eq!(parse_int("10", None), Some(10));
+        assert_eq!(parse_int("0x1A", None), Some(26));
+        assert_eq!(parse_int("010", None), Some(8));
+        assert_eq!(parse_int("0", None), Some(0));
+        assert_eq!(parse_int("$NODEID+0x180", Some(5)), Some(0x185));
+        assert_eq!(parse_int("0x180 + $NODEID", Some(5)), Some(0x185));
+        assert_eq!(parse_int("$NODEID+0x180", None), None);
+        assert_eq!(parse_int("abc", None), None);
+    }
+
+    #[test]
+    fn parse() {
+        let od = ObjectDictionary::parse(
+            "[DeviceComissioning]\r\nNodeID=0x05\r\n\r\n\
+             [1000]\r\nParameterName=Device type\r\nDataType=0x0007\r\nDefaultValue=0x00020192\r\n\r\n\
+             [1800]\r\nParameterName=TPDO communication parameter\r\nSubNumber=2\r\n\r\n\
+             [1800sub1]\r\nParameterName=COB-ID\r\nDataType=0x0007\r\nDefaultValue=$NODEID+0x180\r\n\
+             ParameterValue=0x285\r\n\
+             ; A comment\r\n\
+             [1800sub2]\r\nParameterName=Transmission type\r\nDataType=0x0005\r\nDefaultValue=\r\n",
+        );
+        assert_eq!(od.node_id, Some(5));
+        assert_eq!(od.entries.len(), 3);
+        assert_eq!(od.entries[&(0x1000, 0)].name, "Device type");
+        assert_eq!(od.entries[&(0x1000, 0)].data_type, Some(7));
+        assert_eq!(od.value(0x1800, 1, 5), Some(0x285));
+        assert_eq!(od.entries[&(0x1800, 2)].value, None);
+        assert_eq!(od.value(0x1800, 2, 5), None);
+    }
+}
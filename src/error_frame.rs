@@ -0,0 +1,323 @@
+//! SocketCAN error frame decoding, see `linux/can/error.h`.
+
+use auxon_sdk::api::AttrVal;
+
+// Error class bits, found in the CAN ID
+pub(crate) const CAN_ERR_TX_TIMEOUT: u32 = 0x0000_0001;
+pub(crate) const CAN_ERR_LOSTARB: u32 = 0x0000_0002;
+pub(crate) const CAN_ERR_CRTL: u32 = 0x0000_0004;
+pub(crate) const CAN_ERR_PROT: u32 = 0x0000_0008;
+pub(crate) const CAN_ERR_TRX: u32 = 0x0000_0010;
+pub(crate) const CAN_ERR_ACK: u32 = 0x0000_0020;
+pub(crate) const CAN_ERR_BUSOFF: u32 = 0x0000_0040;
+pub(crate) const CAN_ERR_BUSERROR: u32 = 0x0000_0080;
+pub(crate) const CAN_ERR_RESTARTED: u32 = 0x0000_0100;
+pub(crate) const CAN_ERR_CNT: u32 = 0x0000_0200;
+
+pub(crate) const CAN_ERR_FLAG: u32 = 0x2000_0000;
+pub(crate) const CAN_ERR_MASK: u32 = 0x1FFF_FFFF;
+
+// Controller problems, data[1]
+pub(crate) const CAN_ERR_CRTL_RX_OVERFLOW: u8 = 0x01;
+pub(crate) const CAN_ERR_CRTL_TX_OVERFLOW: u8 = 0x02;
+pub(crate) const CAN_ERR_CRTL_RX_WARNING: u8 = 0x04;
+pub(crate) const CAN_ERR_CRTL_TX_WARNING: u8 = 0x08;
+pub(crate) const CAN_ERR_CRTL_RX_PASSIVE: u8 = 0x10;
+pub(crate) const CAN_ERR_CRTL_TX_PASSIVE: u8 = 0x20;
+pub(crate) const CAN_ERR_CRTL_ACTIVE: u8 = 0x40;
+
+// Protocol violation types, data[2]
+pub(crate) const CAN_ERR_PROT_UNSPEC: u8 = 0x00;
+pub(crate) const CAN_ERR_PROT_BIT: u8 = 0x01;
+pub(crate) const CAN_ERR_PROT_FORM: u8 = 0x02;
+pub(crate) const CAN_ERR_PROT_STUFF: u8 = 0x04;
+pub(crate) const CAN_ERR_PROT_TX: u8 = 0x80;
+
+// Protocol violation locations, data[3]
+pub(crate) const CAN_ERR_PROT_LOC_CRC_SEQ: u8 = 0x08;
+pub(crate) const CAN_ERR_PROT_LOC_ACK_DEL: u8 = 0x1B;
+
+const CLASSES: [(u32, &str); 10] = [
+    (CAN_ERR_TX_TIMEOUT, "tx_timeout"),
+    (CAN_ERR_LOSTARB, "lost_arbitration"),
+    (CAN_ERR_CRTL, "controller"),
+    (CAN_ERR_PROT, "protocol"),
+    (CAN_ERR_TRX, "transceiver"),
+    (CAN_ERR_ACK, "no_ack"),
+    (CAN_ERR_BUSOFF, "bus_off"),
+    (CAN_ERR_BUSERROR, "bus_error"),
+    (CAN_ERR_RESTARTED, "restarted"),
+    (CAN_ERR_CNT, "counters"),
+];
+
+const CONTROLLER_PROBLEMS: [(u8, &str); 7] = [
+    (CAN_ERR_CRTL_RX_OVERFLOW, "rx_overflow"),
+    (CAN_ERR_CRTL_TX_OVERFLOW, "tx_overflow"),
+    (CAN_ERR_CRTL_RX_WARNING, "rx_warning"),
+    (CAN_ERR_CRTL_TX_WARNING, "tx_warning"),
+    (CAN_ERR_CRTL_RX_PASSIVE, "rx_passive"),
+    (CAN_ERR_CRTL_TX_PASSIVE, "tx_passive"),
+    (CAN_ERR_CRTL_ACTIVE, "active"),
+];
+
+const PROTOCOL_TYPES: [(u8, &str); 8] = [
+    (CAN_ERR_PROT_BIT, "bit"),
+    (CAN_ERR_PROT_FORM, "form"),
+    (CAN_ERR_PROT_STUFF, "stuff"),
+    (0x08, "bit0"),
+    (0x10, "bit1"),
+    (0x20, "overload"),
+    (0x40, "active"),
+    (CAN_ERR_PROT_TX, "tx"),
+];
+
+/// A decoded error frame
+#[derive(Debug, PartialEq)]
+pub(crate) struct ErrorFrameInfo {
+    /// A name for the event, based on the most significant error class
+    pub name: &'static str,
+    /// Attribute keys are relative to `event.error.`
+    pub attrs: Vec<(String, AttrVal)>,
+}
+
+/// Decode the error class (CAN ID) and data of an error frame
+pub(crate) fn decode(class: u32, data: &[u8]) -> ErrorFrameInfo {
+    let class = class & CAN_ERR_MASK;
+    let byte = |i: usize| data.get(i).copied().unwrap_or(0);
+    let mut attrs: Vec<(String, AttrVal)> = Vec::new();
+
+    let class_names = CLASSES
+        .iter()
+        .filter(|(bit, _)| class & bit != 0)
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>();
+    if !class_names.is_empty() {
+        attrs.push(("class".to_owned(), class_names.join(",").into()));
+    }
+    attrs.push(("class_bits".to_owned(), class.into()));
+
+    if class & CAN_ERR_LOSTARB != 0 && byte(0) != 0 {
+        attrs.push(("lost_arbitration.bit".to_owned(), byte(0).into()));
+    }
+
+    if class & CAN_ERR_CRTL != 0 {
+        for (bit, name) in CONTROLLER_PROBLEMS.iter() {
+            if byte(1) & bit != 0 {
+                attrs.push((format!("controller.{name}"), true.into()));
+            }
+        }
+    }
+
+    if class & CAN_ERR_PROT != 0 {
+        let types = PROTOCOL_TYPES
+            .iter()
+            .filter(|(bit, _)| byte(2) & bit != 0)
+            .map(|(_, name)| *name)
+            .collect::<Vec<_>>();
+        if !types.is_empty() {
+            attrs.push(("protocol.type".to_owned(), types.join(",").into()));
+        }
+        if let Some(location) = protocol_location(byte(3)) {
+            attrs.push(("protocol.location".to_owned(), location.into()));
+        }
+    }
+
+    if class & CAN_ERR_TRX != 0 {
+        if let Some(status) = can_h_status(byte(4) & 0x0F) {
+            attrs.push(("transceiver.can_h".to_owned(), status.into()));
+        }
+        if let Some(status) = can_l_status(byte(4) & 0xF0) {
+            attrs.push(("transceiver.can_l".to_owned(), status.into()));
+        }
+    }
+
+    if byte(5) != 0 {
+        attrs.push(("controller_specific".to_owned(), byte(5).into()));
+    }
+
+    // Some drivers fill in the counters without setting CAN_ERR_CNT
+    if class & CAN_ERR_CNT != 0 || byte(6) != 0 || byte(7) != 0 {
+        attrs.push(("tx_error_count".to_owned(), byte(6).into()));
+        attrs.push(("rx_error_count".to_owned(), byte(7).into()));
+    }
+
+    ErrorFrameInfo {
+        name: event_name(class, byte(1)),
+        attrs,
+    }
+}
+
+fn event_name(class: u32, controller: u8) -> &'static str {
+    if class & CAN_ERR_BUSOFF != 0 {
+        "bus_off"
+    } else if class & CAN_ERR_RESTARTED != 0 {
+        "controller_restarted"
+    } else if class & CAN_ERR_CRTL != 0 {
+        if controller & (CAN_ERR_CRTL_RX_PASSIVE | CAN_ERR_CRTL_TX_PASSIVE) != 0 {
+            "error_passive"
+        } else if controller & (CAN_ERR_CRTL_RX_WARNING | CAN_ERR_CRTL_TX_WARNING) != 0 {
+            "error_warning"
+        } else if controller & CAN_ERR_CRTL_ACTIVE != 0 {
+            "error_active"
+        } else if controller & (CAN_ERR_CRTL_RX_OVERFLOW | CAN_ERR_CRTL_TX_OVERFLOW) != 0 {
+            "buffer_overflow"
+        } else {
+            "controller_problem"
+        }
+    } else if class & CAN_ERR_PROT != 0 {
+        "protocol_violation"
+    } else if class & CAN_ERR_ACK != 0 {
+        "no_ack"
+    } else if class & CAN_ERR_LOSTARB != 0 {
+        "lost_arbitration"
+    } else if class & CAN_ERR_TX_TIMEOUT != 0 {
+        "tx_timeout"
+    } else if class & CAN_ERR_TRX != 0 {
+        "transceiver_error"
+    } else if class & CAN_ERR_BUSERROR != 0 {
+        "bus_error"
+    } else if class & CAN_ERR_CNT != 0 {
+        "error_counters"
+    } else {
+        "error_frame"
+    }
+}
+
+fn protocol_location(loc: u8) -> Option<&'static str> {
+    Some(match loc {
+        0x03 => "sof",
+        0x02 => "id28_21",
+        0x06 => "id20_18",
+        0x04 => "srtr",
+        0x05 => "ide",
+        0x07 => "id17_13",
+        0x0F => "id12_05",
+        0x0E => "id04_00",
+        0x0C => "rtr",
+        0x0D => "res1",
+        0x09 => "res0",
+        0x0B => "dlc",
+        0x0A => "data",
+        CAN_ERR_PROT_LOC_CRC_SEQ => "crc_sequence",
+        0x18 => "crc_delimiter",
+        0x19 => "ack",
+        CAN_ERR_PROT_LOC_ACK_DEL => "ack_delimiter",
+        0x1A => "eof",
+        0x12 => "intermission",
+        _ => return None,
+    })
+}
+
+fn can_h_status(status: u8) -> Option<&'static str> {
+    Some(match status {
+        0x04 => "no_wire",
+        0x05 => "short_to_bat",
+        0x06 => "short_to_vcc",
+        0x07 => "short_to_gnd",
+        _ => return None,
+    })
+}
+
+fn can_l_status(status: u8) -> Option<&'static str> {
+    Some(match status {
+        0x40 => "no_wire",
+        0x50 => "short_to_bat",
+        0x60 => "short_to_vcc",
+        0x70 => "short_to_gnd",
+        0x80 => "short_to_can_h",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attr<'a>(info: &'a ErrorFrameInfo, key: &str) -> Option<&'a AttrVal> {
+        info.attrs.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    #[test]
+    fn controller_problem() {
+        let info = decode(
+            CAN_ERR_CRTL | CAN_ERR_CNT,
+            &[0, CAN_ERR_CRTL_RX_PASSIVE, 0, 0, 0, 0, 12, 130],
+        );
+        assert_eq!(info.name, "error_passive");
+        assert_eq!(
+            attr(&info, "class"),
+            Some(&AttrVal::from("controller,counters"))
+        );
+        assert_eq!(
+            attr(&info, "controller.rx_passive"),
+            Some(&AttrVal::from(true))
+        );
+        assert_eq!(attr(&info, "tx_error_count"), Some(&AttrVal::from(12_u8)));
+        assert_eq!(attr(&info, "rx_error_count"), Some(&AttrVal::from(130_u8)));
+    }
+
+    #[test]
+    fn protocol_violation() {
+        let info = decode(
+            CAN_ERR_FLAG | CAN_ERR_PROT | CAN_ERR_BUSERROR,
+            &[
+                0,
+                0,
+                CAN_ERR_PROT_STUFF | CAN_ERR_PROT_FORM,
+                0x0A,
+                0,
+                0,
+                0,
+                0,
+            ],
+        );
+        assert_eq!(info.name, "protocol_violation");
+        assert_eq!(
+            attr(&info, "class"),
+            Some(&AttrVal::from("protocol,bus_error"))
+        );
+        assert_eq!(
+            attr(&info, "protocol.type"),
+            Some(&AttrVal::from("form,stuff"))
+        );
+        assert_eq!(
+            attr(&info, "protocol.location"),
+            Some(&AttrVal::from("data"))
+        );
+        assert_eq!(attr(&info, "tx_error_count"), None);
+    }
+
+    #[test]
+    fn other_classes() {
+        assert_eq!(decode(CAN_ERR_BUSOFF | CAN_ERR_CRTL, &[]).name, "bus_off");
+        assert_eq!(decode(CAN_ERR_ACK, &[]).name, "no_ack");
+        assert_eq!(decode(CAN_ERR_RESTARTED, &[]).name, "controller_restarted");
+        assert_eq!(decode(0, &[]).name, "error_frame");
+
+        let info = decode(CAN_ERR_LOSTARB, &[5]);
+        assert_eq!(info.name, "lost_arbitration");
+        assert_eq!(
+            attr(&info, "lost_arbitration.bit"),
+            Some(&AttrVal::from(5_u8))
+        );
+
+        let info = decode(CAN_ERR_TRX, &[0, 0, 0, 0, 0x74]);
+        assert_eq!(info.name, "transceiver_error");
+        assert_eq!(
+            attr(&info, "transceiver.can_h"),
+            Some(&AttrVal::from("no_wire"))
+        );
+        assert_eq!(
+            attr(&info, "transceiver.can_l"),
+            Some(&AttrVal::from("short_to_gnd"))
+        );
+
+        // Only CAN_L can be shorted to CAN_H
+        let info = decode(CAN_ERR_TRX, &[0, 0, 0, 0, 0x88]);
+        assert_eq!(attr(&info, "transceiver.can_h"), None);
+        assert_eq!(
+            attr(&info, "transceiver.can_l"),
+            Some(&AttrVal::from("short_to_can_h"))
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+use socketcan::{CanAnyFrame, Timestamp};
+use std::path::Path;
+
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+
+/// Classic CAN frames carry at most 8 data bytes, whatever their DLC
+pub(crate) const MAX_CLASSIC_DATA_LEN: usize = 8;
+
+/// The supported log file formats
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogFormat {
+    /// Logs produced by the `candump` utility from the `can-utils` package
+    Candump,
+    /// Vector ASCII logs (.asc)
+    Asc,
+    /// Vector Binary Logging Format logs (.blf)
+    Blf,
+    /// PCAP or PCAPNG captures using the SocketCAN link type (.pcap, .pcapng)
+    Pcap,
+    /// ASAM MDF4 files using the bus logging convention (.mf4)
+    Mdf,
+    /// PEAK trace files from PCAN-View or PCAN-Explorer (.trc)
+    Trc,
+}
+
+impl LogFormat {
+    /// Pick a format based on the file extension, if it's a known one
+    pub fn from_path<P: AsRef<Path>>(p: P) -> Option<Self> {
+        let ext = p.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        Some(match ext.as_str() {
+            "log" | "candump" => LogFormat::Candump,
+            "asc" => LogFormat::Asc,
+            "blf" => LogFormat::Blf,
+            "pcap" | "pcapng" => LogFormat::Pcap,
+            "mf4" | "mdf" => LogFormat::Mdf,
+            "trc" => LogFormat::Trc,
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogFormat::Candump => "candump",
+            LogFormat::Asc => "asc",
+            LogFormat::Blf => "blf",
+            LogFormat::Pcap => "pcap",
+            LogFormat::Mdf => "mdf",
+            LogFormat::Trc => "trc",
+        }
+    }
+}
+
+/// Frame direction, for formats that record it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Rx,
+    Tx,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Rx => "rx",
+            Direction::Tx => "tx",
+        }
+    }
+}
+
+/// A CAN frame read from a log file
+#[derive(Debug)]
+pub struct LogFrame {
+    /// Not present for candump console output captured without timestamps
+    pub timestamp: Option<Timestamp>,
+    /// The interface name, or channel number, the frame was captured on
+    pub interface: Option<String>,
+    pub direction: Option<Direction>,
+    pub frame: CanAnyFrame,
+}
+
+/// Parse a decimal seconds string (e.g. "12.000340") into a timestamp.
+/// The fractional part is scaled by its digit count, anything past nanosecond
+/// precision is truncated.
+pub(crate) fn parse_decimal_seconds(s: &str) -> Option<Timestamp> {
+    let (secs, frac) = match s.split_once('.') {
+        Some((secs, frac)) => (secs, frac),
+        None => (s, ""),
+    };
+    if secs.is_empty() || !secs.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    if !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(Timestamp {
+        seconds: secs.parse().ok()?,
+        nanoseconds: fraction_to_nanos(frac)?,
+    })
+}
+
+/// Convert the digits after a decimal point into nanoseconds
+pub(crate) fn fraction_to_nanos(frac: &str) -> Option<i64> {
+    let digits = &frac[..frac.len().min(9)];
+    if digits.is_empty() {
+        return Some(0);
+    }
+    let n: i64 = digits.parse().ok()?;
+    Some(n * 10_i64.pow(9 - digits.len() as u32))
+}
+
+pub(crate) fn timestamp_from_nanos(ns: i64) -> Timestamp {
+    Timestamp {
+        seconds: ns.div_euclid(NANOS_PER_SEC),
+        nanoseconds: ns.rem_euclid(NANOS_PER_SEC),
+    }
+}
+
+pub(crate) fn timestamp_to_nanos(t: &Timestamp) -> i64 {
+    (t.seconds * NANOS_PER_SEC) + t.nanoseconds
+}
+
+pub(crate) fn add_timestamps(a: &Timestamp, b: &Timestamp) -> Timestamp {
+    timestamp_from_nanos(timestamp_to_nanos(a) + timestamp_to_nanos(b))
+}
+
+/// Seconds since the unix epoch for the given UTC civil date and time
+pub(crate) fn unix_seconds(year: i64, month: u32, day: u32, hour: u32, min: u32, sec: u32) -> i64 {
+    // Howard Hinnant's days_from_civil
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    days * 86400 + (hour as i64 * 3600) + (min as i64 * 60) + sec as i64
+}
+
+/// Month number (1-12) from an English month name or abbreviation
+pub(crate) fn month_from_name(s: &str) -> Option<u32> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let s = s.to_ascii_lowercase();
+    MONTHS
+        .iter()
+        .position(|m| s.starts_with(m))
+        .map(|i| i as u32 + 1)
+}
+
+/// The data length of a CAN FD frame with the given DLC
+pub(crate) fn dlc_to_len(dlc: usize) -> usize {
+    match dlc {
+        0..=8 => dlc,
+        9 => 12,
+        10 => 16,
+        11 => 20,
+        12 => 24,
+        13 => 32,
+        14 => 48,
+        _ => 64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decimal_seconds() {
+        assert_eq!(
+            parse_decimal_seconds("1717673394.196203"),
+            Some(Timestamp {
+                seconds: 1717673394,
+                nanoseconds: 196203000,
+            })
+        );
+        assert_eq!(
+            parse_decimal_seconds("0.5"),
+            Some(Timestamp {
+                seconds: 0,
+                nanoseconds: 500000000,
+            })
+        );
+        assert_eq!(
+            parse_decimal_seconds("3"),
+            Some(Timestamp {
+                seconds: 3,
+                nanoseconds: 0,
+            })
+        );
+        assert_eq!(
+            parse_decimal_seconds("1.0123456789"),
+            Some(Timestamp {
+                seconds: 1,
+                nanoseconds: 12345678,
+            })
+        );
+        assert_eq!(parse_decimal_seconds("abc"), None);
+        assert_eq!(parse_decimal_seconds(".1"), None);
+    }
+
+    #[test]
+    fn civil_to_unix() {
+        assert_eq!(unix_seconds(1970, 1, 1, 0, 0, 0), 0);
+        assert_eq!(unix_seconds(2024, 6, 6, 11, 29, 54), 1717673394);
+        assert_eq!(unix_seconds(2000, 2, 29, 0, 0, 0), 951782400);
+    }
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(LogFormat::from_path("a/b.log"), Some(LogFormat::Candump));
+        assert_eq!(LogFormat::from_path("b.ASC"), Some(LogFormat::Asc));
+        assert_eq!(LogFormat::from_path("b.blf"), Some(LogFormat::Blf));
+        assert_eq!(LogFormat::from_path("b.pcapng"), Some(LogFormat::Pcap));
+        assert_eq!(LogFormat::from_path("b.MF4"), Some(LogFormat::Mdf));
+        assert_eq!(LogFormat::from_path("b.trc"), Some(LogFormat::Trc));
+        assert_eq!(LogFormat::from_path("b.txt"), None);
+        assert_eq!(LogFormat::from_path("b"), None);
+    }
+}
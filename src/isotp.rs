@@ -0,0 +1,690 @@
+//! ISO-TP (ISO 15765-2) reassembly.
+
+use crate::parser::{config_message_id, RawCanIdExt};
+use can_dbc::MessageId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::{debug, warn};
+
+// Protocol control information frame types
+const PCI_SINGLE_FRAME: u8 = 0;
+const PCI_FIRST_FRAME: u8 = 1;
+const PCI_CONSECUTIVE_FRAME: u8 = 2;
+const PCI_FLOW_CONTROL: u8 = 3;
+
+// Flow control flow status
+const FC_CONTINUE_TO_SEND: u8 = 0;
+const FC_WAIT: u8 = 1;
+const FC_OVERFLOW: u8 = 2;
+
+/// Default N_Bs and N_Cr timeouts
+const DEFAULT_TIMEOUT_MS: u64 = 1000;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IsoTpAddressing {
+    /// The whole frame data is protocol data
+    #[default]
+    Normal,
+    /// The first data byte is the target address
+    Extended,
+    /// The first data byte is the address extension
+    Mixed,
+}
+
+impl IsoTpAddressing {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IsoTpAddressing::Normal => "normal",
+            IsoTpAddressing::Extended => "extended",
+            IsoTpAddressing::Mixed => "mixed",
+        }
+    }
+}
+
+/// A pair of CAN IDs carrying ISO-TP traffic between a tester and an ECU
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct IsoTpConfig {
+    /// A name for the connection, e.g. the ECU.
+    pub name: Option<String>,
+
+    /// The CAN ID of requests, sent by the tester.
+    #[serde(alias = "tx_id")]
+    pub tx_id: u32,
+
+    /// The CAN ID of responses, sent by the ECU.
+    #[serde(alias = "rx_id")]
+    pub rx_id: u32,
+
+    /// Are the IDs extended (29-bit) IDs? Defaults to false, IDs above 0x7FF are always extended.
+    pub extended: Option<bool>,
+
+    /// The addressing format. Defaults to normal.
+    pub addressing: IsoTpAddressing,
+
+    /// The longest time a sender waits for a flow control frame. Defaults to 1000.
+    #[serde(alias = "n_bs_timeout_ms")]
+    pub n_bs_timeout_ms: Option<u64>,
+
+    /// The longest time a receiver waits for the next consecutive frame. Defaults to 1000.
+    #[serde(alias = "n_cr_timeout_ms")]
+    pub n_cr_timeout_ms: Option<u64>,
+
+    /// Decode the PDUs as UDS (ISO 14229) services. Defaults to true.
+    pub uds: Option<bool>,
+}
+
+/// A complete PDU, or the part of one received before an error
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct IsoTpPdu {
+    pub interface: Option<String>,
+    /// Index of the connection in the configuration
+    pub connection: usize,
+    pub name: Option<String>,
+    pub can_id: u32,
+    pub is_extended: bool,
+    /// Sent on the request (tx) ID
+    pub is_request: bool,
+    pub addressing: IsoTpAddressing,
+    pub address: Option<u8>,
+    /// The length from the single or first frame
+    pub length: usize,
+    pub payload: Vec<u8>,
+    /// Single, first and consecutive frames
+    pub frame_count: u32,
+    /// From the first frame to the last consecutive frame
+    pub duration_ns: Option<i64>,
+    /// From the last flow control frame
+    pub block_size: Option<u8>,
+    pub st_min_ns: Option<u64>,
+    pub wait_count: u32,
+    /// The smallest gap between consecutive frames
+    pub observed_st_min_ns: Option<i64>,
+    pub error: Option<IsoTpError>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum IsoTpError {
+    /// No flow control frame within N_Bs
+    NBsTimeout,
+    /// No consecutive frame within N_Cr
+    NCrTimeout,
+    WrongSequenceNumber {
+        expected: u8,
+        received: u8,
+    },
+    /// The receiver reported an overflow
+    Overflow,
+    /// A new single or first frame arrived before the PDU was complete
+    Interrupted,
+    /// Still incomplete at the end of the input
+    Incomplete,
+}
+
+impl IsoTpError {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IsoTpError::NBsTimeout => "n_bs_timeout",
+            IsoTpError::NCrTimeout => "n_cr_timeout",
+            IsoTpError::WrongSequenceNumber { .. } => "wrong_sequence_number",
+            IsoTpError::Overflow => "overflow",
+            IsoTpError::Interrupted => "interrupted",
+            IsoTpError::Incomplete => "incomplete",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Session {
+    length: usize,
+    payload: Vec<u8>,
+    next_seq: u8,
+    frame_count: u32,
+    start_ns: Option<i64>,
+    last_ns: Option<i64>,
+    last_cf_ns: Option<i64>,
+    observed_st_min_ns: Option<i64>,
+    block_size: Option<u8>,
+    st_min_ns: Option<u64>,
+    wait_count: u32,
+    /// Waiting for flow control, after the first frame or a complete block
+    awaiting_fc: bool,
+    block_count: u32,
+}
+
+/// Interface, CAN ID, whether it's extended, and address byte
+type SessionKey = (Option<String>, u32, bool, Option<u8>);
+
+fn session_key(interface: Option<&str>, id: MessageId, address: Option<u8>) -> SessionKey {
+    (
+        interface.map(str::to_owned),
+        id.raw_can_id(),
+        matches!(id, MessageId::Extended(_)),
+        address,
+    )
+}
+
+fn session_id(key: &SessionKey) -> MessageId {
+    if key.2 {
+        MessageId::Extended(key.1)
+    } else {
+        MessageId::Standard(key.1 as u16)
+    }
+}
+
+/// Reassembles PDUs on the configured CAN ID pairs
+#[derive(Debug)]
+pub(crate) struct IsoTpReassembler {
+    connections: Vec<IsoTpConfig>,
+    /// Connection index, and whether the ID is the request (tx) ID
+    ids: HashMap<MessageId, (usize, bool)>,
+    sessions: HashMap<SessionKey, Session>,
+}
+
+impl IsoTpReassembler {
+    pub fn new(connections: &[IsoTpConfig]) -> Self {
+        let mut ids = HashMap::new();
+        for (idx, c) in connections.iter().enumerate() {
+            for (id, is_request) in [(c.tx_id, true), (c.rx_id, false)] {
+                let message_id = config_message_id(id, c.extended);
+                if ids.insert(message_id, (idx, is_request)).is_some() {
+                    warn!(id, "CAN ID is used by more than one ISO-TP connection");
+                }
+            }
+        }
+        Self {
+            connections: connections.to_vec(),
+            ids,
+            sessions: Default::default(),
+        }
+    }
+
+    pub fn connection(&self, idx: usize) -> &IsoTpConfig {
+        &self.connections[idx]
+    }
+
+    /// Is the ID one of a connection's pair
+    pub fn handles(&self, id: MessageId) -> bool {
+        self.ids.contains_key(&id)
+    }
+
+    /// Handle a frame, returning any PDUs it completes or aborts
+    pub fn push(
+        &mut self,
+        interface: Option<&str>,
+        id: MessageId,
+        data: &[u8],
+        timestamp_ns: Option<i64>,
+    ) -> Vec<IsoTpPdu> {
+        let mut pdus = self.expire(interface, timestamp_ns);
+
+        let Some(&(conn_idx, is_request)) = self.ids.get(&id) else {
+            return pdus;
+        };
+        let can_id = id.raw_can_id();
+        let addressing = self.connections[conn_idx].addressing;
+        let (address, pci) = match addressing {
+            IsoTpAddressing::Normal => (None, data),
+            IsoTpAddressing::Extended | IsoTpAddressing::Mixed => match data.split_first() {
+                Some((addr, pci)) => (Some(*addr), pci),
+                None => return pdus,
+            },
+        };
+        let Some(pci_type) = pci.first().map(|b| b >> 4) else {
+            return pdus;
+        };
+        let key = session_key(interface, id, address);
+
+        match pci_type {
+            PCI_SINGLE_FRAME | PCI_FIRST_FRAME => {
+                if let Some(s) = self.sessions.remove(&key) {
+                    pdus.push(self.pdu(&key, s, Some(IsoTpError::Interrupted)));
+                }
+                let Some((length, payload)) = first_frame_payload(pci_type, pci) else {
+                    debug!(can_id, "Ignoring ISO-TP frame with an invalid length");
+                    return pdus;
+                };
+                let mut session = Session {
+                    length,
+                    payload: payload[..payload.len().min(length)].to_vec(),
+                    next_seq: 1,
+                    frame_count: 1,
+                    start_ns: timestamp_ns,
+                    last_ns: timestamp_ns,
+                    last_cf_ns: None,
+                    observed_st_min_ns: None,
+                    block_size: None,
+                    st_min_ns: None,
+                    wait_count: 0,
+                    awaiting_fc: true,
+                    block_count: 0,
+                };
+                if pci_type == PCI_SINGLE_FRAME {
+                    session.awaiting_fc = false;
+                    pdus.push(self.pdu(&key, session, None));
+                } else {
+                    self.sessions.insert(key, session);
+                }
+            }
+            PCI_CONSECUTIVE_FRAME => {
+                let Some(session) = self.sessions.get_mut(&key) else {
+                    debug!(can_id, "Ignoring unexpected ISO-TP consecutive frame");
+                    return pdus;
+                };
+                let seq = pci[0] & 0xF;
+                if seq != session.next_seq {
+                    let error = IsoTpError::WrongSequenceNumber {
+                        expected: session.next_seq,
+                        received: seq,
+                    };
+                    if let Some(mut s) = self.sessions.remove(&key) {
+                        s.last_ns = timestamp_ns.or(s.last_ns);
+                        pdus.push(self.pdu(&key, s, Some(error)));
+                    }
+                    return pdus;
+                }
+
+                let remaining = session.length - session.payload.len();
+                let data = &pci[1..];
+                session
+                    .payload
+                    .extend_from_slice(&data[..data.len().min(remaining)]);
+                session.next_seq = (session.next_seq + 1) & 0xF;
+                session.frame_count += 1;
+                if let (Some(now), Some(last_cf)) = (timestamp_ns, session.last_cf_ns) {
+                    let gap = now - last_cf;
+                    session.observed_st_min_ns = Some(
+                        session
+                            .observed_st_min_ns
+                            .map_or(gap, |min: i64| min.min(gap)),
+                    );
+                }
+                session.last_cf_ns = timestamp_ns;
+                session.last_ns = timestamp_ns.or(session.last_ns);
+                session.block_count += 1;
+
+                if session.payload.len() >= session.length {
+                    if let Some(s) = self.sessions.remove(&key) {
+                        pdus.push(self.pdu(&key, s, None));
+                    }
+                } else if session
+                    .block_size
+                    .is_some_and(|bs| bs != 0 && session.block_count >= bs as u32)
+                {
+                    session.awaiting_fc = true;
+                    session.block_count = 0;
+                    // The time between blocks isn't separation time
+                    session.last_cf_ns = None;
+                }
+            }
+            PCI_FLOW_CONTROL => {
+                // Flow control is sent on the other ID of the pair, with the target address
+                // in extended addressing being the sender of the data
+                let conn = &self.connections[conn_idx];
+                let data_id = if is_request { conn.rx_id } else { conn.tx_id };
+                let data_key = session_key(
+                    interface,
+                    config_message_id(data_id, conn.extended),
+                    address,
+                );
+                let data_key = if self.sessions.contains_key(&data_key) {
+                    data_key
+                } else {
+                    let mut keys: Vec<_> = self
+                        .sessions
+                        .iter()
+                        .filter(|(k, s)| {
+                            k.0.as_deref() == interface
+                                && k.1 == data_key.1
+                                && k.2 == data_key.2
+                                && s.awaiting_fc
+                        })
+                        .map(|(k, _)| k.clone())
+                        .collect();
+                    keys.sort();
+                    match keys.into_iter().next() {
+                        Some(k) => k,
+                        None => return pdus,
+                    }
+                };
+
+                let flow_status = pci[0] & 0xF;
+                match flow_status {
+                    FC_CONTINUE_TO_SEND | FC_WAIT => {
+                        if let Some(session) = self.sessions.get_mut(&data_key) {
+                            if flow_status == FC_WAIT {
+                                session.wait_count += 1;
+                            } else {
+                                session.awaiting_fc = false;
+                                session.block_size = pci.get(1).copied();
+                                session.st_min_ns = pci.get(2).map(|b| st_min_ns(*b));
+                            }
+                            session.last_ns = timestamp_ns.or(session.last_ns);
+                        }
+                    }
+                    FC_OVERFLOW => {
+                        if let Some(mut s) = self.sessions.remove(&data_key) {
+                            s.last_ns = timestamp_ns.or(s.last_ns);
+                            pdus.push(self.pdu(&data_key, s, Some(IsoTpError::Overflow)));
+                        }
+                    }
+                    _ => debug!(can_id, flow_status, "Ignoring invalid ISO-TP flow status"),
+                }
+            }
+            _ => debug!(can_id, pci_type, "Ignoring invalid ISO-TP frame type"),
+        }
+
+        pdus
+    }
+
+    /// Abort any incomplete PDUs, at the end of the input
+    pub fn finish(&mut self) -> Vec<IsoTpPdu> {
+        let mut keys: Vec<_> = self.sessions.keys().cloned().collect();
+        keys.sort();
+        keys.into_iter()
+            .filter_map(|key| {
+                let s = self.sessions.remove(&key)?;
+                Some(self.pdu(&key, s, Some(IsoTpError::Incomplete)))
+            })
+            .collect()
+    }
+
+    fn expire(&mut self, interface: Option<&str>, timestamp_ns: Option<i64>) -> Vec<IsoTpPdu> {
+        let Some(now) = timestamp_ns else {
+            return Vec::new();
+        };
+        let mut expired: Vec<_> = self
+            .sessions
+            .iter()
+            .filter(|(key, _)| key.0.as_deref() == interface)
+            .filter_map(|(key, s)| {
+                let conn = &self.connections[self.ids.get(&session_id(key))?.0];
+                let (timeout_ms, error) = if s.awaiting_fc {
+                    (conn.n_bs_timeout_ms, IsoTpError::NBsTimeout)
+                } else {
+                    (conn.n_cr_timeout_ms, IsoTpError::NCrTimeout)
+                };
+                let timeout_ns = timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS) as i64 * 1_000_000;
+                s.last_ns
+                    .filter(|last| now - last > timeout_ns)
+                    .map(|_| (key.clone(), error))
+            })
+            .collect();
+        expired.sort_by(|a, b| a.0.cmp(&b.0));
+        expired
+            .into_iter()
+            .filter_map(|(key, error)| {
+                let s = self.sessions.remove(&key)?;
+                Some(self.pdu(&key, s, Some(error)))
+            })
+            .collect()
+    }
+
+    fn pdu(&self, key: &SessionKey, s: Session, error: Option<IsoTpError>) -> IsoTpPdu {
+        let (conn_idx, is_request) = self.ids.get(&session_id(key)).copied().unwrap_or_default();
+        let conn = &self.connections[conn_idx];
+        IsoTpPdu {
+            interface: key.0.clone(),
+            connection: conn_idx,
+            name: conn.name.clone(),
+            can_id: key.1,
+            is_extended: key.2,
+            is_request,
+            addressing: conn.addressing,
+            address: key.3,
+            length: s.length,
+            payload: s.payload,
+            frame_count: s.frame_count,
+            duration_ns: s.start_ns.zip(s.last_ns).map(|(start, last)| last - start),
+            block_size: s.block_size,
+            st_min_ns: s.st_min_ns,
+            wait_count: s.wait_count,
+            observed_st_min_ns: s.observed_st_min_ns,
+            error,
+        }
+    }
+}
+
+/// The payload of a single frame with normal addressing, e.g. a functional request
+pub(crate) fn single_frame_payload(data: &[u8]) -> Option<&[u8]> {
+    if data.first()? >> 4 != PCI_SINGLE_FRAME {
+        return None;
+    }
+    let (len, payload) = first_frame_payload(PCI_SINGLE_FRAME, data)?;
+    Some(&payload[..len])
+}
+
+/// The PDU length and initial payload of a single or first frame,
+/// including the escape sequences for lengths that don't fit in the classic frame formats
+fn first_frame_payload(pci_type: u8, pci: &[u8]) -> Option<(usize, &[u8])> {
+    if pci_type == PCI_SINGLE_FRAME {
+        let len = (pci[0] & 0xF) as usize;
+        let (len, payload) = if len == 0 {
+            // CAN FD single frame, the length is in the next byte
+            (*pci.get(1)? as usize, pci.get(2..)?)
+        } else {
+            (len, &pci[1..])
+        };
+        (len != 0 && len <= payload.len()).then_some((len, payload))
+    } else {
+        let len = ((pci[0] & 0xF) as usize) << 8 | *pci.get(1)? as usize;
+        if len == 0 {
+            // Lengths over 4095 bytes
+            let len = u32::from_be_bytes(pci.get(2..6)?.try_into().ok()?) as usize;
+            Some((len, pci.get(6..)?))
+        } else {
+            Some((len, pci.get(2..)?))
+        }
+    }
+}
+
+/// STmin is in milliseconds, or hundreds of microseconds for 0xF1-0xF9.
+/// Reserved values are treated as the longest time, 127 ms.
+fn st_min_ns(st_min: u8) -> u64 {
+    match st_min {
+        0x00..=0x7F => st_min as u64 * 1_000_000,
+        0xF1..=0xF9 => (st_min - 0xF0) as u64 * 100_000,
+        _ => 127_000_000,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reassembler(addressing: IsoTpAddressing) -> IsoTpReassembler {
+        IsoTpReassembler::new(&[IsoTpConfig {
+            name: Some("engine".to_owned()),
+            tx_id: 0x7E0,
+            rx_id: 0x7E8,
+            addressing,
+            ..Default::default()
+        }])
+    }
+
+    fn push(r: &mut IsoTpReassembler, id: u16, data: &[u8], t_ms: i64) -> Vec<IsoTpPdu> {
+        r.push(
+            Some("can0"),
+            MessageId::Standard(id),
+            data,
+            Some(t_ms * 1_000_000),
+        )
+    }
+
+    #[test]
+    fn single_frames() {
+        let mut r = reassembler(IsoTpAddressing::Normal);
+        let pdus = push(&mut r, 0x7E0, &[0x02, 0x10, 0x03, 0, 0, 0, 0, 0], 0);
+        assert_eq!(
+            pdus,
+            vec![IsoTpPdu {
+                interface: Some("can0".to_owned()),
+                connection: 0,
+                name: Some("engine".to_owned()),
+                can_id: 0x7E0,
+                is_extended: false,
+                is_request: true,
+                addressing: IsoTpAddressing::Normal,
+                address: None,
+                length: 2,
+                payload: vec![0x10, 0x03],
+                frame_count: 1,
+                duration_ns: Some(0),
+                block_size: None,
+                st_min_ns: None,
+                wait_count: 0,
+                observed_st_min_ns: None,
+                error: None,
+            }]
+        );
+
+        // CAN FD escape
+        let mut data = vec![0x00, 12];
+        data.extend(1..=12);
+        data.extend([0xCC; 2]);
+        let pdus = push(&mut r, 0x7E8, &data, 1);
+        assert_eq!(pdus.len(), 1);
+        assert!(!pdus[0].is_request);
+        assert_eq!(pdus[0].payload, (1..=12).collect::<Vec<u8>>());
+
+        // Not configured
+        assert!(push(&mut r, 0x7DF, &[0x02, 0x01, 0x00], 2).is_empty());
+        let sf = [0x02, 0x10, 0x03];
+        assert!(r
+            .push(None, MessageId::Extended(0x7E0), &sf, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn extended_ids() {
+        let mut r = IsoTpReassembler::new(&[IsoTpConfig {
+            tx_id: 0x7E0,
+            rx_id: 0x7E8,
+            extended: Some(true),
+            ..Default::default()
+        }]);
+        assert!(r.handles(MessageId::Extended(0x7E8)));
+        assert!(!r.handles(MessageId::Standard(0x7E8)));
+        assert!(push(&mut r, 0x7E0, &[0x02, 0x10, 0x03], 0).is_empty());
+
+        let ff = [0x10, 8, 1, 2, 3, 4, 5, 6];
+        assert!(r
+            .push(None, MessageId::Extended(0x7E8), &ff, None)
+            .is_empty());
+        // Flow control on the standard ID doesn't belong to the session
+        assert!(push(&mut r, 0x7E0, &[0x30, 0, 0, 0, 0, 0, 0, 0], 1).is_empty());
+        let fc = [0x30, 0, 0, 0, 0, 0, 0, 0];
+        assert!(r
+            .push(None, MessageId::Extended(0x7E0), &fc, None)
+            .is_empty());
+        let cf = [0x21, 7, 8];
+        let pdus = r.push(None, MessageId::Extended(0x7E8), &cf, None);
+        assert_eq!(pdus.len(), 1);
+        assert_eq!(pdus[0].can_id, 0x7E8);
+        assert!(pdus[0].is_extended);
+        assert_eq!(pdus[0].payload, (1..=8).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn multi_frame() {
+        let mut r = reassembler(IsoTpAddressing::Normal);
+        assert!(push(&mut r, 0x7E8, &[0x10, 26, 0x62, 0xF1, 0x90, 1, 2, 3], 0).is_empty());
+        assert!(push(&mut r, 0x7E0, &[0x30, 2, 5, 0, 0, 0, 0, 0], 10).is_empty());
+        assert!(push(&mut r, 0x7E8, &[0x21, 4, 5, 6, 7, 8, 9, 10], 16).is_empty());
+        assert!(push(&mut r, 0x7E8, &[0x22, 11, 12, 13, 14, 15, 16, 17], 22).is_empty());
+        // Wait, then continue
+        assert!(push(&mut r, 0x7E0, &[0x31, 0, 0, 0, 0, 0, 0, 0], 30).is_empty());
+        assert!(push(&mut r, 0x7E0, &[0x30, 0, 0xF5, 0, 0, 0, 0, 0], 40).is_empty());
+        let pdus = push(
+            &mut r,
+            0x7E8,
+            &[0x23, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA],
+            41,
+        );
+        assert_eq!(pdus.len(), 1);
+        let pdu = &pdus[0];
+        assert_eq!(pdu.error, None);
+        assert_eq!(pdu.length, 26);
+        assert_eq!(pdu.payload.len(), 26);
+        assert_eq!(&pdu.payload[..6], &[0x62, 0xF1, 0x90, 1, 2, 3]);
+        assert_eq!(&pdu.payload[20..], &[0xAA; 6]);
+        assert_eq!(pdu.frame_count, 4);
+        assert_eq!(pdu.duration_ns, Some(41_000_000));
+        assert_eq!(pdu.block_size, Some(0));
+        assert_eq!(pdu.st_min_ns, Some(500_000));
+        assert_eq!(pdu.wait_count, 1);
+        assert_eq!(pdu.observed_st_min_ns, Some(6_000_000));
+    }
+
+    #[test]
+    fn long_first_frame() {
+        let mut r = reassembler(IsoTpAddressing::Normal);
+        let mut ff = vec![0x10, 0x00, 0x00, 0x00, 0x10, 0x00];
+        ff.extend([0x36; 58]);
+        assert!(push(&mut r, 0x7E0, &ff, 0).is_empty());
+        assert_eq!(r.finish()[0].length, 4096);
+    }
+
+    #[test]
+    fn extended_addressing() {
+        let mut r = reassembler(IsoTpAddressing::Extended);
+        assert!(push(&mut r, 0x7E0, &[0x10, 0x10, 9, 1, 2, 3, 4, 5], 0).is_empty());
+        // Flow control from the target, addressed to the tester
+        assert!(push(&mut r, 0x7E8, &[0xF1, 0x30, 0, 0, 0, 0, 0, 0], 1).is_empty());
+        let pdus = push(&mut r, 0x7E0, &[0x10, 0x21, 6, 7, 8, 9, 0xCC, 0xCC], 2);
+        assert_eq!(pdus.len(), 1);
+        assert_eq!(pdus[0].address, Some(0x10));
+        assert_eq!(pdus[0].payload, (1..=9).collect::<Vec<u8>>());
+        assert_eq!(pdus[0].block_size, Some(0));
+    }
+
+    #[test]
+    fn errors() {
+        let mut r = reassembler(IsoTpAddressing::Normal);
+
+        // Wrong sequence number
+        assert!(push(&mut r, 0x7E8, &[0x10, 20, 0, 1, 2, 3, 4, 5], 0).is_empty());
+        assert!(push(&mut r, 0x7E0, &[0x30, 0, 0, 0, 0, 0, 0, 0], 1).is_empty());
+        let pdus = push(&mut r, 0x7E8, &[0x22, 6, 7, 8, 9, 10, 11, 12], 2);
+        assert_eq!(
+            pdus[0].error,
+            Some(IsoTpError::WrongSequenceNumber {
+                expected: 1,
+                received: 2
+            })
+        );
+        assert_eq!(pdus[0].payload.len(), 6);
+
+        // No flow control
+        assert!(push(&mut r, 0x7E8, &[0x10, 20, 0, 1, 2, 3, 4, 5], 100).is_empty());
+        let pdus = push(&mut r, 0x123, &[0; 8], 1101);
+        assert_eq!(pdus[0].error, Some(IsoTpError::NBsTimeout));
+
+        // No consecutive frame
+        assert!(push(&mut r, 0x7E8, &[0x10, 20, 0, 1, 2, 3, 4, 5], 2000).is_empty());
+        assert!(push(&mut r, 0x7E0, &[0x30, 0, 0, 0, 0, 0, 0, 0], 2001).is_empty());
+        assert!(push(&mut r, 0x7E8, &[0x21, 6, 7, 8, 9, 10, 11, 12], 2002).is_empty());
+        let pdus = push(&mut r, 0x123, &[0; 8], 3003);
+        assert_eq!(pdus[0].error, Some(IsoTpError::NCrTimeout));
+        assert_eq!(pdus[0].frame_count, 2);
+
+        // Overflow
+        assert!(push(&mut r, 0x7E8, &[0x10, 20, 0, 1, 2, 3, 4, 5], 4000).is_empty());
+        let pdus = push(&mut r, 0x7E0, &[0x32, 0, 0, 0, 0, 0, 0, 0], 4001);
+        assert_eq!(pdus[0].error, Some(IsoTpError::Overflow));
+
+        // Interrupted by a new PDU
+        assert!(push(&mut r, 0x7E8, &[0x10, 20, 0, 1, 2, 3, 4, 5], 5000).is_empty());
+        let pdus = push(&mut r, 0x7E8, &[0x03, 0x7F, 0x22, 0x78, 0, 0, 0, 0], 5001);
+        assert_eq!(pdus.len(), 2);
+        assert_eq!(pdus[0].error, Some(IsoTpError::Interrupted));
+        assert_eq!(pdus[1].error, None);
+        assert_eq!(pdus[1].payload, vec![0x7F, 0x22, 0x78]);
+
+        // Still open at the end
+        assert!(push(&mut r, 0x7E8, &[0x10, 20, 0, 1, 2, 3, 4, 5], 6000).is_empty());
+        let pdus = r.finish();
+        assert_eq!(pdus[0].error, Some(IsoTpError::Incomplete));
+    }
+}
@@ -0,0 +1,733 @@
+//! SAE J1939 identifier decoding and transport protocol reassembly, see J1939-21.
+
+use std::collections::HashMap;
+use tracing::debug;
+
+/// The global (broadcast) destination address
+pub(crate) const GLOBAL_ADDRESS: u8 = 0xFF;
+
+/// Transport protocol connection management
+pub(crate) const PGN_TP_CM: u32 = 0xEC00;
+/// Transport protocol data transfer
+pub(crate) const PGN_TP_DT: u32 = 0xEB00;
+
+// TP.CM control bytes
+const TP_CM_RTS: u8 = 16;
+const TP_CM_CTS: u8 = 17;
+const TP_CM_END_OF_MSG_ACK: u8 = 19;
+const TP_CM_BAM: u8 = 32;
+const TP_CM_ABORT: u8 = 255;
+
+/// Active diagnostic trouble codes
+pub(crate) const PGN_DM1: u32 = 0xFECA;
+/// Previously active diagnostic trouble codes
+pub(crate) const PGN_DM2: u32 = 0xFECB;
+
+/// Bytes of message data in each TP.DT packet
+const TP_DT_PACKET_SIZE: usize = 7;
+
+/// T1, the longest time a receiver waits for the next data packet
+const BAM_TIMEOUT_NS: i64 = 750_000_000;
+/// T2/T3, the longest time either side of a connection waits for the other
+const CMDT_TIMEOUT_NS: i64 = 1_250_000_000;
+
+/// PDU format values below this are PDU1 (destination specific)
+const PDU2_FORMAT_MIN: u8 = 240;
+
+/// The fields of a 29-bit J1939 CAN identifier
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct J1939Id {
+    pub priority: u8,
+    pub pgn: u32,
+    pub source_address: u8,
+    /// The global address for PDU2 (broadcast) parameter groups
+    pub destination_address: u8,
+}
+
+impl J1939Id {
+    pub fn from_can_id(id: u32) -> Self {
+        let priority = ((id >> 26) & 0x7) as u8;
+        let pdu_format = ((id >> 16) & 0xFF) as u8;
+        let pdu_specific = ((id >> 8) & 0xFF) as u8;
+        let source_address = (id & 0xFF) as u8;
+
+        // Extended data page, data page, and the PDU format
+        let mut pgn = (id >> 8) & 0x3FF00;
+        let destination_address = if pdu_format < PDU2_FORMAT_MIN {
+            pdu_specific
+        } else {
+            // The PDU specific field is the group extension
+            pgn |= pdu_specific as u32;
+            GLOBAL_ADDRESS
+        };
+
+        J1939Id {
+            priority,
+            pgn,
+            source_address,
+            destination_address,
+        }
+    }
+
+    /// The 29-bit CAN identifier for these fields
+    pub fn to_can_id(self) -> u32 {
+        let pdu_format = (self.pgn >> 8) & 0xFF;
+        let pdu_specific = if pdu_format < PDU2_FORMAT_MIN as u32 {
+            self.destination_address as u32
+        } else {
+            self.pgn & 0xFF
+        };
+        ((self.priority as u32 & 0x7) << 26)
+            | ((self.pgn & 0x3FF00) << 8)
+            | (pdu_specific << 8)
+            | self.source_address as u32
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum TransportKind {
+    /// Broadcast announce message
+    Bam,
+    /// Connection mode data transfer, RTS/CTS
+    Cmdt,
+}
+
+impl TransportKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransportKind::Bam => "bam",
+            TransportKind::Cmdt => "cmdt",
+        }
+    }
+}
+
+/// A multi-packet message, either reassembled or aborted
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct TransportMessage {
+    pub interface: Option<String>,
+    /// The transferred parameter group, with the priority of the connection management messages
+    pub id: J1939Id,
+    pub kind: TransportKind,
+    /// The complete message data, partial if aborted
+    pub data: Vec<u8>,
+    /// The number of data packets received
+    pub packet_count: u8,
+    /// The time from the connection management message to the last packet
+    pub duration_ns: Option<i64>,
+    pub aborted: Option<&'static str>,
+}
+
+#[derive(Debug)]
+struct Session {
+    kind: TransportKind,
+    id: J1939Id,
+    size: usize,
+    total_packets: u8,
+    data: Vec<u8>,
+    received: Vec<bool>,
+    packet_count: u8,
+    start_ns: Option<i64>,
+    last_ns: Option<i64>,
+}
+
+impl Session {
+    fn timeout_ns(&self) -> i64 {
+        match self.kind {
+            TransportKind::Bam => BAM_TIMEOUT_NS,
+            TransportKind::Cmdt => CMDT_TIMEOUT_NS,
+        }
+    }
+
+    fn into_message(
+        self,
+        interface: Option<String>,
+        aborted: Option<&'static str>,
+    ) -> TransportMessage {
+        let mut data = self.data;
+        data.truncate(self.size);
+        TransportMessage {
+            interface,
+            id: self.id,
+            kind: self.kind,
+            data,
+            packet_count: self.packet_count,
+            duration_ns: self.start_ns.zip(self.last_ns).map(|(s, l)| l - s),
+            aborted,
+        }
+    }
+}
+
+/// Interface, originator source address, and destination address
+type SessionKey = (Option<String>, u8, u8);
+
+/// Tracks BAM and RTS/CTS transport protocol sessions
+#[derive(Debug, Default)]
+pub(crate) struct TransportReassembler {
+    sessions: HashMap<SessionKey, Session>,
+}
+
+impl TransportReassembler {
+    /// Handle a frame, returning any messages it completes or aborts
+    pub fn push(
+        &mut self,
+        interface: Option<&str>,
+        id: &J1939Id,
+        data: &[u8],
+        timestamp_ns: Option<i64>,
+    ) -> Vec<TransportMessage> {
+        let mut msgs = self.expire(interface, timestamp_ns);
+
+        match id.pgn {
+            PGN_TP_CM => msgs.extend(self.connection_management(interface, id, data, timestamp_ns)),
+            PGN_TP_DT => msgs.extend(self.data_transfer(interface, id, data, timestamp_ns)),
+            _ => (),
+        }
+
+        msgs
+    }
+
+    /// Abort any open sessions, at the end of the input
+    pub fn finish(&mut self) -> Vec<TransportMessage> {
+        let mut msgs: Vec<_> = self
+            .sessions
+            .drain()
+            .map(|((iface, _, _), session)| session.into_message(iface, Some("incomplete")))
+            .collect();
+        msgs.sort_by(|a, b| {
+            (&a.interface, a.id.source_address, a.id.destination_address).cmp(&(
+                &b.interface,
+                b.id.source_address,
+                b.id.destination_address,
+            ))
+        });
+        msgs
+    }
+
+    fn expire(
+        &mut self,
+        interface: Option<&str>,
+        timestamp_ns: Option<i64>,
+    ) -> Vec<TransportMessage> {
+        let Some(now) = timestamp_ns else {
+            return Vec::new();
+        };
+        let mut expired: Vec<_> = self
+            .sessions
+            .iter()
+            .filter(|(key, session)| {
+                key.0.as_deref() == interface
+                    && session
+                        .last_ns
+                        .is_some_and(|last| now - last > session.timeout_ns())
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        expired.sort();
+        expired
+            .into_iter()
+            .filter_map(|key| {
+                let session = self.sessions.remove(&key)?;
+                Some(session.into_message(key.0, Some("timeout")))
+            })
+            .collect()
+    }
+
+    fn connection_management(
+        &mut self,
+        interface: Option<&str>,
+        id: &J1939Id,
+        data: &[u8],
+        timestamp_ns: Option<i64>,
+    ) -> Option<TransportMessage> {
+        if data.len() < 8 {
+            debug!(len = data.len(), "Ignoring short J1939 TP.CM frame");
+            return None;
+        }
+        let pgn = u32::from_le_bytes([data[5], data[6], data[7], 0]);
+
+        match data[0] {
+            control @ (TP_CM_RTS | TP_CM_BAM) => {
+                let kind = if control == TP_CM_BAM {
+                    TransportKind::Bam
+                } else {
+                    TransportKind::Cmdt
+                };
+                let size = u16::from_le_bytes([data[1], data[2]]) as usize;
+                let total_packets = data[3];
+                if total_packets == 0 {
+                    debug!(pgn, "Ignoring J1939 TP.CM session without any packets");
+                    return None;
+                }
+                let session = Session {
+                    kind,
+                    id: J1939Id {
+                        priority: id.priority,
+                        pgn,
+                        source_address: id.source_address,
+                        destination_address: id.destination_address,
+                    },
+                    size,
+                    total_packets,
+                    data: vec![0xFF; total_packets as usize * TP_DT_PACKET_SIZE],
+                    received: vec![false; total_packets as usize],
+                    packet_count: 0,
+                    start_ns: timestamp_ns,
+                    last_ns: timestamp_ns,
+                };
+                let key = (
+                    interface.map(str::to_owned),
+                    id.source_address,
+                    id.destination_address,
+                );
+                // A new session replaces any in progress between the same nodes
+                self.sessions
+                    .insert(key, session)
+                    .map(|old| old.into_message(interface.map(str::to_owned), Some("superseded")))
+            }
+            TP_CM_CTS | TP_CM_END_OF_MSG_ACK => {
+                // Sent by the receiver, keep the connection alive
+                let key = (
+                    interface.map(str::to_owned),
+                    id.destination_address,
+                    id.source_address,
+                );
+                if let Some(session) = self.sessions.get_mut(&key) {
+                    session.last_ns = timestamp_ns.or(session.last_ns);
+                }
+                None
+            }
+            TP_CM_ABORT => {
+                // Either side may abort the connection
+                let reason = abort_reason(data[1]);
+                [
+                    (id.source_address, id.destination_address),
+                    (id.destination_address, id.source_address),
+                ]
+                .into_iter()
+                .find_map(|(sa, da)| {
+                    let key = (interface.map(str::to_owned), sa, da);
+                    self.sessions
+                        .get(&key)
+                        .filter(|s| s.kind == TransportKind::Cmdt && s.id.pgn == pgn)?;
+                    self.sessions.remove(&key)
+                })
+                .map(|mut session| {
+                    session.last_ns = timestamp_ns.or(session.last_ns);
+                    session.into_message(interface.map(str::to_owned), Some(reason))
+                })
+            }
+            control => {
+                debug!(control, "Ignoring unknown J1939 TP.CM control byte");
+                None
+            }
+        }
+    }
+
+    fn data_transfer(
+        &mut self,
+        interface: Option<&str>,
+        id: &J1939Id,
+        data: &[u8],
+        timestamp_ns: Option<i64>,
+    ) -> Option<TransportMessage> {
+        let key = (
+            interface.map(str::to_owned),
+            id.source_address,
+            id.destination_address,
+        );
+        let session = self.sessions.get_mut(&key)?;
+
+        let (&seq, payload) = data.split_first()?;
+        if seq == 0 || seq > session.total_packets {
+            debug!(
+                seq,
+                total_packets = session.total_packets,
+                "Ignoring J1939 TP.DT frame with an invalid sequence number"
+            );
+            return None;
+        }
+
+        let idx = seq as usize - 1;
+        let offset = idx * TP_DT_PACKET_SIZE;
+        let len = payload.len().min(TP_DT_PACKET_SIZE);
+        session.data[offset..offset + len].copy_from_slice(&payload[..len]);
+        if !session.received[idx] {
+            session.received[idx] = true;
+            session.packet_count += 1;
+        }
+        session.last_ns = timestamp_ns.or(session.last_ns);
+
+        if session.packet_count == session.total_packets {
+            self.sessions
+                .remove(&key)
+                .map(|session| session.into_message(key.0, None))
+        } else {
+            None
+        }
+    }
+}
+
+fn abort_reason(reason: u8) -> &'static str {
+    match reason {
+        1 => "already_in_session",
+        2 => "resources_needed",
+        3 => "timeout",
+        4 => "cts_during_transfer",
+        5 => "retransmit_limit",
+        6 => "unexpected_data_transfer",
+        7 => "bad_sequence_number",
+        8 => "duplicate_sequence_number",
+        9 => "message_too_large",
+        _ => "other",
+    }
+}
+
+/// The lamps of a DM1/DM2 message, from the most significant bits
+const LAMPS: [&str; 4] = ["mil", "rsl", "awl", "pl"];
+
+/// A DM1 or DM2 message
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct DiagnosticMessage {
+    /// Lamp name, status, and flash status
+    pub lamps: Vec<(&'static str, &'static str, &'static str)>,
+    pub dtcs: Vec<Dtc>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Dtc {
+    pub spn: u32,
+    pub fmi: u8,
+    pub occurrence_count: u8,
+    /// Set when the SPN uses one of the conversion methods from before J1939-73 version 4,
+    /// it is always decoded with the version 4 method
+    pub conversion_method: bool,
+}
+
+impl DiagnosticMessage {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() < 2 {
+            return None;
+        }
+        let (lamp_bytes, dtc_bytes) = data.split_at(2);
+        let lamps = LAMPS
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| {
+                let shift = 6 - (idx * 2);
+                let status = match (lamp_bytes[0] >> shift) & 0x3 {
+                    0 => "off",
+                    1 => "on",
+                    2 => "error",
+                    _ => "not_available",
+                };
+                let flash = match (lamp_bytes[1] >> shift) & 0x3 {
+                    0 => "slow",
+                    1 => "fast",
+                    2 => "reserved",
+                    _ => "none",
+                };
+                (*name, status, flash)
+            })
+            .collect();
+
+        // No DTCs is sent as a zeroed DTC, unused space is padded with 0xFF
+        let dtcs = dtc_bytes
+            .chunks_exact(4)
+            .filter(|b| *b != [0, 0, 0, 0] && *b != [0xFF, 0xFF, 0xFF, 0xFF])
+            .map(|b| Dtc {
+                spn: b[0] as u32 | (b[1] as u32) << 8 | ((b[2] & 0xE0) as u32) << 11,
+                fmi: b[2] & 0x1F,
+                occurrence_count: b[3] & 0x7F,
+                conversion_method: b[3] & 0x80 != 0,
+            })
+            .collect();
+
+        Some(DiagnosticMessage { lamps, dtcs })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum DtcTransition {
+    Appeared(Dtc),
+    Cleared(Dtc),
+}
+
+/// Interface, source address, and PGN of the DM message
+type DtcKey = (Option<String>, u8, u32);
+
+/// Tracks the DTCs last reported by each node, a DTC is identified by its SPN and FMI
+#[derive(Debug, Default)]
+pub(crate) struct DtcTracker {
+    dtcs: HashMap<DtcKey, Vec<Dtc>>,
+}
+
+impl DtcTracker {
+    pub fn update(
+        &mut self,
+        interface: Option<&str>,
+        id: &J1939Id,
+        dtcs: &[Dtc],
+    ) -> Vec<DtcTransition> {
+        let key = (interface.map(str::to_owned), id.source_address, id.pgn);
+        let prev = self.dtcs.insert(key, dtcs.to_vec()).unwrap_or_default();
+        let same = |a: &Dtc, b: &Dtc| a.spn == b.spn && a.fmi == b.fmi;
+
+        prev.iter()
+            .filter(|p| !dtcs.iter().any(|d| same(p, d)))
+            .map(|p| DtcTransition::Cleared(*p))
+            .chain(
+                dtcs.iter()
+                    .filter(|d| !prev.iter().any(|p| same(p, d)))
+                    .map(|d| DtcTransition::Appeared(*d)),
+            )
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers() {
+        // EEC1 from the engine, PDU2
+        assert_eq!(
+            J1939Id::from_can_id(0x0CF00400),
+            J1939Id {
+                priority: 3,
+                pgn: 61444,
+                source_address: 0x00,
+                destination_address: GLOBAL_ADDRESS,
+            }
+        );
+        // ET1, PDU2
+        assert_eq!(
+            J1939Id::from_can_id(0x18FEEE17),
+            J1939Id {
+                priority: 6,
+                pgn: 65262,
+                source_address: 0x17,
+                destination_address: GLOBAL_ADDRESS,
+            }
+        );
+        // Request, PDU1 to the engine
+        assert_eq!(
+            J1939Id::from_can_id(0x18EA00F9),
+            J1939Id {
+                priority: 6,
+                pgn: 59904,
+                source_address: 0xF9,
+                destination_address: 0x00,
+            }
+        );
+        // TP.CM with the data page set, PDU1 to global
+        assert_eq!(
+            J1939Id::from_can_id(0x1DECFF3D),
+            J1939Id {
+                priority: 7,
+                pgn: 0x1EC00,
+                source_address: 0x3D,
+                destination_address: GLOBAL_ADDRESS,
+            }
+        );
+    }
+
+    #[test]
+    fn can_id_round_trip() {
+        for id in [0x0CF00400, 0x18FEEE17, 0x18EA00F9, 0x1DECFF3D, 0x1CEBFF00] {
+            assert_eq!(J1939Id::from_can_id(id).to_can_id(), id);
+        }
+    }
+
+    fn push(
+        r: &mut TransportReassembler,
+        id: u32,
+        data: &[u8],
+        t_ms: i64,
+    ) -> Vec<TransportMessage> {
+        r.push(
+            Some("can0"),
+            &J1939Id::from_can_id(id),
+            data,
+            Some(t_ms * 1_000_000),
+        )
+    }
+
+    #[test]
+    fn bam() {
+        let mut r = TransportReassembler::default();
+        let bam = [TP_CM_BAM, 10, 0, 2, 0xFF, 0xEC, 0xFE, 0x00];
+        assert!(push(&mut r, 0x1CECFF00, &bam, 0).is_empty());
+        assert!(push(&mut r, 0x1CEBFF00, b"\x011234567", 50).is_empty());
+        let msgs = push(&mut r, 0x1CEBFF00, b"\x02890\xFF\xFF\xFF\xFF", 100);
+        assert_eq!(
+            msgs,
+            vec![TransportMessage {
+                interface: Some("can0".to_owned()),
+                id: J1939Id {
+                    priority: 7,
+                    pgn: 65260,
+                    source_address: 0x00,
+                    destination_address: GLOBAL_ADDRESS,
+                },
+                kind: TransportKind::Bam,
+                data: b"1234567890".to_vec(),
+                packet_count: 2,
+                duration_ns: Some(100_000_000),
+                aborted: None,
+            }]
+        );
+        assert!(r.finish().is_empty());
+    }
+
+    #[test]
+    fn cmdt() {
+        let mut r = TransportReassembler::default();
+        let rts = [TP_CM_RTS, 9, 0, 2, 0xFF, 0xCA, 0xFE, 0x00];
+        let cts = [TP_CM_CTS, 2, 1, 0xFF, 0xFF, 0xCA, 0xFE, 0x00];
+        assert!(push(&mut r, 0x1CEC00F9, &rts, 0).is_empty());
+        assert!(push(&mut r, 0x1CECF900, &cts, 10).is_empty());
+        // Retransmitted packets are only counted once
+        assert!(push(&mut r, 0x1CEB00F9, &[1, 1, 2, 3, 4, 5, 6, 7], 20).is_empty());
+        assert!(push(&mut r, 0x1CEB00F9, &[1, 1, 2, 3, 4, 5, 6, 7], 25).is_empty());
+        // Data to other nodes belongs to another session
+        assert!(push(&mut r, 0x1CEB01F9, &[2, 8, 9, 0, 0, 0, 0, 0], 28).is_empty());
+        let msgs = push(
+            &mut r,
+            0x1CEB00F9,
+            &[2, 8, 9, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+            30,
+        );
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].kind, TransportKind::Cmdt);
+        assert_eq!(msgs[0].id.pgn, 65226);
+        assert_eq!(msgs[0].id.source_address, 0xF9);
+        assert_eq!(msgs[0].id.destination_address, 0x00);
+        assert_eq!(msgs[0].data, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(msgs[0].packet_count, 2);
+        assert_eq!(msgs[0].duration_ns, Some(30_000_000));
+        assert_eq!(msgs[0].aborted, None);
+    }
+
+    #[test]
+    fn aborted_sessions() {
+        let mut r = TransportReassembler::default();
+        let rts = [TP_CM_RTS, 9, 0, 2, 0xFF, 0xCA, 0xFE, 0x00];
+        let abort = [TP_CM_ABORT, 1, 0xFF, 0xFF, 0xFF, 0xCA, 0xFE, 0x00];
+        assert!(push(&mut r, 0x1CEC00F9, &rts, 0).is_empty());
+        let msgs = push(&mut r, 0x1CECF900, &abort, 5);
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].aborted, Some("already_in_session"));
+        assert_eq!(msgs[0].packet_count, 0);
+        assert_eq!(msgs[0].duration_ns, Some(5_000_000));
+
+        // Timed out
+        let bam = [TP_CM_BAM, 10, 0, 2, 0xFF, 0xEC, 0xFE, 0x00];
+        assert!(push(&mut r, 0x1CECFF00, &bam, 0).is_empty());
+        assert!(push(&mut r, 0x1CEBFF00, b"\x011234567", 50).is_empty());
+        assert!(push(&mut r, 0x18FEF100, &[0; 8], 500).is_empty());
+        let msgs = push(&mut r, 0x18FEF100, &[0; 8], 850);
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].aborted, Some("timeout"));
+        assert_eq!(msgs[0].packet_count, 1);
+        assert_eq!(msgs[0].duration_ns, Some(50_000_000));
+
+        // Replaced by a new session
+        assert!(push(&mut r, 0x1CECFF00, &bam, 1000).is_empty());
+        let msgs = push(&mut r, 0x1CECFF00, &bam, 1010);
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].aborted, Some("superseded"));
+
+        // Still open at the end
+        let msgs = r.finish();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].aborted, Some("incomplete"));
+        assert_eq!(msgs[0].interface.as_deref(), Some("can0"));
+    }
+
+    const DTC_OIL_PRESSURE: Dtc = Dtc {
+        spn: 100,
+        fmi: 3,
+        occurrence_count: 1,
+        conversion_method: false,
+    };
+
+    #[test]
+    fn diagnostic_messages() {
+        let dm = DiagnosticMessage::decode(&[0x44, 0xFF, 0x64, 0x00, 0x03, 0x01, 0xFF, 0xFF]);
+        assert_eq!(
+            dm,
+            Some(DiagnosticMessage {
+                lamps: vec![
+                    ("mil", "on", "none"),
+                    ("rsl", "off", "none"),
+                    ("awl", "on", "none"),
+                    ("pl", "off", "none"),
+                ],
+                dtcs: vec![DTC_OIL_PRESSURE],
+            })
+        );
+
+        let dm = DiagnosticMessage::decode(&[
+            0x00, 0x04, 0x64, 0x00, 0x03, 0x01, 0x00, 0xF0, 0xFF, 0x81,
+        ])
+        .unwrap();
+        assert_eq!(dm.lamps[2], ("awl", "off", "fast"));
+        assert_eq!(
+            dm.dtcs,
+            vec![
+                DTC_OIL_PRESSURE,
+                Dtc {
+                    spn: 520192,
+                    fmi: 31,
+                    occurrence_count: 1,
+                    conversion_method: true,
+                }
+            ]
+        );
+
+        // No active DTCs
+        let dm = DiagnosticMessage::decode(&[0x00, 0xFF, 0, 0, 0, 0, 0xFF, 0xFF]).unwrap();
+        assert!(dm.dtcs.is_empty());
+        assert_eq!(DiagnosticMessage::decode(&[0x00]), None);
+    }
+
+    #[test]
+    fn dtc_transitions() {
+        let mut t = DtcTracker::default();
+        let engine = J1939Id::from_can_id(0x18FECA00);
+        let brakes = J1939Id::from_can_id(0x18FECA0B);
+        let other = Dtc {
+            spn: 110,
+            fmi: 0,
+            occurrence_count: 2,
+            conversion_method: false,
+        };
+
+        assert_eq!(
+            t.update(Some("can0"), &engine, &[DTC_OIL_PRESSURE]),
+            vec![DtcTransition::Appeared(DTC_OIL_PRESSURE)]
+        );
+        // Occurrence count changes aren't transitions
+        let mut recurred = DTC_OIL_PRESSURE;
+        recurred.occurrence_count = 2;
+        assert!(t.update(Some("can0"), &engine, &[recurred]).is_empty());
+        // Tracked separately for each node and interface
+        assert_eq!(
+            t.update(Some("can0"), &brakes, &[DTC_OIL_PRESSURE]),
+            vec![DtcTransition::Appeared(DTC_OIL_PRESSURE)]
+        );
+        assert_eq!(t.update(Some("can1"), &engine, &[]), vec![]);
+        assert_eq!(
+            t.update(Some("can0"), &engine, &[other]),
+            vec![
+                DtcTransition::Cleared(recurred),
+                DtcTransition::Appeared(other)
+            ]
+        );
+        assert_eq!(
+            t.update(Some("can0"), &engine, &[]),
+            vec![DtcTransition::Cleared(other)]
+        );
+    }
+}
@@ -5,21 +5,42 @@ use auxon_sdk::{
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
+pub use crate::canopen::CanOpenNodeConfig;
+pub use crate::cycle_time::CycleTimeConfig;
 pub use crate::dbc::Dbc;
-pub use crate::parser::{CanParser, ParsedCanFrame};
+pub use crate::e2e::{E2EConfig, E2EDataIdMode, E2EProfile};
+pub use crate::parser::{CanParser, ParsedCanFrame, SignalValueKind, SignalValuesConfig};
 pub use convert::TimelineKey;
-pub use send::Sender;
+pub use import::{Direction, LogFormat, LogFrame};
+pub use isotp::{IsoTpAddressing, IsoTpConfig};
+pub use send::{InterfaceTimelines, Sender};
 
+mod canopen;
 mod convert;
+mod cycle_time;
 mod dbc;
+mod e2e;
+mod eds;
+mod error_frame;
+mod import;
+mod isotp;
+mod j1939;
+mod nmea2000;
+mod obd;
 mod parser;
 mod send;
+mod uds;
 
+pub mod asc;
+pub mod blf;
 pub mod candump;
+pub mod mdf;
+pub mod pcap;
+pub mod trc;
 
 pub const PLUGIN_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(default, rename_all = "kebab-case")]
 pub struct CommonConfig {
     /// Should the transmitting DBC node be used as the timeline identity and name? Defaults to true.
@@ -32,14 +53,111 @@ pub struct CommonConfig {
     #[serde(alias = "default_timeline")]
     pub default_timeline: Option<String>,
 
+    /// Use a separate timeline for each interface, or log channel, frames are captured on.
+    /// Defaults to true, except when collecting from a single interface.
+    #[serde(deserialize_with = "from_str", alias = "timeline_from_interface")]
+    pub timeline_from_interface: Option<bool>,
+
     /// Use the DBC message name for event naming. Defaults to true.
     /// When no DBC file is provided, or there is no message definition, the CAN ID will be used.
     #[serde(deserialize_with = "from_str", alias = "event_from_message")]
     pub event_from_message: Option<bool>,
 
+    /// Clamp the physical values of signals to their DBC `[min|max]` range. Defaults to true.
+    /// Values outside the range are flagged with `<signal>.out_of_range` either way.
+    #[serde(deserialize_with = "from_str", alias = "clamp_signal_values")]
+    pub clamp_signal_values: Option<bool>,
+
+    /// The values logged for each signal: `physical`, `raw` and `label`.
+    /// By default signals are logged as their value description when there is one, otherwise
+    /// their physical value.
+    #[serde(alias = "signal_values")]
+    pub signal_values: Option<Vec<SignalValueKind>>,
+
+    /// The values logged for the signals of particular messages, overriding `signal-values`.
+    #[serde(alias = "signal_value_overrides")]
+    pub signal_value_overrides: Option<Vec<SignalValuesConfig>>,
+
     /// DBC file to use when parsing the CAN frames.
     #[serde(deserialize_with = "from_str")]
     pub dbc: Option<PathBuf>,
+
+    /// Add the DBC comment of each transmitting node to its timeline. Defaults to false.
+    #[serde(deserialize_with = "from_str", alias = "dbc_node_comments")]
+    pub dbc_node_comments: Option<bool>,
+
+    /// DBC node attributes to add to the timeline of each transmitting node.
+    #[serde(alias = "dbc_node_attributes")]
+    pub dbc_node_attributes: Option<Vec<String>>,
+
+    /// DBC message attributes to add to the events of each message.
+    #[serde(alias = "dbc_message_attributes")]
+    pub dbc_message_attributes: Option<Vec<String>>,
+
+    /// DBC signal attributes to add to the events of each message, alongside the signals.
+    #[serde(alias = "dbc_signal_attributes")]
+    pub dbc_signal_attributes: Option<Vec<String>>,
+
+    /// Decode 29-bit frames as SAE J1939, matching DBC messages by PGN regardless of
+    /// source address. Defaults to false.
+    #[serde(deserialize_with = "from_str")]
+    pub j1939: Option<bool>,
+
+    /// In J1939 mode, use a separate timeline for each source address. Defaults to true.
+    #[serde(deserialize_with = "from_str", alias = "timeline_from_source_address")]
+    pub timeline_from_source_address: Option<bool>,
+
+    /// CAN ID pairs carrying ISO-TP (ISO 15765-2) traffic, reassembled into one event per PDU.
+    pub isotp: Option<Vec<IsoTpConfig>>,
+
+    /// Decode OBD-II requests and responses on the standard diagnostic CAN IDs. Defaults to false.
+    #[serde(deserialize_with = "from_str")]
+    pub obd: Option<bool>,
+
+    /// Decode 11-bit frames as CANopen, with a timeline for each node-id. Defaults to false.
+    #[serde(deserialize_with = "from_str")]
+    pub canopen: Option<bool>,
+
+    /// CANopen nodes with an EDS or DCF file, used to decode their PDOs.
+    #[serde(alias = "canopen_nodes")]
+    pub canopen_nodes: Option<Vec<CanOpenNodeConfig>>,
+
+    /// Follow SDO writes to the PDO communication and mapping parameters of CANopen nodes.
+    /// Defaults to false.
+    #[serde(deserialize_with = "from_str", alias = "canopen_sdo_mapping")]
+    pub canopen_sdo_mapping: Option<bool>,
+
+    /// Decode 29-bit frames as NMEA 2000, reassembling fast-packet messages. Implies J1939
+    /// identifiers. Defaults to false.
+    #[serde(deserialize_with = "from_str")]
+    pub nmea2000: Option<bool>,
+
+    /// A canboat-style JSON PGN database used to name and decode NMEA 2000 messages.
+    #[serde(deserialize_with = "from_str", alias = "nmea2000_pgns")]
+    pub nmea2000_pgns: Option<PathBuf>,
+
+    /// AUTOSAR E2E protected messages, in addition to those with DBC attributes.
+    pub e2e: Option<Vec<E2EConfig>>,
+
+    /// Message cycle times, overriding the `GenMsgCycleTime` DBC attributes.
+    #[serde(alias = "cycle_times")]
+    pub cycle_times: Option<Vec<CycleTimeConfig>>,
+
+    /// The fraction of its cycle time a periodic message may be late by before it's timed out.
+    /// Defaults to 0.5.
+    #[serde(deserialize_with = "from_str", alias = "cycle_time_tolerance")]
+    pub cycle_time_tolerance: Option<f64>,
+}
+
+/// Merge two configs of all `Option` fields, preferring the values of `$this`. The struct is
+/// destructured exhaustively, so a field missing from the list fails to compile.
+macro_rules! or_defaults {
+    ($this:expr, $defaults:expr, $ty:ident { $($field:ident),* $(,)? }) => {{
+        let $ty { $($field),* } = $this;
+        $ty {
+            $($field: $field.or_else(|| $defaults.$field.clone()),)*
+        }
+    }};
 }
 
 pub trait HasCommonConfig {
@@ -56,4 +174,51 @@ impl CommonConfig {
             Ok(self.dbc.clone())
         }
     }
+
+    /// Fill in any settings that weren't given from `defaults`
+    pub fn or(self, defaults: &CommonConfig) -> Self {
+        or_defaults!(
+            self,
+            defaults,
+            CommonConfig {
+                timeline_from_node,
+                default_timeline,
+                timeline_from_interface,
+                event_from_message,
+                clamp_signal_values,
+                signal_values,
+                signal_value_overrides,
+                dbc,
+                dbc_node_comments,
+                dbc_node_attributes,
+                dbc_message_attributes,
+                dbc_signal_attributes,
+                j1939,
+                timeline_from_source_address,
+                isotp,
+                obd,
+                canopen,
+                canopen_nodes,
+                canopen_sdo_mapping,
+                nmea2000,
+                nmea2000_pgns,
+                e2e,
+                cycle_times,
+                cycle_time_tolerance,
+            }
+        )
+    }
+
+    pub fn envsub_nmea2000_pgns_path(&self) -> Result<Option<PathBuf>, EnvSubError> {
+        let maybe_str = self
+            .nmea2000_pgns
+            .as_ref()
+            .and_then(|p| p.as_os_str().to_str());
+
+        if let Some(s) = maybe_str {
+            envsub(s).map(|s| Some(PathBuf::from(s)))
+        } else {
+            Ok(self.nmea2000_pgns.clone())
+        }
+    }
 }
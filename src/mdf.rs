@@ -0,0 +1,1247 @@
+//! ASAM MDF4 (.mf4) reader for files following the ASAM bus logging convention,
+//! e.g. from CANedge loggers or INCA exports.
+//!
+//! `CAN_DataFrame`, `CAN_RemoteFrame` and `CAN_ErrorFrame` channel groups are read,
+//! everything else is skipped. Both sorted and unsorted data groups are supported,
+//! including compressed (DZ) and list (DL/HL) data blocks, and variable length
+//! `DataBytes` (VLSD) channels.
+//! Record timestamps are relative to the file start time unless absolute timestamps are
+//! requested, in which case the header start time is added.
+//! Frames from separate data groups are merged in timestamp order.
+
+use crate::error_frame::{
+    CAN_ERR_ACK, CAN_ERR_PROT, CAN_ERR_PROT_BIT, CAN_ERR_PROT_FORM, CAN_ERR_PROT_LOC_CRC_SEQ,
+    CAN_ERR_PROT_STUFF,
+};
+use crate::import::{
+    add_timestamps, dlc_to_len, timestamp_from_nanos, timestamp_to_nanos, Direction, LogFrame,
+    MAX_CLASSIC_DATA_LEN,
+};
+use anyhow::{anyhow, bail};
+use flate2::read::ZlibDecoder;
+use socketcan::{
+    frame::FdFlags, CanAnyFrame, CanDataFrame, CanErrorFrame, CanFdFrame, CanRemoteFrame,
+    EmbeddedFrame, ExtendedId, Id as CanId, StandardId, Timestamp,
+};
+use std::collections::{HashMap, VecDeque};
+use std::io::{Read, Seek, SeekFrom};
+use tracing::{debug, warn};
+
+const ID_BLOCK_SIZE: usize = 64;
+const HD_BLOCK_OFFSET: u64 = 64;
+const BLOCK_HEADER_SIZE: u64 = 24;
+const MIN_VERSION: u16 = 400;
+
+/// Max bytes read at once from uncompressed data blocks
+const READ_CHUNK_SIZE: u64 = 1024 * 1024;
+
+const CN_TYPE_VLSD: u8 = 1;
+const CN_TYPE_MASTER: u8 = 2;
+const CN_TYPE_VIRTUAL_MASTER: u8 = 3;
+const CN_SYNC_TIME: u8 = 1;
+
+const DT_UINT_LE: u8 = 0;
+const DT_UINT_BE: u8 = 1;
+const DT_INT_LE: u8 = 2;
+const DT_INT_BE: u8 = 3;
+const DT_FLOAT_LE: u8 = 4;
+const DT_FLOAT_BE: u8 = 5;
+
+const CG_FLAG_VLSD: u16 = 0x0001;
+
+const CC_TYPE_IDENTITY: u8 = 0;
+const CC_TYPE_LINEAR: u8 = 1;
+
+const ZIP_DEFLATE: u8 = 0;
+const ZIP_TRANSPOSE_DEFLATE: u8 = 1;
+
+const CAN_EFF_MASK: u64 = 0x1FFF_FFFF;
+const ID_IDE_BIT: u64 = 0x8000_0000;
+
+pub struct Reader<R> {
+    reader: R,
+    start_time: Option<Timestamp>,
+    groups: Vec<DataGroup>,
+}
+
+impl<R: Read + Seek> Reader<R> {
+    pub fn new(mut reader: R, absolute_timestamps: bool) -> Result<Self, anyhow::Error> {
+        let mut id = [0_u8; ID_BLOCK_SIZE];
+        reader.seek(SeekFrom::Start(0))?;
+        reader
+            .read_exact(&mut id)
+            .map_err(|e| anyhow!("Failed to read the MDF identification block. {e}"))?;
+        match &id[..8] {
+            b"MDF     " => (),
+            b"UnFinMF " => warn!("MDF file is not finalized, some data may be missing"),
+            _ => bail!("Invalid MDF file identifier"),
+        }
+        let version = u16::from_le_bytes([id[28], id[29]]);
+        if version < MIN_VERSION {
+            bail!("Unsupported MDF version {version}, only MDF 4.x files are supported");
+        }
+
+        let hd = read_block(&mut reader, HD_BLOCK_OFFSET)?;
+        hd.expect_id(b"##HD")?;
+        let start_time_ns = hd.u64_at(0)? as i64;
+        let start_time = timestamp_from_nanos(start_time_ns);
+        debug!(version, ?start_time, "Read MDF header");
+
+        let mut groups = Vec::new();
+        let mut dg_link = hd.link(0);
+        while dg_link != 0 {
+            let dg = read_block(&mut reader, dg_link)?;
+            dg.expect_id(b"##DG")?;
+            if let Some(group) = DataGroup::new(&mut reader, &dg)? {
+                groups.push(group);
+            }
+            dg_link = dg.link(0);
+        }
+        if groups.is_empty() {
+            warn!("MDF file doesn't contain any CAN bus logging channel groups");
+        }
+
+        Ok(Self {
+            reader,
+            start_time: Some(start_time).filter(|_| absolute_timestamps),
+            groups,
+        })
+    }
+}
+
+impl<R: Read + Seek> Iterator for Reader<R> {
+    type Item = Result<LogFrame, anyhow::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for group in self.groups.iter_mut() {
+            if group.next.is_none() && !group.done {
+                match group.read_next_frame(&mut self.reader) {
+                    Ok(f) => group.next = f,
+                    Err(e) => {
+                        group.done = true;
+                        return Some(Err(e));
+                    }
+                }
+            }
+        }
+
+        let group = self
+            .groups
+            .iter_mut()
+            .filter(|g| g.next.is_some())
+            .min_by_key(|g| {
+                g.next
+                    .as_ref()
+                    .and_then(|f| f.timestamp.as_ref().map(timestamp_to_nanos))
+            })?;
+        let mut frame = group.next.take()?;
+        if let Some(start) = self.start_time.as_ref() {
+            frame.timestamp = frame.timestamp.map(|t| add_timestamps(start, &t));
+        }
+        Some(Ok(frame))
+    }
+}
+
+struct DataGroup {
+    stream: DataStream,
+    rec_id_size: usize,
+    /// Record ID to record layout, sorted groups have a single record with ID 0
+    records: HashMap<u64, RecordLayout>,
+    /// VLSD channel group payloads, keyed by record ID and offset
+    vlsd_data: HashMap<(u64, u64), Vec<u8>>,
+    /// Offset of the next payload for each VLSD channel group
+    vlsd_offsets: HashMap<u64, u64>,
+    next: Option<LogFrame>,
+    done: bool,
+}
+
+enum RecordLayout {
+    Fixed {
+        size: usize,
+        bus_group: Option<Box<BusGroup>>,
+    },
+    Vlsd,
+}
+
+impl DataGroup {
+    /// Returns None if the data group doesn't contain any bus logging channel groups
+    fn new<R: Read + Seek>(reader: &mut R, dg: &Block) -> Result<Option<Self>, anyhow::Error> {
+        let rec_id_size = *dg.data.first().unwrap_or(&0) as usize;
+        if ![0, 1, 2, 4, 8].contains(&rec_id_size) {
+            bail!("Invalid MDF record ID size {rec_id_size}");
+        }
+
+        // Channel group block offset to record ID, for VLSD channel references
+        let mut cg_offset_to_record_id = HashMap::new();
+        let mut cgs = Vec::new();
+        let mut cg_link = dg.link(1);
+        while cg_link != 0 {
+            let cg = read_block(reader, cg_link)?;
+            cg.expect_id(b"##CG")?;
+            cg_offset_to_record_id.insert(cg_link, cg.u64_at(0)?);
+            cg_link = cg.link(0);
+            cgs.push(cg);
+        }
+
+        let mut records = HashMap::new();
+        let mut has_bus_groups = false;
+        for cg in cgs.iter() {
+            let record_id = cg.u64_at(0)?;
+            let flags = cg.u16_at(16)?;
+            if flags & CG_FLAG_VLSD != 0 {
+                records.insert(record_id, RecordLayout::Vlsd);
+                continue;
+            }
+            let size = cg.u32_at(24)? as usize + cg.u32_at(28)? as usize;
+            let bus_group = BusGroup::new(reader, cg, &cg_offset_to_record_id)?.map(Box::new);
+            has_bus_groups |= bus_group.is_some();
+            records.insert(record_id, RecordLayout::Fixed { size, bus_group });
+        }
+
+        if !has_bus_groups {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            stream: DataStream::new(reader, dg.link(2))?,
+            rec_id_size,
+            records,
+            vlsd_data: Default::default(),
+            vlsd_offsets: Default::default(),
+            next: None,
+            done: false,
+        }))
+    }
+
+    fn read_next_frame<R: Read + Seek>(
+        &mut self,
+        reader: &mut R,
+    ) -> Result<Option<LogFrame>, anyhow::Error> {
+        let mut rec_id_buf = [0_u8; 8];
+        let mut record = Vec::new();
+        loop {
+            let record_id = if self.rec_id_size == 0 {
+                0
+            } else {
+                if !self
+                    .stream
+                    .read_exact(reader, &mut rec_id_buf[..self.rec_id_size])?
+                {
+                    self.done = true;
+                    return Ok(None);
+                }
+                u64::from_le_bytes(rec_id_buf)
+            };
+
+            let Some(layout) = self.records.get(&record_id) else {
+                bail!("MDF data group contains an unknown record ID {record_id}");
+            };
+
+            match layout {
+                RecordLayout::Vlsd => {
+                    let mut len = [0_u8; 4];
+                    if !self.stream.read_exact(reader, &mut len)? {
+                        self.done = true;
+                        return Ok(None);
+                    }
+                    let len = u32::from_le_bytes(len) as usize;
+                    record.resize(len, 0);
+                    if !self.stream.read_exact(reader, &mut record)? {
+                        self.done = true;
+                        return Ok(None);
+                    }
+                    let offset = self.vlsd_offsets.entry(record_id).or_default();
+                    self.vlsd_data
+                        .insert((record_id, *offset), std::mem::take(&mut record));
+                    *offset += 4 + len as u64;
+                }
+                RecordLayout::Fixed { size, bus_group } => {
+                    record.resize(*size, 0);
+                    if !self.stream.read_exact(reader, &mut record)? {
+                        if self.stream.partial_read {
+                            warn!("MDF data group ended with a truncated record");
+                        }
+                        self.done = true;
+                        return Ok(None);
+                    }
+                    if let Some(bus_group) = bus_group {
+                        let vlsd_data = &mut self.vlsd_data;
+                        let frame = bus_group.decode(&record, |src, offset| match src {
+                            VlsdSource::SignalData(sd) => signal_data_at(sd, offset),
+                            VlsdSource::ChannelGroup(rec_id) => {
+                                vlsd_data.remove(&(*rec_id, offset))
+                            }
+                        });
+                        match frame {
+                            Some(f) => return Ok(Some(f)),
+                            None => debug!("Skipping malformed MDF bus logging record"),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum BusEventKind {
+    Data,
+    Remote,
+    Error,
+}
+
+impl BusEventKind {
+    fn from_name(name: &str) -> Option<Self> {
+        let root = name.split('.').next().unwrap_or(name);
+        Some(match root {
+            "CAN_DataFrame" => BusEventKind::Data,
+            "CAN_RemoteFrame" => BusEventKind::Remote,
+            "CAN_ErrorFrame" => BusEventKind::Error,
+            _ => return None,
+        })
+    }
+}
+
+/// The channels of a bus logging channel group
+struct BusGroup {
+    kind: BusEventKind,
+    time: Channel,
+    bus_channel: Option<Channel>,
+    id: Option<Channel>,
+    ide: Option<Channel>,
+    dlc: Option<Channel>,
+    data_length: Option<Channel>,
+    data_bytes: Option<Channel>,
+    brs: Option<Channel>,
+    esi: Option<Channel>,
+    edl: Option<Channel>,
+    dir: Option<Channel>,
+    error_type: Option<Channel>,
+}
+
+impl BusGroup {
+    fn new<R: Read + Seek>(
+        reader: &mut R,
+        cg: &Block,
+        cg_offset_to_record_id: &HashMap<u64, u64>,
+    ) -> Result<Option<Self>, anyhow::Error> {
+        let acq_name = read_text(reader, cg.link(2))?;
+        let mut channels = Vec::new();
+        collect_channels(reader, cg.link(1), &mut channels, 0)?;
+
+        let Some(kind) = acq_name
+            .as_deref()
+            .and_then(BusEventKind::from_name)
+            .or_else(|| {
+                channels
+                    .iter()
+                    .find_map(|c| BusEventKind::from_name(&c.name))
+            })
+        else {
+            return Ok(None);
+        };
+
+        let mut time = None;
+        let mut by_name: HashMap<String, Channel> = HashMap::new();
+        for mut c in channels.into_iter() {
+            if (c.cn_type == CN_TYPE_MASTER || c.cn_type == CN_TYPE_VIRTUAL_MASTER)
+                && c.sync_type == CN_SYNC_TIME
+            {
+                time = Some(c);
+                continue;
+            }
+            if c.cn_type == CN_TYPE_VLSD {
+                c.vlsd = Some(match cg_offset_to_record_id.get(&c.data_link) {
+                    Some(rec_id) => VlsdSource::ChannelGroup(*rec_id),
+                    None => VlsdSource::SignalData(read_all(reader, c.data_link)?),
+                });
+            }
+            let short_name = c.name.rsplit('.').next().unwrap_or(&c.name).to_owned();
+            by_name.entry(short_name).or_insert(c);
+        }
+
+        let Some(time) = time else {
+            warn!(
+                ?kind,
+                "MDF bus logging channel group is missing a time master channel"
+            );
+            return Ok(None);
+        };
+        if time.cn_type == CN_TYPE_VIRTUAL_MASTER {
+            warn!(?kind, "MDF virtual master channels are not supported");
+            return Ok(None);
+        }
+
+        let group = Self {
+            kind,
+            time,
+            bus_channel: by_name.remove("BusChannel"),
+            id: by_name.remove("ID"),
+            ide: by_name.remove("IDE"),
+            dlc: by_name.remove("DLC"),
+            data_length: by_name.remove("DataLength"),
+            data_bytes: by_name.remove("DataBytes"),
+            brs: by_name.remove("BRS"),
+            esi: by_name.remove("ESI"),
+            edl: by_name.remove("EDL"),
+            dir: by_name.remove("Dir"),
+            error_type: by_name.remove("ErrorType"),
+        };
+        if group.id.is_none() && kind != BusEventKind::Error {
+            warn!(
+                ?kind,
+                "MDF bus logging channel group is missing the ID channel"
+            );
+            return Ok(None);
+        }
+        debug!(?kind, "Found MDF bus logging channel group");
+        Ok(Some(group))
+    }
+
+    fn decode<F>(&self, record: &[u8], mut vlsd_lookup: F) -> Option<LogFrame>
+    where
+        F: FnMut(&VlsdSource, u64) -> Option<Vec<u8>>,
+    {
+        let secs = self.time.read_f64(record)?;
+        let timestamp = timestamp_from_nanos((secs * 1_000_000_000.0).round() as i64);
+
+        let uint = |c: &Option<Channel>| c.as_ref().and_then(|c| c.read_u64(record));
+        let flag = |c: &Option<Channel>| uint(c).map(|v| v != 0).unwrap_or(false);
+
+        let interface = uint(&self.bus_channel).map(|c| c.to_string());
+        let direction = uint(&self.dir).map(|d| if d == 0 { Direction::Rx } else { Direction::Tx });
+
+        let raw_id = uint(&self.id).unwrap_or(0);
+        let is_extended = flag(&self.ide) || (raw_id & ID_IDE_BIT != 0);
+        let id = if is_extended {
+            CanId::from(ExtendedId::new((raw_id & CAN_EFF_MASK) as u32)?)
+        } else {
+            CanId::from(StandardId::new(u16::try_from(raw_id).ok()?)?)
+        };
+        let dlc = uint(&self.dlc).unwrap_or(0) as usize;
+
+        let frame = match self.kind {
+            BusEventKind::Remote => CanAnyFrame::Remote(CanRemoteFrame::new_remote(
+                id,
+                dlc.min(MAX_CLASSIC_DATA_LEN),
+            )?),
+            BusEventKind::Error => {
+                let (class, data) = error_class_and_data(uint(&self.error_type).unwrap_or(0));
+                CanAnyFrame::Error(CanErrorFrame::new_error(class, &data).ok()?)
+            }
+            BusEventKind::Data => {
+                let is_fd = flag(&self.edl);
+                let len = match uint(&self.data_length) {
+                    Some(len) => len as usize,
+                    None if is_fd => dlc_to_len(dlc),
+                    None => dlc.min(MAX_CLASSIC_DATA_LEN),
+                };
+                let bytes = match self.data_bytes.as_ref() {
+                    Some(c) => match c.vlsd.as_ref() {
+                        Some(src) => vlsd_lookup(src, c.read_u64(record)?)?,
+                        None => c.read_bytes(record)?.to_vec(),
+                    },
+                    None => Vec::new(),
+                };
+                let data = bytes.get(..len.min(bytes.len()))?;
+                if is_fd {
+                    let mut flags = FdFlags::empty();
+                    if flag(&self.brs) {
+                        flags |= FdFlags::BRS;
+                    }
+                    if flag(&self.esi) {
+                        flags |= FdFlags::ESI;
+                    }
+                    CanAnyFrame::Fd(CanFdFrame::with_flags(id, data, flags)?)
+                } else {
+                    let len = data.len().min(MAX_CLASSIC_DATA_LEN);
+                    CanAnyFrame::Normal(CanDataFrame::new(id, &data[..len])?)
+                }
+            }
+        };
+
+        Some(LogFrame {
+            timestamp: Some(timestamp),
+            interface,
+            direction,
+            frame,
+        })
+    }
+}
+
+/// Map the bus logging ErrorType (0 unknown, 1 bit, 2 form, 3 stuff, 4 CRC, 5 ACK)
+/// to the SocketCAN error class and data
+fn error_class_and_data(error_type: u64) -> (u32, [u8; 8]) {
+    let mut data = [0_u8; 8];
+    let class = match error_type {
+        1 => {
+            data[2] = CAN_ERR_PROT_BIT;
+            CAN_ERR_PROT
+        }
+        2 => {
+            data[2] = CAN_ERR_PROT_FORM;
+            CAN_ERR_PROT
+        }
+        3 => {
+            data[2] = CAN_ERR_PROT_STUFF;
+            CAN_ERR_PROT
+        }
+        4 => {
+            data[3] = CAN_ERR_PROT_LOC_CRC_SEQ;
+            CAN_ERR_PROT
+        }
+        5 => CAN_ERR_ACK,
+        _ => 0,
+    };
+    (class, data)
+}
+
+enum VlsdSource {
+    /// Signal data (SD) stream of a sorted data group
+    SignalData(Vec<u8>),
+    /// VLSD channel group of an unsorted data group, by record ID
+    ChannelGroup(u64),
+}
+
+fn signal_data_at(sd: &[u8], offset: u64) -> Option<Vec<u8>> {
+    let offset = usize::try_from(offset).ok()?;
+    let len = u32::from_le_bytes(sd.get(offset..offset + 4)?.try_into().ok()?) as usize;
+    sd.get(offset + 4..offset + 4 + len).map(|d| d.to_vec())
+}
+
+struct Channel {
+    name: String,
+    cn_type: u8,
+    sync_type: u8,
+    data_type: u8,
+    bit_offset: u32,
+    byte_offset: usize,
+    bit_count: u32,
+    /// Linear conversion (offset, factor)
+    conversion: Option<(f64, f64)>,
+    data_link: u64,
+    vlsd: Option<VlsdSource>,
+}
+
+impl Channel {
+    fn raw_bits(&self, record: &[u8]) -> Option<u64> {
+        if self.bit_count == 0 || self.bit_count > 64 {
+            return None;
+        }
+        let total_bits = self.bit_offset + self.bit_count;
+        let num_bytes = total_bits.div_ceil(8) as usize;
+        let bytes = record.get(self.byte_offset..self.byte_offset + num_bytes)?;
+        let mut v: u128 = 0;
+        if self.data_type == DT_UINT_BE
+            || self.data_type == DT_INT_BE
+            || self.data_type == DT_FLOAT_BE
+        {
+            for b in bytes.iter() {
+                v = (v << 8) | *b as u128;
+            }
+        } else {
+            for b in bytes.iter().rev() {
+                v = (v << 8) | *b as u128;
+            }
+        }
+        let mask = if self.bit_count == 64 {
+            u64::MAX as u128
+        } else {
+            (1_u128 << self.bit_count) - 1
+        };
+        Some(((v >> self.bit_offset) & mask) as u64)
+    }
+
+    /// Unsigned value of the channel, VLSD offsets are read as little-endian integers
+    fn read_u64(&self, record: &[u8]) -> Option<u64> {
+        self.raw_bits(record)
+    }
+
+    fn read_f64(&self, record: &[u8]) -> Option<f64> {
+        let raw = self.raw_bits(record)?;
+        let v = match self.data_type {
+            DT_UINT_LE | DT_UINT_BE => raw as f64,
+            DT_INT_LE | DT_INT_BE => {
+                let shift = 64 - self.bit_count;
+                (((raw << shift) as i64) >> shift) as f64
+            }
+            DT_FLOAT_LE | DT_FLOAT_BE if self.bit_count == 32 => f32::from_bits(raw as u32) as f64,
+            DT_FLOAT_LE | DT_FLOAT_BE if self.bit_count == 64 => f64::from_bits(raw),
+            _ => return None,
+        };
+        Some(match self.conversion {
+            Some((offset, factor)) => offset + (factor * v),
+            None => v,
+        })
+    }
+
+    fn read_bytes<'a>(&self, record: &'a [u8]) -> Option<&'a [u8]> {
+        let len = (self.bit_count / 8) as usize;
+        record.get(self.byte_offset..self.byte_offset + len)
+    }
+}
+
+/// Walk a channel list, including any nested (composed) channels
+fn collect_channels<R: Read + Seek>(
+    reader: &mut R,
+    mut cn_link: u64,
+    channels: &mut Vec<Channel>,
+    depth: usize,
+) -> Result<(), anyhow::Error> {
+    const MAX_DEPTH: usize = 8;
+    while cn_link != 0 {
+        let cn = read_block(reader, cn_link)?;
+        cn.expect_id(b"##CN")?;
+        let name = read_text(reader, cn.link(2))?.unwrap_or_default();
+        let conversion = read_conversion(reader, cn.link(4))?;
+        channels.push(Channel {
+            name,
+            cn_type: cn.u8_at(0)?,
+            sync_type: cn.u8_at(1)?,
+            data_type: cn.u8_at(2)?,
+            bit_offset: cn.u8_at(3)? as u32,
+            byte_offset: cn.u32_at(4)? as usize,
+            bit_count: cn.u32_at(8)?,
+            conversion,
+            data_link: cn.link(5),
+            vlsd: None,
+        });
+
+        let composition = cn.link(1);
+        if composition != 0 && depth < MAX_DEPTH {
+            let header = read_block_header(reader, composition)?;
+            if &header.id == b"##CN" {
+                collect_channels(reader, composition, channels, depth + 1)?;
+            }
+        }
+        cn_link = cn.link(0);
+    }
+    Ok(())
+}
+
+fn read_conversion<R: Read + Seek>(
+    reader: &mut R,
+    link: u64,
+) -> Result<Option<(f64, f64)>, anyhow::Error> {
+    if link == 0 {
+        return Ok(None);
+    }
+    let cc = read_block(reader, link)?;
+    cc.expect_id(b"##CC")?;
+    match cc.u8_at(0)? {
+        CC_TYPE_IDENTITY => Ok(None),
+        CC_TYPE_LINEAR => Ok(Some((cc.f64_at(24)?, cc.f64_at(32)?))),
+        cc_type => {
+            warn!(
+                cc_type,
+                "Unsupported MDF channel conversion type, using raw values"
+            );
+            Ok(None)
+        }
+    }
+}
+
+fn read_text<R: Read + Seek>(reader: &mut R, link: u64) -> Result<Option<String>, anyhow::Error> {
+    if link == 0 {
+        return Ok(None);
+    }
+    let tx = read_block(reader, link)?;
+    if &tx.id != b"##TX" {
+        // Metadata (XML) blocks aren't used for names
+        return Ok(None);
+    }
+    let text = String::from_utf8_lossy(&tx.data);
+    Ok(Some(text.trim_end_matches('\0').trim().to_owned()))
+}
+
+/// Read an entire data stream into memory
+fn read_all<R: Read + Seek>(reader: &mut R, link: u64) -> Result<Vec<u8>, anyhow::Error> {
+    let mut stream = DataStream::new(reader, link)?;
+    let mut data = Vec::new();
+    while stream.fill(reader)? {
+        data.extend_from_slice(&stream.buf[stream.pos..]);
+        stream.pos = stream.buf.len();
+    }
+    Ok(data)
+}
+
+/// The data of a DT/SD block, or a DZ/DL/HL block chain
+struct DataStream {
+    fragments: VecDeque<Fragment>,
+    buf: Vec<u8>,
+    pos: usize,
+    /// Set when the stream ended part way through a read
+    partial_read: bool,
+}
+
+enum Fragment {
+    Raw {
+        offset: u64,
+        len: u64,
+    },
+    Compressed {
+        offset: u64,
+        len: u64,
+        zip_type: u8,
+        zip_parameter: u32,
+        org_len: u64,
+    },
+}
+
+impl DataStream {
+    fn new<R: Read + Seek>(reader: &mut R, link: u64) -> Result<Self, anyhow::Error> {
+        let mut fragments = VecDeque::new();
+        collect_fragments(reader, link, &mut fragments, 0)?;
+        Ok(Self {
+            fragments,
+            buf: Vec::new(),
+            pos: 0,
+            partial_read: false,
+        })
+    }
+
+    /// Returns false at the end of the stream
+    fn read_exact<R: Read + Seek>(
+        &mut self,
+        reader: &mut R,
+        out: &mut [u8],
+    ) -> Result<bool, anyhow::Error> {
+        let mut filled = 0;
+        while filled < out.len() {
+            if self.pos == self.buf.len() && !self.fill(reader)? {
+                self.partial_read = filled != 0;
+                return Ok(false);
+            }
+            let n = (out.len() - filled).min(self.buf.len() - self.pos);
+            out[filled..filled + n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+            filled += n;
+            self.pos += n;
+        }
+        Ok(true)
+    }
+
+    /// Load the next chunk of data, returns false at the end of the stream
+    fn fill<R: Read + Seek>(&mut self, reader: &mut R) -> Result<bool, anyhow::Error> {
+        let Some(fragment) = self.fragments.pop_front() else {
+            return Ok(false);
+        };
+        self.pos = 0;
+        match fragment {
+            Fragment::Raw { offset, len } => {
+                let chunk = len.min(READ_CHUNK_SIZE);
+                self.buf.resize(chunk as usize, 0);
+                reader.seek(SeekFrom::Start(offset))?;
+                reader.read_exact(&mut self.buf)?;
+                if chunk < len {
+                    self.fragments.push_front(Fragment::Raw {
+                        offset: offset + chunk,
+                        len: len - chunk,
+                    });
+                }
+            }
+            Fragment::Compressed {
+                offset,
+                len,
+                zip_type,
+                zip_parameter,
+                org_len,
+            } => {
+                let mut compressed = vec![0_u8; len as usize];
+                reader.seek(SeekFrom::Start(offset))?;
+                reader.read_exact(&mut compressed)?;
+                self.buf.clear();
+                ZlibDecoder::new(compressed.as_slice())
+                    .read_to_end(&mut self.buf)
+                    .map_err(|e| anyhow!("Failed to decompress MDF data block. {e}"))?;
+                if self.buf.len() as u64 != org_len {
+                    warn!(
+                        expected = org_len,
+                        actual = self.buf.len(),
+                        "MDF compressed data block has an unexpected length"
+                    );
+                }
+                if zip_type == ZIP_TRANSPOSE_DEFLATE {
+                    self.buf = untranspose(&self.buf, zip_parameter as usize);
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
+fn collect_fragments<R: Read + Seek>(
+    reader: &mut R,
+    link: u64,
+    fragments: &mut VecDeque<Fragment>,
+    depth: usize,
+) -> Result<(), anyhow::Error> {
+    const MAX_DEPTH: usize = 4;
+    if link == 0 {
+        return Ok(());
+    }
+    if depth > MAX_DEPTH {
+        bail!("MDF data block lists are nested too deeply");
+    }
+    let header = read_block_header(reader, link)?;
+    match &header.id {
+        b"##DT" | b"##SD" | b"##RD" => fragments.push_back(Fragment::Raw {
+            offset: header.data_offset,
+            len: header.data_len,
+        }),
+        b"##DZ" => {
+            let dz = read_block_data_prefix(reader, &header, 24)?;
+            let zip_type = dz[2];
+            if zip_type != ZIP_DEFLATE && zip_type != ZIP_TRANSPOSE_DEFLATE {
+                bail!("Unsupported MDF compression type {zip_type}");
+            }
+            let len = u64::from_le_bytes(dz[16..24].try_into()?);
+            if len > header.data_len - 24 {
+                bail!("Invalid MDF compressed data length {len} at offset {link}");
+            }
+            fragments.push_back(Fragment::Compressed {
+                offset: header.data_offset + 24,
+                len,
+                zip_type,
+                zip_parameter: u32::from_le_bytes(dz[4..8].try_into()?),
+                org_len: u64::from_le_bytes(dz[8..16].try_into()?),
+            });
+        }
+        b"##DL" => {
+            let mut dl_link = link;
+            while dl_link != 0 {
+                let dl = read_block_header(reader, dl_link)?;
+                for data_link in dl.links.iter().skip(1) {
+                    collect_fragments(reader, *data_link, fragments, depth + 1)?;
+                }
+                dl_link = dl.links.first().copied().unwrap_or(0);
+            }
+        }
+        b"##HL" => {
+            collect_fragments(
+                reader,
+                header.links.first().copied().unwrap_or(0),
+                fragments,
+                depth + 1,
+            )?;
+        }
+        id => bail!(
+            "Unexpected MDF data block '{}'",
+            String::from_utf8_lossy(id)
+        ),
+    }
+    Ok(())
+}
+
+/// Undo the byte transposition of a DZ block, the data was stored column-wise
+/// with the given number of columns (record size), any remainder is stored as-is
+fn untranspose(data: &[u8], cols: usize) -> Vec<u8> {
+    if cols == 0 {
+        return data.to_vec();
+    }
+    let rows = data.len() / cols;
+    let mut out = vec![0_u8; data.len()];
+    for c in 0..cols {
+        for r in 0..rows {
+            out[r * cols + c] = data[c * rows + r];
+        }
+    }
+    out[rows * cols..].copy_from_slice(&data[rows * cols..]);
+    out
+}
+
+struct BlockHeader {
+    id: [u8; 4],
+    links: Vec<u64>,
+    data_offset: u64,
+    data_len: u64,
+}
+
+fn read_block_header<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+) -> Result<BlockHeader, anyhow::Error> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    let mut h = [0_u8; BLOCK_HEADER_SIZE as usize];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader
+        .read_exact(&mut h)
+        .map_err(|e| anyhow!("Failed to read MDF block at offset {offset}. {e}"))?;
+    let mut id = [0_u8; 4];
+    id.copy_from_slice(&h[..4]);
+    if &id[..2] != b"##" {
+        bail!("Invalid MDF block at offset {offset}");
+    }
+    let length = u64::from_le_bytes(h[8..16].try_into()?);
+    if offset
+        .checked_add(length)
+        .filter(|end| *end <= file_len)
+        .is_none()
+    {
+        bail!("MDF block at offset {offset} extends past the end of the file");
+    }
+    let link_count = u64::from_le_bytes(h[16..24].try_into()?);
+    let links_len = link_count
+        .checked_mul(8)
+        .filter(|l| BLOCK_HEADER_SIZE + l <= length)
+        .ok_or_else(|| anyhow!("Invalid MDF block length at offset {offset}"))?;
+    let mut links_buf = vec![0_u8; links_len as usize];
+    reader.read_exact(&mut links_buf)?;
+    let links = links_buf
+        .chunks_exact(8)
+        .map(|c| u64::from_le_bytes([c[0], c[1], c[2], c[3], c[4], c[5], c[6], c[7]]))
+        .collect();
+    Ok(BlockHeader {
+        id,
+        links,
+        data_offset: offset + BLOCK_HEADER_SIZE + links_len,
+        data_len: length - BLOCK_HEADER_SIZE - links_len,
+    })
+}
+
+fn read_block_data_prefix<R: Read + Seek>(
+    reader: &mut R,
+    header: &BlockHeader,
+    len: u64,
+) -> Result<Vec<u8>, anyhow::Error> {
+    if header.data_len < len {
+        bail!("Truncated MDF block");
+    }
+    let mut data = vec![0_u8; len as usize];
+    reader.seek(SeekFrom::Start(header.data_offset))?;
+    reader.read_exact(&mut data)?;
+    Ok(data)
+}
+
+/// A metadata block (everything but the data blocks) read in full
+struct Block {
+    id: [u8; 4],
+    links: Vec<u64>,
+    data: Vec<u8>,
+}
+
+fn read_block<R: Read + Seek>(reader: &mut R, offset: u64) -> Result<Block, anyhow::Error> {
+    let header = read_block_header(reader, offset)?;
+    let data = read_block_data_prefix(reader, &header, header.data_len)?;
+    Ok(Block {
+        id: header.id,
+        links: header.links,
+        data,
+    })
+}
+
+impl Block {
+    fn expect_id(&self, id: &[u8; 4]) -> Result<(), anyhow::Error> {
+        if &self.id != id {
+            bail!(
+                "Expected MDF block '{}', found '{}'",
+                String::from_utf8_lossy(id),
+                String::from_utf8_lossy(&self.id)
+            );
+        }
+        Ok(())
+    }
+
+    fn link(&self, idx: usize) -> u64 {
+        self.links.get(idx).copied().unwrap_or(0)
+    }
+
+    fn bytes_at<const N: usize>(&self, offset: usize) -> Result<[u8; N], anyhow::Error> {
+        self.data
+            .get(offset..offset + N)
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Truncated MDF block '{}'",
+                    String::from_utf8_lossy(&self.id)
+                )
+            })
+    }
+
+    fn u8_at(&self, offset: usize) -> Result<u8, anyhow::Error> {
+        Ok(self.bytes_at::<1>(offset)?[0])
+    }
+
+    fn u16_at(&self, offset: usize) -> Result<u16, anyhow::Error> {
+        Ok(u16::from_le_bytes(self.bytes_at(offset)?))
+    }
+
+    fn u32_at(&self, offset: usize) -> Result<u32, anyhow::Error> {
+        Ok(u32::from_le_bytes(self.bytes_at(offset)?))
+    }
+
+    fn u64_at(&self, offset: usize) -> Result<u64, anyhow::Error> {
+        Ok(u64::from_le_bytes(self.bytes_at(offset)?))
+    }
+
+    fn f64_at(&self, offset: usize) -> Result<f64, anyhow::Error> {
+        Ok(f64::from_le_bytes(self.bytes_at(offset)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::{Cursor, Write};
+
+    /// Builds a file from blocks, links are given as block indices (+1, 0 is nil).
+    /// The first block is the HD block.
+    struct FileBuilder {
+        blocks: Vec<([u8; 4], Vec<usize>, Vec<u8>)>,
+    }
+
+    impl FileBuilder {
+        fn new() -> Self {
+            Self {
+                blocks: vec![(*b"##HD", vec![0; 6], hd_data(0))],
+            }
+        }
+
+        fn set_header(&mut self, dg: usize, start_ns: u64) {
+            self.blocks[0].1[0] = dg;
+            self.blocks[0].2 = hd_data(start_ns);
+        }
+
+        fn add(&mut self, id: &[u8; 4], links: Vec<usize>, data: Vec<u8>) -> usize {
+            self.blocks.push((*id, links, data));
+            self.blocks.len()
+        }
+
+        fn text(&mut self, s: &str) -> usize {
+            let mut data = s.as_bytes().to_vec();
+            data.push(0);
+            self.add(b"##TX", vec![], data)
+        }
+
+        fn offsets(&self) -> Vec<u64> {
+            let mut offsets = Vec::new();
+            let mut offset = HD_BLOCK_OFFSET;
+            for (_, links, data) in self.blocks.iter() {
+                offsets.push(offset);
+                let len = 24 + links.len() as u64 * 8 + data.len() as u64;
+                offset += (len + 7) & !7;
+            }
+            offsets
+        }
+
+        fn build(&self) -> Vec<u8> {
+            let offsets = self.offsets();
+            let mut f = vec![0_u8; ID_BLOCK_SIZE];
+            f[..8].copy_from_slice(b"MDF     ");
+            f[8..16].copy_from_slice(b"4.10    ");
+            f[28..30].copy_from_slice(&410_u16.to_le_bytes());
+            for (id, links, data) in self.blocks.iter() {
+                let len = 24 + links.len() as u64 * 8 + data.len() as u64;
+                f.extend_from_slice(id);
+                f.extend_from_slice(&[0_u8; 4]);
+                f.extend_from_slice(&len.to_le_bytes());
+                f.extend_from_slice(&(links.len() as u64).to_le_bytes());
+                for l in links.iter() {
+                    let link = if *l == 0 { 0 } else { offsets[*l - 1] };
+                    f.extend_from_slice(&link.to_le_bytes());
+                }
+                f.extend_from_slice(data);
+                f.resize((f.len() + 7) & !7, 0);
+            }
+            f
+        }
+    }
+
+    fn cn_data(
+        cn_type: u8,
+        sync: u8,
+        data_type: u8,
+        bit_off: u8,
+        byte_off: u32,
+        bits: u32,
+    ) -> Vec<u8> {
+        let mut d = vec![cn_type, sync, data_type, bit_off];
+        d.extend_from_slice(&byte_off.to_le_bytes());
+        d.extend_from_slice(&bits.to_le_bytes());
+        d.resize(72, 0);
+        d
+    }
+
+    fn cg_data(record_id: u64, flags: u16, data_bytes: u32) -> Vec<u8> {
+        let mut d = Vec::new();
+        d.extend_from_slice(&record_id.to_le_bytes());
+        d.extend_from_slice(&2_u64.to_le_bytes());
+        d.extend_from_slice(&flags.to_le_bytes());
+        d.extend_from_slice(&[0_u8; 6]);
+        d.extend_from_slice(&data_bytes.to_le_bytes());
+        d.extend_from_slice(&0_u32.to_le_bytes());
+        d
+    }
+
+    fn hd_data(start_ns: u64) -> Vec<u8> {
+        let mut d = start_ns.to_le_bytes().to_vec();
+        d.resize(32, 0);
+        d
+    }
+
+    /// Record layout: time f64 @0, BusChannel u8 @8, ID u32 @9 (bit 31 is IDE),
+    /// DLC 4 bits @13, DataLength u8 @14, DataBytes [u8; 8] @15
+    fn data_frame_record(time: f64, channel: u8, id: u32, data: &[u8]) -> Vec<u8> {
+        let mut r = time.to_le_bytes().to_vec();
+        r.push(channel);
+        r.extend_from_slice(&id.to_le_bytes());
+        r.push(data.len() as u8);
+        r.push(data.len() as u8);
+        let mut d = [0_u8; 8];
+        d[..data.len()].copy_from_slice(data);
+        r.extend_from_slice(&d);
+        r
+    }
+
+    /// Adds a sorted data frame group, returns the CG index
+    fn add_data_frame_cg(b: &mut FileBuilder) -> usize {
+        let names = [
+            ("CAN_DataFrame.DataBytes", cn_data(0, 0, 10, 0, 15, 64)),
+            (
+                "CAN_DataFrame.DataLength",
+                cn_data(0, 0, DT_UINT_LE, 0, 14, 8),
+            ),
+            ("CAN_DataFrame.DLC", cn_data(0, 0, DT_UINT_LE, 0, 13, 4)),
+            ("CAN_DataFrame.ID", cn_data(0, 0, DT_UINT_LE, 0, 9, 32)),
+            (
+                "CAN_DataFrame.BusChannel",
+                cn_data(0, 0, DT_UINT_LE, 0, 8, 8),
+            ),
+        ];
+        let mut next = 0;
+        for (name, data) in names.iter() {
+            let tx = b.text(name);
+            next = b.add(b"##CN", vec![next, 0, tx, 0, 0, 0, 0, 0], data.clone());
+        }
+        let frame_tx = b.text("CAN_DataFrame");
+        let frame_cn = b.add(
+            b"##CN",
+            vec![0, next, frame_tx, 0, 0, 0, 0, 0],
+            cn_data(0, 0, 10, 0, 8, 15 * 8),
+        );
+        let time_tx = b.text("Timestamp");
+        let time_cn = b.add(
+            b"##CN",
+            vec![frame_cn, 0, time_tx, 0, 0, 0, 0, 0],
+            cn_data(CN_TYPE_MASTER, CN_SYNC_TIME, DT_FLOAT_LE, 0, 0, 64),
+        );
+        let acq = b.text("CAN_DataFrame");
+        b.add(b"##CG", vec![0, time_cn, acq, 0, 0, 0], cg_data(0, 0, 23))
+    }
+
+    #[test]
+    fn sorted_data_frames() {
+        let mut b = FileBuilder::new();
+        let cg = add_data_frame_cg(&mut b);
+        let mut records = data_frame_record(0.5, 1, 0x18A, &[0xF4, 0x7E]);
+        records.extend(data_frame_record(1.25, 2, 0x1BED56DA | 0x8000_0000, &[1]));
+        let dt = b.add(b"##DT", vec![], records);
+        let dg = b.add(b"##DG", vec![0, cg, dt, 0], vec![0_u8; 8]);
+        b.set_header(dg, 1_717_673_394_000_000_000);
+
+        let file = b.build();
+        let frames: Vec<LogFrame> = Reader::new(Cursor::new(file.clone()), false)
+            .unwrap()
+            .map(|f| f.unwrap())
+            .collect();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].timestamp, Some(timestamp_from_nanos(500_000_000)));
+        assert_eq!(frames[0].interface.as_deref(), Some("1"));
+        let CanAnyFrame::Normal(f) = frames[0].frame else {
+            panic!();
+        };
+        assert_eq!(f.id(), StandardId::new(0x18A).unwrap().into());
+        assert_eq!(f.data(), &[0xF4, 0x7E]);
+        let CanAnyFrame::Normal(f) = frames[1].frame else {
+            panic!();
+        };
+        assert_eq!(f.id(), ExtendedId::new(0x1BED56DA).unwrap().into());
+        assert_eq!(frames[1].interface.as_deref(), Some("2"));
+
+        let frames: Vec<LogFrame> = Reader::new(Cursor::new(file), true)
+            .unwrap()
+            .map(|f| f.unwrap())
+            .collect();
+        assert_eq!(
+            frames[1].timestamp.unwrap(),
+            Timestamp {
+                seconds: 1717673395,
+                nanoseconds: 250_000_000,
+            }
+        );
+    }
+
+    #[test]
+    fn untranspose_data() {
+        // Two 3-byte records, plus a trailing byte
+        let orig = [1_u8, 2, 3, 4, 5, 6, 7];
+        let transposed = [1_u8, 4, 2, 5, 3, 6, 7];
+        assert_eq!(untranspose(&transposed, 3), orig);
+    }
+
+    #[test]
+    fn compressed_data_stream() {
+        let payload: Vec<u8> = (0..100_u8).collect();
+        let mut enc = ZlibEncoder::new(Vec::new(), Compression::default());
+        enc.write_all(&payload).unwrap();
+        let compressed = enc.finish().unwrap();
+
+        let mut b = FileBuilder::new();
+        let mut dz = Vec::new();
+        dz.extend_from_slice(b"DT");
+        dz.push(ZIP_DEFLATE);
+        dz.push(0);
+        dz.extend_from_slice(&0_u32.to_le_bytes());
+        dz.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        dz.extend_from_slice(&(compressed.len() as u64).to_le_bytes());
+        dz.extend_from_slice(&compressed);
+        let dz = b.add(b"##DZ", vec![], dz);
+        let dt = b.add(b"##DT", vec![], vec![0xAA, 0xBB]);
+        let dl = b.add(b"##DL", vec![0, dz, dt], vec![0_u8; 8]);
+        let offset = b.offsets()[dl - 1];
+        let mut cursor = Cursor::new(b.build());
+        let data = read_all(&mut cursor, offset).unwrap();
+        assert_eq!(&data[..100], payload.as_slice());
+        assert_eq!(&data[100..], &[0xAA, 0xBB]);
+    }
+
+    #[test]
+    fn data_block_lengths() {
+        let mut b = FileBuilder::new();
+        let mut dz = Vec::new();
+        dz.extend_from_slice(b"DT");
+        dz.push(ZIP_DEFLATE);
+        dz.push(0);
+        dz.extend_from_slice(&0_u32.to_le_bytes());
+        dz.extend_from_slice(&8_u64.to_le_bytes());
+        // Longer than the block
+        dz.extend_from_slice(&9_u64.to_le_bytes());
+        dz.extend_from_slice(&[0_u8; 8]);
+        let dz = b.add(b"##DZ", vec![], dz);
+        let dt = b.add(b"##DT", vec![], vec![0xAA, 0xBB]);
+        let offsets = b.offsets();
+        let mut file = b.build();
+        let err = read_all(&mut Cursor::new(file.clone()), offsets[dz - 1]).unwrap_err();
+        assert!(err.to_string().contains("compressed data length"));
+
+        // Longer than the file
+        let dt = offsets[dt - 1] as usize;
+        file[dt + 8..dt + 16].copy_from_slice(&u64::MAX.to_le_bytes());
+        let err = read_all(&mut Cursor::new(file), dt as u64).unwrap_err();
+        assert!(err.to_string().contains("past the end of the file"));
+    }
+
+    #[test]
+    fn error_types() {
+        assert_eq!(error_class_and_data(5).0, CAN_ERR_ACK);
+        let (class, data) = error_class_and_data(3);
+        assert_eq!(class, CAN_ERR_PROT);
+        assert_eq!(data[2], CAN_ERR_PROT_STUFF);
+        assert_eq!(error_class_and_data(0).0, 0);
+    }
+
+    #[test]
+    fn invalid_identifier() {
+        assert!(Reader::new(Cursor::new(vec![0_u8; 128]), false).is_err());
+    }
+}
@@ -0,0 +1,809 @@
+//! NMEA 2000 fast-packet reassembly, and decoding with a canboat-style PGN database.
+
+use crate::{j1939::J1939Id, parser::hex};
+use anyhow::anyhow;
+use auxon_sdk::api::AttrVal;
+use bitvec::prelude::*;
+use serde::Deserialize;
+use std::{collections::HashMap, fs, path::Path};
+use tracing::info;
+
+/// 6 bytes in the first frame, and 7 in each of the following 31
+pub(crate) const FAST_PACKET_MAX_SIZE: usize = 223;
+
+/// Same as the J1939 T1 timeout between the packets of a broadcast
+const FAST_PACKET_TIMEOUT_NS: i64 = 750_000_000;
+
+/// A fast-packet message, either reassembled or incomplete
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct FastPacketMessage {
+    pub interface: Option<String>,
+    /// The identifier of the first frame
+    pub id: J1939Id,
+    pub sequence: u8,
+    /// The complete message data, partial if there's an error
+    pub data: Vec<u8>,
+    pub frame_count: u8,
+    /// The time from the first frame to the last
+    pub duration_ns: Option<i64>,
+    pub error: Option<&'static str>,
+}
+
+#[derive(Debug)]
+struct Session {
+    id: J1939Id,
+    sequence: u8,
+    size: usize,
+    data: Vec<u8>,
+    frame_count: u8,
+    start_ns: Option<i64>,
+    last_ns: Option<i64>,
+}
+
+impl Session {
+    fn into_message(
+        self,
+        interface: Option<String>,
+        error: Option<&'static str>,
+    ) -> FastPacketMessage {
+        let mut data = self.data;
+        data.truncate(self.size);
+        FastPacketMessage {
+            interface,
+            id: self.id,
+            sequence: self.sequence,
+            data,
+            frame_count: self.frame_count,
+            duration_ns: self.start_ns.zip(self.last_ns).map(|(s, l)| l - s),
+            error,
+        }
+    }
+}
+
+/// Interface, PGN, and source address
+type SessionKey = (Option<String>, u32, u8);
+
+/// Tracks the fast-packet messages being sent by each device
+#[derive(Debug, Default)]
+pub(crate) struct FastPacketReassembler {
+    sessions: HashMap<SessionKey, Session>,
+}
+
+impl FastPacketReassembler {
+    /// Handle a fast-packet frame, returning any messages it completes or interrupts
+    pub fn push(
+        &mut self,
+        interface: Option<&str>,
+        id: &J1939Id,
+        data: &[u8],
+        timestamp_ns: Option<i64>,
+    ) -> Vec<FastPacketMessage> {
+        let mut msgs = self.expire(interface, timestamp_ns);
+        let Some(&b0) = data.first() else {
+            return msgs;
+        };
+        let sequence = b0 >> 5;
+        let frame = b0 & 0x1F;
+        let key = (interface.map(str::to_owned), id.pgn, id.source_address);
+
+        if frame == 0 {
+            if let Some(session) = self.sessions.remove(&key) {
+                msgs.push(session.into_message(key.0.clone(), Some("interrupted")));
+            }
+            let Some(&size) = data.get(1) else {
+                return msgs;
+            };
+            let session = Session {
+                id: *id,
+                sequence,
+                size: size as usize,
+                data: data[2..].to_vec(),
+                frame_count: 1,
+                start_ns: timestamp_ns,
+                last_ns: timestamp_ns,
+            };
+            if session.size > FAST_PACKET_MAX_SIZE {
+                msgs.push(session.into_message(key.0, Some("invalid_size")));
+            } else if session.data.len() >= session.size {
+                msgs.push(session.into_message(key.0, None));
+            } else {
+                self.sessions.insert(key, session);
+            }
+            return msgs;
+        }
+
+        // Frames are ignored until the first frame of a message is seen
+        let Some(session) = self.sessions.get_mut(&key) else {
+            return msgs;
+        };
+        if session.sequence != sequence || session.frame_count != frame {
+            if let Some(session) = self.sessions.remove(&key) {
+                msgs.push(session.into_message(key.0, Some("missing_frame")));
+            }
+            return msgs;
+        }
+        session.data.extend_from_slice(&data[1..]);
+        session.frame_count += 1;
+        session.last_ns = timestamp_ns;
+        if session.data.len() >= session.size {
+            if let Some(session) = self.sessions.remove(&key) {
+                msgs.push(session.into_message(key.0, None));
+            }
+        }
+        msgs
+    }
+
+    /// Report any open sessions as incomplete, at the end of the input
+    pub fn finish(&mut self) -> Vec<FastPacketMessage> {
+        let mut msgs: Vec<_> = self
+            .sessions
+            .drain()
+            .map(|((iface, _, _), session)| session.into_message(iface, Some("incomplete")))
+            .collect();
+        msgs.sort_by(|a, b| {
+            (&a.interface, a.id.pgn, a.id.source_address).cmp(&(
+                &b.interface,
+                b.id.pgn,
+                b.id.source_address,
+            ))
+        });
+        msgs
+    }
+
+    fn expire(
+        &mut self,
+        interface: Option<&str>,
+        timestamp_ns: Option<i64>,
+    ) -> Vec<FastPacketMessage> {
+        let Some(now) = timestamp_ns else {
+            return Vec::new();
+        };
+        let mut expired: Vec<_> = self
+            .sessions
+            .iter()
+            .filter(|(key, session)| {
+                key.0.as_deref() == interface
+                    && session
+                        .last_ns
+                        .is_some_and(|last| now - last > FAST_PACKET_TIMEOUT_NS)
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        expired.sort();
+        expired
+            .into_iter()
+            .filter_map(|key| {
+                let session = self.sessions.remove(&key)?;
+                Some(session.into_message(key.0, Some("timeout")))
+            })
+            .collect()
+    }
+}
+
+/// The manufacturer proprietary fast-packet ranges, for PGNs missing from the database
+pub(crate) fn is_proprietary_fast_packet(pgn: u32) -> bool {
+    (0x1EF00..=0x1EFFF).contains(&pgn) || (0x1FF00..=0x1FFFF).contains(&pgn)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DatabaseFile {
+    #[serde(rename = "PGNs")]
+    pgns: Vec<PgnDefinition>,
+    #[serde(default)]
+    lookup_enumerations: Vec<LookupEnumeration>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct LookupEnumeration {
+    name: String,
+    #[serde(default)]
+    enum_values: Vec<EnumValue>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EnumValue {
+    name: String,
+    value: i64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct PgnDefinition {
+    #[serde(rename = "PGN")]
+    pub pgn: u32,
+    pub id: String,
+    #[serde(default)]
+    pub description: String,
+    /// `Single`, `Fast` or `ISO`
+    #[serde(rename = "Type", default)]
+    pub kind: Option<String>,
+    #[serde(default)]
+    fields: Vec<Field>,
+    /// The number of fields in the repeating set
+    repeating_field_set1_size: Option<usize>,
+    /// The `Order` of the first repeating field
+    repeating_field_set1_start_field: Option<usize>,
+    /// The `Order` of the field holding the number of repetitions
+    repeating_field_set1_count_field: Option<usize>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Field {
+    #[serde(default)]
+    order: usize,
+    id: String,
+    bit_length: Option<usize>,
+    bit_offset: Option<usize>,
+    #[serde(default)]
+    signed: bool,
+    resolution: Option<f64>,
+    offset: Option<f64>,
+    unit: Option<String>,
+    #[serde(default)]
+    field_type: String,
+    lookup_enumeration: Option<String>,
+    /// The value identifying a definition, for PGNs with several (e.g. proprietary)
+    #[serde(rename = "Match")]
+    match_value: Option<i64>,
+}
+
+/// Parameter group definitions and lookup tables, from a canboat-style JSON file
+#[derive(Debug, Default)]
+pub(crate) struct PgnDatabase {
+    pgns: HashMap<u32, Vec<PgnDefinition>>,
+    lookups: HashMap<String, HashMap<i64, String>>,
+}
+
+impl PgnDatabase {
+    pub fn from_file<P: AsRef<Path>>(p: P) -> Result<Self, anyhow::Error> {
+        let path_display = p.as_ref().display();
+        info!(pgns = %path_display, "Reading NMEA 2000 PGN database");
+        let content = fs::read_to_string(p.as_ref()).map_err(|e| {
+            anyhow!(
+                "Failed to read NMEA 2000 PGN database '{}'. {}",
+                path_display,
+                e
+            )
+        })?;
+        Self::parse(&content).map_err(|e| {
+            anyhow!(
+                "Failed to parse NMEA 2000 PGN database '{}'. {}",
+                path_display,
+                e
+            )
+        })
+    }
+
+    pub fn parse(content: &str) -> Result<Self, serde_json::Error> {
+        let file: DatabaseFile = serde_json::from_str(content)?;
+        let mut db = PgnDatabase::default();
+        for lookup in file.lookup_enumerations.into_iter() {
+            let values = lookup
+                .enum_values
+                .into_iter()
+                .map(|v| (v.value, v.name))
+                .collect();
+            db.lookups.insert(lookup.name, values);
+        }
+        for mut def in file.pgns.into_iter() {
+            def.fields.sort_by_key(|f| f.order);
+            db.pgns.entry(def.pgn).or_default().push(def);
+        }
+        Ok(db)
+    }
+
+    pub fn is_fast_packet(&self, pgn: u32) -> bool {
+        match self.pgns.get(&pgn) {
+            Some(defs) => defs.iter().any(|d| d.kind.as_deref() == Some("Fast")),
+            None => is_proprietary_fast_packet(pgn),
+        }
+    }
+
+    /// The definition matching the data, preferring those with matching `Match` fields
+    pub fn definition(&self, pgn: u32, data: &[u8]) -> Option<&PgnDefinition> {
+        let defs = self.pgns.get(&pgn)?;
+        defs.iter()
+            .find(|def| {
+                let mut match_fields = def.fields.iter().filter(|f| f.match_value.is_some());
+                match_fields.clone().next().is_some()
+                    && match_fields.all(|f| {
+                        f.bit_offset
+                            .zip(f.bit_length)
+                            .and_then(|(offset, bits)| read_bits(data, offset, bits))
+                            .is_some_and(|raw| Some(raw as i64) == f.match_value)
+                    })
+            })
+            .or_else(|| {
+                defs.iter()
+                    .find(|def| def.fields.iter().all(|f| f.match_value.is_none()))
+            })
+    }
+
+    /// Decode the fields of a message, keyed by field id. Fields that aren't available are
+    /// left out, and the fields of a repeating set are suffixed with the repetition number.
+    pub fn decode(&self, def: &PgnDefinition, data: &[u8]) -> Vec<(String, AttrVal)> {
+        let mut attrs = Vec::new();
+        let repeating = def
+            .repeating_field_set1_start_field
+            .zip(def.repeating_field_set1_size)
+            .filter(|(start, size)| *start > 0 && *size > 0);
+        let fixed_count = match repeating {
+            Some((start, _)) => def.fields.iter().take_while(|f| f.order < start).count(),
+            None => def.fields.len(),
+        };
+
+        let mut offset = 0;
+        let mut count = None;
+        for field in def.fields[..fixed_count].iter() {
+            let Some(raw) = self.decode_field(field, data, &mut offset, None, &mut attrs) else {
+                return attrs;
+            };
+            if Some(field.order) == def.repeating_field_set1_count_field {
+                count = raw;
+            }
+        }
+
+        if let Some((_, size)) = repeating {
+            let set = &def.fields[fixed_count..(fixed_count + size).min(def.fields.len())];
+            let count = count.unwrap_or(u64::MAX);
+            let mut n = 1;
+            while !set.is_empty() && offset < data.len() * 8 && n <= count {
+                let set_offset = offset;
+                for field in set.iter() {
+                    if self
+                        .decode_field(field, data, &mut offset, Some(n), &mut attrs)
+                        .is_none()
+                    {
+                        return attrs;
+                    }
+                }
+                // Sets of zero-width fields would repeat forever
+                if offset == set_offset {
+                    break;
+                }
+                n += 1;
+            }
+        }
+        attrs
+    }
+
+    /// Decode a field at `offset`, advancing it. Returns `None` when decoding can't continue,
+    /// otherwise the raw value of integer fields.
+    fn decode_field(
+        &self,
+        field: &Field,
+        data: &[u8],
+        offset: &mut usize,
+        repetition: Option<u64>,
+        attrs: &mut Vec<(String, AttrVal)>,
+    ) -> Option<Option<u64>> {
+        let name = match repetition {
+            Some(n) => format!("{}.{n}", field.id),
+            None => field.id.clone(),
+        };
+        let field_type = field.field_type.as_str();
+
+        // Variable length strings carry their length in the first byte
+        if matches!(field_type, "STRING_LZ" | "STRING_LAU") {
+            if *offset & 0x7 != 0 {
+                return None;
+            }
+            let start = *offset / 8;
+            let len = *data.get(start)? as usize;
+            let (text_start, end) = if field_type == "STRING_LZ" {
+                (start + 1, start + 1 + len)
+            } else {
+                // The length includes itself and the encoding byte
+                (start + 2, start + len.max(2))
+            };
+            let bytes = data.get(text_start..end)?;
+            let is_utf16 = field_type == "STRING_LAU" && data.get(start + 1) == Some(&0);
+            let text = if is_utf16 {
+                let units: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect();
+                String::from_utf16_lossy(&units)
+            } else {
+                bytes.iter().map(|b| *b as char).collect()
+            };
+            let text = trim_string(&text);
+            if !text.is_empty() {
+                attrs.push((name, text.into()));
+            }
+            *offset = end * 8;
+            return Some(None);
+        }
+
+        let mut bits = field.bit_length?;
+        // Proprietary data fields take up the rest of the message
+        if field_type == "BINARY" && *offset & 0x7 == 0 {
+            bits = bits.min(data.len() * 8 - (*offset).min(data.len() * 8));
+        }
+        if *offset + bits > data.len() * 8 {
+            return None;
+        }
+        let start = *offset;
+        *offset += bits;
+        if matches!(field_type, "RESERVED" | "SPARE") {
+            return Some(None);
+        }
+
+        let byte_aligned = (start | bits) & 0x7 == 0;
+        if field_type == "STRING_FIX" && byte_aligned {
+            let bytes = &data[start / 8..(start + bits) / 8];
+            let text: String = bytes.iter().map(|b| *b as char).collect();
+            let text = trim_string(&text);
+            if !text.is_empty() {
+                attrs.push((name, text.into()));
+            }
+            return Some(None);
+        }
+        if bits > 64 {
+            if byte_aligned {
+                attrs.push((name, hex(&data[start / 8..(start + bits) / 8]).into()));
+            }
+            return Some(None);
+        }
+
+        let raw = read_bits(data, start, bits)?;
+        if field_type == "FLOAT" && bits == 32 {
+            let v = f32::from_bits(raw as u32);
+            if !v.is_nan() {
+                attrs.push((name, (v as f64).into()));
+            }
+            return Some(Some(raw));
+        }
+        if let Some(lookup) = field.lookup_enumeration.as_ref() {
+            let label = self.lookups.get(lookup).and_then(|l| l.get(&(raw as i64)));
+            match label {
+                Some(label) => attrs.push((name, label.as_str().into())),
+                None if !is_unavailable(raw, bits, false) => attrs.push((name, raw.into())),
+                None => (),
+            }
+            return Some(Some(raw));
+        }
+        if field_type == "BINARY" && byte_aligned && bits > 8 {
+            attrs.push((name, hex(&data[start / 8..(start + bits) / 8]).into()));
+            return Some(Some(raw));
+        }
+        if is_unavailable(raw, bits, field.signed) {
+            return Some(Some(raw));
+        }
+
+        if let Some(unit) = field.unit.as_ref() {
+            attrs.push((format!("{name}.unit"), unit.as_str().into()));
+        }
+        let resolution = field.resolution.unwrap_or(1.0);
+        let value_offset = field.offset.unwrap_or(0.0);
+        let val: AttrVal = if field.signed {
+            let shift = 64 - bits;
+            let v = ((raw << shift) as i64) >> shift;
+            if resolution == 1.0 && value_offset == 0.0 {
+                v.into()
+            } else {
+                (v as f64 * resolution + value_offset).into()
+            }
+        } else if resolution == 1.0 && value_offset == 0.0 {
+            raw.into()
+        } else {
+            (raw as f64 * resolution + value_offset).into()
+        };
+        attrs.push((name, val));
+        Some(Some(raw))
+    }
+}
+
+fn read_bits(data: &[u8], offset: usize, bits: usize) -> Option<u64> {
+    if bits == 0 || bits > 64 {
+        return None;
+    }
+    let view = data.view_bits::<Lsb0>();
+    Some(view.get(offset..offset + bits)?.load_le::<u64>())
+}
+
+/// The maximum value of a field signals that it's not available
+fn is_unavailable(raw: u64, bits: usize, signed: bool) -> bool {
+    if bits < 2 {
+        return false;
+    }
+    let max = if signed {
+        (1_u64 << (bits - 1)) - 1
+    } else {
+        u64::MAX >> (64 - bits)
+    };
+    raw == max
+}
+
+/// Fixed length strings are padded with `0xFF`, `NUL`, `@` or spaces
+fn trim_string(s: &str) -> &str {
+    s.trim_end_matches(['\u{ff}', '\0', '@', ' '])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PGNS: &str = r#"{
+        "PGNs": [
+            {
+                "PGN": 127250, "Id": "vesselHeading", "Description": "Vessel Heading",
+                "Type": "Single", "Length": 8,
+                "Fields": [
+                    {"Order": 1, "Id": "sid", "BitLength": 8, "BitOffset": 0, "Signed": false,
+                     "FieldType": "NUMBER"},
+                    {"Order": 2, "Id": "heading", "BitLength": 16, "BitOffset": 8,
+                     "Signed": false, "Resolution": 0.0001, "Unit": "rad", "FieldType": "NUMBER"},
+                    {"Order": 3, "Id": "deviation", "BitLength": 16, "BitOffset": 24,
+                     "Signed": true, "Resolution": 0.0001, "Unit": "rad", "FieldType": "NUMBER"},
+                    {"Order": 4, "Id": "variation", "BitLength": 16, "BitOffset": 40,
+                     "Signed": true, "Resolution": 0.0001, "Unit": "rad", "FieldType": "NUMBER"},
+                    {"Order": 5, "Id": "reference", "BitLength": 2, "BitOffset": 56,
+                     "FieldType": "LOOKUP", "LookupEnumeration": "DIRECTION_REFERENCE"},
+                    {"Order": 6, "Id": "reserved", "BitLength": 6, "BitOffset": 58,
+                     "FieldType": "RESERVED"}
+                ]
+            },
+            {
+                "PGN": 126996, "Id": "productInformation", "Description": "Product Information",
+                "Type": "Fast", "Length": 134,
+                "Fields": [
+                    {"Order": 1, "Id": "nmea2000Version", "BitLength": 16, "BitOffset": 0,
+                     "Resolution": 0.001, "FieldType": "NUMBER"},
+                    {"Order": 2, "Id": "productCode", "BitLength": 16, "BitOffset": 16,
+                     "FieldType": "NUMBER"},
+                    {"Order": 3, "Id": "modelId", "BitLength": 256, "BitOffset": 32,
+                     "FieldType": "STRING_FIX"}
+                ]
+            },
+            {
+                "PGN": 130820, "Id": "acmeTemperature", "Description": "Acme: Temperature",
+                "Type": "Fast",
+                "Fields": [
+                    {"Order": 1, "Id": "manufacturerCode", "BitLength": 11, "BitOffset": 0,
+                     "Match": 1857, "FieldType": "LOOKUP", "LookupEnumeration": "MANUFACTURER_CODE"},
+                    {"Order": 2, "Id": "reserved", "BitLength": 2, "BitOffset": 11,
+                     "FieldType": "RESERVED"},
+                    {"Order": 3, "Id": "industryCode", "BitLength": 3, "BitOffset": 13,
+                     "Match": 4, "FieldType": "LOOKUP", "LookupEnumeration": "INDUSTRY_CODE"},
+                    {"Order": 4, "Id": "name", "FieldType": "STRING_LAU"},
+                    {"Order": 5, "Id": "count", "BitLength": 8, "FieldType": "NUMBER"},
+                    {"Order": 6, "Id": "temperature", "BitLength": 16, "Resolution": 0.01,
+                     "Unit": "K", "FieldType": "NUMBER"}
+                ],
+                "RepeatingFieldSet1Size": 1,
+                "RepeatingFieldSet1StartField": 6,
+                "RepeatingFieldSet1CountField": 5
+            },
+            {
+                "PGN": 130820, "Id": "manufacturerProprietary",
+                "Description": "Manufacturer Proprietary fast-packet non-addressed",
+                "Type": "Fast",
+                "Fields": [
+                    {"Order": 1, "Id": "data", "BitLength": 1784, "BitOffset": 0,
+                     "FieldType": "BINARY"}
+                ]
+            }
+        ],
+        "LookupEnumerations": [
+            {"Name": "DIRECTION_REFERENCE", "MaxValue": 3,
+             "EnumValues": [{"Name": "True", "Value": 0}, {"Name": "Magnetic", "Value": 1}]},
+            {"Name": "MANUFACTURER_CODE", "MaxValue": 2047,
+             "EnumValues": [{"Name": "Acme", "Value": 1857}]},
+            {"Name": "INDUSTRY_CODE", "MaxValue": 7,
+             "EnumValues": [{"Name": "Marine Industry", "Value": 4}]}
+        ]
+    }"#;
+
+    fn attr<'a>(attrs: &'a [(String, AttrVal)], key: &str) -> Option<&'a AttrVal> {
+        attrs.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    fn id(pgn: u32, source_address: u8) -> J1939Id {
+        J1939Id {
+            priority: 3,
+            pgn,
+            source_address,
+            destination_address: 0xFF,
+        }
+    }
+
+    #[test]
+    fn fast_packets() {
+        let mut fp = FastPacketReassembler::default();
+        let id = id(126996, 0x23);
+        let payload: Vec<u8> = (0..20).collect();
+
+        // Sequence 2, 20 bytes in 3 frames
+        let mut frame = vec![0x40, 20];
+        frame.extend_from_slice(&payload[..6]);
+        assert!(fp.push(None, &id, &frame, Some(0)).is_empty());
+        let mut frame = vec![0x41];
+        frame.extend_from_slice(&payload[6..13]);
+        assert!(fp.push(None, &id, &frame, Some(1_000_000)).is_empty());
+        let mut frame = vec![0x42];
+        frame.extend_from_slice(&payload[13..]);
+        frame.push(0xFF);
+        let msgs = fp.push(None, &id, &frame, Some(2_000_000));
+        assert_eq!(
+            msgs,
+            vec![FastPacketMessage {
+                interface: None,
+                id,
+                sequence: 2,
+                data: payload.clone(),
+                frame_count: 3,
+                duration_ns: Some(2_000_000),
+                error: None,
+            }]
+        );
+
+        // A message that fits in the first frame
+        let msgs = fp.push(
+            Some("can1"),
+            &id,
+            &[0x60, 3, 1, 2, 3, 0xFF, 0xFF, 0xFF],
+            None,
+        );
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].data, vec![1, 2, 3]);
+        assert_eq!(msgs[0].interface.as_deref(), Some("can1"));
+
+        // Frames without a first frame are ignored
+        assert!(fp
+            .push(None, &id, &[0x21, 0, 0, 0, 0, 0, 0, 0], None)
+            .is_empty());
+    }
+
+    #[test]
+    fn fast_packet_errors() {
+        let mut fp = FastPacketReassembler::default();
+        let id = id(126996, 0x23);
+        let first = [0x00, 20, 0, 1, 2, 3, 4, 5];
+
+        fp.push(None, &id, &first, Some(0));
+        let msgs = fp.push(None, &id, &[0x02, 6, 7, 8, 9, 10, 11, 12], Some(1));
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].error, Some("missing_frame"));
+        assert_eq!(msgs[0].data, vec![0, 1, 2, 3, 4, 5]);
+
+        fp.push(None, &id, &first, Some(0));
+        let msgs = fp.push(None, &id, &[0x20, 20, 0, 1, 2, 3, 4, 5], Some(1));
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].error, Some("interrupted"));
+        assert_eq!(msgs[0].sequence, 0);
+
+        // A different device and PGN in the meantime
+        fp.push(None, &self::id(126996, 0x24), &first, Some(2));
+        let msgs = fp.push(None, &self::id(126464, 0x23), &[0x00], Some(1_000_000_000));
+        assert_eq!(msgs.len(), 2);
+        assert!(msgs.iter().all(|m| m.error == Some("timeout")));
+
+        let msgs = fp.push(None, &id, &[0x00, 224, 0, 1, 2, 3, 4, 5], Some(0));
+        assert_eq!(msgs[0].error, Some("invalid_size"));
+
+        fp.push(None, &id, &first, Some(0));
+        let msgs = fp.finish();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].error, Some("incomplete"));
+        assert!(fp.finish().is_empty());
+    }
+
+    #[test]
+    fn decode_fields() {
+        let db = PgnDatabase::parse(PGNS).unwrap();
+        assert!(!db.is_fast_packet(127250));
+        assert!(db.is_fast_packet(126996));
+        assert!(db.is_fast_packet(0x1FF01));
+        assert!(!db.is_fast_packet(0x1F000));
+
+        // Heading 1.5 rad, deviation -0.01 rad, variation not available, magnetic
+        let data = [0x07, 0x98, 0x3A, 0x9C, 0xFF, 0xFF, 0x7F, 0xFD];
+        let def = db.definition(127250, &data).unwrap();
+        assert_eq!(def.id, "vesselHeading");
+        let attrs = db.decode(def, &data);
+        assert_eq!(attr(&attrs, "sid"), Some(&7_u64.into()));
+        assert_eq!(attr(&attrs, "heading"), Some(&(15000.0 * 0.0001).into()));
+        assert_eq!(attr(&attrs, "heading.unit"), Some(&"rad".into()));
+        assert_eq!(attr(&attrs, "deviation"), Some(&(-100.0 * 0.0001).into()));
+        assert_eq!(attr(&attrs, "variation"), None);
+        assert_eq!(attr(&attrs, "reference"), Some(&"Magnetic".into()));
+        assert_eq!(attr(&attrs, "reserved"), None);
+
+        let mut data = vec![0x34, 0x08, 0x39, 0x30];
+        data.extend_from_slice(b"Compass 9000");
+        data.resize(36, 0xFF);
+        let def = db.definition(126996, &data).unwrap();
+        let attrs = db.decode(def, &data);
+        assert_eq!(
+            attr(&attrs, "nmea2000Version"),
+            Some(&(2100.0 * 0.001).into())
+        );
+        assert_eq!(attr(&attrs, "productCode"), Some(&12345_u64.into()));
+        assert_eq!(attr(&attrs, "modelId"), Some(&"Compass 9000".into()));
+
+        // Truncated messages decode the fields that fit
+        let attrs = db.decode(def, &data[..4]);
+        assert_eq!(attrs.len(), 2);
+    }
+
+    #[test]
+    fn proprietary_and_repeating_fields() {
+        let db = PgnDatabase::parse(PGNS).unwrap();
+
+        // Acme (1857), marine industry (4), "T1", 2 temperatures
+        let mut data = vec![0x41, 0x87, 0x04, 0x01, b'T', b'1', 0x02];
+        data.extend_from_slice(&29315_u16.to_le_bytes());
+        data.extend_from_slice(&0xFFFF_u16.to_le_bytes());
+        let def = db.definition(130820, &data).unwrap();
+        assert_eq!(def.id, "acmeTemperature");
+        let attrs = db.decode(def, &data);
+        assert_eq!(attr(&attrs, "manufacturerCode"), Some(&"Acme".into()));
+        assert_eq!(
+            attr(&attrs, "industryCode"),
+            Some(&"Marine Industry".into())
+        );
+        assert_eq!(attr(&attrs, "name"), Some(&"T1".into()));
+        assert_eq!(attr(&attrs, "count"), Some(&2_u64.into()));
+        assert_eq!(
+            attr(&attrs, "temperature.1"),
+            Some(&(29315.0 * 0.01).into())
+        );
+        assert_eq!(attr(&attrs, "temperature.1.unit"), Some(&"K".into()));
+        assert_eq!(attr(&attrs, "temperature.2"), None);
+
+        // Other manufacturers use the generic definition
+        let data = [0x42, 0x87, 0x01, 0x02];
+        let def = db.definition(130820, &data).unwrap();
+        assert_eq!(def.id, "manufacturerProprietary");
+        let attrs = db.decode(def, &data);
+        assert_eq!(attr(&attrs, "data"), Some(&"42870102".into()));
+
+        assert!(db.definition(60928, &data).is_none());
+    }
+
+    #[test]
+    fn degenerate_repeating_fields() {
+        // A repeating set starting past the last field, and one of zero-width fields, with no
+        // count field to bound them
+        let db = PgnDatabase::parse(
+            r#"{
+            "PGNs": [
+                {
+                    "PGN": 130821, "Id": "missingSet", "Description": "Missing set",
+                    "Type": "Single",
+                    "Fields": [
+                        {"Order": 1, "Id": "value", "BitLength": 8, "FieldType": "NUMBER"}
+                    ],
+                    "RepeatingFieldSet1Size": 2,
+                    "RepeatingFieldSet1StartField": 5
+                },
+                {
+                    "PGN": 130822, "Id": "emptySet", "Description": "Empty set",
+                    "Type": "Single",
+                    "Fields": [
+                        {"Order": 1, "Id": "value", "BitLength": 8, "FieldType": "NUMBER"},
+                        {"Order": 2, "Id": "reserved", "BitLength": 0, "FieldType": "RESERVED"}
+                    ],
+                    "RepeatingFieldSet1Size": 1,
+                    "RepeatingFieldSet1StartField": 2
+                }
+            ],
+            "LookupEnumerations": []
+        }"#,
+        )
+        .unwrap();
+
+        let data = [0x01, 0x02, 0x03];
+        for pgn in [130821, 130822] {
+            let def = db.definition(pgn, &data).unwrap();
+            let attrs = db.decode(def, &data);
+            assert_eq!(attrs.len(), 1);
+            assert_eq!(attr(&attrs, "value"), Some(&1_u64.into()));
+        }
+    }
+}
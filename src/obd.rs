@@ -0,0 +1,419 @@
+//! OBD-II (SAE J1979) mode and PID decoding.
+
+use crate::{isotp::IsoTpConfig, parser::hex, uds::nrc_name};
+use auxon_sdk::api::AttrVal;
+
+/// Functional (broadcast) request IDs, 11-bit and 29-bit
+pub(crate) const FUNCTIONAL_IDS: [u32; 2] = [0x7DF, 0x18DB33F1];
+
+const SID_NEGATIVE_RESPONSE: u8 = 0x7F;
+const POSITIVE_RESPONSE_OFFSET: u8 = 0x40;
+const TESTER_ADDRESS: u32 = 0xF1;
+
+/// The physical request/response ID pairs, each responding ECU is its own connection
+pub(crate) fn connections() -> Vec<IsoTpConfig> {
+    let standard = (0..8).map(|ecu| (0x7E0 + ecu, 0x7E8 + ecu));
+    let extended = (0..=0xFF).filter(|ecu| *ecu != TESTER_ADDRESS).map(|ecu| {
+        (
+            0x18DA0000 | ecu << 8 | TESTER_ADDRESS,
+            0x18DA0000 | TESTER_ADDRESS << 8 | ecu,
+        )
+    });
+    standard
+        .chain(extended)
+        .map(|(tx_id, rx_id)| IsoTpConfig {
+            name: Some(format!("ecu_{rx_id:#x}")),
+            tx_id,
+            rx_id,
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Is this the first byte of an OBD-II request or response, rather than a UDS one
+pub(crate) fn is_obd(pdu: &[u8]) -> bool {
+    let mode = match pdu {
+        [SID_NEGATIVE_RESPONSE, mode, ..] => *mode,
+        [sid, ..] => *sid & !POSITIVE_RESPONSE_OFFSET,
+        [] => return false,
+    };
+    (0x01..=0x0A).contains(&mode)
+}
+
+/// A decoded OBD-II request or response
+#[derive(Debug, PartialEq)]
+pub(crate) struct ObdInfo {
+    /// A name for the event, based on the mode and first PID
+    pub name: String,
+    /// Attribute keys are relative to `event.obd.`
+    pub attrs: Vec<(String, AttrVal)>,
+}
+
+/// Decode a complete OBD-II PDU
+pub(crate) fn decode(pdu: &[u8]) -> Option<ObdInfo> {
+    let mut attrs: Vec<(String, AttrVal)> = Vec::new();
+
+    if let [SID_NEGATIVE_RESPONSE, mode, nrc, ..] = pdu {
+        attrs.push(("mode".to_owned(), (*mode as u64).into()));
+        attrs.push(("type".to_owned(), "negative_response".into()));
+        attrs.push(("nrc".to_owned(), (*nrc as u64).into()));
+        if let Some(name) = nrc_name(*nrc) {
+            attrs.push(("nrc_name".to_owned(), name.into()));
+        }
+        return Some(ObdInfo {
+            name: format!("obd.mode{mode:02x}.negative_response"),
+            attrs,
+        });
+    }
+
+    let (&sid, params) = pdu.split_first()?;
+    let is_response = sid & POSITIVE_RESPONSE_OFFSET != 0;
+    let mode = sid & !POSITIVE_RESPONSE_OFFSET;
+    attrs.push(("mode".to_owned(), (mode as u64).into()));
+    attrs.push((
+        "type".to_owned(),
+        if is_response { "response" } else { "request" }.into(),
+    ));
+
+    let mut name = match mode {
+        // Current and freeze frame data
+        0x01 | 0x02 => {
+            let pid = *params.first()?;
+            if is_response {
+                decode_pid_values(mode, params, &mut attrs);
+            } else {
+                let pids: Vec<u8> = if mode == 0x01 {
+                    params.to_vec()
+                } else {
+                    // PID and frame number pairs
+                    params.iter().step_by(2).copied().collect()
+                };
+                push_pids(&pids, &mut attrs);
+            }
+            format!("obd.mode{mode:02x}.{}", pid_name(pid))
+        }
+        // Stored, pending and permanent DTCs
+        0x03 | 0x07 | 0x0A => {
+            if is_response {
+                decode_dtcs(params, &mut attrs);
+            }
+            format!("obd.mode{mode:02x}.{}", dtc_mode_name(mode))
+        }
+        0x04 => "obd.mode04.clear_dtcs".to_owned(),
+        // Vehicle information
+        0x09 => {
+            let pid = *params.first()?;
+            if is_response {
+                decode_vehicle_info(pid, &params[1..], &mut attrs);
+            } else {
+                push_pids(&[pid], &mut attrs);
+            }
+            format!("obd.mode09.{}", vehicle_info_name(pid))
+        }
+        0x05 | 0x06 | 0x08 => {
+            if let Some(tid) = params.first() {
+                attrs.push(("tid".to_owned(), (*tid as u64).into()));
+            }
+            if is_response && params.len() > 1 {
+                attrs.push(("data".to_owned(), hex(&params[1..]).into()));
+            }
+            format!("obd.mode{mode:02x}")
+        }
+        _ => return None,
+    };
+
+    if !is_response {
+        name.push_str(".request");
+    }
+    Some(ObdInfo { name, attrs })
+}
+
+fn push_pids(pids: &[u8], attrs: &mut Vec<(String, AttrVal)>) {
+    if let Some(pid) = pids.first() {
+        attrs.push(("pid".to_owned(), (*pid as u64).into()));
+    }
+    if pids.len() > 1 {
+        attrs.push(("pid_count".to_owned(), (pids.len() as u64).into()));
+    }
+}
+
+/// Responses may contain several PIDs, decoded until one with an unknown length
+fn decode_pid_values(mode: u8, mut params: &[u8], attrs: &mut Vec<(String, AttrVal)>) {
+    let mut pids = Vec::new();
+    while let Some((&pid, rest)) = params.split_first() {
+        pids.push(pid);
+        let rest = if mode == 0x02 {
+            // Skip the frame number
+            match rest.split_first() {
+                Some((&frame, rest)) => {
+                    attrs.push(("frame".to_owned(), (frame as u64).into()));
+                    rest
+                }
+                None => break,
+            }
+        } else {
+            rest
+        };
+
+        let Some(len) = pid_len(pid) else {
+            if !rest.is_empty() {
+                attrs.push((format!("{}.data", pid_name(pid)), hex(rest).into()));
+            }
+            break;
+        };
+        if rest.len() < len {
+            break;
+        }
+        let (data, rest) = rest.split_at(len);
+        decode_pid(pid, data, attrs);
+        params = rest;
+    }
+    push_pids(&pids, attrs);
+}
+
+fn decode_pid(pid: u8, d: &[u8], attrs: &mut Vec<(String, AttrVal)>) {
+    let name = pid_name(pid);
+    let a = d[0] as f64;
+    let ab = || (d[0] as u64) << 8 | d[1] as u64;
+    let (value, unit): (AttrVal, Option<&str>) = match pid {
+        // Supported PIDs bitmasks
+        0x00 | 0x20 | 0x40 | 0x60 | 0x80 | 0xA0 | 0xC0 => {
+            (u32::from_be_bytes([d[0], d[1], d[2], d[3]]).into(), None)
+        }
+        0x01 => {
+            attrs.push(("mil".to_owned(), (d[0] & 0x80 != 0).into()));
+            attrs.push(("dtc_count".to_owned(), ((d[0] & 0x7F) as u64).into()));
+            (u32::from_be_bytes([d[0], d[1], d[2], d[3]]).into(), None)
+        }
+        0x04 | 0x11 | 0x2F | 0x45 | 0x47 | 0x49 | 0x4A | 0x4C => {
+            (((a * 100.0) / 255.0).into(), Some("%"))
+        }
+        0x05 | 0x0F | 0x46 | 0x5C => ((a - 40.0).into(), Some("degC")),
+        0x06..=0x09 => ((a * 100.0 / 128.0 - 100.0).into(), Some("%")),
+        0x0A => ((a * 3.0).into(), Some("kPa")),
+        0x0B | 0x33 => ((d[0] as u64).into(), Some("kPa")),
+        0x0C => ((ab() as f64 / 4.0).into(), Some("rpm")),
+        0x0D => ((d[0] as u64).into(), Some("km/h")),
+        0x0E => ((a / 2.0 - 64.0).into(), Some("deg")),
+        0x10 => ((ab() as f64 / 100.0).into(), Some("g/s")),
+        0x1F => (ab().into(), Some("s")),
+        0x21 | 0x31 => (ab().into(), Some("km")),
+        0x42 => ((ab() as f64 / 1000.0).into(), Some("V")),
+        0x43 => ((ab() as f64 * 100.0 / 255.0).into(), Some("%")),
+        0x5E => ((ab() as f64 / 20.0).into(), Some("L/h")),
+        0xA6 => (
+            (u32::from_be_bytes([d[0], d[1], d[2], d[3]]) as f64 / 10.0).into(),
+            Some("km"),
+        ),
+        // OBD standard and fuel type enumerations
+        _ => ((d[0] as u64).into(), None),
+    };
+    if let Some(unit) = unit {
+        attrs.push((format!("{name}.unit"), unit.into()));
+    }
+    attrs.push((name.into_owned(), value));
+}
+
+fn pid_len(pid: u8) -> Option<usize> {
+    Some(match pid {
+        0x00 | 0x01 | 0x20 | 0x40 | 0x60 | 0x80 | 0xA0 | 0xC0 | 0xA6 => 4,
+        0x0C | 0x10 | 0x1F | 0x21 | 0x31 | 0x42 | 0x43 | 0x5E => 2,
+        0x04..=0x0B
+        | 0x0D..=0x0F
+        | 0x11
+        | 0x1C
+        | 0x2F
+        | 0x33
+        | 0x45..=0x47
+        | 0x49
+        | 0x4A
+        | 0x4C
+        | 0x51
+        | 0x5C => 1,
+        _ => return None,
+    })
+}
+
+fn pid_name(pid: u8) -> std::borrow::Cow<'static, str> {
+    let name = match pid {
+        0x00 => "pids_supported_01_20",
+        0x01 => "monitor_status",
+        0x04 => "calculated_engine_load",
+        0x05 => "engine_coolant_temperature",
+        0x06 => "short_term_fuel_trim_bank1",
+        0x07 => "long_term_fuel_trim_bank1",
+        0x08 => "short_term_fuel_trim_bank2",
+        0x09 => "long_term_fuel_trim_bank2",
+        0x0A => "fuel_pressure",
+        0x0B => "intake_manifold_absolute_pressure",
+        0x0C => "engine_rpm",
+        0x0D => "vehicle_speed",
+        0x0E => "timing_advance",
+        0x0F => "intake_air_temperature",
+        0x10 => "maf_air_flow_rate",
+        0x11 => "throttle_position",
+        0x1C => "obd_standards",
+        0x1F => "run_time_since_engine_start",
+        0x20 => "pids_supported_21_40",
+        0x21 => "distance_traveled_with_mil_on",
+        0x2F => "fuel_tank_level",
+        0x31 => "distance_traveled_since_codes_cleared",
+        0x33 => "absolute_barometric_pressure",
+        0x40 => "pids_supported_41_60",
+        0x42 => "control_module_voltage",
+        0x43 => "absolute_load_value",
+        0x45 => "relative_throttle_position",
+        0x46 => "ambient_air_temperature",
+        0x47 => "absolute_throttle_position_b",
+        0x49 => "accelerator_pedal_position_d",
+        0x4A => "accelerator_pedal_position_e",
+        0x4C => "commanded_throttle_actuator",
+        0x51 => "fuel_type",
+        0x5C => "engine_oil_temperature",
+        0x5E => "engine_fuel_rate",
+        0x60 => "pids_supported_61_80",
+        0x80 => "pids_supported_81_a0",
+        0xA0 => "pids_supported_a1_c0",
+        0xA6 => "odometer",
+        0xC0 => "pids_supported_c1_e0",
+        _ => return format!("pid_{pid:#04x}").into(),
+    };
+    name.into()
+}
+
+fn dtc_mode_name(mode: u8) -> &'static str {
+    match mode {
+        0x03 => "stored_dtcs",
+        0x07 => "pending_dtcs",
+        _ => "permanent_dtcs",
+    }
+}
+
+/// DTCs are preceded by a count on CAN
+fn decode_dtcs(params: &[u8], attrs: &mut Vec<(String, AttrVal)>) {
+    let Some((_, dtcs)) = params.split_first() else {
+        return;
+    };
+    let dtcs: Vec<_> = dtcs
+        .chunks_exact(2)
+        .filter(|c| c != &[0, 0])
+        .map(|c| dtc_string(c[0], c[1]))
+        .collect();
+    attrs.push(("dtc_count".to_owned(), (dtcs.len() as u64).into()));
+    for (idx, dtc) in dtcs.into_iter().enumerate() {
+        attrs.push((format!("dtc.{idx}"), dtc.into()));
+    }
+}
+
+/// The two byte DTC as text, e.g. `P0301`
+fn dtc_string(a: u8, b: u8) -> String {
+    let system = ['P', 'C', 'B', 'U'][(a >> 6) as usize];
+    format!("{system}{}{:X}{b:02X}", (a >> 4) & 0x3, a & 0xF)
+}
+
+fn vehicle_info_name(pid: u8) -> std::borrow::Cow<'static, str> {
+    let name = match pid {
+        0x00 => "pids_supported_01_20",
+        0x02 => "vin",
+        0x04 => "calibration_id",
+        0x06 => "calibration_verification_numbers",
+        0x0A => "ecu_name",
+        _ => return format!("pid_{pid:#04x}").into(),
+    };
+    name.into()
+}
+
+/// Text items are preceded by the number of data items
+fn decode_vehicle_info(pid: u8, data: &[u8], attrs: &mut Vec<(String, AttrVal)>) {
+    attrs.push(("pid".to_owned(), (pid as u64).into()));
+    let name = vehicle_info_name(pid);
+    match (pid, data.split_first()) {
+        (0x02 | 0x04 | 0x0A, Some((_, text))) => {
+            let text: String = String::from_utf8_lossy(text)
+                .trim_matches(|c: char| c == '\0' || c.is_whitespace())
+                .to_owned();
+            attrs.push((name.into_owned(), text.into()));
+        }
+        (0x00, _) if data.len() >= 4 => {
+            let mask = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+            attrs.push((name.into_owned(), mask.into()));
+        }
+        _ if !data.is_empty() => {
+            attrs.push((format!("{name}.data"), hex(data).into()));
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attr<'a>(info: &'a ObdInfo, key: &str) -> Option<&'a AttrVal> {
+        info.attrs.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    #[test]
+    fn current_data() {
+        let info = decode(&[0x01, 0x0C]).unwrap();
+        assert_eq!(info.name, "obd.mode01.engine_rpm.request");
+        assert_eq!(attr(&info, "type"), Some(&"request".into()));
+        assert_eq!(attr(&info, "pid"), Some(&0x0C_u64.into()));
+
+        let info = decode(&[0x41, 0x0C, 0x1A, 0xF8]).unwrap();
+        assert_eq!(info.name, "obd.mode01.engine_rpm");
+        assert_eq!(attr(&info, "engine_rpm"), Some(&1726.0.into()));
+        assert_eq!(attr(&info, "engine_rpm.unit"), Some(&"rpm".into()));
+
+        // Several PIDs
+        let info = decode(&[0x41, 0x05, 0x7B, 0x0D, 0x32, 0x01, 0x83, 0x07, 0xE5, 0x00]).unwrap();
+        assert_eq!(info.name, "obd.mode01.engine_coolant_temperature");
+        assert_eq!(
+            attr(&info, "engine_coolant_temperature"),
+            Some(&83.0.into())
+        );
+        assert_eq!(attr(&info, "vehicle_speed"), Some(&50_u64.into()));
+        assert_eq!(attr(&info, "mil"), Some(&true.into()));
+        assert_eq!(attr(&info, "dtc_count"), Some(&3_u64.into()));
+        assert_eq!(attr(&info, "pid_count"), Some(&3_u64.into()));
+
+        // Freeze frame
+        let info = decode(&[0x42, 0x0D, 0x00, 0x64]).unwrap();
+        assert_eq!(info.name, "obd.mode02.vehicle_speed");
+        assert_eq!(attr(&info, "frame"), Some(&0_u64.into()));
+        assert_eq!(attr(&info, "vehicle_speed"), Some(&100_u64.into()));
+
+        let info = decode(&[0x41, 0xE0, 0x01, 0x02]).unwrap();
+        assert_eq!(info.name, "obd.mode01.pid_0xe0");
+        assert_eq!(attr(&info, "pid_0xe0.data"), Some(&"0102".into()));
+    }
+
+    #[test]
+    fn dtcs_and_vehicle_info() {
+        let info = decode(&[0x43, 0x02, 0x03, 0x01, 0xC1, 0x23, 0x00, 0x00]).unwrap();
+        assert_eq!(info.name, "obd.mode03.stored_dtcs");
+        assert_eq!(attr(&info, "dtc_count"), Some(&2_u64.into()));
+        assert_eq!(attr(&info, "dtc.0"), Some(&"P0301".into()));
+        assert_eq!(attr(&info, "dtc.1"), Some(&"U0123".into()));
+
+        let mut vin = vec![0x49, 0x02, 0x01];
+        vin.extend(b"1HGCM82633A004352");
+        let info = decode(&vin).unwrap();
+        assert_eq!(info.name, "obd.mode09.vin");
+        assert_eq!(attr(&info, "vin"), Some(&"1HGCM82633A004352".into()));
+
+        let info = decode(&[0x7F, 0x09, 0x12]).unwrap();
+        assert_eq!(info.name, "obd.mode09.negative_response");
+        assert_eq!(
+            attr(&info, "nrc_name"),
+            Some(&"sub_function_not_supported".into())
+        );
+
+        assert!(is_obd(&[0x01, 0x0C]));
+        assert!(is_obd(&[0x49, 0x02]));
+        assert!(is_obd(&[0x7F, 0x01, 0x11]));
+        assert!(!is_obd(&[0x22, 0xF1, 0x90]));
+        assert!(!is_obd(&[0x7F, 0x22, 0x31]));
+        assert_eq!(decode(&[0x10, 0x03]), None);
+    }
+}
@@ -1,47 +1,181 @@
-use crate::{dbc::EmptyStringExt, CommonConfig};
+use crate::{
+    canopen::{CanOpenDecoder, CanOpenInfo, CanOpenNode},
+    cycle_time::{CycleMonitor, MessageTimeout, Period},
+    dbc::{AttributeDefinitions, AttributeObject, EmptyStringExt},
+    e2e::{E2EChecker, E2EResult},
+    eds::ObjectDictionary,
+    error_frame,
+    import::{timestamp_from_nanos, timestamp_to_nanos, Direction},
+    isotp::{self, IsoTpError, IsoTpPdu, IsoTpReassembler},
+    j1939::{
+        DiagnosticMessage, DtcTracker, DtcTransition, J1939Id, TransportMessage,
+        TransportReassembler, PGN_DM1, PGN_DM2,
+    },
+    nmea2000::{FastPacketMessage, FastPacketReassembler, PgnDatabase},
+    obd::{self, ObdInfo},
+    uds::{nrc_name, RequestMatch, UdsKind, UdsMessage, UdsTracker},
+    CommonConfig,
+};
+use anyhow::anyhow;
 use auxon_sdk::api::{AttrKey, AttrVal, Nanoseconds};
 use bitvec::prelude::*;
 use can_dbc::{
-    ByteOrder, Message, MessageId, MultiplexIndicator, Signal, SignalExtendedValueType,
-    Transmitter, ValueDescription, ValueType, DBC,
+    AttributeValue, AttributeValuedForObjectType, ByteOrder, Message, MessageId,
+    MultiplexIndicator, Signal, SignalExtendedValueType, Transmitter, ValueDescription, ValueType,
+    DBC,
 };
+use serde::{Deserialize, Serialize};
 use socketcan::{CanAnyFrame, EmbeddedFrame, Id, Timestamp};
-use std::collections::HashMap;
+use std::{borrow::Cow, collections::HashMap, ops::RangeInclusive};
 use tracing::warn;
 
 #[derive(Debug)]
 pub struct ParsedCanFrame {
     id: CanId,
     pub msg_name: Option<String>,
+    /// Set for error frames, based on the error class, and protocol events
+    name: Option<Cow<'static, str>>,
     pub transmitter_node: Option<String>,
+    pub interface: Option<String>,
+    /// Set in J1939 mode
+    pub source_address: Option<u8>,
+    /// Set in CANopen mode, for frames sent by a node
+    pub node_id: Option<u8>,
+    /// Set in J1939 mode, for unmatched parameter groups
+    pgn: Option<u32>,
+    is_extended: bool,
+    /// Set for UDS requests and the responses paired with them
+    pub(crate) interaction: Option<Interaction>,
     pub attrs: HashMap<AttrKey, AttrVal>,
 }
 
+/// Links a response event to its request event, which may be on another timeline
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Interaction {
+    /// `superseded` is an earlier request on the connection that will no longer be answered
+    Request {
+        nonce: i64,
+        superseded: Option<i64>,
+        awaits_response: bool,
+    },
+    /// `last` is false when more responses to the request will follow
+    Response { remote_nonce: i64, last: bool },
+}
+
 impl ParsedCanFrame {
     pub fn event_name(&self) -> String {
         if let Some(msg) = &self.msg_name {
             msg.to_owned()
+        } else if let Some(name) = &self.name {
+            name.to_string()
+        } else if let Some(pgn) = self.pgn {
+            format!("pgn_{pgn}")
         } else {
             format!("{}", self.id)
         }
     }
+
+    fn message_id(&self) -> MessageId {
+        if self.is_extended {
+            MessageId::Extended(self.id)
+        } else {
+            MessageId::Standard(self.id as u16)
+        }
+    }
+
+    /// Record the interface name, or channel number, the frame was captured on
+    pub fn set_interface(&mut self, interface: &str) {
+        self.add_attr("interface", interface);
+        self.interface = Some(interface.to_owned());
+    }
+
+    pub fn set_direction(&mut self, direction: Direction) {
+        self.add_attr("frame.direction", direction.as_str());
+    }
+}
+
+/// A value logged for each signal
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SignalValueKind {
+    /// The scaled value, `<signal>`
+    Physical,
+    /// The unscaled value, `<signal>.raw`
+    Raw,
+    /// The value description of the raw value, `<signal>.label`
+    Label,
+}
+
+/// The values logged for the signals of a message, or a single signal
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct SignalValuesConfig {
+    /// The DBC message name, all messages when not set.
+    pub message: Option<String>,
+
+    /// The signal name, all signals of the message when not set.
+    pub signal: Option<String>,
+
+    pub values: Vec<SignalValueKind>,
 }
 
 #[derive(Debug)]
 pub struct CanParser {
     use_msg_as_event_name: bool,
+    clamp_signal_values: bool,
     id_to_msg_info: HashMap<CanId, DbcMessageInfo>,
+    j1939: Option<J1939State>,
+    isotp: Option<IsoTpReassembler>,
+    uds: UdsTracker,
+    obd: bool,
+    canopen: Option<CanOpenDecoder>,
+    nmea2000: Option<Nmea2000State>,
+    e2e: E2EChecker,
+    cycle_times: CycleMonitor,
+}
+
+#[derive(Debug, Default)]
+struct Nmea2000State {
+    pgns: PgnDatabase,
+    fast_packet: FastPacketReassembler,
+}
+
+#[derive(Debug, Default)]
+struct J1939State {
+    /// DBC messages keyed by PGN, the first message defined wins
+    pgn_to_id: HashMap<Pgn, CanId>,
+    /// From the `NmStationAddress` node attributes
+    source_address_to_node: HashMap<u8, String>,
+    transport: TransportReassembler,
+    dtcs: DtcTracker,
 }
 
+type Pgn = u32;
+
 impl CanParser {
     pub fn new(cfg: &CommonConfig, dbc: Option<&DBC>) -> Result<Self, anyhow::Error> {
         let mut id_to_msg_info = HashMap::new();
+        for msg_name in cfg
+            .signal_value_overrides
+            .iter()
+            .flatten()
+            .filter_map(|o| o.message.as_ref())
+        {
+            if !dbc.is_some_and(|dbc| dbc.messages().iter().any(|m| m.message_name() == msg_name)) {
+                return Err(anyhow!(
+                    "Signal values message '{}' not found in the DBC",
+                    msg_name
+                ));
+            }
+        }
+        let msg_attr_names = cfg.dbc_message_attributes.as_deref().unwrap_or_default();
+        let signal_attr_names = cfg.dbc_signal_attributes.as_deref().unwrap_or_default();
 
         if let Some(dbc) = dbc {
+            let attr_defs = AttributeDefinitions::new(dbc);
             for msg in dbc.messages().iter() {
                 let mut signal_to_type = HashMap::new();
                 let mut signal_to_values = HashMap::new();
-                let mut muxed_to_info = HashMap::new();
 
                 // Default signal value types, may get overridden by the extended types
                 for s in msg.signals().iter() {
@@ -88,36 +222,97 @@ impl CanParser {
                     }
                 }
 
-                // Setup the multiplexed signal maps
-                if let Some(muxer_sig) = dbc
-                    .message_multiplexor_switch(*msg.message_id())
-                    .ok()
-                    .flatten()
+                let mut signal_to_spn = HashMap::new();
+                for attr in dbc
+                    .attribute_values()
+                    .iter()
+                    .filter(|a| a.attribute_name() == "SPN")
                 {
-                    // We have a "simple" multiplexed signal (single multiplexor)
-                    // Find all of the child signals in the signal tree
-                    for s in msg.signals().iter() {
-                        let indicator = match s.multiplexer_indicator() {
-                            MultiplexIndicator::MultiplexedSignal(i) => *i,
-                            MultiplexIndicator::MultiplexorAndMultiplexedSignal(i) => *i,
-                            _ => continue,
-                        };
-                        muxed_to_info.insert(
-                            s.name().clone(),
-                            MuxedSignalInfo {
-                                muxer: muxer_sig.name().clone(),
-                                indicator,
-                            },
-                        );
+                    if let AttributeValuedForObjectType::SignalAttributeValue(id, name, val) =
+                        attr.attribute_value()
+                    {
+                        if *id == *msg.message_id() {
+                            if let Some(spn) = attr_value_u64(val) {
+                                signal_to_spn.insert(name.clone(), spn);
+                            }
+                        }
+                    }
+                }
+
+                // Configured message and signal attributes, logged with each frame
+                let mut attrs = Vec::new();
+                let mut signal_to_attrs: HashMap<SignalName, Vec<(String, AttrVal)>> =
+                    HashMap::new();
+                for attr in dbc.attribute_values().iter() {
+                    match attr.attribute_value() {
+                        AttributeValuedForObjectType::MessageDefinitionAttributeValue(
+                            id,
+                            Some(val),
+                        ) if *id == *msg.message_id()
+                            && msg_attr_names.contains(attr.attribute_name()) =>
+                        {
+                            attrs.push((
+                                attr.attribute_name().clone(),
+                                attr_defs.value(attr.attribute_name(), val),
+                            ));
+                        }
+                        AttributeValuedForObjectType::SignalAttributeValue(id, name, val)
+                            if *id == *msg.message_id()
+                                && signal_attr_names.contains(attr.attribute_name()) =>
+                        {
+                            signal_to_attrs.entry(name.clone()).or_default().push((
+                                attr.attribute_name().clone(),
+                                attr_defs.value(attr.attribute_name(), val),
+                            ));
+                        }
+                        _ => (),
+                    }
+                }
+                // Objects without a value of their own take the attribute's default
+                for name in msg_attr_names {
+                    if !attrs.iter().any(|(n, _)| n == name) {
+                        if let Some(val) = attr_defs.default_value(name, AttributeObject::Message) {
+                            attrs.push((name.clone(), val));
+                        }
+                    }
+                }
+                for s in msg.signals().iter() {
+                    for name in signal_attr_names {
+                        let sig_attrs = signal_to_attrs.get(s.name());
+                        if sig_attrs.is_some_and(|a| a.iter().any(|(n, _)| n == name)) {
+                            continue;
+                        }
+                        if let Some(val) = attr_defs.default_value(name, AttributeObject::Signal) {
+                            signal_to_attrs
+                                .entry(s.name().clone())
+                                .or_default()
+                                .push((name.clone(), val));
+                        }
                     }
                 }
-                // TODO add support for extended signal multiplexing
+
+                let signal_to_outputs = msg
+                    .signals()
+                    .iter()
+                    .filter_map(|s| {
+                        signal_outputs(cfg, msg, s).map(|outputs| (s.name().clone(), outputs))
+                    })
+                    .collect();
+
+                // Setup the multiplexed signal maps
+                let muxed_to_info = muxed_signal_info(dbc, msg);
+                let signal_order = signal_order(msg, &muxed_to_info);
 
                 let msg_info = DbcMessageInfo {
                     msg: msg.clone(),
+                    attrs,
+                    signal_order,
                     signal_state: SignalState {
                         signal_to_type,
                         signal_to_values,
+                        signal_to_spn,
+                        signal_to_attrs,
+                        signal_to_outputs,
                         muxed_to_info,
                         muxer_to_value: Default::default(),
                     },
@@ -135,9 +330,115 @@ impl CanParser {
             }
         }
 
+        // NMEA 2000 uses J1939 identifiers, addressing and transport
+        let nmea2000 = cfg.nmea2000.unwrap_or(false);
+        let j1939 = if cfg.j1939.unwrap_or(false) || nmea2000 {
+            let mut state = J1939State::default();
+            if let Some(dbc) = dbc {
+                for msg in dbc.messages().iter() {
+                    if let MessageId::Extended(id) = msg.message_id() {
+                        let pgn = J1939Id::from_can_id(*id).pgn;
+                        state.pgn_to_id.entry(pgn).or_insert(*id);
+                    }
+                }
+                for attr in dbc
+                    .attribute_values()
+                    .iter()
+                    .filter(|a| a.attribute_name() == "NmStationAddress")
+                {
+                    if let AttributeValuedForObjectType::NetworkNodeAttributeValue(node, val) =
+                        attr.attribute_value()
+                    {
+                        match attr_value_u64(val).and_then(|sa| u8::try_from(sa).ok()) {
+                            Some(sa) => {
+                                state.source_address_to_node.insert(sa, node.clone());
+                            }
+                            None => warn!(node, "Invalid J1939 node station address"),
+                        }
+                    }
+                }
+            }
+            Some(state)
+        } else {
+            None
+        };
+
+        // The OBD-II connections give way to any configured on the same IDs
+        let obd = cfg.obd.unwrap_or(false);
+        let mut isotp_connections = cfg.isotp.clone().unwrap_or_default();
+        if obd {
+            let builtin: Vec<_> = obd::connections()
+                .into_iter()
+                .filter(|b| {
+                    let builtin_ids = [b.tx_id, b.rx_id].map(|id| config_message_id(id, None));
+                    !isotp_connections.iter().any(|c| {
+                        [c.tx_id, c.rx_id]
+                            .map(|id| config_message_id(id, c.extended))
+                            .iter()
+                            .any(|id| builtin_ids.contains(id))
+                    })
+                })
+                .collect();
+            isotp_connections.extend(builtin);
+        }
+        let isotp = Some(isotp_connections)
+            .filter(|c| !c.is_empty())
+            .map(|c| IsoTpReassembler::new(&c));
+
+        let canopen = if cfg.canopen.unwrap_or(false) {
+            let mut nodes = Vec::new();
+            for node in cfg.canopen_nodes.iter().flatten() {
+                let od = match node.envsub_eds_path()? {
+                    Some(path) => ObjectDictionary::from_file(path)?,
+                    None => Default::default(),
+                };
+                let Some(node_id) = node.node_id.or(od.node_id) else {
+                    return Err(anyhow!(
+                        "CANopen node '{}' has no node-id",
+                        node.name.as_deref().unwrap_or_default()
+                    ));
+                };
+                nodes.push(CanOpenNode {
+                    node_id,
+                    name: node.name.clone(),
+                    od,
+                });
+            }
+            Some(CanOpenDecoder::new(
+                nodes,
+                cfg.canopen_sdo_mapping.unwrap_or(false),
+            ))
+        } else {
+            None
+        };
+
         Ok(Self {
             use_msg_as_event_name: cfg.event_from_message.unwrap_or(true),
+            clamp_signal_values: cfg.clamp_signal_values.unwrap_or(true),
             id_to_msg_info,
+            j1939,
+            isotp,
+            uds: Default::default(),
+            obd,
+            canopen,
+            nmea2000: if nmea2000 {
+                let pgns = match cfg.envsub_nmea2000_pgns_path()? {
+                    Some(path) => PgnDatabase::from_file(path)?,
+                    None => Default::default(),
+                };
+                Some(Nmea2000State {
+                    pgns,
+                    fast_packet: Default::default(),
+                })
+            } else {
+                None
+            },
+            e2e: E2EChecker::new(cfg.e2e.as_deref().unwrap_or_default(), dbc)?,
+            cycle_times: CycleMonitor::new(
+                cfg.cycle_times.as_deref().unwrap_or_default(),
+                cfg.cycle_time_tolerance,
+                dbc,
+            )?,
         })
     }
 
@@ -154,8 +455,388 @@ impl CanParser {
             pcf.add_hw_timestamp_attrs(&hw_timestamp);
         }
 
-        // Add DBC-related info
-        if let Some(msg_info) = self.id_to_msg_info.get_mut(&pcf.id) {
+        // Add DBC-related info, error frame IDs are the error class
+        if !matches!(frame, CanAnyFrame::Error(_)) {
+            let j1939_id = self.j1939_id(&pcf);
+            self.add_dbc_info(&mut pcf, j1939_id, Some(frame_data(frame)), false);
+        }
+
+        Ok(pcf)
+    }
+
+    /// Parse a frame captured on the given interface, returning its event followed by
+    /// any events the frame completes, e.g. reassembled J1939 transport protocol messages
+    /// or ISO-TP PDUs. Timeouts of periodic messages that were due before the frame
+    /// precede its event.
+    /// Protocol sessions are tracked separately for each interface.
+    pub fn parse_all(
+        &mut self,
+        frame: &CanAnyFrame,
+        timestamp: Option<Timestamp>,
+        interface: Option<&str>,
+    ) -> Result<Vec<ParsedCanFrame>, anyhow::Error> {
+        let mut pcf = self.parse(frame, timestamp)?;
+        if let Some(iface) = interface {
+            pcf.set_interface(iface);
+        }
+        if matches!(frame, CanAnyFrame::Error(_)) {
+            return Ok(vec![pcf]);
+        }
+
+        // Messages are timed out before this frame is recorded, and their timeouts precede it,
+        // so that a late frame follows the timeout of its own message
+        let timestamp_ns = timestamp.as_ref().map(timestamp_to_nanos);
+        let timeouts = match timestamp_ns {
+            Some(now) => self.cycle_times.expire(interface, now),
+            None => Vec::new(),
+        };
+        if let Some(period) =
+            timestamp_ns.and_then(|now| self.cycle_times.frame(interface, pcf.message_id(), now))
+        {
+            pcf.add_period_attrs(&period);
+        }
+
+        let data = frame_data(frame);
+        if let Some(result) = self.e2e.check(interface, pcf.message_id(), data) {
+            pcf.add_e2e_attrs(&result);
+        }
+        // Frames on an ISO-TP connection's IDs aren't CANopen
+        let isotp_frame = self
+            .isotp
+            .as_ref()
+            .is_some_and(|isotp| isotp.handles(pcf.message_id()));
+        if let (Some(canopen), false) = (self.canopen.as_mut(), pcf.is_extended || isotp_frame) {
+            if let Some(info) = canopen.decode(interface, pcf.id, data) {
+                pcf.add_canopen_attrs(&info);
+            }
+        }
+        let mut events = Vec::new();
+        if let Some(j1939_id) = self.j1939_id(&pcf) {
+            events.extend(self.diagnostic_events(&mut pcf, interface, &j1939_id, data));
+            events.extend(self.transport_events(interface, &j1939_id, data, timestamp));
+            events.extend(self.nmea2000_events(&mut pcf, interface, &j1939_id, data, timestamp));
+        }
+        let pdus = match self.isotp.as_mut() {
+            Some(isotp) => isotp.push(
+                interface,
+                pcf.message_id(),
+                data,
+                timestamp.as_ref().map(timestamp_to_nanos),
+            ),
+            None => Vec::new(),
+        };
+        events.extend(pdus.iter().map(|pdu| self.parse_isotp_pdu(pdu, timestamp)));
+        if self.obd && obd::FUNCTIONAL_IDS.contains(&pcf.id) {
+            if let Some(info) = isotp::single_frame_payload(data).and_then(obd::decode) {
+                let mut obd_pcf = ParsedCanFrame::new_obd_request(&pcf, &info);
+                if let Some(hw_timestamp) = timestamp {
+                    obd_pcf.add_hw_timestamp_attrs(&hw_timestamp);
+                }
+                events.push(obd_pcf);
+            }
+        }
+
+        let mut pcfs: Vec<ParsedCanFrame> =
+            timeouts.iter().map(|t| self.message_timeout(t)).collect();
+        pcfs.push(pcf);
+        pcfs.extend(events);
+        Ok(pcfs)
+    }
+
+    /// Events for periodic messages that have gone missing on the interface by `now`.
+    /// Frames only time out other messages when they arrive, so this is for live capture,
+    /// where the bus may go quiet.
+    pub fn poll(&mut self, interface: Option<&str>, now: Timestamp) -> Vec<ParsedCanFrame> {
+        self.cycle_times
+            .expire(interface, timestamp_to_nanos(&now))
+            .iter()
+            .map(|t| self.message_timeout(t))
+            .collect()
+    }
+
+    /// A `message_timeout` event, on the timeline of the message's transmitter
+    fn message_timeout(&self, timeout: &MessageTimeout) -> ParsedCanFrame {
+        let mut pcf = ParsedCanFrame::new_message_timeout(timeout);
+        if let Some(msg_info) = self.id_to_msg_info.get(&timeout.id.raw_can_id()) {
+            pcf.add_attr("timeout.message", msg_info.msg.message_name());
+            if let Transmitter::NodeName(node) = msg_info.msg.transmitter() {
+                pcf.transmitter_node = Some(node.clone());
+            }
+        }
+        if let (Some(j1939), Some(j1939_id)) = (self.j1939.as_ref(), self.j1939_id(&pcf)) {
+            pcf.source_address = Some(j1939_id.source_address);
+            if let Some(node) = j1939.source_address_to_node.get(&j1939_id.source_address) {
+                pcf.transmitter_node = Some(node.clone());
+            }
+        }
+        pcf
+    }
+
+    /// Events for any protocol sessions left incomplete at the end of the input
+    pub fn finish(&mut self) -> Vec<ParsedCanFrame> {
+        let msgs = self
+            .j1939
+            .as_mut()
+            .map(|j1939| j1939.transport.finish())
+            .unwrap_or_default();
+        let pdus = self
+            .isotp
+            .as_mut()
+            .map(|isotp| isotp.finish())
+            .unwrap_or_default();
+        let fast_packets = self
+            .nmea2000
+            .as_mut()
+            .map(|n2k| n2k.fast_packet.finish())
+            .unwrap_or_default();
+        let mut pcfs: Vec<_> = msgs
+            .iter()
+            .map(|msg| self.parse_transport_message(msg, None))
+            .collect();
+        pcfs.extend(pdus.iter().map(|pdu| self.parse_isotp_pdu(pdu, None)));
+        pcfs.extend(
+            fast_packets
+                .iter()
+                .map(|msg| self.parse_fast_packet_message(msg, None)),
+        );
+        pcfs
+    }
+
+    /// Feed a J1939 frame to the transport protocol, returning the events for any messages
+    /// it completes or aborts
+    fn transport_events(
+        &mut self,
+        interface: Option<&str>,
+        j1939_id: &J1939Id,
+        data: &[u8],
+        timestamp: Option<Timestamp>,
+    ) -> Vec<ParsedCanFrame> {
+        let msgs = match self.j1939.as_mut() {
+            Some(j1939) => j1939.transport.push(
+                interface,
+                j1939_id,
+                data,
+                timestamp.as_ref().map(timestamp_to_nanos),
+            ),
+            None => Vec::new(),
+        };
+        let mut pcfs = Vec::new();
+        for msg in msgs.iter() {
+            let mut pcf = self.parse_transport_message(msg, timestamp);
+            let dtc_events = if msg.aborted.is_none() {
+                self.diagnostic_events(&mut pcf, msg.interface.as_deref(), &msg.id, &msg.data)
+            } else {
+                Vec::new()
+            };
+            pcfs.push(pcf);
+            pcfs.extend(dtc_events);
+        }
+        pcfs
+    }
+
+    /// PDUs are placed on the timeline of the DBC node transmitting their CAN ID, otherwise
+    /// requests are placed on the `tester` timeline and responses on the connection's timeline
+    fn parse_isotp_pdu(&mut self, pdu: &IsoTpPdu, timestamp: Option<Timestamp>) -> ParsedCanFrame {
+        let mut pcf = ParsedCanFrame::new_isotp_pdu(pdu);
+        if let Some(hw_timestamp) = timestamp {
+            pcf.add_hw_timestamp_attrs(&hw_timestamp);
+        }
+        pcf.transmitter_node = self
+            .id_to_msg_info
+            .get(&pdu.can_id)
+            .and_then(|info| match info.msg.transmitter() {
+                Transmitter::NodeName(node) => Some(node.clone()),
+                Transmitter::VectorXXX => None,
+            })
+            .or_else(|| {
+                if pdu.is_request {
+                    Some("tester".to_owned())
+                } else {
+                    pdu.name.clone()
+                }
+            });
+
+        let uds = self
+            .isotp
+            .as_ref()
+            .map(|isotp| isotp.connection(pdu.connection).uds.unwrap_or(true))
+            .unwrap_or(false);
+        if pdu.error.is_some() {
+            return pcf;
+        }
+        if self.obd && obd::is_obd(&pdu.payload) {
+            if let Some(info) = obd::decode(&pdu.payload) {
+                pcf.add_obd_attrs(&info);
+            }
+        } else if uds {
+            if let Some(msg) = UdsMessage::decode(&pdu.payload) {
+                let timestamp_ns = timestamp.as_ref().map(timestamp_to_nanos);
+                let interface = pdu.interface.as_deref();
+                let request = if msg.kind == UdsKind::Request {
+                    let (nonce, superseded) =
+                        self.uds
+                            .request(interface, pdu.connection, &msg, timestamp_ns);
+                    pcf.interaction = Some(Interaction::Request {
+                        nonce,
+                        superseded,
+                        awaits_response: !msg.suppress_positive_response,
+                    });
+                    None
+                } else {
+                    self.uds
+                        .response(interface, pdu.connection, &msg, timestamp_ns)
+                };
+                pcf.add_uds_attrs(&msg, request);
+            }
+        }
+
+        pcf
+    }
+
+    fn parse_transport_message(
+        &mut self,
+        msg: &TransportMessage,
+        timestamp: Option<Timestamp>,
+    ) -> ParsedCanFrame {
+        let mut pcf = ParsedCanFrame::new_transport_message(msg);
+        if let Some(hw_timestamp) = timestamp {
+            pcf.add_hw_timestamp_attrs(&hw_timestamp);
+        }
+        // Aborted messages are named, but their partial data isn't decoded
+        let data = Some(msg.data.as_slice()).filter(|_| msg.aborted.is_none());
+        self.add_dbc_info(&mut pcf, Some(msg.id), data, true);
+        if let Some(data) = data {
+            self.add_nmea2000_fields(&mut pcf, msg.id.pgn, data);
+        }
+        pcf
+    }
+
+    /// Decode a single frame NMEA 2000 message, or feed a fast-packet frame to the
+    /// reassembler, returning the events for any messages it completes
+    fn nmea2000_events(
+        &mut self,
+        pcf: &mut ParsedCanFrame,
+        interface: Option<&str>,
+        id: &J1939Id,
+        data: &[u8],
+        timestamp: Option<Timestamp>,
+    ) -> Vec<ParsedCanFrame> {
+        let Some(n2k) = self.nmea2000.as_mut() else {
+            return Vec::new();
+        };
+        if !n2k.pgns.is_fast_packet(id.pgn) {
+            self.add_nmea2000_fields(pcf, id.pgn, data);
+            return Vec::new();
+        }
+
+        if let Some(&b0) = data.first() {
+            pcf.add_attr("nmea2000.fast_packet.sequence", (b0 >> 5) as u64);
+            pcf.add_attr("nmea2000.fast_packet.frame", (b0 & 0x1F) as u64);
+        }
+        let msgs = n2k.fast_packet.push(
+            interface,
+            id,
+            data,
+            timestamp.as_ref().map(timestamp_to_nanos),
+        );
+        msgs.iter()
+            .map(|msg| self.parse_fast_packet_message(msg, timestamp))
+            .collect()
+    }
+
+    fn parse_fast_packet_message(
+        &mut self,
+        msg: &FastPacketMessage,
+        timestamp: Option<Timestamp>,
+    ) -> ParsedCanFrame {
+        let mut pcf = ParsedCanFrame::new_fast_packet_message(msg);
+        if let Some(hw_timestamp) = timestamp {
+            pcf.add_hw_timestamp_attrs(&hw_timestamp);
+        }
+        // Incomplete messages are named, but their partial data isn't decoded
+        let data = Some(msg.data.as_slice()).filter(|_| msg.error.is_none());
+        self.add_dbc_info(&mut pcf, Some(msg.id), data, true);
+        if let Some(data) = data {
+            self.add_nmea2000_fields(&mut pcf, msg.id.pgn, data);
+        }
+        pcf
+    }
+
+    /// Name the event and add the fields from the PGN database, unless the DBC defines
+    /// the message
+    fn add_nmea2000_fields(&self, pcf: &mut ParsedCanFrame, pgn: Pgn, data: &[u8]) {
+        let Some(n2k) = self.nmea2000.as_ref() else {
+            return;
+        };
+        if pcf.msg_name.is_some() {
+            return;
+        }
+        let Some(def) = n2k.pgns.definition(pgn, data) else {
+            return;
+        };
+        pcf.name = Some(def.id.clone().into());
+        if !def.description.is_empty() {
+            pcf.add_attr("nmea2000.description", def.description.as_str());
+        }
+        for (k, v) in n2k.pgns.decode(def, data) {
+            pcf.add_attr(k, v);
+        }
+    }
+
+    /// Decode DM1/DM2 messages, returning an event for each DTC that appeared or cleared
+    fn diagnostic_events(
+        &mut self,
+        pcf: &mut ParsedCanFrame,
+        interface: Option<&str>,
+        id: &J1939Id,
+        data: &[u8],
+    ) -> Vec<ParsedCanFrame> {
+        let dm = match id.pgn {
+            PGN_DM1 => "dm1",
+            PGN_DM2 => "dm2",
+            _ => return Vec::new(),
+        };
+        let (Some(j1939), Some(dm_msg)) = (self.j1939.as_mut(), DiagnosticMessage::decode(data))
+        else {
+            return Vec::new();
+        };
+
+        pcf.add_dm_attrs(&dm_msg);
+        j1939
+            .dtcs
+            .update(interface, id, &dm_msg.dtcs)
+            .iter()
+            .map(|transition| ParsedCanFrame::new_dtc_transition(pcf, dm, transition))
+            .collect()
+    }
+
+    fn j1939_id(&self, pcf: &ParsedCanFrame) -> Option<J1939Id> {
+        self.j1939
+            .as_ref()
+            .filter(|_| pcf.is_extended)
+            .map(|_| J1939Id::from_can_id(pcf.id))
+    }
+
+    /// Multi-packet messages may be longer than the DBC message definition
+    fn add_dbc_info(
+        &mut self,
+        pcf: &mut ParsedCanFrame,
+        j1939_id: Option<J1939Id>,
+        data: Option<&[u8]>,
+        multi_packet: bool,
+    ) {
+        // J1939 parameter groups are matched by PGN, when there isn't an exact match
+        let mut msg_id = pcf.id;
+        if let (Some(j1939), Some(j1939_id)) = (self.j1939.as_ref(), j1939_id) {
+            if !self.id_to_msg_info.contains_key(&pcf.id) {
+                if let Some(id) = j1939.pgn_to_id.get(&j1939_id.pgn) {
+                    msg_id = *id;
+                }
+            }
+            pcf.add_j1939_attrs(&j1939_id);
+        }
+
+        if let Some(msg_info) = self.id_to_msg_info.get_mut(&msg_id) {
             if self.use_msg_as_event_name {
                 if let Some(msg_name) = msg_info.msg.message_name().empty_opt() {
                     pcf.msg_name = Some(msg_name.to_owned());
@@ -163,51 +844,84 @@ impl CanParser {
             }
 
             // Message-level info
-            pcf.add_dbc_msg_attrs(&msg_info.msg);
-
-            let data = match frame {
-                CanAnyFrame::Normal(f) => f.data(),
-                CanAnyFrame::Remote(f) => f.data(),
-                CanAnyFrame::Error(f) => f.data(),
-                CanAnyFrame::Fd(f) => f.data(),
-            };
+            pcf.add_dbc_msg_attrs(&msg_info.msg, &msg_info.attrs);
 
             // Parse the message signal
-            if data.len() as u64 == *msg_info.msg.message_size() {
-                for signal in msg_info.msg.signals().iter() {
-                    if !msg_info
-                        .signal_state
-                        .signal_to_type
-                        .contains_key(signal.name())
+            let msg_size = *msg_info.msg.message_size();
+            match data {
+                Some(data)
+                    if data.len() as u64 == msg_size
+                        || (multi_packet && data.len() as u64 >= msg_size) =>
+                {
+                    for signal in msg_info
+                        .signal_order
+                        .iter()
+                        .map(|idx| &msg_info.msg.signals()[*idx])
                     {
-                        warn!(
-                            id = ?msg_info.msg.message_id(),
-                            msg = msg_info.msg.message_name(),
-                            signal = signal.name(),
-                            "CAN signal missing value type"
+                        if !msg_info
+                            .signal_state
+                            .signal_to_type
+                            .contains_key(signal.name())
+                        {
+                            warn!(
+                                id = ?msg_info.msg.message_id(),
+                                msg = msg_info.msg.message_name(),
+                                signal = signal.name(),
+                                "CAN signal missing value type"
+                            );
+                        }
+
+                        pcf.add_dbc_signal_attrs(
+                            &mut msg_info.signal_state,
+                            signal,
+                            data,
+                            self.clamp_signal_values,
                         );
                     }
-
-                    pcf.add_dbc_signal_attrs(&mut msg_info.signal_state, signal, data);
                 }
-            } else {
-                warn!(
-                    id = ?msg_info.msg.message_id(),
-                    msg = msg_info.msg.message_name(),
-                    data_len = data.len(),
-                    msg_size = *msg_info.msg.message_size(),
-                    "CAN frame data length doesn't match the message defintion"
-                );
+                Some(data) => {
+                    warn!(
+                        id = ?msg_info.msg.message_id(),
+                        msg = msg_info.msg.message_name(),
+                        data_len = data.len(),
+                        msg_size,
+                        "CAN frame data length doesn't match the message defintion"
+                    );
+                }
+                None => (),
             }
 
             // Clear out any muxer signal state
             msg_info.signal_state.muxer_to_value.clear();
+        } else if let Some(j1939_id) = j1939_id {
+            pcf.pgn = Some(j1939_id.pgn);
         }
 
-        Ok(pcf)
+        // The transmitter of a parameter group depends on the source address
+        if let (Some(j1939), Some(j1939_id)) = (self.j1939.as_ref(), j1939_id) {
+            let from_dbc_msg = msg_id == pcf.id;
+            pcf.transmitter_node = j1939
+                .source_address_to_node
+                .get(&j1939_id.source_address)
+                .cloned()
+                .or_else(|| pcf.transmitter_node.take().filter(|_| from_dbc_msg));
+        }
+    }
+}
+
+fn frame_data(frame: &CanAnyFrame) -> &[u8] {
+    match frame {
+        CanAnyFrame::Normal(f) => f.data(),
+        CanAnyFrame::Remote(f) => f.data(),
+        CanAnyFrame::Error(f) => f.data(),
+        CanAnyFrame::Fd(f) => f.data(),
     }
 }
 
+pub(crate) fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{b:02x}")).collect()
+}
+
 impl ParsedCanFrame {
     fn new(frame: &CanAnyFrame) -> Self {
         let is_extended;
@@ -260,7 +974,14 @@ impl ParsedCanFrame {
         let mut pcf = Self {
             id: id.raw_can_id(),
             msg_name: None,
+            name: None,
             transmitter_node: None,
+            interface: None,
+            source_address: None,
+            node_id: None,
+            pgn: None,
+            is_extended,
+            interaction: None,
             attrs: Default::default(),
         };
 
@@ -283,72 +1004,479 @@ impl ParsedCanFrame {
             pcf.add_attr("frame.esi", true);
         }
 
+        if let CanAnyFrame::Error(f) = frame {
+            let info = error_frame::decode(f.error_bits(), f.data());
+            for (k, v) in info.attrs.into_iter() {
+                pcf.add_attr(format!("error.{k}"), v);
+            }
+            pcf.name = Some(info.name.into());
+        }
+
         pcf
     }
 
-    fn add_hw_timestamp_attrs(&mut self, timestamp: &Timestamp) {
-        const NANOS_PER_SEC: i64 = 1_000_000_000;
+    fn new_transport_message(msg: &TransportMessage) -> Self {
+        let id = msg.id.to_can_id();
+        let mut pcf = Self {
+            id,
+            msg_name: None,
+            name: None,
+            transmitter_node: None,
+            interface: None,
+            source_address: None,
+            node_id: None,
+            pgn: None,
+            is_extended: true,
+            interaction: None,
+            attrs: Default::default(),
+        };
 
-        self.add_internal_attr("timestamp.seconds", timestamp.seconds);
-        self.add_internal_attr("timestamp.nanoseconds", timestamp.nanoseconds);
+        if let Some(iface) = msg.interface.as_deref() {
+            pcf.set_interface(iface);
+        }
+        pcf.add_attr("j1939.tp.type", msg.kind.as_str());
+        pcf.add_attr("j1939.tp.packet_count", msg.packet_count as u64);
+        pcf.add_attr("j1939.tp.size", msg.data.len() as u64);
+        if let Some(duration) = msg.duration_ns {
+            pcf.add_attr(
+                "j1939.tp.duration",
+                Nanoseconds::from(duration.max(0) as u64),
+            );
+        }
+        if let Some(reason) = msg.aborted {
+            pcf.add_attr("j1939.tp.aborted", reason);
+        }
 
-        let secs = if timestamp.seconds < 0 && timestamp.nanoseconds > 0 {
-            // This is what nix does
-            timestamp.seconds + 1
-        } else {
-            timestamp.seconds
-        };
+        pcf
+    }
 
-        let nanos_mod_sec = if timestamp.seconds < 0 && timestamp.nanoseconds > 0 {
-            timestamp.nanoseconds - NANOS_PER_SEC
-        } else {
-            timestamp.nanoseconds
+    fn new_message_timeout(timeout: &MessageTimeout) -> Self {
+        let mut pcf = Self {
+            id: timeout.id.raw_can_id(),
+            msg_name: None,
+            name: Some("message_timeout".into()),
+            transmitter_node: None,
+            interface: None,
+            source_address: None,
+            node_id: None,
+            pgn: None,
+            is_extended: matches!(timeout.id, MessageId::Extended(_)),
+            interaction: None,
+            attrs: Default::default(),
         };
 
-        let ns = (secs * NANOS_PER_SEC) + nanos_mod_sec;
+        if let Some(iface) = timeout.interface.as_deref() {
+            pcf.set_interface(iface);
+        }
+        pcf.add_hw_timestamp_attrs(&timestamp_from_nanos(timeout.deadline_ns));
+        pcf.add_attr("timeout.id", timeout.id.raw_can_id());
+        pcf.add_attr(
+            "timeout.cycle_time_ns",
+            Nanoseconds::from(timeout.cycle_time_ns as u64),
+        );
 
-        self.add_attr("timestamp", Nanoseconds::from(ns as u64));
+        pcf
     }
 
-    fn add_dbc_msg_attrs(&mut self, msg: &Message) {
-        self.add_internal_attr("message.signal.count", msg.signals().len() as u32);
-        self.add_attr("message.size", *msg.message_size());
-        self.add_attr("message.name", msg.message_name());
-        if let Transmitter::NodeName(node) = msg.transmitter() {
-            self.add_attr("message.trasmitter", node);
-            self.transmitter_node = Some(node.clone());
+    fn new_fast_packet_message(msg: &FastPacketMessage) -> Self {
+        let mut pcf = Self {
+            id: msg.id.to_can_id(),
+            msg_name: None,
+            name: None,
+            transmitter_node: None,
+            interface: None,
+            source_address: None,
+            node_id: None,
+            pgn: None,
+            is_extended: true,
+            interaction: None,
+            attrs: Default::default(),
+        };
+
+        if let Some(iface) = msg.interface.as_deref() {
+            pcf.set_interface(iface);
+        }
+        pcf.add_attr("nmea2000.fast_packet.sequence", msg.sequence as u64);
+        pcf.add_attr("nmea2000.fast_packet.frame_count", msg.frame_count as u64);
+        pcf.add_attr("nmea2000.fast_packet.size", msg.data.len() as u64);
+        if let Some(duration) = msg.duration_ns {
+            pcf.add_attr(
+                "nmea2000.fast_packet.duration",
+                Nanoseconds::from(duration.max(0) as u64),
+            );
+        }
+        if let Some(error) = msg.error {
+            pcf.add_attr("nmea2000.fast_packet.error", error);
         }
+
+        pcf
     }
 
-    fn add_dbc_signal_attrs(
-        &mut self,
+    fn new_isotp_pdu(pdu: &IsoTpPdu) -> Self {
+        let mut pcf = Self {
+            id: pdu.can_id,
+            msg_name: None,
+            name: Some("isotp_pdu".into()),
+            transmitter_node: None,
+            interface: None,
+            source_address: None,
+            node_id: None,
+            pgn: None,
+            is_extended: pdu.is_extended,
+            interaction: None,
+            attrs: Default::default(),
+        };
+
+        if let Some(iface) = pdu.interface.as_deref() {
+            pcf.set_interface(iface);
+        }
+        if let Some(name) = pdu.name.as_deref() {
+            pcf.add_attr("isotp.name", name);
+        }
+        pcf.add_attr("isotp.can_id", pdu.can_id);
+        pcf.add_attr(
+            "isotp.direction",
+            if pdu.is_request {
+                "request"
+            } else {
+                "response"
+            },
+        );
+        pcf.add_attr("isotp.addressing", pdu.addressing.as_str());
+        if let Some(address) = pdu.address {
+            pcf.add_attr("isotp.address", address as u64);
+        }
+        pcf.add_attr("isotp.length", pdu.length as u64);
+        pcf.add_attr("isotp.payload", hex(&pdu.payload));
+        pcf.add_attr("isotp.frame_count", pdu.frame_count as u64);
+        if let Some(duration) = pdu.duration_ns {
+            pcf.add_attr("isotp.duration", Nanoseconds::from(duration.max(0) as u64));
+        }
+        if let Some(bs) = pdu.block_size {
+            pcf.add_attr("isotp.block_size", bs as u64);
+        }
+        if let Some(st_min) = pdu.st_min_ns {
+            pcf.add_attr("isotp.st_min", Nanoseconds::from(st_min));
+        }
+        if pdu.wait_count != 0 {
+            pcf.add_attr("isotp.wait_count", pdu.wait_count as u64);
+        }
+        if let Some(st_min) = pdu.observed_st_min_ns {
+            pcf.add_attr(
+                "isotp.observed_st_min",
+                Nanoseconds::from(st_min.max(0) as u64),
+            );
+        }
+        if let Some(error) = pdu.error {
+            pcf.add_attr("isotp.error", error.as_str());
+            if let IsoTpError::WrongSequenceNumber { expected, received } = error {
+                pcf.add_attr("isotp.expected_sequence_number", expected as u64);
+                pcf.add_attr("isotp.sequence_number", received as u64);
+            }
+        }
+
+        pcf
+    }
+
+    fn add_period_attrs(&mut self, period: &Period) {
+        self.add_attr(
+            "period.cycle_time_ns",
+            Nanoseconds::from(period.cycle_time_ns as u64),
+        );
+        // Timestamps that go backwards have no meaningful inter-arrival time
+        if let Ok(delta_ns) = u64::try_from(period.delta_ns) {
+            self.add_attr("period.delta_ns", Nanoseconds::from(delta_ns));
+            self.add_attr("period.deviation", period.deviation());
+        }
+    }
+
+    fn add_e2e_attrs(&mut self, result: &E2EResult) {
+        self.add_attr("e2e.profile", result.profile.as_str());
+        self.add_attr("e2e.status", result.status.as_str());
+        if let Some(counter) = result.counter {
+            self.add_attr("e2e.counter", counter as u64);
+        }
+        if let Some(delta) = result.counter_delta {
+            self.add_attr("e2e.counter_delta", delta as u64);
+        }
+        if let Some(crc) = result.crc {
+            self.add_attr("e2e.crc", crc as u64);
+        }
+        if let Some(crc) = result.computed_crc {
+            self.add_attr("e2e.computed_crc", crc as u64);
+        }
+        if let Some(field) = result.mismatch {
+            self.add_attr("e2e.mismatch", field);
+        }
+    }
+
+    fn add_canopen_attrs(&mut self, info: &CanOpenInfo) {
+        self.name = Some(info.name.into());
+        self.node_id = info.node_id;
+        if self.transmitter_node.is_none() {
+            self.transmitter_node.clone_from(&info.node_name);
+        }
+        for (k, v) in info.attrs.iter() {
+            self.add_attr(format!("canopen.{k}"), v.clone());
+        }
+        // A DBC message definition takes precedence over the object dictionary
+        if self.msg_name.is_none() {
+            for (k, v) in info.signals.iter() {
+                self.add_attr(k, v.clone());
+            }
+        }
+    }
+
+    /// A functional (broadcast) request, from the tester
+    fn new_obd_request(frame_pcf: &ParsedCanFrame, info: &ObdInfo) -> Self {
+        let mut pcf = Self {
+            id: frame_pcf.id,
+            msg_name: None,
+            name: None,
+            transmitter_node: Some("tester".to_owned()),
+            interface: frame_pcf.interface.clone(),
+            source_address: None,
+            node_id: None,
+            pgn: None,
+            is_extended: frame_pcf.is_extended,
+            interaction: None,
+            attrs: Default::default(),
+        };
+        if let Some(iface) = frame_pcf.interface.as_deref() {
+            pcf.set_interface(iface);
+        }
+        pcf.add_attr("obd.functional", true);
+        pcf.add_obd_attrs(info);
+        pcf
+    }
+
+    fn add_obd_attrs(&mut self, info: &ObdInfo) {
+        self.name = Some(info.name.clone().into());
+        for (k, v) in info.attrs.iter() {
+            self.add_attr(format!("obd.{k}"), v.clone());
+        }
+    }
+
+    /// Names the event after the service, e.g. `uds.read_data_by_identifier` for a request
+    /// and `uds.read_data_by_identifier.positive_response` for its response
+    fn add_uds_attrs(&mut self, msg: &UdsMessage, request: Option<RequestMatch>) {
+        let service = msg.service_name();
+        self.name = Some(match msg.kind {
+            UdsKind::Request => format!("uds.{service}").into(),
+            kind => format!("uds.{service}.{}", kind.as_str()).into(),
+        });
+        self.add_attr("uds.service", service);
+        self.add_attr("uds.sid", msg.sid as u64);
+        self.add_attr("uds.type", msg.kind.as_str());
+        if let Some(sf) = msg.sub_function {
+            self.add_attr("uds.sub_function", sf as u64);
+            if let Some(name) = msg.sub_function_name() {
+                self.add_attr("uds.sub_function_name", name);
+            }
+        }
+        if msg.suppress_positive_response {
+            self.add_attr("uds.suppress_positive_response", true);
+        }
+        if let Some(did) = msg.did {
+            self.add_attr("uds.did", did as u64);
+        }
+        if msg.did_count > 1 {
+            self.add_attr("uds.did_count", msg.did_count as u64);
+        }
+        if let Some(routine_id) = msg.routine_id {
+            self.add_attr("uds.routine_id", routine_id as u64);
+        }
+        if !msg.data.is_empty() {
+            self.add_attr("uds.data", hex(msg.data));
+        }
+        if let UdsKind::NegativeResponse { nrc } = msg.kind {
+            self.add_attr("uds.nrc", nrc as u64);
+            if let Some(name) = nrc_name(nrc) {
+                self.add_attr("uds.nrc_name", name);
+            }
+            if msg.is_response_pending() {
+                self.add_attr("uds.response_pending", true);
+            }
+        }
+
+        match (self.interaction, request) {
+            (Some(Interaction::Request { nonce, .. }), _) => {
+                self.add_attr("nonce", nonce);
+            }
+            (None, Some(request)) => {
+                self.add_attr("interaction.remote_nonce", request.nonce);
+                if let Some(response_time) = request.response_time_ns {
+                    self.add_attr(
+                        "uds.response_time",
+                        Nanoseconds::from(response_time.max(0) as u64),
+                    );
+                }
+                self.interaction = Some(Interaction::Response {
+                    remote_nonce: request.nonce,
+                    last: !request.pending,
+                });
+            }
+            _ => (),
+        }
+    }
+
+    /// An event on the same timeline as the DM1/DM2 message that reported the transition
+    fn new_dtc_transition(dm_pcf: &ParsedCanFrame, dm: &str, transition: &DtcTransition) -> Self {
+        let (name, dtc) = match transition {
+            DtcTransition::Appeared(dtc) => ("dtc_appeared", dtc),
+            DtcTransition::Cleared(dtc) => ("dtc_cleared", dtc),
+        };
+        let mut pcf = Self {
+            id: dm_pcf.id,
+            msg_name: None,
+            name: Some(name.into()),
+            transmitter_node: dm_pcf.transmitter_node.clone(),
+            interface: dm_pcf.interface.clone(),
+            source_address: dm_pcf.source_address,
+            node_id: None,
+            pgn: None,
+            is_extended: true,
+            interaction: None,
+            attrs: Default::default(),
+        };
+
+        for key in [
+            "timestamp",
+            "interface",
+            "j1939.pgn",
+            "j1939.priority",
+            "j1939.source_address",
+            "j1939.destination_address",
+        ] {
+            let key = AttrKey::from(format!("event.{key}"));
+            if let Some(val) = dm_pcf.attrs.get(&key) {
+                pcf.attrs.insert(key, val.clone());
+            }
+        }
+        pcf.add_attr("j1939.dm", dm);
+        pcf.add_attr("j1939.dtc.spn", dtc.spn);
+        pcf.add_attr("j1939.dtc.fmi", dtc.fmi as u64);
+        pcf.add_attr("j1939.dtc.occurrence_count", dtc.occurrence_count as u64);
+        pcf.add_attr("j1939.dtc.conversion_method", dtc.conversion_method);
+
+        pcf
+    }
+
+    fn add_dm_attrs(&mut self, dm_msg: &DiagnosticMessage) {
+        for (lamp, status, flash) in dm_msg.lamps.iter() {
+            self.add_attr(format!("j1939.dm.lamp.{lamp}"), *status);
+            self.add_attr(format!("j1939.dm.lamp.{lamp}_flash"), *flash);
+        }
+        self.add_attr("j1939.dm.dtc_count", dm_msg.dtcs.len() as u64);
+        for (idx, dtc) in dm_msg.dtcs.iter().enumerate() {
+            self.add_attr(format!("j1939.dm.dtc.{idx}.spn"), dtc.spn);
+            self.add_attr(format!("j1939.dm.dtc.{idx}.fmi"), dtc.fmi as u64);
+            self.add_attr(
+                format!("j1939.dm.dtc.{idx}.occurrence_count"),
+                dtc.occurrence_count as u64,
+            );
+            self.add_attr(
+                format!("j1939.dm.dtc.{idx}.conversion_method"),
+                dtc.conversion_method,
+            );
+        }
+    }
+
+    fn add_hw_timestamp_attrs(&mut self, timestamp: &Timestamp) {
+        const NANOS_PER_SEC: i64 = 1_000_000_000;
+
+        self.add_internal_attr("timestamp.seconds", timestamp.seconds);
+        self.add_internal_attr("timestamp.nanoseconds", timestamp.nanoseconds);
+
+        let secs = if timestamp.seconds < 0 && timestamp.nanoseconds > 0 {
+            // This is what nix does
+            timestamp.seconds + 1
+        } else {
+            timestamp.seconds
+        };
+
+        let nanos_mod_sec = if timestamp.seconds < 0 && timestamp.nanoseconds > 0 {
+            timestamp.nanoseconds - NANOS_PER_SEC
+        } else {
+            timestamp.nanoseconds
+        };
+
+        let ns = (secs * NANOS_PER_SEC) + nanos_mod_sec;
+
+        self.add_attr("timestamp", Nanoseconds::from(ns as u64));
+    }
+
+    fn add_j1939_attrs(&mut self, id: &J1939Id) {
+        self.add_attr("j1939.pgn", id.pgn);
+        self.add_attr("j1939.priority", id.priority as u64);
+        self.add_attr("j1939.source_address", id.source_address as u64);
+        self.add_attr("j1939.destination_address", id.destination_address as u64);
+        self.source_address = Some(id.source_address);
+    }
+
+    fn add_dbc_msg_attrs(&mut self, msg: &Message, attrs: &[(String, AttrVal)]) {
+        self.add_internal_attr("message.signal.count", msg.signals().len() as u32);
+        self.add_attr("message.size", *msg.message_size());
+        self.add_attr("message.name", msg.message_name());
+        for (name, val) in attrs.iter() {
+            self.add_attr(format!("message.{name}"), val.clone());
+        }
+        if let Transmitter::NodeName(node) = msg.transmitter() {
+            self.add_attr("message.trasmitter", node);
+            self.transmitter_node = Some(node.clone());
+        }
+    }
+
+    fn add_dbc_signal_attrs(
+        &mut self,
         signal_state: &mut SignalState,
         signal: &Signal,
         data: &[u8],
+        clamp: bool,
     ) {
-        // Skip if this is a multiplexed signal and the muxer indicator doesn't match
-        let is_muxed = matches!(
-            signal.multiplexer_indicator(),
-            MultiplexIndicator::MultiplexedSignal(_)
-                | MultiplexIndicator::MultiplexorAndMultiplexedSignal(_)
-        );
-        if is_muxed {
-            if let Some(muxed_info) = signal_state.muxed_to_info.get(signal.name()) {
-                if let Some(muxer_indicator) = signal_state.muxer_to_value.get(&muxed_info.muxer) {
-                    if *muxer_indicator != muxed_info.indicator {
-                        return;
-                    }
-                }
-            }
+        // Skip if this is a multiplexed signal that isn't active in this frame
+        if !signal_state.is_active(signal) {
+            return;
         }
 
-        if let Some(val) = parse_signal(signal_state, signal, data) {
+        if let Some(val) = parse_signal(signal_state, signal, data, clamp) {
             // I think the spec prohibits this...
             let normalized_signal_name = signal.name().replace(' ', "_");
             if let Some(unit) = signal.unit().empty_opt() {
                 self.add_attr(format!("{normalized_signal_name}.unit"), unit);
             }
-            self.add_attr(normalized_signal_name, val);
+            if let Some(spn) = signal_state.signal_to_spn.get(signal.name()) {
+                self.add_attr(format!("{normalized_signal_name}.spn"), *spn);
+            }
+            for (name, attr_val) in signal_state
+                .signal_to_attrs
+                .get(signal.name())
+                .into_iter()
+                .flatten()
+            {
+                self.add_attr(format!("{normalized_signal_name}.{name}"), attr_val.clone());
+            }
+            if val.out_of_range {
+                self.add_attr(format!("{normalized_signal_name}.out_of_range"), true);
+            }
+            match signal_state.signal_to_outputs.get(signal.name()) {
+                Some(outputs) => {
+                    if outputs.raw {
+                        self.add_attr(format!("{normalized_signal_name}.raw"), val.raw);
+                    }
+                    if let Some(label) = val.label.filter(|_| outputs.label) {
+                        self.add_attr(format!("{normalized_signal_name}.label"), label);
+                    }
+                    if outputs.physical {
+                        self.add_attr(normalized_signal_name, val.physical);
+                    }
+                }
+                // The value description replaces the value, except for multiplexors
+                None => match val.label.filter(|_| !val.multiplexor) {
+                    Some(label) => self.add_attr(normalized_signal_name, label),
+                    None => self.add_attr(normalized_signal_name, val.physical),
+                },
+            }
         } else {
             warn!(signal = signal.name(), "Failed to parse signal");
         }
@@ -367,11 +1495,20 @@ impl ParsedCanFrame {
 
 type CanId = u32;
 
-trait RawCanIdExt {
+pub(crate) trait RawCanIdExt {
     /// Does *not* contain the extended bit for extended IDs
     fn raw_can_id(&self) -> CanId;
 }
 
+/// A CAN ID given in the configuration, which is extended when flagged as such or when it
+/// doesn't fit in 11 bits
+pub(crate) fn config_message_id(id: u32, extended: Option<bool>) -> MessageId {
+    match u16::try_from(id) {
+        Ok(id) if id <= 0x7FF && !extended.unwrap_or(false) => MessageId::Standard(id),
+        _ => MessageId::Extended(id),
+    }
+}
+
 impl RawCanIdExt for MessageId {
     fn raw_can_id(&self) -> CanId {
         match self {
@@ -401,13 +1538,57 @@ enum SignalValueType {
 #[derive(Debug)]
 struct DbcMessageInfo {
     msg: Message,
+    /// The configured message attributes
+    attrs: Vec<(String, AttrVal)>,
+    /// Indices of the message signals, ordered so that each multiplexor
+    /// is read before the signals it multiplexes.
+    signal_order: Vec<usize>,
     signal_state: SignalState,
 }
 
+/// Which values are logged for a signal
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+struct SignalOutputs {
+    physical: bool,
+    raw: bool,
+    label: bool,
+}
+
+impl SignalOutputs {
+    fn new(values: &[SignalValueKind]) -> Self {
+        SignalOutputs {
+            physical: values.contains(&SignalValueKind::Physical),
+            raw: values.contains(&SignalValueKind::Raw),
+            label: values.contains(&SignalValueKind::Label),
+        }
+    }
+}
+
+/// The configured values of a signal, the most specific override wins over the global setting
+fn signal_outputs(cfg: &CommonConfig, msg: &Message, signal: &Signal) -> Option<SignalOutputs> {
+    cfg.signal_value_overrides
+        .iter()
+        .flatten()
+        .filter(|o| {
+            o.message.iter().all(|m| m == msg.message_name())
+                && o.signal.iter().all(|s| s == signal.name())
+        })
+        .max_by_key(|o| (o.signal.is_some(), o.message.is_some()))
+        .map(|o| o.values.as_slice())
+        .or(cfg.signal_values.as_deref())
+        .map(SignalOutputs::new)
+}
+
 #[derive(Debug)]
 struct SignalState {
     signal_to_type: HashMap<SignalName, SignalValueType>,
     signal_to_values: HashMap<SignalName, ValueDescriptionMap>,
+    /// J1939 suspect parameter numbers, from the `SPN` signal attributes
+    signal_to_spn: HashMap<SignalName, u64>,
+    /// The configured signal attributes
+    signal_to_attrs: HashMap<SignalName, Vec<(String, AttrVal)>>,
+    /// The configured values to log, otherwise the value description or physical value
+    signal_to_outputs: HashMap<SignalName, SignalOutputs>,
     muxed_to_info: HashMap<MuxedSignal, MuxedSignalInfo>,
     /// Set when a multiplexor signal is read, contains it's value.
     /// Cleared after processing each frame.
@@ -426,17 +1607,178 @@ type MuxedSignal = String;
 struct MuxedSignalInfo {
     /// The parent multiplexor indicator signal
     muxer: MuxerSignal,
-    /// The indicator value of the parent multiplexor
-    /// that flags this multiplexed signal as active.
-    indicator: MuxerIndicatorValue,
+    /// The ranges of indicator values of the parent multiplexor
+    /// that flag this multiplexed signal as active.
+    indicators: Vec<RangeInclusive<MuxerIndicatorValue>>,
+}
+
+impl SignalState {
+    /// A multiplexed signal is active when its parent multiplexor was read in the
+    /// current frame, and has one of the signal's indicator values.
+    /// Nested multiplexors are only read when active themselves.
+    fn is_active(&self, signal: &Signal) -> bool {
+        if !is_multiplexed(signal) {
+            return true;
+        }
+        let Some(muxed_info) = self.muxed_to_info.get(signal.name()) else {
+            return false;
+        };
+        self.muxer_to_value
+            .get(&muxed_info.muxer)
+            .is_some_and(|value| muxed_info.indicators.iter().any(|r| r.contains(value)))
+    }
+}
+
+fn attr_value_u64(val: &AttributeValue) -> Option<u64> {
+    match val {
+        AttributeValue::AttributeValueU64(v) => Some(*v),
+        AttributeValue::AttributeValueI64(v) => u64::try_from(*v).ok(),
+        AttributeValue::AttributeValueF64(v) if v.fract() == 0.0 && *v >= 0.0 => Some(*v as u64),
+        _ => None,
+    }
+}
+
+fn is_multiplexor(signal: &Signal) -> bool {
+    matches!(
+        signal.multiplexer_indicator(),
+        MultiplexIndicator::Multiplexor | MultiplexIndicator::MultiplexorAndMultiplexedSignal(_)
+    )
+}
+
+fn is_multiplexed(signal: &Signal) -> bool {
+    matches!(
+        signal.multiplexer_indicator(),
+        MultiplexIndicator::MultiplexedSignal(_)
+            | MultiplexIndicator::MultiplexorAndMultiplexedSignal(_)
+    )
 }
 
-fn parse_signal(signal_state: &mut SignalState, sig: &Signal, data: &[u8]) -> Option<AttrVal> {
+/// Find the parent multiplexor and active indicator values of each multiplexed signal
+/// in the message.
+/// Extended multiplexing (`SG_MUL_VAL_`) entries name the multiplexor and value ranges directly,
+/// otherwise the signal's indicator value refers to the message's only multiplexor.
+fn muxed_signal_info(dbc: &DBC, msg: &Message) -> HashMap<MuxedSignal, MuxedSignalInfo> {
+    let mut muxed_to_info: HashMap<MuxedSignal, MuxedSignalInfo> = HashMap::new();
+
+    for ext_mux in dbc
+        .extended_multiplex()
+        .iter()
+        .filter(|m| *m.message_id() == *msg.message_id())
+    {
+        let indicators = ext_mux
+            .mappings()
+            .iter()
+            .map(|m| *m.min_value()..=*m.max_value());
+        match muxed_to_info.get_mut(ext_mux.signal_name()) {
+            Some(info) if info.muxer == *ext_mux.multiplexor_signal_name() => {
+                info.indicators.extend(indicators)
+            }
+            Some(info) => warn!(
+                id = ?msg.message_id(),
+                msg = msg.message_name(),
+                signal = ext_mux.signal_name(),
+                muxer = info.muxer,
+                "Multiplexed signal has more than one multiplexor, ignoring '{}'",
+                ext_mux.multiplexor_signal_name()
+            ),
+            None => {
+                muxed_to_info.insert(
+                    ext_mux.signal_name().clone(),
+                    MuxedSignalInfo {
+                        muxer: ext_mux.multiplexor_signal_name().clone(),
+                        indicators: indicators.collect(),
+                    },
+                );
+            }
+        }
+    }
+
+    for s in msg.signals().iter() {
+        let indicator = match s.multiplexer_indicator() {
+            MultiplexIndicator::MultiplexedSignal(i) => *i,
+            MultiplexIndicator::MultiplexorAndMultiplexedSignal(i) => *i,
+            _ => continue,
+        };
+        if muxed_to_info.contains_key(s.name()) {
+            continue;
+        }
+
+        let mut muxers = msg
+            .signals()
+            .iter()
+            .filter(|m| is_multiplexor(m) && m.name() != s.name());
+        match (muxers.next(), muxers.next()) {
+            (Some(muxer), None) => {
+                muxed_to_info.insert(
+                    s.name().clone(),
+                    MuxedSignalInfo {
+                        muxer: muxer.name().clone(),
+                        indicators: vec![indicator..=indicator],
+                    },
+                );
+            }
+            _ => warn!(
+                id = ?msg.message_id(),
+                msg = msg.message_name(),
+                signal = s.name(),
+                "Could not determine the multiplexor of a multiplexed signal"
+            ),
+        }
+    }
+
+    muxed_to_info
+}
+
+/// Order the message signals by their depth in the multiplexor tree,
+/// so that multiplexor values are known before their multiplexed signals are read
+fn signal_order(
+    msg: &Message,
+    muxed_to_info: &HashMap<MuxedSignal, MuxedSignalInfo>,
+) -> Vec<usize> {
+    let depth = |signal: &Signal| {
+        let mut depth = 0;
+        let mut name = signal.name();
+        while let Some(info) = muxed_to_info.get(name) {
+            depth += 1;
+            name = &info.muxer;
+            // Multiplexor cycles can never be active, don't follow them forever
+            if depth > msg.signals().len() {
+                break;
+            }
+        }
+        depth
+    };
+
+    let mut order: Vec<usize> = (0..msg.signals().len()).collect();
+    order.sort_by_cached_key(|idx| depth(&msg.signals()[*idx]));
+    order
+}
+
+/// A decoded signal value
+#[derive(Debug, PartialEq)]
+struct SignalValue {
+    /// The physical value, or the value of a multiplexor
+    physical: AttrVal,
+    /// The physical value is outside the signal's `[min|max]` range
+    out_of_range: bool,
+    raw: AttrVal,
+    /// The value description of the raw value
+    label: Option<String>,
+    multiplexor: bool,
+}
+
+/// Decode a signal, with its physical value clamped to the signal's range when `clamp` is set
+fn parse_signal(
+    signal_state: &mut SignalState,
+    sig: &Signal,
+    data: &[u8],
+    clamp: bool,
+) -> Option<SignalValue> {
     let typ = *signal_state.signal_to_type.get(sig.name())?;
 
     let mut raw = parse_raw_val(sig, typ, data)?;
 
-    let maybe_muxer_value = if *sig.multiplexer_indicator() == MultiplexIndicator::Multiplexor {
+    let maybe_muxer_value = if is_multiplexor(sig) {
         if let RawVal::U64(indicator) = raw {
             Some(indicator)
         } else {
@@ -450,35 +1792,80 @@ fn parse_signal(signal_state: &mut SignalState, sig: &Signal, data: &[u8]) -> Op
         None
     };
 
-    let maybe_value_description = signal_state
+    let label = signal_state
         .signal_to_values
         .get(sig.name())
         .and_then(|vd| match raw {
             RawVal::I64(v) => vd.get(&v),
             RawVal::U64(v) => vd.get(&(v as i64)),
             _ => None,
-        });
-
-    if let Some(muxer_value) = maybe_muxer_value {
+        })
+        .cloned();
+    let raw_val = raw.as_attr_val();
+
+    let range = signal_range(sig.min, sig.max);
+    let (physical, out_of_range) = if let (RawVal::Wide { bytes, .. }, None) = (&raw, raw.as_i128())
+    {
+        // Too wide for an integer, so it's logged unscaled
+        (hex(bytes).into(), false)
+    } else if let Some(muxer_value) = maybe_muxer_value {
         signal_state
             .muxer_to_value
             .insert(sig.name().clone(), muxer_value);
-        Some(muxer_value.into())
-    } else if let Some(val_desc) = maybe_value_description {
-        Some(val_desc.as_str().into())
+        (muxer_value.into(), false)
     } else if sig.signal_size == 1 {
-        raw.as_bool()
+        (raw.as_bool()?, false)
     } else if is_float(sig) || typ == SignalValueType::F32 || typ == SignalValueType::F64 {
         // Scaling/offset floats always promote value to f64
         raw.promote_to_f64();
-        raw.as_scaled_float(sig.factor, sig.offset, sig.min, sig.max)
+        raw.as_scaled_float(sig.factor, sig.offset, range, clamp)?
     } else {
-        raw.as_scaled_int(sig.factor, sig.offset, sig.min, sig.max)
+        raw.as_scaled_int(sig.factor, sig.offset, range, clamp)?
+    };
+
+    Some(SignalValue {
+        physical,
+        out_of_range,
+        raw: raw_val,
+        label,
+        multiplexor: maybe_muxer_value.is_some(),
+    })
+}
+
+/// The signal's range, DBC files commonly use `[0|0]` for signals without one
+fn signal_range(min: f64, max: f64) -> Option<RangeInclusive<f64>> {
+    if (min == 0.0 && max == 0.0) || min > max || min.is_nan() || max.is_nan() {
+        None
+    } else {
+        Some(min..=max)
+    }
+}
+
+/// Integer physical values are logged as 64-bit integers where they fit
+fn int_attr_val(v: i128) -> AttrVal {
+    match i64::try_from(v) {
+        Ok(v) => v.into(),
+        Err(_) => v.into(),
     }
 }
 
 fn parse_raw_val(sig: &Signal, typ: SignalValueType, data: &[u8]) -> Option<RawVal> {
     let (bit_start, bit_end) = signal_start_end_bit(sig, data.len())?;
+    if sig.signal_size > 64 && matches!(typ, SignalValueType::Signed | SignalValueType::Unsigned) {
+        // Most significant bit first
+        let bits: Vec<bool> = if sig.byte_order() == &ByteOrder::LittleEndian {
+            let bits = data.try_view_bits::<Lsb0>().ok()?;
+            bits[bit_start..bit_end].iter().by_vals().rev().collect()
+        } else {
+            let bits = data.try_view_bits::<Msb0>().ok()?;
+            bits[bit_start..bit_end].iter().by_vals().collect()
+        };
+        let signed = typ == SignalValueType::Signed;
+        return Some(RawVal::Wide {
+            bytes: pack_bits(&bits, signed),
+            signed,
+        });
+    }
     let raw = if sig.byte_order() == &ByteOrder::LittleEndian {
         let bits = data.try_view_bits::<Lsb0>().ok()?;
         match typ {
@@ -507,15 +1894,68 @@ fn parse_raw_val(sig: &Signal, typ: SignalValueType, data: &[u8]) -> Option<RawV
     Some(raw)
 }
 
+/// Pack most significant bit first bits into big-endian bytes, sign-extending signed values
+fn pack_bits(bits: &[bool], signed: bool) -> Vec<u8> {
+    let fill = signed && bits.first().copied().unwrap_or(false);
+    let pad = (8 - (bits.len() & 0x7)) & 0x7;
+    let mut padded = vec![fill; pad];
+    padded.extend_from_slice(bits);
+    padded
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0_u8, |b, bit| (b << 1) | u8::from(*bit)))
+        .collect()
+}
+
 #[derive(Debug)]
 enum RawVal {
     I64(i64),
     U64(u64),
     F32(f32),
     F64(f64),
+    /// Integer signals wider than 64 bits, as big-endian two's complement bytes
+    Wide {
+        bytes: Vec<u8>,
+        signed: bool,
+    },
 }
 
 impl RawVal {
+    fn as_attr_val(&self) -> AttrVal {
+        match self {
+            RawVal::I64(v) => (*v).into(),
+            RawVal::U64(v) => (*v).into(),
+            RawVal::F32(v) => (*v).into(),
+            RawVal::F64(v) => (*v).into(),
+            RawVal::Wide { bytes, .. } => match self.as_i128() {
+                Some(v) => int_attr_val(v),
+                None => hex(bytes).into(),
+            },
+        }
+    }
+
+    /// Integer values, when they fit in an `i128`
+    fn as_i128(&self) -> Option<i128> {
+        match self {
+            RawVal::I64(v) => Some(i128::from(*v)),
+            RawVal::U64(v) => Some(i128::from(*v)),
+            RawVal::Wide { bytes, signed } => {
+                let negative = *signed && bytes.first().is_some_and(|b| b & 0x80 != 0);
+                let fill = if negative { 0xFF } else { 0x00 };
+                // Leading bytes beyond 128 bits may only be sign extension
+                let (extra, bytes) = bytes.split_at(bytes.len().saturating_sub(16));
+                if extra.iter().any(|b| *b != fill) {
+                    return None;
+                }
+                let mut buf = [fill; 16];
+                buf[16 - bytes.len()..].copy_from_slice(bytes);
+                let v = i128::from_be_bytes(buf);
+                // Unsigned values with the top bit set don't fit
+                ((v < 0) == negative).then_some(v)
+            }
+            RawVal::F32(_) | RawVal::F64(_) => None,
+        }
+    }
+
     fn as_bool(&self) -> Option<AttrVal> {
         Some(match self {
             RawVal::I64(v) => (*v != 0).into(),
@@ -530,54 +1970,68 @@ impl RawVal {
             RawVal::U64(v) => *v as f64,
             RawVal::F32(v) => *v as f64,
             RawVal::F64(v) => *v,
+            RawVal::Wide { .. } => match self.as_i128() {
+                Some(v) => v as f64,
+                None => return,
+            },
         };
         *self = RawVal::F64(f);
     }
 
-    fn as_scaled_float(&self, scale: f64, offset: f64, min: f64, max: f64) -> Option<AttrVal> {
+    fn as_scaled_float(
+        &self,
+        factor: f64,
+        offset: f64,
+        range: Option<RangeInclusive<f64>>,
+        clamp: bool,
+    ) -> Option<(AttrVal, bool)> {
         let f = match self {
             RawVal::F32(v) => *v as f64,
             RawVal::F64(v) => *v,
             _ => return None,
         };
-        let sf = (f * scale) + offset;
-        if min == 0.0 && max == 0.0 {
-            Some(AttrVal::from(sf))
-        } else {
-            Some(AttrVal::from(sf.clamp(min, max)))
-        }
+        let val = (f * factor) + offset;
+        // Allow for rounding error, e.g. 3 * 0.1 with a maximum of 0.3, by half a raw step
+        let tolerance = factor.abs() / 2.0;
+        Some(match range {
+            Some(range) if val < range.start() - tolerance || val > range.end() + tolerance => {
+                let val = if clamp {
+                    val.clamp(*range.start(), *range.end())
+                } else {
+                    val
+                };
+                (val.into(), true)
+            }
+            _ => (val.into(), false),
+        })
     }
 
-    fn as_scaled_int(&self, scale: f64, offset: f64, min: f64, max: f64) -> Option<AttrVal> {
-        match self {
-            RawVal::I64(v) => {
-                let s = scale as i64;
-                let o = offset as i64;
-                let min = min as i64;
-                let max = max as i64;
-                v.checked_mul(s).and_then(|n| n.checked_add(o)).map(|n| {
-                    if min == 0 && max == 0 {
-                        n.into()
-                    } else {
-                        n.clamp(min, max).into()
-                    }
-                })
-            }
-            RawVal::U64(v) => {
-                let s = scale as u64;
-                let o = offset as u64;
-                let min = min as u64;
-                let max = max as u64;
-                v.checked_mul(s).and_then(|n| n.checked_add(o)).map(|n| {
-                    if min == 0 && max == 0 {
-                        n.into()
-                    } else {
-                        n.clamp(min, max).into()
-                    }
-                })
+    /// Integer factors and offsets are applied exactly, so that any raw value and offset sign
+    /// produce the correct physical value
+    fn as_scaled_int(
+        &self,
+        factor: f64,
+        offset: f64,
+        range: Option<RangeInclusive<f64>>,
+        clamp: bool,
+    ) -> Option<(AttrVal, bool)> {
+        let val = self
+            .as_i128()?
+            .checked_mul(factor as i128)?
+            .checked_add(offset as i128)?;
+        // The integers within the range
+        let range = range.map(|r| (r.start().ceil() as i128, r.end().floor() as i128));
+        Some(match range {
+            Some((min, max)) if val < min || val > max => {
+                let val = if clamp && min <= max {
+                    val.clamp(min, max)
+                } else {
+                    val
+                };
+                (int_attr_val(val), true)
             }
-            _ => None,
-        }
+            _ => (int_attr_val(val), false),
+        })
     }
 }
 
@@ -628,3 +2082,1303 @@ fn le_start_end_bit(sig: &Signal) -> Option<(usize, usize)> {
     let end_bit = sig.start_bit.checked_add(sig.signal_size)?;
     Some((start_bit as usize, end_bit as usize))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{convert::TimelineKey, dbc::Dbc, IsoTpConfig};
+    use socketcan::{CanDataFrame, CanFdFrame, ExtendedId, StandardId};
+
+    const DBC_MUX: &str = r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: ECU
+
+BO_ 100 Simple: 2 ECU
+ SG_ Mux M : 0|8@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ A m0 : 8|8@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ B m1 : 8|8@1+ (1,0) [0|0] "" Vector__XXX
+
+BO_ 200 Extended: 4 ECU
+ SG_ Nested m0 : 24|8@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ Other m0 : 16|8@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ Ranged m0 : 16|8@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ Inner m1M : 8|8@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ Outer M : 0|8@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ Plain : 24|8@1+ (1,0) [0|0] "" Vector__XXX
+
+SG_MUL_VAL_ 200 Nested Inner 5-5, 7-9;
+SG_MUL_VAL_ 200 Other Inner 0-0;
+SG_MUL_VAL_ 200 Ranged Outer 2-4, 10-12;
+SG_MUL_VAL_ 200 Inner Outer 1-1;
+"#;
+
+    fn parse_frame(parser: &mut CanParser, id: u16, data: &[u8]) -> ParsedCanFrame {
+        let frame = CanDataFrame::new(StandardId::new(id).unwrap(), data).unwrap();
+        parser.parse(&CanAnyFrame::Normal(frame), None).unwrap()
+    }
+
+    fn signals(pcf: &ParsedCanFrame, names: &[&str]) -> Vec<(String, AttrVal)> {
+        let mut sigs: Vec<_> = names
+            .iter()
+            .filter_map(|n| {
+                pcf.attrs
+                    .get(&AttrKey::from(format!("event.{n}")))
+                    .map(|v| (n.to_string(), v.clone()))
+            })
+            .collect();
+        sigs.sort_by(|a, b| a.0.cmp(&b.0));
+        sigs
+    }
+
+    fn sig(name: &str, val: u64) -> (String, AttrVal) {
+        (name.to_owned(), val.into())
+    }
+
+    #[test]
+    fn simple_multiplexing() {
+        let dbc = DBC::try_from(DBC_MUX).unwrap();
+        let mut parser = CanParser::new(&CommonConfig::default(), Some(&dbc)).unwrap();
+        let names = &["Mux", "A", "B"];
+
+        let pcf = parse_frame(&mut parser, 100, &[0, 7]);
+        assert_eq!(signals(&pcf, names), vec![sig("A", 7), sig("Mux", 0)]);
+
+        let pcf = parse_frame(&mut parser, 100, &[1, 8]);
+        assert_eq!(signals(&pcf, names), vec![sig("B", 8), sig("Mux", 1)]);
+
+        let pcf = parse_frame(&mut parser, 100, &[2, 9]);
+        assert_eq!(signals(&pcf, names), vec![sig("Mux", 2)]);
+    }
+
+    #[test]
+    fn extended_multiplexing() {
+        let dbc = DBC::try_from(DBC_MUX).unwrap();
+        let mut parser = CanParser::new(&CommonConfig::default(), Some(&dbc)).unwrap();
+        let names = &["Outer", "Inner", "Ranged", "Nested", "Other", "Plain"];
+
+        // Ranged is active for 2..=4 and 10..=12
+        for outer in [2, 3, 4, 10, 12] {
+            let pcf = parse_frame(&mut parser, 200, &[outer, 5, 6, 7]);
+            assert_eq!(
+                signals(&pcf, names),
+                vec![
+                    sig("Outer", outer.into()),
+                    sig("Plain", 7),
+                    sig("Ranged", 6)
+                ]
+            );
+        }
+        for outer in [0, 5, 9, 13] {
+            let pcf = parse_frame(&mut parser, 200, &[outer, 5, 6, 7]);
+            assert_eq!(
+                signals(&pcf, names),
+                vec![sig("Outer", outer.into()), sig("Plain", 7)]
+            );
+        }
+
+        // Inner is itself multiplexed by Outer, and multiplexes Nested and Other
+        let pcf = parse_frame(&mut parser, 200, &[1, 0, 6, 7]);
+        assert_eq!(
+            signals(&pcf, names),
+            vec![
+                sig("Inner", 0),
+                sig("Other", 6),
+                sig("Outer", 1),
+                sig("Plain", 7)
+            ]
+        );
+        for inner in [5, 7, 8, 9] {
+            let pcf = parse_frame(&mut parser, 200, &[1, inner, 6, 7]);
+            assert_eq!(
+                signals(&pcf, names),
+                vec![
+                    sig("Inner", inner.into()),
+                    sig("Nested", 7),
+                    sig("Outer", 1),
+                    sig("Plain", 7)
+                ]
+            );
+        }
+        let pcf = parse_frame(&mut parser, 200, &[1, 6, 6, 7]);
+        assert_eq!(
+            signals(&pcf, names),
+            vec![sig("Inner", 6), sig("Outer", 1), sig("Plain", 7)]
+        );
+
+        // Nested signals aren't active when their multiplexor isn't
+        let pcf = parse_frame(&mut parser, 200, &[0, 5, 6, 7]);
+        assert_eq!(signals(&pcf, names), vec![sig("Outer", 0), sig("Plain", 7)]);
+    }
+
+    #[test]
+    fn physical_values() {
+        struct Case {
+            signal: &'static str,
+            data: [u8; 8],
+            clamp: bool,
+            val: AttrVal,
+            out_of_range: bool,
+        }
+        let case = |signal, data: u64, clamp, val: AttrVal, out_of_range| Case {
+            signal,
+            data: data.to_le_bytes(),
+            clamp,
+            val,
+            out_of_range,
+        };
+        let cases = [
+            // Unsigned with a negative offset
+            case("0|8@1+ (1,-40) [-40|215]", 0, true, (-40_i64).into(), false),
+            case("0|8@1+ (1,-40) [-40|215]", 255, true, 215_i64.into(), false),
+            case(
+                "0|8@1+ (10,-1000) [-1000|1550]",
+                1,
+                true,
+                (-990_i64).into(),
+                false,
+            ),
+            // Signed
+            case(
+                "0|8@1- (1,0) [-128|127]",
+                0xFF,
+                true,
+                (-1_i64).into(),
+                false,
+            ),
+            case(
+                "0|8@1- (2,-10) [-266|244]",
+                0x80,
+                true,
+                (-266_i64).into(),
+                false,
+            ),
+            case("0|16@1- (3,5) [0|0]", 0xFFFE, true, (-1_i64).into(), false),
+            // Fractional factor or offset
+            case(
+                "0|8@1+ (0.5,-20) [-20|107.5]",
+                3,
+                true,
+                (-18.5).into(),
+                false,
+            ),
+            case(
+                "0|8@1- (0.25,0) [-32|31.75]",
+                0xFC,
+                true,
+                (-1.0).into(),
+                false,
+            ),
+            case("0|8@1+ (1,0.5) [0.5|255.5]", 2, true, 2.5.into(), false),
+            // Range endpoints that aren't exact in floating point
+            case("0|8@1+ (0.1,0) [0|0.3]", 3, true, (3.0 * 0.1).into(), false),
+            case(
+                "0|8@1- (0.1,0) [-0.3|0]",
+                0xFD,
+                true,
+                (-3.0 * 0.1).into(),
+                false,
+            ),
+            case("0|8@1+ (0.1,0) [0|0.3]", 4, true, 0.3.into(), true),
+            // Out of range
+            case("0|8@1+ (1,0) [0|100]", 200, true, 100_i64.into(), true),
+            case("0|8@1+ (1,0) [0|100]", 200, false, 200_i64.into(), true),
+            case("0|8@1- (1,0) [-10|10]", 0xEC, true, (-10_i64).into(), true),
+            case("0|8@1- (1,0) [-10|10]", 0xEC, false, (-20_i64).into(), true),
+            case("0|8@1+ (1,-40) [0|100]", 10, true, 0_i64.into(), true),
+            case("0|8@1+ (0.5,0) [0|10]", 40, true, 10.0.into(), true),
+            case("0|8@1+ (0.5,0) [0|10]", 40, false, 20.0.into(), true),
+            case("0|8@1+ (1,0) [0.5|0.7]", 1, true, 1_i64.into(), true),
+            // No range
+            case("0|8@1+ (1,0) [0|0]", 255, true, 255_i64.into(), false),
+            // 64-bit values
+            case(
+                "0|64@1+ (1,0) [0|0]",
+                u64::MAX,
+                true,
+                u64::MAX.into(),
+                false,
+            ),
+            case(
+                "0|64@1+ (1,-1) [0|0]",
+                u64::MAX,
+                true,
+                (u64::MAX - 1).into(),
+                false,
+            ),
+            case(
+                "0|64@1- (2,0) [0|0]",
+                i64::MIN as u64,
+                true,
+                (i64::MIN as i128 * 2).into(),
+                false,
+            ),
+            // Big endian
+            Case {
+                signal: "7|16@0- (1,-100) [0|0]",
+                data: [0xFF, 0xFE, 0, 0, 0, 0, 0, 0],
+                clamp: true,
+                val: (-102_i64).into(),
+                out_of_range: false,
+            },
+        ];
+
+        for c in cases {
+            let dbc = DBC::try_from(
+                format!(
+                    "VERSION \"\"\n\nNS_ :\n\nBS_:\n\nBU_: ECU\n\n\
+                     BO_ 256 M: 8 ECU\n SG_ S : {} \"\" Vector__XXX\n",
+                    c.signal
+                )
+                .as_str(),
+            )
+            .unwrap();
+            let cfg = CommonConfig {
+                clamp_signal_values: Some(c.clamp),
+                ..Default::default()
+            };
+            let mut parser = CanParser::new(&cfg, Some(&dbc)).unwrap();
+            let pcf = parse_frame(&mut parser, 256, &c.data);
+            assert_eq!(attr(&pcf, "S"), Some(&c.val), "{}", c.signal);
+            assert_eq!(
+                attr(&pcf, "S.out_of_range").is_some(),
+                c.out_of_range,
+                "{}",
+                c.signal
+            );
+        }
+    }
+
+    #[test]
+    fn signal_values() {
+        let dbc = DBC::try_from(
+            r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: ECU
+
+BO_ 256 Transmission: 2 ECU
+ SG_ Gear : 0|8@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ Temp : 8|8@1+ (1,-40) [-40|215] "C" Vector__XXX
+
+BO_ 512 Shifter: 1 ECU
+ SG_ Lever : 0|8@1+ (1,0) [0|0] "" Vector__XXX
+
+VAL_ 256 Gear 0 "Park" 1 "Reverse" 2 "Neutral" 3 "Drive" ;
+VAL_ 512 Lever 0 "Park" 3 "Drive" ;
+"#,
+        )
+        .unwrap();
+        let names = &[
+            "Gear",
+            "Gear.raw",
+            "Gear.label",
+            "Temp",
+            "Temp.raw",
+            "Lever",
+            "Lever.raw",
+            "Lever.label",
+        ];
+        let val = |name: &str, val: AttrVal| (name.to_owned(), val);
+
+        // The value description replaces the value by default
+        let mut parser = CanParser::new(&CommonConfig::default(), Some(&dbc)).unwrap();
+        let pcf = parse_frame(&mut parser, 256, &[3, 60]);
+        assert_eq!(
+            signals(&pcf, names),
+            vec![val("Gear", "Drive".into()), val("Temp", 20_i64.into())]
+        );
+
+        let cfg = CommonConfig {
+            signal_values: Some(vec![SignalValueKind::Physical, SignalValueKind::Raw]),
+            signal_value_overrides: Some(vec![
+                SignalValuesConfig {
+                    message: Some("Transmission".to_owned()),
+                    values: vec![SignalValueKind::Physical, SignalValueKind::Label],
+                    ..Default::default()
+                },
+                SignalValuesConfig {
+                    message: Some("Transmission".to_owned()),
+                    signal: Some("Temp".to_owned()),
+                    values: vec![SignalValueKind::Raw],
+                },
+            ]),
+            ..Default::default()
+        };
+        let mut parser = CanParser::new(&cfg, Some(&dbc)).unwrap();
+        let pcf = parse_frame(&mut parser, 256, &[3, 60]);
+        assert_eq!(
+            signals(&pcf, names),
+            vec![
+                val("Gear", 3_i64.into()),
+                val("Gear.label", "Drive".into()),
+                val("Temp.raw", 60_u64.into()),
+            ]
+        );
+        // Labels are only logged for described values
+        let pcf = parse_frame(&mut parser, 256, &[7, 60]);
+        assert_eq!(attr(&pcf, "Gear"), Some(&7_i64.into()));
+        assert_eq!(attr(&pcf, "Gear.label"), None);
+
+        let pcf = parse_frame(&mut parser, 512, &[0]);
+        assert_eq!(
+            signals(&pcf, names),
+            vec![val("Lever", 0_i64.into()), val("Lever.raw", 0_u64.into())]
+        );
+
+        let cfg = CommonConfig {
+            signal_value_overrides: Some(vec![SignalValuesConfig {
+                message: Some("Missing".to_owned()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        assert!(CanParser::new(&cfg, Some(&dbc)).is_err());
+    }
+
+    #[test]
+    fn wide_signals() {
+        let dbc = DBC::try_from(
+            r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: ECU
+
+BO_ 1024 Payload: 64 ECU
+ SG_ Big : 8|72@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ Neg : 80|80@1- (2,0) [0|0] "" Vector__XXX
+ SG_ Be : 199|16@0+ (1,0) [0|0] "" Vector__XXX
+ SG_ Vin : 215|136@0+ (1,0) [0|0] "" Vector__XXX
+ SG_ Top : 391|128@0+ (1,0) [0|0] "" Vector__XXX
+"#,
+        )
+        .unwrap();
+        let mut parser = CanParser::new(&CommonConfig::default(), Some(&dbc)).unwrap();
+        let mut data = [0_u8; 64];
+        data[1] = 0x01;
+        data[9] = 0x01;
+        data[10..20].fill(0xFF);
+        data[24..26].copy_from_slice(&[0x12, 0x34]);
+        data[26..43].copy_from_slice(b"WDB1234567890ABCD");
+        data[48..64].fill(0xFF);
+        let frame = CanFdFrame::new(StandardId::new(1024).unwrap(), &data).unwrap();
+        let pcf = parser.parse(&CanAnyFrame::Fd(frame), None).unwrap();
+
+        assert_eq!(attr(&pcf, "Big"), Some(&((1_i128 << 64) + 1).into()));
+        assert_eq!(attr(&pcf, "Neg"), Some(&(-2_i64).into()));
+        assert_eq!(attr(&pcf, "Be"), Some(&0x1234_i64.into()));
+        assert_eq!(attr(&pcf, "Vin"), Some(&hex(b"WDB1234567890ABCD").into()));
+        assert_eq!(attr(&pcf, "Top"), Some(&"ff".repeat(16).into()));
+    }
+
+    const DBC_J1939: &str = r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: Engine Brakes
+
+BO_ 2364540158 EEC1: 8 Engine
+ SG_ EngineSpeed : 24|16@1+ (0.125,0) [0|8031.875] "rpm" Vector__XXX
+
+BO_ 2566843646 VI: 8 Engine
+ SG_ VinFirst : 0|8@1+ (1,0) [0|255] "" Vector__XXX
+
+BA_DEF_ BU_ "NmStationAddress" INT 0 255;
+BA_DEF_ SG_ "SPN" INT 0 524287;
+BA_ "NmStationAddress" BU_ Engine 0;
+BA_ "NmStationAddress" BU_ Brakes 11;
+BA_ "SPN" SG_ 2364540158 EngineSpeed 190;
+"#;
+
+    fn parse_ext_frame(parser: &mut CanParser, id: u32, data: &[u8]) -> ParsedCanFrame {
+        let frame = CanDataFrame::new(ExtendedId::new(id).unwrap(), data).unwrap();
+        parser.parse(&CanAnyFrame::Normal(frame), None).unwrap()
+    }
+
+    fn attr<'a>(pcf: &'a ParsedCanFrame, key: &str) -> Option<&'a AttrVal> {
+        pcf.attrs.get(&AttrKey::from(format!("event.{key}")))
+    }
+
+    #[test]
+    fn j1939() {
+        let dbc = DBC::try_from(DBC_J1939).unwrap();
+        let cfg = CommonConfig {
+            j1939: Some(true),
+            ..Default::default()
+        };
+        let mut parser = CanParser::new(&cfg, Some(&dbc)).unwrap();
+        let data = [0xFF, 0xFF, 0xFF, 0x40, 0x1F, 0xFF, 0xFF, 0xFF];
+
+        let pcf = parse_ext_frame(&mut parser, 0x0CF00400, &data);
+        assert_eq!(pcf.event_name(), "EEC1");
+        assert_eq!(attr(&pcf, "j1939.pgn"), Some(&61444_u32.into()));
+        assert_eq!(attr(&pcf, "j1939.priority"), Some(&3_u64.into()));
+        assert_eq!(attr(&pcf, "j1939.source_address"), Some(&0_u64.into()));
+        assert_eq!(
+            attr(&pcf, "j1939.destination_address"),
+            Some(&255_u64.into())
+        );
+        assert_eq!(attr(&pcf, "EngineSpeed"), Some(&1000.0.into()));
+        assert_eq!(attr(&pcf, "EngineSpeed.spn"), Some(&190_u64.into()));
+        assert_eq!(pcf.transmitter_node.as_deref(), Some("Engine"));
+        assert_eq!(pcf.source_address, Some(0));
+        assert_eq!(
+            TimelineKey::for_parsed_frame(&pcf, &cfg).timeline_name(),
+            "Engine"
+        );
+
+        // Same PGN from another node
+        let pcf = parse_ext_frame(&mut parser, 0x0CF0040B, &data);
+        assert_eq!(pcf.event_name(), "EEC1");
+        assert_eq!(attr(&pcf, "j1939.source_address"), Some(&11_u64.into()));
+        assert_eq!(pcf.transmitter_node.as_deref(), Some("Brakes"));
+
+        // Same PGN from an unknown node
+        let pcf = parse_ext_frame(&mut parser, 0x0CF00417, &data);
+        assert_eq!(pcf.event_name(), "EEC1");
+        assert_eq!(pcf.transmitter_node, None);
+        assert_eq!(
+            TimelineKey::for_parsed_frame(&pcf, &cfg).timeline_name(),
+            "canbus_sa_0x17"
+        );
+
+        // Unknown PGN
+        let pcf = parse_ext_frame(&mut parser, 0x18FEEE00, &data);
+        assert_eq!(pcf.event_name(), "pgn_65262");
+        assert_eq!(pcf.transmitter_node.as_deref(), Some("Engine"));
+
+        // Not in J1939 mode, only exact IDs match
+        let mut parser = CanParser::new(&CommonConfig::default(), Some(&dbc)).unwrap();
+        let pcf = parse_ext_frame(&mut parser, 0x0CF00400, &data);
+        assert_eq!(pcf.msg_name, None);
+        assert_eq!(attr(&pcf, "j1939.pgn"), None);
+        let pcf = parse_ext_frame(&mut parser, 0x0CF004FE, &data);
+        assert_eq!(pcf.event_name(), "EEC1");
+    }
+
+    #[test]
+    fn j1939_transport_protocol() {
+        let dbc = DBC::try_from(DBC_J1939).unwrap();
+        let cfg = CommonConfig {
+            j1939: Some(true),
+            ..Default::default()
+        };
+        let mut parser = CanParser::new(&cfg, Some(&dbc)).unwrap();
+        let mut parse_all = |id: u32, data: &[u8], nanoseconds: i64| {
+            let frame = CanDataFrame::new(ExtendedId::new(id).unwrap(), data).unwrap();
+            let ts = Timestamp {
+                seconds: 1,
+                nanoseconds,
+            };
+            parser
+                .parse_all(&CanAnyFrame::Normal(frame), Some(ts), Some("can0"))
+                .unwrap()
+        };
+
+        let pcfs = parse_all(0x1CECFF00, &[32, 10, 0, 2, 0xFF, 0xEC, 0xFE, 0x00], 0);
+        assert_eq!(pcfs.len(), 1);
+        assert_eq!(pcfs[0].event_name(), "pgn_60416");
+        assert_eq!(pcfs[0].interface.as_deref(), Some("can0"));
+        assert_eq!(parse_all(0x1CEBFF00, b"\x011234567", 50_000_000).len(), 1);
+        let pcfs = parse_all(0x1CEBFF00, b"\x02890\xFF\xFF\xFF\xFF", 100_000_000);
+        assert_eq!(pcfs.len(), 2);
+        assert_eq!(pcfs[0].event_name(), "pgn_60160");
+
+        let pcf = &pcfs[1];
+        assert_eq!(pcf.event_name(), "VI");
+        assert_eq!(pcf.interface.as_deref(), Some("can0"));
+        assert_eq!(pcf.transmitter_node.as_deref(), Some("Engine"));
+        assert_eq!(attr(pcf, "j1939.pgn"), Some(&65260_u32.into()));
+        assert_eq!(attr(pcf, "j1939.tp.type"), Some(&"bam".into()));
+        assert_eq!(attr(pcf, "j1939.tp.packet_count"), Some(&2_u64.into()));
+        assert_eq!(attr(pcf, "j1939.tp.size"), Some(&10_u64.into()));
+        assert_eq!(
+            attr(pcf, "j1939.tp.duration"),
+            Some(&Nanoseconds::from(100_000_000).into())
+        );
+        assert_eq!(attr(pcf, "j1939.tp.aborted"), None);
+        assert_eq!(attr(pcf, "VinFirst"), Some(&(b'1' as u64).into()));
+
+        // Left incomplete
+        parse_all(
+            0x1CECFF00,
+            &[32, 10, 0, 2, 0xFF, 0xEC, 0xFE, 0x00],
+            200_000_000,
+        );
+        let pcfs = parser.finish();
+        assert_eq!(pcfs.len(), 1);
+        assert_eq!(pcfs[0].event_name(), "VI");
+        assert_eq!(
+            attr(&pcfs[0], "j1939.tp.aborted"),
+            Some(&"incomplete".into())
+        );
+        assert_eq!(attr(&pcfs[0], "VinFirst"), None);
+    }
+
+    #[test]
+    fn j1939_diagnostic_messages() {
+        let dbc = DBC::try_from(DBC_J1939).unwrap();
+        let cfg = CommonConfig {
+            j1939: Some(true),
+            ..Default::default()
+        };
+        let mut parser = CanParser::new(&cfg, Some(&dbc)).unwrap();
+        let mut parse_all = |id: u32, data: &[u8]| {
+            let frame = CanDataFrame::new(ExtendedId::new(id).unwrap(), data).unwrap();
+            parser
+                .parse_all(&CanAnyFrame::Normal(frame), None, Some("can0"))
+                .unwrap()
+        };
+
+        // Single frame DM1
+        let pcfs = parse_all(
+            0x18FECA00,
+            &[0x40, 0xFF, 0x64, 0x00, 0x03, 0x01, 0xFF, 0xFF],
+        );
+        assert_eq!(pcfs.len(), 2);
+        assert_eq!(pcfs[0].event_name(), "pgn_65226");
+        assert_eq!(attr(&pcfs[0], "j1939.dm.lamp.mil"), Some(&"on".into()));
+        assert_eq!(attr(&pcfs[0], "j1939.dm.lamp.awl"), Some(&"off".into()));
+        assert_eq!(attr(&pcfs[0], "j1939.dm.dtc_count"), Some(&1_u64.into()));
+        assert_eq!(attr(&pcfs[0], "j1939.dm.dtc.0.spn"), Some(&100_u32.into()));
+        assert_eq!(attr(&pcfs[0], "j1939.dm.dtc.0.fmi"), Some(&3_u64.into()));
+        let pcf = &pcfs[1];
+        assert_eq!(pcf.event_name(), "dtc_appeared");
+        assert_eq!(pcf.transmitter_node.as_deref(), Some("Engine"));
+        assert_eq!(pcf.source_address, Some(0));
+        assert_eq!(pcf.interface.as_deref(), Some("can0"));
+        assert_eq!(attr(pcf, "j1939.dm"), Some(&"dm1".into()));
+        assert_eq!(attr(pcf, "j1939.dtc.spn"), Some(&100_u32.into()));
+        assert_eq!(attr(pcf, "j1939.dtc.fmi"), Some(&3_u64.into()));
+        assert_eq!(attr(pcf, "j1939.dtc.occurrence_count"), Some(&1_u64.into()));
+        assert_eq!(
+            attr(pcf, "j1939.dtc.conversion_method"),
+            Some(&false.into())
+        );
+        assert_eq!(attr(pcf, "j1939.source_address"), Some(&0_u64.into()));
+
+        // Same DTCs, nothing changed
+        let pcfs = parse_all(
+            0x18FECA00,
+            &[0x40, 0xFF, 0x64, 0x00, 0x03, 0x02, 0xFF, 0xFF],
+        );
+        assert_eq!(pcfs.len(), 1);
+
+        // Multi-packet DM1, with the first DTC cleared and three new ones
+        assert_eq!(
+            parse_all(0x1CECFF00, &[32, 14, 0, 2, 0xFF, 0xCA, 0xFE, 0x00]).len(),
+            1
+        );
+        assert_eq!(
+            parse_all(0x1CEBFF00, &[1, 0x04, 0xFF, 0x6E, 0x00, 0x00, 0x01, 0xBE]).len(),
+            1
+        );
+        let pcfs = parse_all(0x1CEBFF00, &[2, 0x00, 0x04, 0x02, 0x00, 0xF0, 0xFF, 0x81]);
+        let names: Vec<_> = pcfs.iter().map(|p| p.event_name()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "pgn_60160",
+                "pgn_65226",
+                "dtc_cleared",
+                "dtc_appeared",
+                "dtc_appeared",
+                "dtc_appeared"
+            ]
+        );
+        assert_eq!(attr(&pcfs[1], "j1939.dm.lamp.awl"), Some(&"on".into()));
+        assert_eq!(attr(&pcfs[1], "j1939.dm.dtc_count"), Some(&3_u64.into()));
+        assert_eq!(attr(&pcfs[2], "j1939.dtc.spn"), Some(&100_u32.into()));
+        assert_eq!(attr(&pcfs[3], "j1939.dtc.spn"), Some(&110_u32.into()));
+        assert_eq!(attr(&pcfs[4], "j1939.dtc.spn"), Some(&190_u32.into()));
+        assert_eq!(attr(&pcfs[5], "j1939.dtc.spn"), Some(&520192_u32.into()));
+        assert_eq!(
+            attr(&pcfs[5], "j1939.dtc.conversion_method"),
+            Some(&true.into())
+        );
+    }
+
+    #[test]
+    fn isotp() {
+        let cfg = CommonConfig {
+            isotp: Some(vec![IsoTpConfig {
+                name: Some("engine".to_owned()),
+                tx_id: 0x7E0,
+                rx_id: 0x7E8,
+                uds: Some(false),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        let mut parser = CanParser::new(&cfg, None).unwrap();
+        let mut parse_all = |id: u16, data: &[u8]| {
+            let frame = CanDataFrame::new(StandardId::new(id).unwrap(), data).unwrap();
+            parser
+                .parse_all(&CanAnyFrame::Normal(frame), None, Some("can0"))
+                .unwrap()
+        };
+
+        let pcfs = parse_all(0x7E0, &[0x02, 0x10, 0x03, 0, 0, 0, 0, 0]);
+        assert_eq!(pcfs.len(), 2);
+        let pcf = &pcfs[1];
+        assert_eq!(pcf.event_name(), "isotp_pdu");
+        assert_eq!(pcf.interface.as_deref(), Some("can0"));
+        assert_eq!(attr(pcf, "isotp.name"), Some(&"engine".into()));
+        assert_eq!(attr(pcf, "isotp.direction"), Some(&"request".into()));
+        assert_eq!(attr(pcf, "isotp.payload"), Some(&"1003".into()));
+        assert_eq!(attr(pcf, "isotp.frame_count"), Some(&1_u64.into()));
+
+        assert_eq!(
+            parse_all(0x7E8, &[0x10, 0x09, 0x62, 0xF1, 0x90, 0x57, 0x44, 0x42]).len(),
+            1
+        );
+        assert_eq!(parse_all(0x7E0, &[0x30, 0, 0, 0, 0, 0, 0, 0]).len(), 1);
+        let pcfs = parse_all(0x7E8, &[0x21, 0x31, 0x32, 0x33, 0x55, 0x55, 0x55, 0x55]);
+        assert_eq!(pcfs.len(), 2);
+        let pcf = &pcfs[1];
+        assert_eq!(attr(pcf, "isotp.direction"), Some(&"response".into()));
+        assert_eq!(attr(pcf, "isotp.length"), Some(&9_u64.into()));
+        assert_eq!(
+            attr(pcf, "isotp.payload"),
+            Some(&"62f190574442313233".into())
+        );
+        assert_eq!(attr(pcf, "isotp.block_size"), Some(&0_u64.into()));
+        assert_eq!(attr(pcf, "isotp.error"), None);
+
+        assert_eq!(
+            parse_all(0x7E8, &[0x10, 0x09, 0x62, 0xF1, 0x90, 0, 0, 0]).len(),
+            1
+        );
+        assert_eq!(parse_all(0x7E0, &[0x30, 0, 0, 0, 0, 0, 0, 0]).len(), 1);
+        let pcfs = parse_all(0x7E8, &[0x22, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(pcfs.len(), 2);
+        let pcf = &pcfs[1];
+        assert_eq!(
+            attr(pcf, "isotp.error"),
+            Some(&"wrong_sequence_number".into())
+        );
+        assert_eq!(
+            attr(pcf, "isotp.expected_sequence_number"),
+            Some(&1_u64.into())
+        );
+        assert_eq!(attr(pcf, "isotp.sequence_number"), Some(&2_u64.into()));
+        assert!(parser.finish().is_empty());
+    }
+
+    #[test]
+    fn isotp_extended_ids() {
+        let cfg = CommonConfig {
+            canopen: Some(true),
+            isotp: Some(vec![IsoTpConfig {
+                tx_id: 0x601,
+                rx_id: 0x581,
+                extended: Some(true),
+                uds: Some(false),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        let mut parser = CanParser::new(&cfg, None).unwrap();
+        let sf = [0x02, 0x10, 0x03, 0, 0, 0, 0, 0];
+
+        let frame = CanDataFrame::new(ExtendedId::new(0x601).unwrap(), &sf).unwrap();
+        let pcfs = parser
+            .parse_all(&CanAnyFrame::Normal(frame), None, None)
+            .unwrap();
+        assert_eq!(pcfs.len(), 2);
+        assert_eq!(pcfs[0].node_id, None);
+        assert_eq!(pcfs[1].event_name(), "isotp_pdu");
+        assert!(pcfs[1].is_extended);
+
+        // The standard ID is a CANopen SDO response
+        let frame = CanDataFrame::new(StandardId::new(0x581).unwrap(), &sf).unwrap();
+        let pcfs = parser
+            .parse_all(&CanAnyFrame::Normal(frame), None, None)
+            .unwrap();
+        assert_eq!(pcfs.len(), 1);
+        assert_eq!(pcfs[0].node_id, Some(1));
+
+        // Unless it's an ISO-TP connection's ID
+        let cfg = CommonConfig {
+            isotp: Some(vec![IsoTpConfig {
+                tx_id: 0x601,
+                rx_id: 0x581,
+                uds: Some(false),
+                ..Default::default()
+            }]),
+            ..cfg
+        };
+        let mut parser = CanParser::new(&cfg, None).unwrap();
+        let pcfs = parser
+            .parse_all(&CanAnyFrame::Normal(frame), None, None)
+            .unwrap();
+        assert_eq!(pcfs.len(), 2);
+        assert_eq!(pcfs[0].node_id, None);
+        assert!(!pcfs[1].is_extended);
+    }
+
+    #[test]
+    fn uds() {
+        let cfg = CommonConfig {
+            isotp: Some(vec![IsoTpConfig {
+                name: Some("engine".to_owned()),
+                tx_id: 0x7E0,
+                rx_id: 0x7E8,
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        let mut parser = CanParser::new(&cfg, None).unwrap();
+        let mut parse_pdu = |id: u16, data: &[u8], t_ms: i64| {
+            let frame = CanDataFrame::new(StandardId::new(id).unwrap(), data).unwrap();
+            let ts = Timestamp {
+                seconds: 0,
+                nanoseconds: t_ms * 1_000_000,
+            };
+            let mut pcfs = parser
+                .parse_all(&CanAnyFrame::Normal(frame), Some(ts), Some("can0"))
+                .unwrap();
+            assert_eq!(pcfs.len(), 2);
+            pcfs.remove(1)
+        };
+
+        let req = parse_pdu(0x7E0, &[0x04, 0x31, 0x01, 0xFF, 0x00], 0);
+        assert_eq!(req.event_name(), "uds.routine_control");
+        assert_eq!(req.transmitter_node.as_deref(), Some("tester"));
+        assert_eq!(attr(&req, "uds.type"), Some(&"request".into()));
+        assert_eq!(attr(&req, "uds.sub_function"), Some(&1_u64.into()));
+        assert_eq!(
+            attr(&req, "uds.sub_function_name"),
+            Some(&"start_routine".into())
+        );
+        assert_eq!(attr(&req, "uds.routine_id"), Some(&0xFF00_u64.into()));
+        let Some(Interaction::Request { nonce, .. }) = req.interaction else {
+            panic!("Missing request interaction");
+        };
+        assert_eq!(attr(&req, "nonce"), Some(&nonce.into()));
+
+        let pending = parse_pdu(0x7E8, &[0x03, 0x7F, 0x31, 0x78], 10);
+        assert_eq!(
+            pending.event_name(),
+            "uds.routine_control.negative_response"
+        );
+        assert_eq!(pending.transmitter_node.as_deref(), Some("engine"));
+        assert_eq!(
+            attr(&pending, "uds.nrc_name"),
+            Some(&"response_pending".into())
+        );
+        assert_eq!(attr(&pending, "uds.response_pending"), Some(&true.into()));
+        assert_eq!(
+            pending.interaction,
+            Some(Interaction::Response {
+                remote_nonce: nonce,
+                last: false
+            })
+        );
+
+        let resp = parse_pdu(0x7E8, &[0x05, 0x71, 0x01, 0xFF, 0x00, 0x00], 50);
+        assert_eq!(resp.event_name(), "uds.routine_control.positive_response");
+        assert_eq!(attr(&resp, "uds.data"), Some(&"00".into()));
+        assert_eq!(attr(&resp, "interaction.remote_nonce"), Some(&nonce.into()));
+        assert_eq!(
+            attr(&resp, "uds.response_time"),
+            Some(&Nanoseconds::from(50_000_000).into())
+        );
+        assert_eq!(
+            resp.interaction,
+            Some(Interaction::Response {
+                remote_nonce: nonce,
+                last: true
+            })
+        );
+
+        // No outstanding request
+        let resp = parse_pdu(0x7E8, &[0x03, 0x7F, 0x22, 0x31], 60);
+        assert_eq!(
+            resp.event_name(),
+            "uds.read_data_by_identifier.negative_response"
+        );
+        assert_eq!(attr(&resp, "uds.nrc"), Some(&0x31_u64.into()));
+        assert_eq!(resp.interaction, None);
+    }
+
+    #[test]
+    fn obd() {
+        let cfg = CommonConfig {
+            obd: Some(true),
+            ..Default::default()
+        };
+        let mut parser = CanParser::new(&cfg, None).unwrap();
+        let mut parse_all = |id: u32, data: &[u8]| {
+            let frame = if id > 0x7FF {
+                CanDataFrame::new(ExtendedId::new(id).unwrap(), data).unwrap()
+            } else {
+                CanDataFrame::new(StandardId::new(id as u16).unwrap(), data).unwrap()
+            };
+            parser
+                .parse_all(&CanAnyFrame::Normal(frame), None, None)
+                .unwrap()
+        };
+
+        let pcfs = parse_all(0x7DF, &[0x02, 0x01, 0x0D, 0x55, 0x55, 0x55, 0x55, 0x55]);
+        assert_eq!(pcfs.len(), 2);
+        let pcf = &pcfs[1];
+        assert_eq!(pcf.event_name(), "obd.mode01.vehicle_speed.request");
+        assert_eq!(pcf.transmitter_node.as_deref(), Some("tester"));
+        assert_eq!(attr(pcf, "obd.functional"), Some(&true.into()));
+        assert_eq!(attr(pcf, "obd.pid"), Some(&0x0D_u64.into()));
+
+        let pcfs = parse_all(0x7E9, &[0x03, 0x41, 0x0D, 0x32, 0x55, 0x55, 0x55, 0x55]);
+        assert_eq!(pcfs.len(), 2);
+        let pcf = &pcfs[1];
+        assert_eq!(pcf.event_name(), "obd.mode01.vehicle_speed");
+        assert_eq!(pcf.transmitter_node.as_deref(), Some("ecu_0x7e9"));
+        assert_eq!(attr(pcf, "obd.vehicle_speed"), Some(&50_u64.into()));
+        assert_eq!(attr(pcf, "obd.vehicle_speed.unit"), Some(&"km/h".into()));
+
+        // 29-bit, multi-frame VIN
+        let pcfs = parse_all(
+            0x18DAF110,
+            &[0x10, 0x14, 0x49, 0x02, 0x01, b'1', b'H', b'G'],
+        );
+        assert_eq!(pcfs.len(), 1);
+        assert_eq!(parse_all(0x18DA10F1, &[0x30, 0, 0, 0, 0, 0, 0, 0]).len(), 1);
+        assert_eq!(
+            parse_all(
+                0x18DAF110,
+                &[0x21, b'C', b'M', b'8', b'2', b'6', b'3', b'3']
+            )
+            .len(),
+            1
+        );
+        let pcfs = parse_all(
+            0x18DAF110,
+            &[0x22, b'A', b'0', b'0', b'4', b'3', b'5', b'2'],
+        );
+        assert_eq!(pcfs.len(), 2);
+        let pcf = &pcfs[1];
+        assert_eq!(pcf.event_name(), "obd.mode09.vin");
+        assert_eq!(pcf.transmitter_node.as_deref(), Some("ecu_0x18daf110"));
+        assert_eq!(attr(pcf, "obd.vin"), Some(&"1HGCM82633A004352".into()));
+
+        // UDS on the same IDs
+        let pcfs = parse_all(0x7E8, &[0x04, 0x62, 0xF1, 0x90, 0x01, 0, 0, 0]);
+        assert_eq!(
+            pcfs[1].event_name(),
+            "uds.read_data_by_identifier.positive_response"
+        );
+    }
+
+    #[test]
+    fn canopen() {
+        let cfg = CommonConfig {
+            canopen: Some(true),
+            ..Default::default()
+        };
+        let mut parser = CanParser::new(&cfg, None).unwrap();
+        let mut parse_all = |id: u16, data: &[u8]| {
+            let frame = CanDataFrame::new(StandardId::new(id).unwrap(), data).unwrap();
+            let mut pcfs = parser
+                .parse_all(&CanAnyFrame::Normal(frame), None, None)
+                .unwrap();
+            assert_eq!(pcfs.len(), 1);
+            pcfs.remove(0)
+        };
+
+        let pcf = parse_all(0x000, &[0x01, 0x05]);
+        assert_eq!(pcf.event_name(), "nmt");
+        assert_eq!(pcf.node_id, None);
+        assert_eq!(
+            attr(&pcf, "canopen.nmt.command"),
+            Some(&"start_remote_node".into())
+        );
+        assert_eq!(
+            TimelineKey::for_parsed_frame(&pcf, &cfg).timeline_name(),
+            "canbus"
+        );
+
+        let pcf = parse_all(0x705, &[0x05]);
+        assert_eq!(pcf.event_name(), "heartbeat");
+        assert_eq!(pcf.node_id, Some(5));
+        assert_eq!(attr(&pcf, "canopen.state"), Some(&"operational".into()));
+        assert_eq!(attr(&pcf, "canopen.node_id"), Some(&5_u64.into()));
+        assert_eq!(
+            TimelineKey::for_parsed_frame(&pcf, &cfg).timeline_name(),
+            "canbus_node_5"
+        );
+
+        let pcf = parse_all(0x185, &[0x01, 0x02]);
+        assert_eq!(pcf.event_name(), "tpdo1");
+        assert_eq!(pcf.node_id, Some(5));
+
+        // Only 11-bit frames
+        let frame = CanDataFrame::new(ExtendedId::new(0x705).unwrap(), &[0x05]).unwrap();
+        let pcfs = parser
+            .parse_all(&CanAnyFrame::Normal(frame), None, None)
+            .unwrap();
+        assert_eq!(pcfs[0].node_id, None);
+        assert_eq!(pcfs[0].event_name(), "1797");
+    }
+
+    #[test]
+    fn nmea2000() {
+        let cfg = CommonConfig {
+            nmea2000: Some(true),
+            ..Default::default()
+        };
+        let mut parser = CanParser::new(&cfg, None).unwrap();
+        parser.nmea2000.as_mut().unwrap().pgns = PgnDatabase::parse(
+            r#"{"PGNs": [{"PGN": 128267, "Id": "waterDepth", "Description": "Water Depth",
+                "Type": "Single", "Fields": [
+                    {"Order": 1, "Id": "sid", "BitLength": 8, "FieldType": "NUMBER"},
+                    {"Order": 2, "Id": "depth", "BitLength": 32, "Resolution": 0.01,
+                     "Unit": "m", "FieldType": "NUMBER"},
+                    {"Order": 3, "Id": "offset", "BitLength": 16, "Signed": true,
+                     "Resolution": 0.001, "Unit": "m", "FieldType": "NUMBER"}]}]}"#,
+        )
+        .unwrap();
+        let mut parse_all = |id: u32, data: &[u8]| {
+            let frame = CanDataFrame::new(ExtendedId::new(id).unwrap(), data).unwrap();
+            parser
+                .parse_all(&CanAnyFrame::Normal(frame), None, Some("can0"))
+                .unwrap()
+        };
+
+        // Water depth of 12.34 m from source address 0x23
+        let pcfs = parse_all(0x0DF50B23, &[0x01, 0xD2, 0x04, 0, 0, 0xFF, 0x7F, 0xFF]);
+        assert_eq!(pcfs.len(), 1);
+        assert_eq!(pcfs[0].event_name(), "waterDepth");
+        assert_eq!(
+            attr(&pcfs[0], "nmea2000.description"),
+            Some(&"Water Depth".into())
+        );
+        assert_eq!(attr(&pcfs[0], "depth"), Some(&(1234.0 * 0.01).into()));
+        assert_eq!(attr(&pcfs[0], "depth.unit"), Some(&"m".into()));
+        assert_eq!(attr(&pcfs[0], "offset"), None);
+        assert_eq!(attr(&pcfs[0], "j1939.pgn"), Some(&128267_u32.into()));
+        assert_eq!(
+            TimelineKey::for_parsed_frame(&pcfs[0], &cfg).timeline_name(),
+            "canbus_sa_0x23"
+        );
+
+        // A proprietary fast-packet message, not in the database
+        let pcfs = parse_all(0x09FF0123, &[0x20, 0x09, 1, 2, 3, 4, 5, 6]);
+        assert_eq!(pcfs.len(), 1);
+        assert_eq!(pcfs[0].event_name(), "pgn_130817");
+        assert_eq!(
+            attr(&pcfs[0], "nmea2000.fast_packet.sequence"),
+            Some(&1_u64.into())
+        );
+        assert_eq!(
+            attr(&pcfs[0], "nmea2000.fast_packet.frame"),
+            Some(&0_u64.into())
+        );
+        let pcfs = parse_all(0x09FF0123, &[0x21, 7, 8, 9, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(pcfs.len(), 2);
+        let msg = &pcfs[1];
+        assert_eq!(msg.event_name(), "pgn_130817");
+        assert_eq!(msg.interface.as_deref(), Some("can0"));
+        assert_eq!(
+            attr(msg, "nmea2000.fast_packet.frame_count"),
+            Some(&2_u64.into())
+        );
+        assert_eq!(attr(msg, "nmea2000.fast_packet.size"), Some(&9_u64.into()));
+        assert_eq!(attr(msg, "j1939.source_address"), Some(&0x23_u64.into()));
+        assert_eq!(
+            TimelineKey::for_parsed_frame(msg, &cfg).timeline_name(),
+            "canbus_sa_0x23"
+        );
+
+        parse_all(0x09FF0123, &[0x40, 0x09, 1, 2, 3, 4, 5, 6]);
+        let pcfs = parser.finish();
+        assert_eq!(pcfs.len(), 1);
+        assert_eq!(
+            attr(&pcfs[0], "nmea2000.fast_packet.error"),
+            Some(&"incomplete".into())
+        );
+    }
+
+    #[test]
+    fn e2e() {
+        let dbc = DBC::try_from(
+            r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: Brakes
+
+BO_ 256 BrakeStatus: 5 Brakes
+ SG_ BrakeStatus_CRC : 0|16@1+ (1,0) [0|65535] "" Vector__XXX
+ SG_ BrakeStatus_Counter : 16|8@1+ (1,0) [0|255] "" Vector__XXX
+ SG_ Pressure : 24|16@1+ (0.1,0) [0|6553.5] "bar" Vector__XXX
+
+BA_DEF_ BO_ "E2EProfile" STRING ;
+BA_DEF_ BO_ "E2EDataID" INT 0 65535;
+BA_DEF_ SG_ "E2ESignal" STRING ;
+BA_ "E2EProfile" BO_ 256 "PROFILE_05";
+BA_ "E2EDataID" BO_ 256 4660;
+BA_ "E2ESignal" SG_ 256 BrakeStatus_CRC "crc";
+BA_ "E2ESignal" SG_ 256 BrakeStatus_Counter "counter";
+"#,
+        )
+        .unwrap();
+        let mut parser = CanParser::new(&Default::default(), Some(&dbc)).unwrap();
+        let mut parse_all = |data: &[u8]| {
+            let frame = CanDataFrame::new(StandardId::new(0x100).unwrap(), data).unwrap();
+            let mut pcfs = parser
+                .parse_all(&CanAnyFrame::Normal(frame), None, Some("can0"))
+                .unwrap();
+            assert_eq!(pcfs.len(), 1);
+            pcfs.remove(0)
+        };
+
+        let pcf = parse_all(&[0x57, 0xB2, 0x00, 0xE8, 0x03]);
+        assert_eq!(pcf.event_name(), "BrakeStatus");
+        assert_eq!(attr(&pcf, "e2e.profile"), Some(&"p05".into()));
+        assert_eq!(attr(&pcf, "e2e.status"), Some(&"OK".into()));
+        assert_eq!(attr(&pcf, "e2e.counter"), Some(&0_u64.into()));
+        assert_eq!(attr(&pcf, "e2e.counter_delta"), None);
+        assert_eq!(attr(&pcf, "Pressure"), Some(&100.0.into()));
+
+        let pcf = parse_all(&[0x06, 0x18, 0x01, 0xE8, 0x03]);
+        assert_eq!(attr(&pcf, "e2e.status"), Some(&"OK".into()));
+        assert_eq!(attr(&pcf, "e2e.counter_delta"), Some(&1_u64.into()));
+
+        let pcf = parse_all(&[0x06, 0x18, 0x01, 0xE9, 0x03]);
+        assert_eq!(attr(&pcf, "e2e.status"), Some(&"WRONG_CRC".into()));
+        assert_eq!(attr(&pcf, "e2e.crc"), Some(&0x1806_u64.into()));
+        assert!(attr(&pcf, "e2e.computed_crc").is_some());
+
+        let pcf = parse_all(&[0x06, 0x18, 0x01, 0xE8, 0x03]);
+        assert_eq!(attr(&pcf, "e2e.status"), Some(&"REPEATED".into()));
+        assert_eq!(attr(&pcf, "e2e.counter_delta"), Some(&0_u64.into()));
+    }
+
+    #[test]
+    fn cycle_times() {
+        let dbc = DBC::try_from(
+            r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: Engine Gateway
+
+BO_ 256 EngineStatus: 8 Engine
+ SG_ Speed : 0|16@1+ (1,0) [0|65535] "rpm" Vector__XXX
+
+BO_ 512 GatewayStatus: 8 Gateway
+ SG_ State : 0|8@1+ (1,0) [0|255] "" Vector__XXX
+
+BA_DEF_ BO_ "GenMsgCycleTime" INT 0 65535;
+BA_DEF_DEF_ "GenMsgCycleTime" 0;
+BA_ "GenMsgCycleTime" BO_ 256 10;
+BA_ "GenMsgCycleTime" BO_ 512 100;
+"#,
+        )
+        .unwrap();
+        let cfg = CommonConfig {
+            cycle_time_tolerance: Some(0.2),
+            ..Default::default()
+        };
+        let mut parser = CanParser::new(&cfg, Some(&dbc)).unwrap();
+        let mut parse_all = |id: u16, nanoseconds: i64| {
+            let frame = CanDataFrame::new(StandardId::new(id).unwrap(), &[0; 8]).unwrap();
+            let ts = Timestamp {
+                seconds: 1,
+                nanoseconds,
+            };
+            parser
+                .parse_all(&CanAnyFrame::Normal(frame), Some(ts), Some("can0"))
+                .unwrap()
+        };
+
+        assert_eq!(parse_all(0x100, 0).len(), 1);
+        assert_eq!(parse_all(0x200, 0).len(), 1);
+        let pcfs = parse_all(0x100, 11_000_000);
+        assert_eq!(pcfs.len(), 1);
+        assert_eq!(
+            attr(&pcfs[0], "period.delta_ns"),
+            Some(&Nanoseconds::from(11_000_000).into())
+        );
+        assert_eq!(
+            attr(&pcfs[0], "period.cycle_time_ns"),
+            Some(&Nanoseconds::from(10_000_000).into())
+        );
+        assert_eq!(attr(&pcfs[0], "period.deviation"), Some(&0.1.into()));
+
+        // EngineStatus was due by 24ms
+        let pcfs = parse_all(0x200, 30_000_000);
+        assert_eq!(pcfs.len(), 2);
+        let pcf = &pcfs[0];
+        assert_eq!(pcf.event_name(), "message_timeout");
+        assert_eq!(pcf.id, 0x100);
+        assert_eq!(pcf.interface.as_deref(), Some("can0"));
+        assert_eq!(pcf.transmitter_node.as_deref(), Some("Engine"));
+        assert_eq!(attr(pcf, "timeout.message"), Some(&"EngineStatus".into()));
+        assert_eq!(
+            attr(pcf, "timeout.cycle_time_ns"),
+            Some(&Nanoseconds::from(10_000_000).into())
+        );
+        assert_eq!(
+            attr(pcf, "timestamp"),
+            Some(&Nanoseconds::from(1_023_000_000).into())
+        );
+        assert_eq!(pcfs[1].event_name(), "GatewayStatus");
+
+        // Live capture, with the bus quiet
+        let now = Timestamp {
+            seconds: 1,
+            nanoseconds: 200_000_000,
+        };
+        let pcfs = parser.poll(Some("can0"), now);
+        assert_eq!(pcfs.len(), 1);
+        assert_eq!(pcfs[0].transmitter_node.as_deref(), Some("Gateway"));
+        assert!(parser.poll(Some("can0"), now).is_empty());
+
+        // Timestamps going backwards
+        let frame = CanDataFrame::new(StandardId::new(0x200).unwrap(), &[0; 8]).unwrap();
+        let ts = Timestamp {
+            seconds: 1,
+            nanoseconds: 0,
+        };
+        let pcfs = parser
+            .parse_all(&CanAnyFrame::Normal(frame), Some(ts), Some("can0"))
+            .unwrap();
+        assert_eq!(pcfs.len(), 1);
+        assert!(attr(&pcfs[0], "period.cycle_time_ns").is_some());
+        assert!(attr(&pcfs[0], "period.delta_ns").is_none());
+        assert!(attr(&pcfs[0], "period.deviation").is_none());
+    }
+
+    #[test]
+    fn timeline_from_interface() {
+        let mut parser = CanParser::new(&Default::default(), None).unwrap();
+        let frame = CanDataFrame::new(StandardId::new(0x100).unwrap(), &[0; 8]).unwrap();
+        let mut parse = |iface| {
+            parser
+                .parse_all(&CanAnyFrame::Normal(frame), None, Some(iface))
+                .unwrap()
+                .remove(0)
+        };
+        let (can0, can1) = (parse("can0"), parse("can1"));
+
+        let cfg = CommonConfig::default();
+        let key = TimelineKey::for_parsed_frame(&can0, &cfg);
+        assert!(key != TimelineKey::for_parsed_frame(&can1, &cfg));
+        assert_eq!(
+            key.timeline_attrs(&None, &cfg),
+            vec![("timeline.interface".to_owned(), "can0".into())]
+        );
+
+        let cfg = CommonConfig {
+            timeline_from_interface: Some(false),
+            ..Default::default()
+        };
+        let key = TimelineKey::for_parsed_frame(&can0, &cfg);
+        assert!(key == TimelineKey::for_parsed_frame(&can1, &cfg));
+        assert!(!key
+            .timeline_attrs(&None, &cfg)
+            .iter()
+            .any(|(k, _)| k == "timeline.interface"));
+    }
+
+    #[test]
+    fn dbc_attributes() {
+        let dbc = DBC::try_from(
+            r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: Engine
+
+BO_ 256 EngineStatus: 8 Engine
+ SG_ Speed : 0|16@1+ (1,0) [0|65535] "rpm" Vector__XXX
+ SG_ Temp : 16|8@1+ (1,-40) [-40|215] "C" Vector__XXX
+
+BO_ 512 EngineLoad: 1 Engine
+ SG_ Load : 0|8@1+ (1,0) [0|100] "%" Vector__XXX
+
+CM_ BU_ Engine "The engine controller";
+CM_ BO_ 256 "Engine state";
+
+BA_DEF_ BU_ "ECUVariant" STRING ;
+BA_DEF_ BO_ "GenMsgSendType" ENUM "Cyclic","OnChange";
+BA_DEF_ BO_ "GenMsgCycleTime" INT 0 65535;
+BA_DEF_ SG_ "GenSigStartValue" INT 0 65535;
+BA_DEF_DEF_ "ECUVariant" "";
+BA_DEF_DEF_ "GenMsgSendType" "Cyclic";
+BA_DEF_DEF_ "GenSigStartValue" 0;
+BA_ "ECUVariant" BU_ Engine "V8";
+BA_ "GenMsgSendType" BO_ 256 1;
+BA_ "GenMsgCycleTime" BO_ 256 10;
+BA_ "GenSigStartValue" SG_ 256 Speed 800;
+"#,
+        )
+        .unwrap();
+        let cfg = CommonConfig {
+            dbc_node_comments: Some(true),
+            dbc_node_attributes: Some(vec!["ECUVariant".to_owned()]),
+            dbc_message_attributes: Some(vec!["GenMsgSendType".to_owned()]),
+            dbc_signal_attributes: Some(vec!["GenSigStartValue".to_owned()]),
+            ..Default::default()
+        };
+        let mut parser = CanParser::new(&cfg, Some(&dbc)).unwrap();
+        let frame = CanDataFrame::new(StandardId::new(0x100).unwrap(), &[0; 8]).unwrap();
+        let pcf = parser.parse(&CanAnyFrame::Normal(frame), None).unwrap();
+        assert_eq!(
+            attr(&pcf, "message.GenMsgSendType"),
+            Some(&"OnChange".into())
+        );
+        assert_eq!(attr(&pcf, "message.GenMsgCycleTime"), None);
+        assert_eq!(attr(&pcf, "Speed.GenSigStartValue"), Some(&800_u64.into()));
+        assert_eq!(attr(&pcf, "Temp.GenSigStartValue"), Some(&0_u64.into()));
+
+        // Enumeration defaults are given as labels
+        let frame = CanDataFrame::new(StandardId::new(0x200).unwrap(), &[0; 1]).unwrap();
+        let pcf2 = parser.parse(&CanAnyFrame::Normal(frame), None).unwrap();
+        assert_eq!(
+            attr(&pcf2, "message.GenMsgSendType"),
+            Some(&"Cyclic".into())
+        );
+
+        let dbc = Some(Dbc {
+            file_name: None,
+            sha256: String::new(),
+            inner: dbc,
+        });
+        let tl_attrs = TimelineKey::for_parsed_frame(&pcf, &cfg).timeline_attrs(&dbc, &cfg);
+        let tl_attr = |k: &str| tl_attrs.iter().find(|(key, _)| key == k).map(|(_, v)| v);
+        assert_eq!(
+            tl_attr("timeline.transmitter.comment"),
+            Some(&"The engine controller".into())
+        );
+        assert_eq!(
+            tl_attr("timeline.transmitter.ECUVariant"),
+            Some(&"V8".into())
+        );
+
+        // Nothing is added by default
+        let tl_attrs =
+            TimelineKey::for_parsed_frame(&pcf, &cfg).timeline_attrs(&dbc, &Default::default());
+        assert!(!tl_attrs
+            .iter()
+            .any(|(k, _)| k.starts_with("timeline.transmitter.")));
+    }
+}
@@ -0,0 +1,734 @@
+//! PCAP and PCAPNG capture reader, for captures using the SocketCAN link type
+//! (LINKTYPE_CAN_SOCKETCAN), as produced by Wireshark and tcpdump on a CAN interface.
+//!
+//! The file format is detected from the leading magic number.
+//! PCAPNG captures may contain several interfaces, the interface names from the
+//! interface description blocks are used when present, otherwise the interface index is.
+
+use crate::import::{timestamp_from_nanos, LogFrame, MAX_CLASSIC_DATA_LEN};
+use anyhow::{anyhow, bail};
+use socketcan::{
+    frame::FdFlags, CanAnyFrame, CanDataFrame, CanErrorFrame, CanFdFrame, CanRemoteFrame,
+    EmbeddedFrame, ExtendedId, Id as CanId, StandardId,
+};
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+use tracing::{debug, warn};
+
+pub const LINKTYPE_CAN_SOCKETCAN: u16 = 227;
+
+const PCAP_MAGIC_MICROS: u32 = 0xA1B2_C3D4;
+const PCAP_MAGIC_NANOS: u32 = 0xA1B2_3C4D;
+
+const PCAPNG_SHB: u32 = 0x0A0D_0D0A;
+const PCAPNG_IDB: u32 = 0x0000_0001;
+const PCAPNG_OPB: u32 = 0x0000_0002;
+const PCAPNG_EPB: u32 = 0x0000_0006;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+const OPT_END: u16 = 0;
+const OPT_IF_NAME: u16 = 2;
+const OPT_IF_TSRESOL: u16 = 9;
+const OPT_IF_TSOFFSET: u16 = 14;
+
+const CAN_EFF_FLAG: u32 = 0x8000_0000;
+const CAN_RTR_FLAG: u32 = 0x4000_0000;
+const CAN_ERR_FLAG: u32 = 0x2000_0000;
+const CAN_EFF_MASK: u32 = 0x1FFF_FFFF;
+const CAN_SFF_MASK: u32 = 0x0000_07FF;
+
+const CANFD_BRS: u8 = 0x01;
+const CANFD_ESI: u8 = 0x02;
+const CANFD_FDF: u8 = 0x04;
+const CANXL_XLF: u8 = 0x80;
+
+const SOCKETCAN_HEADER_SIZE: usize = 8;
+const CAN_MTU: usize = 16;
+/// Packets larger than this can only come from a corrupt file
+const MAX_PACKET_LEN: usize = 256 * 1024;
+/// PCAPNG blocks hold at most a packet, its header and options
+const MAX_BLOCK_LEN: usize = MAX_PACKET_LEN + 64 * 1024;
+
+pub struct Reader<R> {
+    reader: R,
+    format: Format,
+    big_endian: bool,
+    /// Link type to count of packets that aren't supported
+    unsupported_packets: BTreeMap<u16, u64>,
+    done: bool,
+}
+
+enum Format {
+    Pcap {
+        nanos: bool,
+        link_type: u16,
+        snaplen: usize,
+    },
+    Pcapng {
+        interfaces: Vec<Interface>,
+    },
+}
+
+struct Interface {
+    name: String,
+    link_type: u16,
+    /// Timestamp units, as a power of 10 or 2 of a second
+    ts_resolution: TsResolution,
+    ts_offset_seconds: i64,
+}
+
+#[derive(Copy, Clone)]
+enum TsResolution {
+    Pow10(u32),
+    Pow2(u32),
+}
+
+impl TsResolution {
+    fn to_nanos(self, ts: u64) -> i64 {
+        let ns = match self {
+            TsResolution::Pow10(exp) if exp <= 9 => ts as u128 * 10_u128.pow(9 - exp),
+            TsResolution::Pow10(exp) => ts as u128 / 10_u128.pow(exp.min(38) - 9),
+            TsResolution::Pow2(exp) => (ts as u128 * 1_000_000_000) >> exp.min(127),
+        };
+        ns as i64
+    }
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(mut reader: R) -> Result<Self, anyhow::Error> {
+        let mut magic = [0_u8; 4];
+        reader
+            .read_exact(&mut magic)
+            .map_err(|e| anyhow!("Failed to read the capture file header. {e}"))?;
+
+        if u32::from_le_bytes(magic) == PCAPNG_SHB {
+            let mut r = Self {
+                reader,
+                format: Format::Pcapng { interfaces: vec![] },
+                big_endian: false,
+                unsupported_packets: Default::default(),
+                done: false,
+            };
+            r.read_section_header()?;
+            return Ok(r);
+        }
+
+        let (big_endian, nanos) = if u32::from_le_bytes(magic) == PCAP_MAGIC_MICROS {
+            (false, false)
+        } else if u32::from_le_bytes(magic) == PCAP_MAGIC_NANOS {
+            (false, true)
+        } else if u32::from_be_bytes(magic) == PCAP_MAGIC_MICROS {
+            (true, false)
+        } else if u32::from_be_bytes(magic) == PCAP_MAGIC_NANOS {
+            (true, true)
+        } else {
+            bail!("Unrecognized capture file magic number, expected a PCAP or PCAPNG file");
+        };
+
+        // version, thiszone, sigfigs, snaplen, network
+        let mut header = [0_u8; 20];
+        reader
+            .read_exact(&mut header)
+            .map_err(|e| anyhow!("Failed to read the PCAP file header. {e}"))?;
+        let snaplen = u32_at(&header, 12, big_endian) as usize;
+        let link_type = u32_at(&header, 16, big_endian) as u16;
+        debug!(link_type, nanos, snaplen, "Read PCAP file header");
+
+        Ok(Self {
+            reader,
+            format: Format::Pcap {
+                nanos,
+                link_type,
+                snaplen,
+            },
+            big_endian,
+            unsupported_packets: Default::default(),
+            done: false,
+        })
+    }
+
+    /// Reads the remainder of a section header block, after the block type.
+    /// This determines the byte order of the section and resets the interfaces.
+    fn read_section_header(&mut self) -> Result<(), anyhow::Error> {
+        let mut b = [0_u8; 8];
+        self.reader.read_exact(&mut b)?;
+        self.big_endian = if u32::from_le_bytes([b[4], b[5], b[6], b[7]]) == PCAPNG_BYTE_ORDER_MAGIC
+        {
+            false
+        } else if u32::from_be_bytes([b[4], b[5], b[6], b[7]]) == PCAPNG_BYTE_ORDER_MAGIC {
+            true
+        } else {
+            bail!("Invalid PCAPNG section header byte order magic");
+        };
+        let total_len = u32_at(&b, 0, self.big_endian) as usize;
+        if total_len < 12 + 16 {
+            bail!("Invalid PCAPNG section header length {total_len}");
+        }
+        // Skip the version, section length, options and trailing length
+        self.skip(total_len - 12)?;
+        self.format = Format::Pcapng { interfaces: vec![] };
+        Ok(())
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), anyhow::Error> {
+        io::copy(&mut (&mut self.reader).take(len as u64), &mut io::sink())?;
+        Ok(())
+    }
+
+    /// Returns the next frame, None if the packet was skipped, or Err(None) at
+    /// the end of the file.
+    fn read_packet(&mut self) -> Result<Option<LogFrame>, Option<anyhow::Error>> {
+        match &self.format {
+            Format::Pcap { .. } => self.read_pcap_record(),
+            Format::Pcapng { .. } => self.read_pcapng_block(),
+        }
+    }
+
+    fn read_pcap_record(&mut self) -> Result<Option<LogFrame>, Option<anyhow::Error>> {
+        let Format::Pcap {
+            nanos,
+            link_type,
+            snaplen,
+        } = self.format
+        else {
+            return Ok(None);
+        };
+        let mut header = [0_u8; 16];
+        read_or_eof(&mut self.reader, &mut header)?;
+        let ts_sec = u32_at(&header, 0, self.big_endian) as i64;
+        let ts_frac = u32_at(&header, 4, self.big_endian) as i64;
+        let incl_len = u32_at(&header, 8, self.big_endian) as usize;
+        if incl_len > snaplen || incl_len > MAX_PACKET_LEN {
+            return Err(Some(anyhow!(
+                "Invalid PCAP record length {incl_len}, the snapshot length is {snaplen}"
+            )));
+        }
+        let mut packet = vec![0_u8; incl_len];
+        self.reader
+            .read_exact(&mut packet)
+            .map_err(|e| Some(e.into()))?;
+
+        let ns = if nanos { ts_frac } else { ts_frac * 1_000 };
+        let timestamp = timestamp_from_nanos((ts_sec * 1_000_000_000) + ns);
+
+        if link_type != LINKTYPE_CAN_SOCKETCAN {
+            *self.unsupported_packets.entry(link_type).or_default() += 1;
+            return Ok(None);
+        }
+        match parse_socketcan(&packet) {
+            Some(frame) => Ok(Some(LogFrame {
+                timestamp: Some(timestamp),
+                interface: None,
+                direction: None,
+                frame,
+            })),
+            None => {
+                *self.unsupported_packets.entry(link_type).or_default() += 1;
+                Ok(None)
+            }
+        }
+    }
+
+    fn read_pcapng_block(&mut self) -> Result<Option<LogFrame>, Option<anyhow::Error>> {
+        let mut block_type = [0_u8; 4];
+        read_or_eof(&mut self.reader, &mut block_type)?;
+        let block_type = u32_at(&block_type, 0, self.big_endian);
+        if block_type == PCAPNG_SHB {
+            self.read_section_header().map_err(Some)?;
+            return Ok(None);
+        }
+
+        let mut len = [0_u8; 4];
+        self.reader
+            .read_exact(&mut len)
+            .map_err(|e| Some(e.into()))?;
+        let total_len = u32_at(&len, 0, self.big_endian) as usize;
+        if !(12..=MAX_BLOCK_LEN).contains(&total_len) {
+            return Err(Some(anyhow!("Invalid PCAPNG block length {total_len}")));
+        }
+        let mut body = vec![0_u8; total_len - 8];
+        self.reader
+            .read_exact(&mut body)
+            .map_err(|e| Some(e.into()))?;
+        // Drop the trailing block length
+        body.truncate(total_len - 12);
+
+        match block_type {
+            PCAPNG_IDB => {
+                self.add_interface(&body).map_err(Some)?;
+                Ok(None)
+            }
+            PCAPNG_EPB => {
+                if body.len() < 20 {
+                    return Err(Some(anyhow!("Truncated PCAPNG enhanced packet block")));
+                }
+                let iface = u32_at(&body, 0, self.big_endian) as usize;
+                let ts = ((u32_at(&body, 4, self.big_endian) as u64) << 32)
+                    | u32_at(&body, 8, self.big_endian) as u64;
+                let cap_len = u32_at(&body, 12, self.big_endian) as usize;
+                let packet = body
+                    .get(20..20 + cap_len)
+                    .ok_or_else(|| Some(anyhow!("Truncated PCAPNG enhanced packet block data")))?;
+                Ok(self.packet_frame(iface, ts, packet))
+            }
+            PCAPNG_OPB => {
+                if body.len() < 20 {
+                    return Err(Some(anyhow!("Truncated PCAPNG packet block")));
+                }
+                let iface = u16_at(&body, 0, self.big_endian) as usize;
+                let ts = ((u32_at(&body, 4, self.big_endian) as u64) << 32)
+                    | u32_at(&body, 8, self.big_endian) as u64;
+                let cap_len = u32_at(&body, 12, self.big_endian) as usize;
+                let packet = body
+                    .get(20..20 + cap_len)
+                    .ok_or_else(|| Some(anyhow!("Truncated PCAPNG packet block data")))?;
+                Ok(self.packet_frame(iface, ts, packet))
+            }
+            _ => {
+                // Simple packet blocks have no interface or timestamp, the
+                // rest (statistics, name resolution, etc) aren't relevant
+                debug!(block_type, "Skipping PCAPNG block");
+                Ok(None)
+            }
+        }
+    }
+
+    fn add_interface(&mut self, body: &[u8]) -> Result<(), anyhow::Error> {
+        let Format::Pcapng { interfaces } = &mut self.format else {
+            return Ok(());
+        };
+        if body.len() < 8 {
+            bail!("Truncated PCAPNG interface description block");
+        }
+        let mut iface = Interface {
+            name: interfaces.len().to_string(),
+            link_type: u16_at(body, 0, self.big_endian),
+            ts_resolution: TsResolution::Pow10(6),
+            ts_offset_seconds: 0,
+        };
+
+        let mut opts = &body[8..];
+        while opts.len() >= 4 {
+            let code = u16_at(opts, 0, self.big_endian);
+            let len = u16_at(opts, 2, self.big_endian) as usize;
+            let Some(val) = opts.get(4..4 + len) else {
+                break;
+            };
+            match code {
+                OPT_END => break,
+                OPT_IF_NAME => {
+                    iface.name = String::from_utf8_lossy(val)
+                        .trim_end_matches('\0')
+                        .to_owned();
+                }
+                OPT_IF_TSRESOL if len == 1 => {
+                    iface.ts_resolution = if val[0] & 0x80 == 0 {
+                        TsResolution::Pow10(val[0] as u32)
+                    } else {
+                        TsResolution::Pow2((val[0] & 0x7F) as u32)
+                    };
+                }
+                OPT_IF_TSOFFSET if len == 8 => {
+                    let mut b = [0_u8; 8];
+                    b.copy_from_slice(val);
+                    iface.ts_offset_seconds = if self.big_endian {
+                        i64::from_be_bytes(b)
+                    } else {
+                        i64::from_le_bytes(b)
+                    };
+                }
+                _ => (),
+            }
+            // Options are padded to 4 bytes
+            let padded_len = (len + 3) & !3;
+            opts = opts.get(4 + padded_len..).unwrap_or(&[]);
+        }
+
+        debug!(
+            interface = iface.name,
+            link_type = iface.link_type,
+            "Read PCAPNG interface description"
+        );
+        interfaces.push(iface);
+        Ok(())
+    }
+
+    fn packet_frame(&mut self, iface_idx: usize, ts: u64, packet: &[u8]) -> Option<LogFrame> {
+        let Format::Pcapng { interfaces } = &self.format else {
+            return None;
+        };
+        let Some(iface) = interfaces.get(iface_idx) else {
+            warn!(
+                interface = iface_idx,
+                "PCAPNG packet references an unknown interface"
+            );
+            return None;
+        };
+        if iface.link_type != LINKTYPE_CAN_SOCKETCAN {
+            *self.unsupported_packets.entry(iface.link_type).or_default() += 1;
+            return None;
+        }
+        let Some(frame) = parse_socketcan(packet) else {
+            *self.unsupported_packets.entry(iface.link_type).or_default() += 1;
+            return None;
+        };
+        let ns = iface.ts_resolution.to_nanos(ts) + (iface.ts_offset_seconds * 1_000_000_000);
+        Some(LogFrame {
+            timestamp: Some(timestamp_from_nanos(ns)),
+            interface: Some(iface.name.clone()),
+            direction: None,
+            frame,
+        })
+    }
+
+    fn report_unsupported_packets(&self) {
+        for (link_type, count) in self.unsupported_packets.iter() {
+            warn!(
+                link_type,
+                count, "Skipped packets with an unsupported link type or frame"
+            );
+        }
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<LogFrame, anyhow::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            match self.read_packet() {
+                Ok(Some(f)) => return Some(Ok(f)),
+                Ok(None) => (),
+                Err(maybe_err) => {
+                    self.done = true;
+                    self.report_unsupported_packets();
+                    if let Some(e) = maybe_err {
+                        return Some(Err(e));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Parse a SocketCAN link-layer packet (struct can_frame/canfd_frame).
+/// The CAN ID and flags are in network byte order.
+/// Returns None for CAN XL and malformed packets.
+fn parse_socketcan(packet: &[u8]) -> Option<CanAnyFrame> {
+    let header = packet.get(..SOCKETCAN_HEADER_SIZE)?;
+    let can_id = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+    if header[4] & CANXL_XLF != 0 {
+        return None;
+    }
+    let len = header[4] as usize;
+    let flags = header[5];
+    let data = packet.get(SOCKETCAN_HEADER_SIZE..SOCKETCAN_HEADER_SIZE + len)?;
+    let is_fd = flags & CANFD_FDF != 0 || packet.len() > CAN_MTU;
+
+    let id = if can_id & CAN_EFF_FLAG != 0 {
+        CanId::from(ExtendedId::new(can_id & CAN_EFF_MASK)?)
+    } else {
+        CanId::from(StandardId::new((can_id & CAN_SFF_MASK) as u16)?)
+    };
+
+    let frame = if can_id & CAN_ERR_FLAG != 0 {
+        CanAnyFrame::Error(
+            CanErrorFrame::new_error(
+                can_id & CAN_EFF_MASK,
+                &data[..len.min(MAX_CLASSIC_DATA_LEN)],
+            )
+            .ok()?,
+        )
+    } else if can_id & CAN_RTR_FLAG != 0 {
+        CanAnyFrame::Remote(CanRemoteFrame::new_remote(id, len)?)
+    } else if is_fd {
+        let mut fd_flags = FdFlags::empty();
+        if flags & CANFD_BRS != 0 {
+            fd_flags |= FdFlags::BRS;
+        }
+        if flags & CANFD_ESI != 0 {
+            fd_flags |= FdFlags::ESI;
+        }
+        CanAnyFrame::Fd(CanFdFrame::with_flags(id, data, fd_flags)?)
+    } else {
+        CanAnyFrame::Normal(CanDataFrame::new(id, data)?)
+    };
+    Some(frame)
+}
+
+fn read_or_eof<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<(), Option<anyhow::Error>> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Err(None),
+        Err(e) => Err(Some(e.into())),
+    }
+}
+
+fn u16_at(b: &[u8], offset: usize, big_endian: bool) -> u16 {
+    let bytes = [b[offset], b[offset + 1]];
+    if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    }
+}
+
+fn u32_at(b: &[u8], offset: usize, big_endian: bool) -> u32 {
+    let bytes = [b[offset], b[offset + 1], b[offset + 2], b[offset + 3]];
+    if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socketcan::Timestamp;
+
+    fn socketcan_packet(can_id: u32, flags: u8, data: &[u8], mtu: usize) -> Vec<u8> {
+        let mut p = Vec::new();
+        p.extend_from_slice(&can_id.to_be_bytes());
+        p.push(data.len() as u8);
+        p.push(flags);
+        p.extend_from_slice(&[0, 0]);
+        p.extend_from_slice(data);
+        p.resize(mtu, 0);
+        p
+    }
+
+    fn pcap_file(big_endian: bool, magic: u32, packets: &[(u32, u32, Vec<u8>)]) -> Vec<u8> {
+        let w32 = |v: u32| {
+            if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        };
+        let mut f = Vec::new();
+        f.extend_from_slice(&w32(magic));
+        f.extend_from_slice(&[0_u8; 12]);
+        f.extend_from_slice(&w32(0xFFFF));
+        f.extend_from_slice(&w32(LINKTYPE_CAN_SOCKETCAN as u32));
+        for (sec, frac, p) in packets {
+            f.extend_from_slice(&w32(*sec));
+            f.extend_from_slice(&w32(*frac));
+            f.extend_from_slice(&w32(p.len() as u32));
+            f.extend_from_slice(&w32(p.len() as u32));
+            f.extend_from_slice(p);
+        }
+        f
+    }
+
+    fn pcapng_block(block_type: u32, body: &[u8]) -> Vec<u8> {
+        let mut body = body.to_vec();
+        body.resize((body.len() + 3) & !3, 0);
+        let total_len = (body.len() + 12) as u32;
+        let mut b = Vec::new();
+        b.extend_from_slice(&block_type.to_le_bytes());
+        b.extend_from_slice(&total_len.to_le_bytes());
+        b.extend_from_slice(&body);
+        b.extend_from_slice(&total_len.to_le_bytes());
+        b
+    }
+
+    fn shb_body() -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1_u16.to_le_bytes());
+        body.extend_from_slice(&0_u16.to_le_bytes());
+        body.extend_from_slice(&u64::MAX.to_le_bytes());
+        body
+    }
+
+    fn idb(name: &str, tsresol: Option<u8>) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&LINKTYPE_CAN_SOCKETCAN.to_le_bytes());
+        body.extend_from_slice(&[0_u8; 6]);
+        body.extend_from_slice(&OPT_IF_NAME.to_le_bytes());
+        body.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        body.extend_from_slice(name.as_bytes());
+        body.resize((body.len() + 3) & !3, 0);
+        if let Some(r) = tsresol {
+            body.extend_from_slice(&OPT_IF_TSRESOL.to_le_bytes());
+            body.extend_from_slice(&1_u16.to_le_bytes());
+            body.extend_from_slice(&[r, 0, 0, 0]);
+        }
+        body.extend_from_slice(&[0_u8; 4]);
+        pcapng_block(PCAPNG_IDB, &body)
+    }
+
+    fn epb(iface: u32, ts: u64, packet: &[u8]) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&iface.to_le_bytes());
+        body.extend_from_slice(&((ts >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(ts as u32).to_le_bytes());
+        body.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        body.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        body.extend_from_slice(packet);
+        pcapng_block(PCAPNG_EPB, &body)
+    }
+
+    #[test]
+    fn pcap_frames() {
+        for big_endian in [false, true] {
+            let file = pcap_file(
+                big_endian,
+                PCAP_MAGIC_NANOS,
+                &[
+                    (
+                        1717689368,
+                        527737123,
+                        socketcan_packet(0x18A, 0, &[0xF4, 0x7E], CAN_MTU),
+                    ),
+                    (
+                        1717689369,
+                        1,
+                        socketcan_packet(0x1C9 | CAN_RTR_FLAG, 0, &[0; 8], CAN_MTU),
+                    ),
+                ],
+            );
+            let frames: Vec<LogFrame> = Reader::new(file.as_slice())
+                .unwrap()
+                .map(|f| f.unwrap())
+                .collect();
+            assert_eq!(frames.len(), 2);
+            assert_eq!(
+                frames[0].timestamp.unwrap(),
+                Timestamp {
+                    seconds: 1717689368,
+                    nanoseconds: 527737123,
+                }
+            );
+            assert_eq!(frames[0].interface, None);
+            let CanAnyFrame::Normal(f) = frames[0].frame else {
+                panic!();
+            };
+            assert_eq!(f.id(), StandardId::new(0x18A).unwrap().into());
+            assert_eq!(f.data(), &[0xF4, 0x7E]);
+            let CanAnyFrame::Remote(f) = frames[1].frame else {
+                panic!();
+            };
+            assert_eq!(f.dlc(), 8);
+        }
+    }
+
+    #[test]
+    fn pcap_micros() {
+        let file = pcap_file(
+            false,
+            PCAP_MAGIC_MICROS,
+            &[(10, 5, socketcan_packet(0x1, 0, &[], CAN_MTU))],
+        );
+        let frames: Vec<LogFrame> = Reader::new(file.as_slice())
+            .unwrap()
+            .map(|f| f.unwrap())
+            .collect();
+        assert_eq!(
+            frames[0].timestamp.unwrap(),
+            Timestamp {
+                seconds: 10,
+                nanoseconds: 5000,
+            }
+        );
+    }
+
+    #[test]
+    fn pcap_record_length() {
+        let mut file = pcap_file(
+            false,
+            PCAP_MAGIC_NANOS,
+            &[(1, 0, socketcan_packet(0x1, 0, &[], CAN_MTU))],
+        );
+        // Beyond the 0xFFFF snapshot length
+        file[24 + 8..24 + 12].copy_from_slice(&0x1_0000_u32.to_le_bytes());
+        let mut reader = Reader::new(file.as_slice()).unwrap();
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+
+        // Beyond the maximum, with a corrupt snapshot length
+        file[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        file[24 + 8..24 + 12].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut reader = Reader::new(file.as_slice()).unwrap();
+        assert!(reader.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn pcapng_interfaces() {
+        let mut file = pcapng_block(PCAPNG_SHB, &shb_body());
+        file.extend(idb("can0", None));
+        file.extend(idb("vcan1", Some(9)));
+        file.extend(epb(
+            1,
+            1_717_689_368_000_000_001,
+            &socketcan_packet(
+                0x17F4200A | CAN_EFF_FLAG,
+                CANFD_FDF | CANFD_BRS,
+                &[0xAA; 12],
+                72,
+            ),
+        ));
+        file.extend(epb(
+            0,
+            1_717_689_368_000_001,
+            &socketcan_packet(CAN_ERR_FLAG | 0x04, 0, &[0, 0x10, 0, 0, 0, 0, 0, 0], 16),
+        ));
+        // Statistics block is skipped
+        file.extend(pcapng_block(5, &[0_u8; 12]));
+
+        let frames: Vec<LogFrame> = Reader::new(file.as_slice())
+            .unwrap()
+            .map(|f| f.unwrap())
+            .collect();
+        assert_eq!(frames.len(), 2);
+
+        assert_eq!(frames[0].interface.as_deref(), Some("vcan1"));
+        assert_eq!(
+            frames[0].timestamp.unwrap(),
+            Timestamp {
+                seconds: 1717689368,
+                nanoseconds: 1,
+            }
+        );
+        let CanAnyFrame::Fd(f) = frames[0].frame else {
+            panic!();
+        };
+        assert_eq!(f.id(), ExtendedId::new(0x17F4200A).unwrap().into());
+        assert!(f.is_brs());
+        assert_eq!(f.data().len(), 12);
+
+        assert_eq!(frames[1].interface.as_deref(), Some("can0"));
+        assert_eq!(
+            frames[1].timestamp.unwrap(),
+            Timestamp {
+                seconds: 1717689368,
+                nanoseconds: 1000,
+            }
+        );
+        let CanAnyFrame::Error(f) = frames[1].frame else {
+            panic!();
+        };
+        assert_eq!(f.data()[1], 0x10);
+    }
+
+    #[test]
+    fn pcapng_block_length() {
+        let mut file = pcapng_block(PCAPNG_SHB, &shb_body());
+        file.extend(idb("can0", None));
+        let block = file.len();
+        file.extend(epb(0, 1, &socketcan_packet(0x1, 0, &[], CAN_MTU)));
+        file[block + 4..block + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut reader = Reader::new(file.as_slice()).unwrap();
+        assert!(reader.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn ts_resolution() {
+        assert_eq!(TsResolution::Pow10(6).to_nanos(3), 3000);
+        assert_eq!(TsResolution::Pow10(9).to_nanos(3), 3);
+        assert_eq!(TsResolution::Pow10(12).to_nanos(3000), 3);
+        assert_eq!(TsResolution::Pow2(1).to_nanos(3), 1_500_000_000);
+    }
+}
@@ -1,4 +1,9 @@
-use crate::{convert::TimelineKey, dbc::Dbc, parser::ParsedCanFrame, HasCommonConfig};
+use crate::{
+    convert::TimelineKey,
+    dbc::Dbc,
+    parser::{Interaction, ParsedCanFrame},
+    CommonConfig, HasCommonConfig,
+};
 use auxon_sdk::{
     api::{AttrKey, AttrVal, TimelineId},
     plugin_utils::ingest::Config,
@@ -6,12 +11,24 @@ use auxon_sdk::{
 use std::collections::HashMap;
 use tracing::debug;
 
+/// Timeline settings for the frames captured on a particular interface.
+/// These take the place of the sender's own DBC and configuration for that interface.
+pub struct InterfaceTimelines {
+    pub config: CommonConfig,
+    pub dbc: Option<Dbc>,
+    /// Added to the attributes of every timeline created for the interface
+    pub timeline_attrs: HashMap<AttrKey, AttrVal>,
+}
+
 pub struct Sender<C: HasCommonConfig> {
     client: auxon_sdk::plugin_utils::ingest::Client,
     common_timeline_attrs: HashMap<AttrKey, AttrVal>,
     dbc: Option<Dbc>,
     config: Config<C>,
+    interfaces: HashMap<String, InterfaceTimelines>,
     known_timelines: HashMap<TimelineKey, TimelineId>,
+    /// The timelines of requests awaiting a response, by interface and nonce
+    request_timelines: HashMap<(Option<String>, i64), TimelineId>,
     current_timeline: Option<TimelineId>,
     event_ordering: u128,
 }
@@ -28,12 +45,19 @@ impl<C: HasCommonConfig> Sender<C> {
             common_timeline_attrs,
             dbc,
             config,
+            interfaces: Default::default(),
             known_timelines: Default::default(),
+            request_timelines: Default::default(),
             current_timeline: None,
             event_ordering: 0,
         }
     }
 
+    /// Use the given timeline settings for frames whose interface is `interface`
+    pub fn add_interface(&mut self, interface: impl Into<String>, timelines: InterfaceTimelines) {
+        self.interfaces.insert(interface.into(), timelines);
+    }
+
     pub async fn close(self) -> Result<(), anyhow::Error> {
         let mut client = self.client;
         client.flush().await?;
@@ -51,14 +75,24 @@ impl<C: HasCommonConfig> Sender<C> {
     }
 
     pub async fn handle_frame(&mut self, pcf: ParsedCanFrame) -> Result<(), anyhow::Error> {
-        let tl_key = TimelineKey::for_parsed_frame(&pcf, self.config.plugin.common_config());
-        match self.known_timelines.get(&tl_key) {
+        let iface = pcf
+            .interface
+            .as_ref()
+            .and_then(|iface| self.interfaces.get(iface));
+        let (common_config, dbc) = match iface {
+            Some(iface) => (&iface.config, &iface.dbc),
+            None => (self.config.plugin.common_config(), &self.dbc),
+        };
+
+        let tl_key = TimelineKey::for_parsed_frame(&pcf, common_config);
+        let tl_id = match self.known_timelines.get(&tl_key) {
             Some(tl_id) => {
                 // It's a known timeline; switch to it if necessary
                 if self.current_timeline != Some(*tl_id) {
                     self.client.switch_timeline(*tl_id).await?;
                     self.current_timeline = Some(*tl_id);
                 }
+                *tl_id
             }
             None => {
                 // We've never seen this timeline before; allocate an
@@ -68,24 +102,58 @@ impl<C: HasCommonConfig> Sender<C> {
                 self.client.switch_timeline(tl_id).await?;
                 self.current_timeline = Some(tl_id);
 
+                let tl_attrs = tl_key.timeline_attrs(dbc, common_config);
                 let attrs: Vec<_> = self
                     .common_timeline_attrs
                     .iter()
+                    .chain(iface.iter().flat_map(|iface| iface.timeline_attrs.iter()))
                     .map(|(k, v)| (k.as_ref(), v.clone()))
-                    .chain(tl_key.timeline_attrs(&self.dbc))
+                    .chain(tl_attrs.iter().map(|(k, v)| (k.as_str(), v.clone())))
                     .collect();
                 self.client
                     .send_timeline_attrs(tl_key.timeline_name(), attrs)
                     .await?;
                 self.known_timelines.insert(tl_key, tl_id);
+                tl_id
             }
         };
 
+        // Responses refer to the timeline of their request
+        let mut remote_timeline = None;
+        match pcf.interaction {
+            Some(Interaction::Request {
+                nonce,
+                superseded,
+                awaits_response,
+            }) => {
+                if let Some(superseded) = superseded {
+                    self.request_timelines
+                        .remove(&(pcf.interface.clone(), superseded));
+                }
+                if awaits_response {
+                    self.request_timelines
+                        .insert((pcf.interface.clone(), nonce), tl_id);
+                }
+            }
+            Some(Interaction::Response { remote_nonce, last }) => {
+                let key = (pcf.interface.clone(), remote_nonce);
+                remote_timeline = if last {
+                    self.request_timelines.remove(&key)
+                } else {
+                    self.request_timelines.get(&key).copied()
+                };
+            }
+            None => (),
+        }
+
         let ev_name = pcf.event_name();
         let ev_attrs: Vec<_> = pcf
             .attrs
             .iter()
             .map(|(k, v)| (k.as_ref(), v.clone()))
+            .chain(
+                remote_timeline.map(|tl_id| ("event.interaction.remote_timeline_id", tl_id.into())),
+            )
             .collect();
         self.client
             .send_event(&ev_name, self.event_ordering, ev_attrs)
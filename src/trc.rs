@@ -0,0 +1,697 @@
+//! PEAK trace (.trc) reader, as produced by PCAN-View and PCAN-Explorer.
+//!
+//! Supports file versions 1.0 through 2.1, including the `$COLUMNS` layouts of 2.x files,
+//! CAN FD frame types (FD, FB, FE, BI), remote frames, and error/status lines.
+//! Timestamps are offsets from the start of the trace unless absolute timestamps are
+//! requested, in which case the `$STARTTIME` (or `Start time` comment) is added.
+
+use crate::error_frame::{
+    CAN_ERR_BUSOFF, CAN_ERR_CNT, CAN_ERR_CRTL, CAN_ERR_CRTL_ACTIVE, CAN_ERR_CRTL_RX_OVERFLOW,
+    CAN_ERR_CRTL_RX_PASSIVE, CAN_ERR_CRTL_RX_WARNING, CAN_ERR_CRTL_TX_PASSIVE,
+    CAN_ERR_CRTL_TX_WARNING, CAN_ERR_PROT, CAN_ERR_PROT_BIT, CAN_ERR_PROT_FORM, CAN_ERR_PROT_STUFF,
+};
+use crate::import::{
+    add_timestamps, dlc_to_len, fraction_to_nanos, timestamp_from_nanos, unix_seconds, Direction,
+    LogFrame, MAX_CLASSIC_DATA_LEN,
+};
+use socketcan::{
+    frame::FdFlags, CanAnyFrame, CanDataFrame, CanErrorFrame, CanFdFrame, CanRemoteFrame,
+    EmbeddedFrame, ExtendedId, Id as CanId, StandardId, Timestamp,
+};
+use std::io::BufRead;
+use tracing::{debug, warn};
+
+/// Days between the OLE automation date epoch (1899-12-30) and the unix epoch
+const OLE_DATE_UNIX_EPOCH_DAYS: i64 = 25569;
+const NANOS_PER_DAY: f64 = 86_400_000_000_000.0;
+
+// PEAK error frame data, type byte
+const PEAK_ERR_BIT: u8 = 0x01;
+const PEAK_ERR_FORM: u8 = 0x02;
+const PEAK_ERR_STUFF: u8 = 0x04;
+
+// PCAN status bits, as found in status (ST/Warng) lines
+const PCAN_ERROR_OVERRUN: u32 = 0x0000_0002;
+const PCAN_ERROR_BUSLIGHT: u32 = 0x0000_0004;
+const PCAN_ERROR_BUSHEAVY: u32 = 0x0000_0008;
+const PCAN_ERROR_BUSOFF: u32 = 0x0000_0010;
+const PCAN_ERROR_BUSPASSIVE: u32 = 0x0004_0000;
+
+/// Default 2.x column layouts, used when there's no `$COLUMNS` header
+const DEFAULT_COLUMNS_V2_0: &str = "N,O,T,I,d,l,D";
+const DEFAULT_COLUMNS_V2_1: &str = "N,O,T,B,I,d,R,L,D";
+
+pub struct Reader<R> {
+    reader: R,
+    parser: Parser,
+    line_buf: String,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(reader: R, absolute_timestamps: bool) -> Self {
+        Self {
+            reader,
+            parser: Parser::new(absolute_timestamps),
+            line_buf: String::with_capacity(8 * 1024),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = Result<LogFrame, anyhow::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line_buf.clear();
+            match self.reader.read_line(&mut self.line_buf) {
+                Ok(0) => return None,
+                Ok(_) => {
+                    if let Some(f) = self.parser.parse_line(&self.line_buf) {
+                        return Some(Ok(f));
+                    }
+                }
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+    }
+}
+
+/// The line layouts of the various file versions
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Version {
+    /// `N) O I L D`
+    V1_0,
+    /// `N) O T I L D`
+    V1_1,
+    /// `N) O B T I [-] L D`
+    V1_3,
+    /// Layout given by `$COLUMNS`
+    V2,
+}
+
+#[derive(Debug)]
+struct Parser {
+    absolute_timestamps: bool,
+    version: Version,
+    /// Column letters of 2.x files
+    columns: Vec<char>,
+    start_time: Option<Timestamp>,
+    /// Set once `$STARTTIME` is seen, it takes precedence over the `Start time` comment
+    has_start_time_header: bool,
+}
+
+impl Parser {
+    fn new(absolute_timestamps: bool) -> Self {
+        Self {
+            absolute_timestamps,
+            version: Version::V1_0,
+            columns: Vec::new(),
+            start_time: None,
+            has_start_time_header: false,
+        }
+    }
+
+    fn parse_line(&mut self, line: &str) -> Option<LogFrame> {
+        let line = line.trim();
+        if let Some(comment) = line.strip_prefix(';') {
+            self.parse_header(comment.trim());
+            return None;
+        }
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.is_empty() {
+            return None;
+        }
+
+        let res = match self.version {
+            Version::V2 => self.parse_v2_line(&tokens),
+            _ => self.parse_v1_line(&tokens),
+        };
+        let (timestamp, interface, direction, frame) = match res {
+            Some(f) => f,
+            None => {
+                debug!(line, "Skipping TRC line");
+                return None;
+            }
+        };
+
+        let timestamp = match self.start_time.as_ref() {
+            Some(start) if self.absolute_timestamps => add_timestamps(start, &timestamp),
+            _ => timestamp,
+        };
+
+        Some(LogFrame {
+            timestamp: Some(timestamp),
+            interface,
+            direction,
+            frame,
+        })
+    }
+
+    fn parse_header(&mut self, header: &str) {
+        if let Some(v) = header.strip_prefix("$FILEVERSION=") {
+            self.version = match v.trim() {
+                "1.0" => Version::V1_0,
+                "1.1" => Version::V1_1,
+                "1.2" | "1.3" => Version::V1_3,
+                v if v.starts_with('2') => {
+                    if self.columns.is_empty() {
+                        let default = if v == "2.0" {
+                            DEFAULT_COLUMNS_V2_0
+                        } else {
+                            DEFAULT_COLUMNS_V2_1
+                        };
+                        self.columns = parse_columns(default);
+                    }
+                    Version::V2
+                }
+                v => {
+                    warn!(version = v, "Unknown TRC file version, assuming 1.0");
+                    Version::V1_0
+                }
+            };
+        } else if let Some(v) = header.strip_prefix("$STARTTIME=") {
+            self.start_time = parse_ole_date(v.trim());
+            self.has_start_time_header = self.start_time.is_some();
+            if self.start_time.is_none() {
+                warn!(header, "Failed to parse TRC start time header");
+            }
+        } else if let Some(v) = header.strip_prefix("$COLUMNS=") {
+            self.columns = parse_columns(v.trim());
+        } else if let Some(v) = header.strip_prefix("Start time:") {
+            if !self.has_start_time_header {
+                self.start_time = parse_start_time_comment(v.trim());
+            }
+        }
+    }
+
+    /// Message lines of the 1.x versions
+    #[allow(clippy::type_complexity)]
+    fn parse_v1_line(
+        &self,
+        tokens: &[&str],
+    ) -> Option<(Timestamp, Option<String>, Option<Direction>, CanAnyFrame)> {
+        // Message number, e.g. '12)'
+        tokens.first()?.strip_suffix(')')?;
+        let timestamp = parse_time_offset(tokens.get(1)?)?;
+        let mut rest = &tokens[2..];
+
+        let mut interface = None;
+        if self.version == Version::V1_3 {
+            interface = Some((*rest.first()?).to_owned());
+            rest = &rest[1..];
+        }
+
+        // 1.0 files don't record the type (direction), everything is a received message
+        let mut kind = "Rx";
+        let mut has_direction = false;
+        if self.version != Version::V1_0 {
+            kind = rest.first()?;
+            rest = &rest[1..];
+            has_direction = true;
+        }
+
+        let id_str = *rest.first()?;
+        rest = &rest[1..];
+        // 1.3 has a reserved column
+        if rest.first() == Some(&"-") {
+            rest = &rest[1..];
+        }
+
+        let (direction, frame) = match kind {
+            "Rx" | "Tx" => {
+                let direction = parse_direction(kind).filter(|_| has_direction);
+                // Status information lines in 1.0 files use an ID of FFFFFFFF
+                if self.version == Version::V1_0 && id_str == "FFFFFFFF" {
+                    (None, status_frame(trailing_hex_bytes(rest, 4))?)
+                } else {
+                    let id = parse_id(id_str)?;
+                    let dlc: usize = rest.first()?.parse().ok()?;
+                    let frame = if rest.get(1) == Some(&"RTR") {
+                        CanAnyFrame::Remote(CanRemoteFrame::new_remote(
+                            id,
+                            dlc.min(MAX_CLASSIC_DATA_LEN),
+                        )?)
+                    } else {
+                        let data = parse_data(&rest[1..], dlc.min(MAX_CLASSIC_DATA_LEN))?;
+                        CanAnyFrame::Normal(CanDataFrame::new(id, &data)?)
+                    };
+                    (direction, frame)
+                }
+            }
+            "Error" => (None, peak_error_frame(trailing_hex_bytes(rest, 5))?),
+            "Warng" => (None, status_frame(trailing_hex_bytes(rest, 4))?),
+            _ => return None,
+        };
+
+        Some((timestamp, interface, direction, frame))
+    }
+
+    /// Message lines of the 2.x versions, laid out according to `$COLUMNS`
+    #[allow(clippy::type_complexity)]
+    fn parse_v2_line(
+        &self,
+        tokens: &[&str],
+    ) -> Option<(Timestamp, Option<String>, Option<Direction>, CanAnyFrame)> {
+        let col = |c: char| {
+            self.columns
+                .iter()
+                .position(|x| *x == c)
+                .and_then(|i| tokens.get(i).copied())
+        };
+        let data_tokens = || {
+            self.columns
+                .iter()
+                .position(|x| *x == 'D')
+                .and_then(|i| tokens.get(i..))
+                .unwrap_or_default()
+        };
+
+        let timestamp = parse_time_offset(col('O')?)?;
+        let kind = col('T')?;
+        let interface = col('B').map(|b| b.to_owned());
+
+        let (direction, frame) = match kind {
+            "DT" | "RR" | "FD" | "FB" | "FE" | "BI" => {
+                let direction = col('d').and_then(parse_direction);
+                let id = parse_id(col('I')?)?;
+                let is_fd = kind != "DT" && kind != "RR";
+                let len = match (col('l'), col('L')) {
+                    (Some(l), _) => l.parse().ok()?,
+                    (None, Some(dlc)) => {
+                        let dlc: usize = dlc.parse().ok()?;
+                        if is_fd {
+                            dlc_to_len(dlc)
+                        } else {
+                            dlc
+                        }
+                    }
+                    (None, None) => return None,
+                };
+                let frame = match kind {
+                    "RR" => CanAnyFrame::Remote(CanRemoteFrame::new_remote(
+                        id,
+                        len.min(MAX_CLASSIC_DATA_LEN),
+                    )?),
+                    "DT" => {
+                        let data = parse_data(data_tokens(), len.min(MAX_CLASSIC_DATA_LEN))?;
+                        CanAnyFrame::Normal(CanDataFrame::new(id, &data)?)
+                    }
+                    _ => {
+                        let data = parse_data(data_tokens(), len)?;
+                        let mut flags = FdFlags::empty();
+                        if kind == "FB" || kind == "BI" {
+                            flags |= FdFlags::BRS;
+                        }
+                        if kind == "FE" || kind == "BI" {
+                            flags |= FdFlags::ESI;
+                        }
+                        CanAnyFrame::Fd(CanFdFrame::with_flags(id, &data, flags)?)
+                    }
+                };
+                (direction, frame)
+            }
+            // The ID and length columns aren't always filled in for these, so the direction
+            // and data are found by content rather than position
+            "ER" | "EC" | "ST" => {
+                let after_type = tokens
+                    .iter()
+                    .position(|t| *t == kind)
+                    .and_then(|i| tokens.get(i + 1..))?;
+                let direction = after_type.iter().find_map(|t| parse_direction(t));
+                let frame = match kind {
+                    "ER" => peak_error_frame(trailing_hex_bytes(after_type, 5))?,
+                    "EC" => error_counter_frame(trailing_hex_bytes(after_type, 2))?,
+                    _ => status_frame(trailing_hex_bytes(after_type, 4))?,
+                };
+                (direction, frame)
+            }
+            // Events (EV) and anything else don't map to frames
+            _ => return None,
+        };
+
+        Some((timestamp, interface, direction, frame))
+    }
+}
+
+fn parse_columns(s: &str) -> Vec<char> {
+    s.split(',')
+        .filter_map(|c| c.trim().chars().next())
+        .collect()
+}
+
+fn parse_direction(s: &str) -> Option<Direction> {
+    match s {
+        "Rx" => Some(Direction::Rx),
+        "Tx" => Some(Direction::Tx),
+        _ => None,
+    }
+}
+
+/// Extended IDs are written with 8 digits, standard IDs with 4
+fn parse_id(s: &str) -> Option<CanId> {
+    if s.len() > 4 {
+        u32::from_str_radix(s, 16)
+            .ok()
+            .and_then(ExtendedId::new)
+            .map(CanId::from)
+    } else {
+        u16::from_str_radix(s, 16)
+            .ok()
+            .and_then(StandardId::new)
+            .map(CanId::from)
+    }
+}
+
+fn parse_data(tokens: &[&str], len: usize) -> Option<Vec<u8>> {
+    if tokens.len() < len {
+        return None;
+    }
+    tokens[..len]
+        .iter()
+        .map(|t| u8::from_str_radix(t, 16).ok())
+        .collect()
+}
+
+/// The last (up to) `max` two digit hex bytes of the contiguous run of hex bytes at the
+/// end of the line, ignoring any trailing text (e.g. 'BUSHEAVY')
+fn trailing_hex_bytes(tokens: &[&str], max: usize) -> Vec<u8> {
+    let is_hex_byte = |t: &&str| t.len() == 2 && t.bytes().all(|b| b.is_ascii_hexdigit());
+    let end = tokens
+        .iter()
+        .rposition(is_hex_byte)
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let start = tokens[..end]
+        .iter()
+        .rposition(|t| !is_hex_byte(t))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let start = start.max(end.saturating_sub(max));
+    tokens[start..end]
+        .iter()
+        .filter_map(|t| u8::from_str_radix(t, 16).ok())
+        .collect()
+}
+
+/// Time offsets are in milliseconds, e.g. '1059.900'
+fn parse_time_offset(s: &str) -> Option<Timestamp> {
+    let (ms, frac) = s.split_once('.').unwrap_or((s, ""));
+    let ms: i64 = ms.parse().ok()?;
+    if !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(timestamp_from_nanos(
+        (ms * 1_000_000) + (fraction_to_nanos(frac)? / 1000),
+    ))
+}
+
+/// `$STARTTIME` is an OLE automation date, fractional days since 1899-12-30
+fn parse_ole_date(s: &str) -> Option<Timestamp> {
+    let (days, frac) = s.split_once('.').unwrap_or((s, "0"));
+    let days: i64 = days.parse().ok()?;
+    let frac: f64 = format!("0.{frac}").parse().ok()?;
+    let nanos = (frac * NANOS_PER_DAY).round() as i64;
+    let secs = (days - OLE_DATE_UNIX_EPOCH_DAYS) * 86400;
+    Some(add_timestamps(
+        &Timestamp {
+            seconds: secs,
+            nanoseconds: 0,
+        },
+        &timestamp_from_nanos(nanos),
+    ))
+}
+
+/// `Start time: dd.mm.yyyy hh:mm:ss[.ms[.us]]`
+fn parse_start_time_comment(s: &str) -> Option<Timestamp> {
+    let (date, time) = s.split_once(' ')?;
+    let mut date_parts = date.splitn(3, '.');
+    let day: u32 = date_parts.next()?.parse().ok()?;
+    let month: u32 = date_parts.next()?.parse().ok()?;
+    let year: i64 = date_parts.next()?.parse().ok()?;
+
+    let mut time_parts = time.trim().splitn(3, ':');
+    let hour: u32 = time_parts.next()?.parse().ok()?;
+    let min: u32 = time_parts.next()?.parse().ok()?;
+    let mut sec_parts = time_parts.next()?.split('.');
+    let sec: u32 = sec_parts.next()?.parse().ok()?;
+    let ms: i64 = match sec_parts.next() {
+        Some(ms) => ms.parse().ok()?,
+        None => 0,
+    };
+
+    Some(Timestamp {
+        seconds: unix_seconds(year, month, day, hour, min, sec),
+        nanoseconds: ms * 1_000_000,
+    })
+}
+
+/// PEAK error frame data is `<type> <direction> <position> <rx count> <tx count>`
+fn peak_error_frame(data: Vec<u8>) -> Option<CanAnyFrame> {
+    let mut err = [0_u8; 8];
+    let mut class = CAN_ERR_PROT;
+    err[2] = match data.first() {
+        Some(&PEAK_ERR_BIT) => CAN_ERR_PROT_BIT,
+        Some(&PEAK_ERR_FORM) => CAN_ERR_PROT_FORM,
+        Some(&PEAK_ERR_STUFF) => CAN_ERR_PROT_STUFF,
+        _ => 0,
+    };
+    if let (Some(rx), Some(tx)) = (data.get(3), data.get(4)) {
+        class |= CAN_ERR_CNT;
+        err[6] = *tx;
+        err[7] = *rx;
+    }
+    CanErrorFrame::new_error(class, &err)
+        .ok()
+        .map(CanAnyFrame::Error)
+}
+
+/// Error counter change data is `<rx count> <tx count>`
+fn error_counter_frame(data: Vec<u8>) -> Option<CanAnyFrame> {
+    let mut err = [0_u8; 8];
+    err[6] = *data.get(1)?;
+    err[7] = *data.first()?;
+    CanErrorFrame::new_error(CAN_ERR_CNT, &err)
+        .ok()
+        .map(CanAnyFrame::Error)
+}
+
+/// Status data is the big-endian PCAN status bits
+fn status_frame(data: Vec<u8>) -> Option<CanAnyFrame> {
+    let status = data.iter().fold(0_u32, |acc, b| (acc << 8) | *b as u32);
+    let mut err = [0_u8; 8];
+    let mut class = 0;
+    if status & PCAN_ERROR_BUSOFF != 0 {
+        class |= CAN_ERR_BUSOFF;
+    }
+    if status & PCAN_ERROR_OVERRUN != 0 {
+        class |= CAN_ERR_CRTL;
+        err[1] |= CAN_ERR_CRTL_RX_OVERFLOW;
+    }
+    if status & PCAN_ERROR_BUSHEAVY != 0 {
+        class |= CAN_ERR_CRTL;
+        err[1] |= CAN_ERR_CRTL_RX_WARNING | CAN_ERR_CRTL_TX_WARNING;
+    }
+    if status & PCAN_ERROR_BUSPASSIVE != 0 {
+        class |= CAN_ERR_CRTL;
+        err[1] |= CAN_ERR_CRTL_RX_PASSIVE | CAN_ERR_CRTL_TX_PASSIVE;
+    }
+    if status & PCAN_ERROR_BUSLIGHT != 0 || status == 0 {
+        // Back to error active
+        class |= CAN_ERR_CRTL;
+        err[1] |= CAN_ERR_CRTL_ACTIVE;
+    }
+    CanErrorFrame::new_error(class, &err)
+        .ok()
+        .map(CanAnyFrame::Error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(seconds: i64, nanoseconds: i64) -> Timestamp {
+        Timestamp {
+            seconds,
+            nanoseconds,
+        }
+    }
+
+    fn parse_all(p: &mut Parser, lines: &str) -> Vec<LogFrame> {
+        lines.lines().filter_map(|l| p.parse_line(l)).collect()
+    }
+
+    #[test]
+    fn version_1_0() {
+        let mut p = Parser::new(true);
+        let frames = parse_all(
+            &mut p,
+            ";##########################################################################
+;   Start time: 06.06.2024 11:29:54.500
+;   Message Number
+;   |         Time Offset (ms)
+;   |         |       ID (hex)
+;   |         |       |     Data Length Code
+;   |         |       |     |   Data Bytes (hex) ...
+;---+--   ----+----  --+--  +  -+ -- -- -- -- -- -- --
+     1)      1059  0300  2  F4 7E
+     2)      1283  18EFC862  1  01
+     3)      1300  FFFFFFFF  4  00 00 00 10  BUSOFF",
+        );
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].timestamp, Some(ts(1717673395, 559_000_000)));
+        assert_eq!(frames[0].interface, None);
+        let CanAnyFrame::Normal(f) = frames[0].frame else {
+            panic!();
+        };
+        assert_eq!(f.id(), StandardId::new(0x300).unwrap().into());
+        assert_eq!(f.data(), &[0xF4, 0x7E]);
+        let CanAnyFrame::Normal(f) = frames[1].frame else {
+            panic!();
+        };
+        assert_eq!(f.id(), ExtendedId::new(0x18EFC862).unwrap().into());
+        let CanAnyFrame::Error(f) = frames[2].frame else {
+            panic!();
+        };
+        assert_eq!(f.error_bits() & CAN_ERR_BUSOFF, CAN_ERR_BUSOFF);
+    }
+
+    #[test]
+    fn version_1_1() {
+        let mut p = Parser::new(false);
+        let frames = parse_all(
+            &mut p,
+            ";$FILEVERSION=1.1
+;$STARTTIME=45449.4791
+     1)        17.3  Rx         0300  8  00 01 02 03 04 05 06 07
+     2)        17.6  Tx     18EFC862  1  AA
+     3)        17.8  Rx         0301  4  RTR
+     4)        18.0  Warng  FFFFFFFF  4  00 00 00 08  BUSHEAVY
+     5)        18.5  Error  00000000  5  04 01 09 00 00",
+        );
+        assert_eq!(frames.len(), 5);
+        assert_eq!(frames[0].timestamp, Some(ts(0, 17_300_000)));
+        assert_eq!(frames[0].direction, Some(Direction::Rx));
+        assert_eq!(frames[1].direction, Some(Direction::Tx));
+        let CanAnyFrame::Remote(f) = frames[2].frame else {
+            panic!();
+        };
+        assert_eq!(f.dlc(), 4);
+        let CanAnyFrame::Error(f) = frames[3].frame else {
+            panic!();
+        };
+        assert_eq!(f.error_bits(), CAN_ERR_CRTL);
+        assert_eq!(
+            f.data()[1],
+            CAN_ERR_CRTL_RX_WARNING | CAN_ERR_CRTL_TX_WARNING
+        );
+        let CanAnyFrame::Error(f) = frames[4].frame else {
+            panic!();
+        };
+        assert_eq!(f.error_bits(), CAN_ERR_PROT | CAN_ERR_CNT);
+        assert_eq!(f.data()[2], CAN_ERR_PROT_STUFF);
+    }
+
+    #[test]
+    fn version_1_3() {
+        let mut p = Parser::new(false);
+        let frames = parse_all(
+            &mut p,
+            ";$FILEVERSION=1.3
+     1)      1059.900 1  Rx        0300 -  2    F4 7E
+     2)      1060.125 2  Tx    18EFC862 -  0",
+        );
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].timestamp, Some(ts(1, 59_900_000)));
+        assert_eq!(frames[0].interface.as_deref(), Some("1"));
+        assert_eq!(frames[1].interface.as_deref(), Some("2"));
+        assert_eq!(frames[1].direction, Some(Direction::Tx));
+    }
+
+    #[test]
+    fn version_2_0() {
+        let mut p = Parser::new(false);
+        let frames = parse_all(
+            &mut p,
+            ";$FILEVERSION=2.0
+;$STARTTIME=45449.4791
+;$COLUMNS=N,O,T,I,d,l,D
+      1      1059.900 DT     0300 Rx 2  F4 7E
+      2      1283.231 FB     0400 Rx 12 01 02 03 04 05 06 07 08 09 0A 0B 0C
+      3      1290.000 RR     0401 Tx 4
+      4      1300.000 EV     User event",
+        );
+        assert_eq!(frames.len(), 3);
+        assert!(matches!(frames[0].frame, CanAnyFrame::Normal(_)));
+        let CanAnyFrame::Fd(f) = frames[1].frame else {
+            panic!();
+        };
+        assert!(f.is_brs());
+        assert!(!f.is_esi());
+        assert_eq!(f.data().len(), 12);
+        assert!(matches!(frames[2].frame, CanAnyFrame::Remote(_)));
+        assert_eq!(frames[2].direction, Some(Direction::Tx));
+    }
+
+    #[test]
+    fn version_2_1() {
+        let mut p = Parser::new(true);
+        let frames = parse_all(
+            &mut p,
+            ";$FILEVERSION=2.1
+;$STARTTIME=45449.5
+;$COLUMNS=N,O,T,B,I,d,R,L,D
+      1      1059.900 DT 1      0300 Rx -  2    F4 7E
+      2      1283.231 BI 2  18EFC862 Tx -  9    01 02 03 04 05 06 07 08 09 0A 0B 0C
+      3      1300.000 ER 1         - Rx -  5    01 01 09 02 03
+      4      1301.000 EC 1         - Rx -  2    05 06
+      5      1302.000 ST 1         - Rx -  4    00 04 00 00",
+        );
+        assert_eq!(frames.len(), 5);
+        assert_eq!(frames[0].timestamp, Some(ts(1717675201, 59_900_000)));
+        assert_eq!(frames[0].interface.as_deref(), Some("1"));
+        let CanAnyFrame::Fd(f) = frames[1].frame else {
+            panic!();
+        };
+        assert!(f.is_brs());
+        assert!(f.is_esi());
+        assert!(f.is_extended());
+        assert_eq!(f.data().len(), 12);
+        let CanAnyFrame::Error(f) = frames[2].frame else {
+            panic!();
+        };
+        assert_eq!(f.data()[2], CAN_ERR_PROT_BIT);
+        assert_eq!(f.data()[6], 3);
+        assert_eq!(f.data()[7], 2);
+        assert_eq!(frames[2].direction, Some(Direction::Rx));
+        let CanAnyFrame::Error(f) = frames[3].frame else {
+            panic!();
+        };
+        assert_eq!(f.error_bits(), CAN_ERR_CNT);
+        assert_eq!(f.data()[7], 5);
+        let CanAnyFrame::Error(f) = frames[4].frame else {
+            panic!();
+        };
+        assert_eq!(
+            f.data()[1],
+            CAN_ERR_CRTL_RX_PASSIVE | CAN_ERR_CRTL_TX_PASSIVE
+        );
+    }
+
+    #[test]
+    fn start_times() {
+        assert_eq!(parse_ole_date("45449.5"), Some(ts(1717675200, 0)));
+        assert_eq!(parse_ole_date("25569"), Some(ts(0, 0)));
+        assert_eq!(
+            parse_start_time_comment("06.06.2024 11:29:54.500.0"),
+            Some(ts(1717673394, 500_000_000))
+        );
+    }
+
+    #[test]
+    fn time_offsets() {
+        assert_eq!(parse_time_offset("1059"), Some(ts(1, 59_000_000)));
+        assert_eq!(parse_time_offset("17.3"), Some(ts(0, 17_300_000)));
+        assert_eq!(parse_time_offset("1283.2315"), Some(ts(1, 283_231_500)));
+        assert_eq!(parse_time_offset("abc"), None);
+    }
+}
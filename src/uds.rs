@@ -0,0 +1,395 @@
+//! UDS (ISO 14229) service decoding, and request/response pairing.
+
+use std::collections::HashMap;
+
+const SID_NEGATIVE_RESPONSE: u8 = 0x7F;
+const POSITIVE_RESPONSE_OFFSET: u8 = 0x40;
+const NRC_RESPONSE_PENDING: u8 = 0x78;
+const SUPPRESS_POSITIVE_RESPONSE: u8 = 0x80;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum UdsKind {
+    Request,
+    PositiveResponse,
+    NegativeResponse { nrc: u8 },
+}
+
+impl UdsKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UdsKind::Request => "request",
+            UdsKind::PositiveResponse => "positive_response",
+            UdsKind::NegativeResponse { .. } => "negative_response",
+        }
+    }
+}
+
+/// A decoded UDS request or response
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct UdsMessage<'a> {
+    /// The request service ID, also for responses
+    pub sid: u8,
+    pub kind: UdsKind,
+    /// Without the suppress positive response bit
+    pub sub_function: Option<u8>,
+    pub suppress_positive_response: bool,
+    /// The first data identifier, and the number of them in a ReadDataByIdentifier request
+    pub did: Option<u16>,
+    pub did_count: usize,
+    pub routine_id: Option<u16>,
+    /// The remaining parameters
+    pub data: &'a [u8],
+}
+
+impl<'a> UdsMessage<'a> {
+    pub fn decode(pdu: &'a [u8]) -> Option<Self> {
+        let (&first, mut params) = pdu.split_first()?;
+        let (sid, kind) = if first == SID_NEGATIVE_RESPONSE {
+            let (&sid, rest) = params.split_first()?;
+            let (&nrc, rest) = rest.split_first()?;
+            params = rest;
+            (sid, UdsKind::NegativeResponse { nrc })
+        } else if service_name(first).is_some() {
+            (first, UdsKind::Request)
+        } else if first >= POSITIVE_RESPONSE_OFFSET
+            && service_name(first - POSITIVE_RESPONSE_OFFSET).is_some()
+        {
+            (first - POSITIVE_RESPONSE_OFFSET, UdsKind::PositiveResponse)
+        } else {
+            return None;
+        };
+
+        let mut msg = UdsMessage {
+            sid,
+            kind,
+            sub_function: None,
+            suppress_positive_response: false,
+            did: None,
+            did_count: 0,
+            routine_id: None,
+            data: params,
+        };
+        if matches!(kind, UdsKind::NegativeResponse { .. }) {
+            return Some(msg);
+        }
+
+        if has_sub_function(sid) {
+            if let Some((&sf, rest)) = msg.data.split_first() {
+                msg.sub_function = Some(sf & !SUPPRESS_POSITIVE_RESPONSE);
+                msg.suppress_positive_response =
+                    kind == UdsKind::Request && sf & SUPPRESS_POSITIVE_RESPONSE != 0;
+                msg.data = rest;
+            }
+        }
+
+        match sid {
+            // ReadDataByIdentifier requests are a list of DIDs
+            0x22 if kind == UdsKind::Request => {
+                msg.did_count = msg.data.len() / 2;
+                msg.did = read_u16(msg.data);
+                msg.data = &msg.data[msg.did_count * 2..];
+            }
+            0x22 | 0x24 | 0x2E | 0x2F => {
+                msg.did = read_u16(msg.data);
+                if msg.did.is_some() {
+                    msg.did_count = 1;
+                    msg.data = &msg.data[2..];
+                }
+            }
+            0x31 => {
+                msg.routine_id = read_u16(msg.data);
+                if msg.routine_id.is_some() {
+                    msg.data = &msg.data[2..];
+                }
+            }
+            _ => (),
+        }
+
+        Some(msg)
+    }
+
+    pub fn service_name(&self) -> String {
+        match service_name(self.sid) {
+            Some(name) => name.to_owned(),
+            None => format!("service_{:#04x}", self.sid),
+        }
+    }
+
+    pub fn sub_function_name(&self) -> Option<&'static str> {
+        let sf = self.sub_function?;
+        Some(match (self.sid, sf) {
+            (0x10, 0x01) => "default_session",
+            (0x10, 0x02) => "programming_session",
+            (0x10, 0x03) => "extended_diagnostic_session",
+            (0x10, 0x04) => "safety_system_diagnostic_session",
+            (0x11, 0x01) => "hard_reset",
+            (0x11, 0x02) => "key_off_on_reset",
+            (0x11, 0x03) => "soft_reset",
+            (0x11, 0x04) => "enable_rapid_power_shut_down",
+            (0x11, 0x05) => "disable_rapid_power_shut_down",
+            (0x27, sf) if sf % 2 == 1 => "request_seed",
+            (0x27, _) => "send_key",
+            (0x31, 0x01) => "start_routine",
+            (0x31, 0x02) => "stop_routine",
+            (0x31, 0x03) => "request_routine_results",
+            (0x3E, 0x00) => "zero_sub_function",
+            (0x85, 0x01) => "on",
+            (0x85, 0x02) => "off",
+            _ => return None,
+        })
+    }
+
+    /// The request is still being processed, a final response will follow
+    pub fn is_response_pending(&self) -> bool {
+        self.kind
+            == UdsKind::NegativeResponse {
+                nrc: NRC_RESPONSE_PENDING,
+            }
+    }
+}
+
+fn read_u16(data: &[u8]) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(..2)?.try_into().ok()?))
+}
+
+fn service_name(sid: u8) -> Option<&'static str> {
+    Some(match sid {
+        0x10 => "diagnostic_session_control",
+        0x11 => "ecu_reset",
+        0x14 => "clear_diagnostic_information",
+        0x19 => "read_dtc_information",
+        0x22 => "read_data_by_identifier",
+        0x23 => "read_memory_by_address",
+        0x24 => "read_scaling_data_by_identifier",
+        0x27 => "security_access",
+        0x28 => "communication_control",
+        0x29 => "authentication",
+        0x2A => "read_data_by_periodic_identifier",
+        0x2C => "dynamically_define_data_identifier",
+        0x2E => "write_data_by_identifier",
+        0x2F => "input_output_control_by_identifier",
+        0x31 => "routine_control",
+        0x34 => "request_download",
+        0x35 => "request_upload",
+        0x36 => "transfer_data",
+        0x37 => "request_transfer_exit",
+        0x38 => "request_file_transfer",
+        0x3D => "write_memory_by_address",
+        0x3E => "tester_present",
+        0x83 => "access_timing_parameter",
+        0x84 => "secured_data_transmission",
+        0x85 => "control_dtc_setting",
+        0x86 => "response_on_event",
+        0x87 => "link_control",
+        _ => return None,
+    })
+}
+
+fn has_sub_function(sid: u8) -> bool {
+    matches!(
+        sid,
+        0x10 | 0x11 | 0x19 | 0x27 | 0x28 | 0x29 | 0x2C | 0x31 | 0x3E | 0x83 | 0x85 | 0x86 | 0x87
+    )
+}
+
+pub(crate) fn nrc_name(nrc: u8) -> Option<&'static str> {
+    Some(match nrc {
+        0x10 => "general_reject",
+        0x11 => "service_not_supported",
+        0x12 => "sub_function_not_supported",
+        0x13 => "incorrect_message_length_or_invalid_format",
+        0x14 => "response_too_long",
+        0x21 => "busy_repeat_request",
+        0x22 => "conditions_not_correct",
+        0x24 => "request_sequence_error",
+        0x25 => "no_response_from_subnet_component",
+        0x26 => "failure_prevents_execution_of_requested_action",
+        0x31 => "request_out_of_range",
+        0x33 => "security_access_denied",
+        0x34 => "authentication_required",
+        0x35 => "invalid_key",
+        0x36 => "exceeded_number_of_attempts",
+        0x37 => "required_time_delay_not_expired",
+        0x70 => "upload_download_not_accepted",
+        0x71 => "transfer_data_suspended",
+        0x72 => "general_programming_failure",
+        0x73 => "wrong_block_sequence_counter",
+        0x78 => "response_pending",
+        0x7E => "sub_function_not_supported_in_active_session",
+        0x7F => "service_not_supported_in_active_session",
+        _ => return None,
+    })
+}
+
+#[derive(Debug)]
+struct PendingRequest {
+    sid: u8,
+    nonce: i64,
+    timestamp_ns: Option<i64>,
+}
+
+/// A response's request
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct RequestMatch {
+    pub nonce: i64,
+    /// From the request to the response
+    pub response_time_ns: Option<i64>,
+    /// More responses to the request will follow
+    pub pending: bool,
+}
+
+/// Pairs responses with the outstanding request on the same connection
+#[derive(Debug, Default)]
+pub(crate) struct UdsTracker {
+    /// Keyed by interface and connection
+    pending: HashMap<(Option<String>, usize), PendingRequest>,
+    next_nonce: i64,
+}
+
+impl UdsTracker {
+    /// Record a request, returning its nonce and the nonce of the outstanding request on the
+    /// connection that it replaces. Requests that suppress the positive response aren't
+    /// awaited.
+    pub fn request(
+        &mut self,
+        interface: Option<&str>,
+        connection: usize,
+        msg: &UdsMessage,
+        timestamp_ns: Option<i64>,
+    ) -> (i64, Option<i64>) {
+        let nonce = self.next_nonce;
+        self.next_nonce += 1;
+        let key = (interface.map(str::to_owned), connection);
+        let superseded = if msg.suppress_positive_response {
+            self.pending.remove(&key)
+        } else {
+            self.pending.insert(
+                key,
+                PendingRequest {
+                    sid: msg.sid,
+                    nonce,
+                    timestamp_ns,
+                },
+            )
+        };
+        (nonce, superseded.map(|req| req.nonce))
+    }
+
+    /// Find the request for a response, if there is one outstanding
+    pub fn response(
+        &mut self,
+        interface: Option<&str>,
+        connection: usize,
+        msg: &UdsMessage,
+        timestamp_ns: Option<i64>,
+    ) -> Option<RequestMatch> {
+        let key = (interface.map(str::to_owned), connection);
+        let req = self.pending.get(&key).filter(|req| req.sid == msg.sid)?;
+        let found = RequestMatch {
+            nonce: req.nonce,
+            response_time_ns: timestamp_ns
+                .zip(req.timestamp_ns)
+                .map(|(resp, req)| resp - req),
+            pending: msg.is_response_pending(),
+        };
+        if !found.pending {
+            self.pending.remove(&key);
+        }
+        Some(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        let msg = UdsMessage::decode(&[0x10, 0x03]).unwrap();
+        assert_eq!(msg.kind, UdsKind::Request);
+        assert_eq!(msg.service_name(), "diagnostic_session_control");
+        assert_eq!(msg.sub_function, Some(3));
+        assert_eq!(msg.sub_function_name(), Some("extended_diagnostic_session"));
+
+        let msg = UdsMessage::decode(&[0x3E, 0x80]).unwrap();
+        assert_eq!(msg.sub_function, Some(0));
+        assert!(msg.suppress_positive_response);
+
+        let msg = UdsMessage::decode(&[0x22, 0xF1, 0x90, 0xF1, 0x8C]).unwrap();
+        assert_eq!(msg.did, Some(0xF190));
+        assert_eq!(msg.did_count, 2);
+        assert!(msg.data.is_empty());
+
+        let msg = UdsMessage::decode(&[0x62, 0xF1, 0x90, b'W', b'D', b'B']).unwrap();
+        assert_eq!(msg.kind, UdsKind::PositiveResponse);
+        assert_eq!(msg.sid, 0x22);
+        assert_eq!(msg.did, Some(0xF190));
+        assert_eq!(msg.data, b"WDB");
+
+        let msg = UdsMessage::decode(&[0x71, 0x01, 0xFF, 0x00, 0x00]).unwrap();
+        assert_eq!(msg.sid, 0x31);
+        assert_eq!(msg.sub_function_name(), Some("start_routine"));
+        assert_eq!(msg.routine_id, Some(0xFF00));
+        assert_eq!(msg.data, &[0x00]);
+
+        let msg = UdsMessage::decode(&[0x7F, 0x27, 0x35]).unwrap();
+        assert_eq!(msg.sid, 0x27);
+        assert_eq!(msg.kind, UdsKind::NegativeResponse { nrc: 0x35 });
+        assert_eq!(nrc_name(0x35), Some("invalid_key"));
+        assert!(!msg.is_response_pending());
+        assert!(UdsMessage::decode(&[0x7F, 0x27, 0x78])
+            .unwrap()
+            .is_response_pending());
+
+        assert_eq!(UdsMessage::decode(&[0x01, 0x0C]), None);
+        assert_eq!(UdsMessage::decode(&[0x7F, 0x27]), None);
+        assert_eq!(UdsMessage::decode(&[]), None);
+    }
+
+    #[test]
+    fn pairing() {
+        let mut tracker = UdsTracker::default();
+        let req = UdsMessage::decode(&[0x31, 0x01, 0xFF, 0x00]).unwrap();
+        let pending = UdsMessage::decode(&[0x7F, 0x31, 0x78]).unwrap();
+        let resp = UdsMessage::decode(&[0x71, 0x01, 0xFF, 0x00]).unwrap();
+
+        let (nonce, superseded) = tracker.request(Some("can0"), 0, &req, Some(0));
+        assert_eq!(superseded, None);
+        // Different interface
+        assert_eq!(tracker.response(Some("can1"), 0, &resp, Some(5)), None);
+        assert_eq!(
+            tracker.response(Some("can0"), 0, &pending, Some(10)),
+            Some(RequestMatch {
+                nonce,
+                response_time_ns: Some(10),
+                pending: true
+            })
+        );
+        assert_eq!(
+            tracker.response(Some("can0"), 0, &resp, Some(20)),
+            Some(RequestMatch {
+                nonce,
+                response_time_ns: Some(20),
+                pending: false
+            })
+        );
+        // Already answered
+        assert_eq!(tracker.response(Some("can0"), 0, &resp, Some(30)), None);
+
+        // Different service
+        let (next, superseded) = tracker.request(Some("can0"), 0, &req, None);
+        assert_ne!(next, nonce);
+        assert_eq!(superseded, None);
+        let other = UdsMessage::decode(&[0x50, 0x03]).unwrap();
+        assert_eq!(tracker.response(Some("can0"), 0, &other, None), None);
+
+        // Replaced by another request
+        let (last, superseded) = tracker.request(Some("can0"), 0, &req, None);
+        assert_eq!(superseded, Some(next));
+        // Not awaited when the positive response is suppressed
+        let suppressed = UdsMessage::decode(&[0x3E, 0x80]).unwrap();
+        let (_, superseded) = tracker.request(Some("can0"), 0, &suppressed, None);
+        assert_eq!(superseded, Some(last));
+        assert_eq!(tracker.response(Some("can0"), 0, &resp, None), None);
+    }
+}